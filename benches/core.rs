@@ -0,0 +1,126 @@
+//! Benchmarks of the paths which dominate long backfills once the responses
+//! are available: planning the windows, parsing them and aggregating the series
+//!
+//! Run with `cargo bench`, no network access is needed.
+
+use std::hint::black_box;
+
+use carbonintensity::{
+    aggregate_blocks, parse_body, plan_windows, ramp_rates, BlockDefinition, Data, FixedClock,
+    IntensityForDate, QueryOptions, RampSummary, RollingAnnualIntensity,
+};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+/// Half-hours in a window of 13 days, the largest requested at once
+const WINDOW: usize = 13 * 48;
+
+const FUELS: [&str; 9] = [
+    "biomass", "coal", "imports", "gas", "nuclear", "other", "hydro", "solar", "wind",
+];
+
+fn start() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2023, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+}
+
+/// Body of a response for a window, with a generation mix for each
+/// half-hour if `regional`
+fn window_json(regional: bool) -> String {
+    let records: Vec<String> = (0..WINDOW)
+        .map(|i| {
+            let from = start() + Duration::minutes(30 * i as i64);
+            let to = from + Duration::minutes(30);
+            let mix = if regional {
+                let fuels: Vec<String> = FUELS
+                    .iter()
+                    .map(|fuel| format!(r#"{{"fuel":"{fuel}","perc":11.1}}"#))
+                    .collect();
+                format!(r#","generationmix":[{}]"#, fuels.join(","))
+            } else {
+                String::new()
+            };
+            format!(
+                r#"{{"from":"{}","to":"{}","intensity":{{"forecast":{},"actual":null,"index":"moderate"}}{mix}}}"#,
+                from.format("%Y-%m-%dT%H:%MZ"),
+                to.format("%Y-%m-%dT%H:%MZ"),
+                150 + (i % 100)
+            )
+        })
+        .collect();
+    format!("[{}]", records.join(","))
+}
+
+/// A year of half-hours
+fn series() -> Vec<IntensityForDate> {
+    (0..365 * 48)
+        .map(|i: i32| {
+            let time = start() + Duration::minutes(30 * i64::from(i));
+            (time, 100 + (i * 7) % 150)
+        })
+        .collect()
+}
+
+fn planning(c: &mut Criterion) {
+    let now = NaiveDate::from_ymd_opt(2024, 6, 1)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap();
+    let options = QueryOptions::new().with_clock(FixedClock::new(now));
+    c.bench_function("plan_windows 5 years", |b| {
+        b.iter(|| plan_windows(black_box("2019-01-01"), &Some("2024-01-01"), &options).unwrap())
+    });
+}
+
+fn parsing(c: &mut Criterion) {
+    // as the client does, with SIMD instructions if the simd-json feature is enabled
+    let mut group = c.benchmark_group("parse window");
+    for (name, regional) in [("national", false), ("regional", true)] {
+        let json = window_json(regional);
+        group.throughput(Throughput::Bytes(json.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &json, |b, json| {
+            b.iter(|| parse_body::<Vec<Data>>(black_box(json.as_bytes())).unwrap())
+        });
+    }
+    group.finish();
+
+    let data: Vec<Data> = serde_json::from_str(&window_json(true)).unwrap();
+    c.bench_function("intensities of a window", |b| {
+        b.iter(|| {
+            black_box(&data)
+                .iter()
+                .map(Data::intensity_for_date)
+                .collect::<carbonintensity::Result<Vec<_>>>()
+                .unwrap()
+        })
+    });
+}
+
+fn merging(c: &mut Criterion) {
+    let series = series();
+    c.bench_function("rolling annual intensity, windows of 13 days", |b| {
+        b.iter(|| {
+            let mut rolling = RollingAnnualIntensity::new();
+            for window in series.chunks(WINDOW) {
+                rolling.push(black_box(window));
+            }
+            rolling.mean()
+        })
+    });
+}
+
+fn aggregation(c: &mut Criterion) {
+    let series = series();
+    let efa = BlockDefinition::efa();
+    c.bench_function("EFA blocks over a year", |b| {
+        b.iter(|| aggregate_blocks(black_box(&series), &efa, &chrono::Utc))
+    });
+    c.bench_function("ramp summary over a year", |b| {
+        b.iter(|| RampSummary::new(&ramp_rates(black_box(&series))))
+    });
+}
+
+criterion_group!(benches, planning, parsing, merging, aggregation);
+criterion_main!(benches);
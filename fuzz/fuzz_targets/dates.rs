@@ -0,0 +1,22 @@
+//! Dates given on the command line, parsed and split into windows
+#![no_main]
+
+use carbonintensity::{plan_windows, FixedClock, QueryOptions};
+use chrono::NaiveDate;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (&str, Option<&str>)| {
+    let now = NaiveDate::from_ymd_opt(2024, 6, 1)
+        .unwrap()
+        .and_hms_opt(12, 34, 0)
+        .unwrap();
+    let options = QueryOptions::new().with_clock(FixedClock::new(now));
+    let (start, end) = input;
+
+    if let Ok(windows) = plan_windows(start, &end, &options) {
+        for window in windows {
+            assert!(window.start <= window.end);
+            let _ = window.url_bounds();
+        }
+    }
+});
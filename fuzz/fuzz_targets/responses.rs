@@ -0,0 +1,18 @@
+//! Bodies sent by the API, which tolerates nulls and unknown fuels
+#![no_main]
+
+use carbonintensity::Data;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|body: &[u8]| {
+    let Ok(data) = serde_json::from_slice::<Vec<Data>>(body) else {
+        return;
+    };
+    for item in &data {
+        let _ = item.intensity_for_date();
+        let _ = item.intensity().implausible_value();
+        let _ = item.renewable_share();
+        let _ = item.mix_total();
+        let _ = item.is_likely_surplus();
+    }
+});
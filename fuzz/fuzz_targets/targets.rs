@@ -0,0 +1,13 @@
+//! Targets given on the command line: postcodes, region ids and network operators
+#![no_main]
+
+use carbonintensity::{Region, Target};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    let _ = input.parse::<Region>();
+    let _ = Region::from_dno(input);
+
+    let target = Target::from(input.to_string());
+    let _ = target.to_string();
+});
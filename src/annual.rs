@@ -0,0 +1,234 @@
+use std::io::{self, Write};
+
+use crate::{
+    ApiError, CarbonIntensityClient, Data, DateRange, QueryOptions, QueryOutcome, Region, Result,
+};
+use chrono::{Datelike, NaiveDate};
+
+/// Intensity and renewable share of a region over a calendar year
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionSummary {
+    pub region: Region,
+    /// Mean intensity over the year, in gCO2/kWh
+    pub mean: f64,
+    pub min: i32,
+    pub max: i32,
+    /// Mean share of renewable generation in %, `None` if no generation mix was sent
+    pub renewable_share: Option<f64>,
+    /// Number of half-hours summarised
+    pub count: usize,
+}
+
+impl RegionSummary {
+    /// Summarises the half-hours of a region starting in `year`
+    ///
+    /// Returns `None` if there is no such half-hour.
+    fn from_data(region: Region, year: i32, data: &[Data]) -> Result<Option<Self>> {
+        let mut values = Vec::new();
+        let mut shares = Vec::new();
+        for item in data {
+            let (start, value) = item.intensity_for_date()?;
+            if start.year() != year {
+                continue;
+            }
+            values.push(value);
+            shares.extend(item.renewable_share());
+        }
+
+        if values.is_empty() {
+            return Ok(None);
+        }
+
+        let total: i64 = values.iter().copied().map(i64::from).sum();
+        let renewable_share =
+            (!shares.is_empty()).then(|| shares.iter().sum::<f64>() / shares.len() as f64);
+
+        Ok(Some(Self {
+            region,
+            mean: total as f64 / values.len() as f64,
+            min: values.iter().copied().min().unwrap_or_default(),
+            max: values.iter().copied().max().unwrap_or_default(),
+            renewable_share,
+            count: values.len(),
+        }))
+    }
+}
+
+/// Summarises every region over a calendar year, ordered by region id
+///
+/// All the regions are retrieved at once, the windows of the year concurrently.
+/// Years in progress are summarised up to the forecast horizon, 48 hours from now.
+pub async fn annual_summary(year: i32, options: &QueryOptions) -> Result<Vec<RegionSummary>> {
+    CarbonIntensityClient::shared()
+        .annual_summary(year, options)
+        .await
+}
+
+impl CarbonIntensityClient {
+    /// Summary of every region over a calendar year, see `annual_summary()`
+    pub async fn annual_summary(
+        &self,
+        year: i32,
+        options: &QueryOptions,
+    ) -> Result<Vec<RegionSummary>> {
+        let invalid = || ApiError::Error(format!("Invalid year {year}"));
+        let start = NaiveDate::from_ymd_opt(year, 1, 1).ok_or_else(invalid)?;
+        let end = NaiveDate::from_ymd_opt(year + 1, 1, 1).ok_or_else(invalid)?;
+        let range = DateRange::within_horizon(start, Some(end.into()), options.clock())?;
+
+        let outcome = self
+            .get_detailed_intensities_all_regions_in_range(&range, options)
+            .await?;
+        summarise(year, &outcome)
+    }
+}
+
+/// Summarises the regions of an outcome, failing if some of the data is missing
+///
+/// The other warnings, e.g. for the part of a year after the horizon, are not fatal.
+fn summarise(year: i32, outcome: &QueryOutcome<(Region, Vec<Data>)>) -> Result<Vec<RegionSummary>> {
+    if outcome.is_partial() {
+        let missing: Vec<String> = outcome.warnings.iter().map(ToString::to_string).collect();
+        return Err(ApiError::Error(format!(
+            "Incomplete data: {}",
+            missing.join(", ")
+        )));
+    }
+
+    Region::ALL
+        .iter()
+        .map(|&region| {
+            let data = outcome
+                .intensities
+                .iter()
+                .find(|(r, _)| *r == region)
+                .map(|(_, data)| data.as_slice())
+                .unwrap_or_default();
+            RegionSummary::from_data(region, year, data)?
+                .ok_or_else(|| ApiError::Error(format!("No data found for {region} in {year}")))
+        })
+        .collect()
+}
+
+/// Writes the annual summary as a table, one line per region
+pub fn write_annual_summary<W: Write>(
+    writer: &mut W,
+    summaries: &[RegionSummary],
+) -> io::Result<()> {
+    writeln!(
+        writer,
+        "{:<3} {:<38} {:>6} {:>5} {:>5} {:>10}",
+        "id", "region", "mean", "min", "max", "renewable"
+    )?;
+    for summary in summaries {
+        let share = summary
+            .renewable_share
+            .map(|share| format!("{share:.1}%"))
+            .unwrap_or_else(|| "-".to_string());
+        writeln!(
+            writer,
+            "{:<3} {:<38} {:>6.1} {:>5} {:>5} {:>10}",
+            summary.region as u8,
+            summary.region.to_string(),
+            summary.mean,
+            summary.min,
+            summary.max,
+            share
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Fuel, Warning};
+
+    fn data(from: &str, forecast: i32, wind: f64) -> Data {
+        Data::fixture(from, forecast)
+            .with_generation_mix(&[(Fuel::Wind, wind), (Fuel::Gas, 100.0 - wind)])
+    }
+
+    #[test]
+    fn summarise_region() {
+        let data = vec![
+            data("2023-12-31T23:30Z", 500, 0.0),
+            data("2024-01-01T00:00Z", 100, 50.0),
+            data("2024-06-01T12:00Z", 200, 30.0),
+            data("2024-12-31T23:30Z", 300, 10.0),
+            data("2025-01-01T00:00Z", 500, 0.0),
+        ];
+        let summary = RegionSummary::from_data(Region::London, 2024, &data)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            summary,
+            RegionSummary {
+                region: Region::London,
+                mean: 200.0,
+                min: 100,
+                max: 300,
+                renewable_share: Some(30.0),
+                count: 3,
+            }
+        );
+
+        assert_eq!(
+            RegionSummary::from_data(Region::London, 2022, &data).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn summarise_outcome() {
+        let mut outcome = QueryOutcome {
+            intensities: Region::ALL
+                .iter()
+                .map(|&r| (r, vec![data("2024-03-01T12:00Z", 200, 30.0)]))
+                .collect(),
+            ..Default::default()
+        };
+        // warnings about the quality of the data still give a summary
+        outcome.warnings.push(Warning::InconsistentMix {
+            from: "2024-03-01T12:00Z".to_string(),
+            total: 97.0,
+            renormalised: false,
+        });
+        outcome.warnings.push(Warning::ImplausibleIntensity {
+            from: "2024-03-01T12:30Z".to_string(),
+            value: -5,
+        });
+        let summaries = summarise(2024, &outcome).unwrap();
+        assert_eq!(summaries.len(), Region::ALL.len());
+        assert_eq!(summaries[0].mean, 200.0);
+
+        let end = NaiveDate::from_ymd_opt(2024, 3, 2)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        outcome
+            .warnings
+            .push(Warning::DeadlineExceeded { start: end, end });
+        assert!(summarise(2024, &outcome).is_err());
+    }
+
+    #[test]
+    fn write_summary() {
+        let summary = RegionSummary {
+            region: Region::London,
+            mean: 150.25,
+            min: 50,
+            max: 280,
+            renewable_share: Some(41.23),
+            count: 17568,
+        };
+        let mut output = Vec::new();
+        write_annual_summary(&mut output, &[summary]).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("id  region"));
+        assert!(lines[1].starts_with("13  London "));
+        assert!(lines[1].ends_with("150.2    50   280      41.2%"));
+    }
+}
@@ -0,0 +1,367 @@
+use std::{
+    fmt::Display,
+    io::{self, Write},
+};
+
+use futures::future;
+
+use crate::{csv_field, ApiError, CarbonIntensityClient, QueryOptions, Result, Target};
+
+/// Site of an estate, with its electricity consumption
+#[derive(Debug, Clone, PartialEq)]
+pub struct Site {
+    pub name: String,
+    pub postcode: String,
+    /// Consumption over the period audited, in kWh
+    pub kwh: f64,
+}
+
+/// Parses sites from CSV
+///
+/// Expects a header line followed by lines with the name of the site,
+/// its postcode and its consumption in kWh e.g.
+///
+/// ```text
+/// site,postcode,kwh
+/// Head office,BS7,125000
+/// Warehouse,RG10,48000.5
+/// ```
+pub fn parse_sites(csv: &str) -> Result<Vec<Site>> {
+    csv.lines()
+        .enumerate()
+        // skip the header
+        .skip(1)
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            let invalid =
+                || ApiError::Error(format!("Invalid site on line {}: {line}", number + 1));
+            let columns: Vec<&str> = line.split(',').map(str::trim).collect();
+            let [name, postcode, kwh] = columns[..] else {
+                return Err(invalid());
+            };
+            Ok(Site {
+                name: name.to_string(),
+                postcode: postcode.to_string(),
+                kwh: kwh.parse().map_err(|_| invalid())?,
+            })
+        })
+        .collect()
+}
+
+/// Average intensity and estimated emissions of a site over a period
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiteAudit {
+    pub site: Site,
+    /// Mean intensity over the period, in gCO2/kWh
+    pub mean_intensity: f64,
+    /// Estimated emissions in kgCO2, assuming a flat consumption over the period
+    pub emissions_kg: f64,
+}
+
+impl SiteAudit {
+    fn new(site: Site, mean_intensity: f64) -> Self {
+        let emissions_kg = site.kwh * mean_intensity / 1000.0;
+        Self {
+            site,
+            mean_intensity,
+            emissions_kg,
+        }
+    }
+}
+
+/// Site which couldn't be audited, e.g. because of an invalid postcode
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiteFailure {
+    pub site: Site,
+    pub error: String,
+}
+
+/// Results of an audit carried on despite failing sites, see `audit_sites_keep_going`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditOutcome {
+    /// One result per site, in the order of the sites
+    pub results: Vec<std::result::Result<SiteAudit, SiteFailure>>,
+}
+
+impl AuditOutcome {
+    pub fn audits(&self) -> impl Iterator<Item = &SiteAudit> {
+        self.results
+            .iter()
+            .filter_map(|result| result.as_ref().ok())
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &SiteFailure> {
+        self.results
+            .iter()
+            .filter_map(|result| result.as_ref().err())
+    }
+}
+
+/// Summary of the successes and failures e.g. "3 sites audited, 1 failed"
+impl Display for AuditOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} sites audited, {} failed",
+            self.audits().count(),
+            self.failures().count()
+        )?;
+        for failure in self.failures() {
+            write!(f, "\n  {}: {}", failure.site.name, failure.error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes the average intensity and estimated emissions of each site
+///
+/// The sites are queried concurrently with the `options`, the first error aborts
+/// the audit. A site whose data is incomplete, e.g. when the deadline expires, fails.
+pub async fn audit_sites(
+    sites: &[Site],
+    start: &str,
+    end: &Option<&str>,
+    options: &QueryOptions,
+) -> Result<Vec<SiteAudit>> {
+    CarbonIntensityClient::shared()
+        .audit_sites(sites, start, end, options)
+        .await
+}
+
+/// Same as `audit_sites` but records the error of each site which fails
+/// and carries on with the others
+pub async fn audit_sites_keep_going(
+    sites: &[Site],
+    start: &str,
+    end: &Option<&str>,
+    options: &QueryOptions,
+) -> AuditOutcome {
+    CarbonIntensityClient::shared()
+        .audit_sites_keep_going(sites, start, end, options)
+        .await
+}
+
+impl CarbonIntensityClient {
+    /// Average intensity and estimated emissions of each site, see `audit_sites()`
+    pub async fn audit_sites(
+        &self,
+        sites: &[Site],
+        start: &str,
+        end: &Option<&str>,
+        options: &QueryOptions,
+    ) -> Result<Vec<SiteAudit>> {
+        let audits = sites
+            .iter()
+            .map(|site| audit_site(self, site, start, end, options));
+        future::try_join_all(audits).await
+    }
+
+    /// See `audit_sites_keep_going()`
+    pub async fn audit_sites_keep_going(
+        &self,
+        sites: &[Site],
+        start: &str,
+        end: &Option<&str>,
+        options: &QueryOptions,
+    ) -> AuditOutcome {
+        let audits = sites.iter().map(|site| async move {
+            audit_site(self, site, start, end, options)
+                .await
+                .map_err(|error| SiteFailure {
+                    site: site.clone(),
+                    error: error.to_string(),
+                })
+        });
+        AuditOutcome {
+            results: future::join_all(audits).await,
+        }
+    }
+}
+
+async fn audit_site(
+    client: &CarbonIntensityClient,
+    site: &Site,
+    start: &str,
+    end: &Option<&str>,
+    options: &QueryOptions,
+) -> Result<SiteAudit> {
+    let target = Target::Postcode(site.postcode.clone());
+    let outcome = client
+        .get_intensities_with_options(&target, start, end, options)
+        .await?;
+    if outcome.is_partial() {
+        return Err(ApiError::Error(format!(
+            "Incomplete data for {}",
+            site.name
+        )));
+    }
+    let intensities = outcome.intensities;
+    if intensities.is_empty() {
+        return Err(ApiError::Error(format!("No data found for {}", site.name)));
+    }
+    let total: i64 = intensities.iter().map(|(_, value)| i64::from(*value)).sum();
+    let mean = total as f64 / intensities.len() as f64;
+    Ok(SiteAudit::new(site.clone(), mean))
+}
+
+/// Writes the audit as CSV, the names of the sites are quoted if needed
+pub fn write_audit_csv<W: Write>(writer: &mut W, audits: &[SiteAudit]) -> io::Result<()> {
+    writeln!(writer, "site,postcode,kwh,mean_intensity,emissions_kg")?;
+    for audit in audits {
+        writeln!(
+            writer,
+            "{},{},{},{:.1},{:.1}",
+            csv_field(&audit.site.name),
+            csv_field(&audit.site.postcode),
+            audit.site.kwh,
+            audit.mean_intensity,
+            audit.emissions_kg
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the outcome of an audit as CSV, with an `error` column
+///
+/// The columns of the intensity and emissions are empty for the sites which
+/// failed, the error is empty for the others.
+pub fn write_audit_outcome_csv<W: Write>(writer: &mut W, outcome: &AuditOutcome) -> io::Result<()> {
+    writeln!(
+        writer,
+        "site,postcode,kwh,mean_intensity,emissions_kg,error"
+    )?;
+    for result in &outcome.results {
+        match result {
+            Ok(audit) => writeln!(
+                writer,
+                "{},{},{},{:.1},{:.1},",
+                csv_field(&audit.site.name),
+                csv_field(&audit.site.postcode),
+                audit.site.kwh,
+                audit.mean_intensity,
+                audit.emissions_kg
+            )?,
+            Err(failure) => writeln!(
+                writer,
+                "{},{},{},,,{}",
+                csv_field(&failure.site.name),
+                csv_field(&failure.site.postcode),
+                failure.site.kwh,
+                csv_field(&failure.error)
+            )?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sites_test() {
+        let csv = "site,postcode,kwh\nHead office, BS7 ,125000\n\nWarehouse,RG10,48000.5\n";
+        let sites = parse_sites(csv).unwrap();
+        assert_eq!(
+            sites,
+            vec![
+                Site {
+                    name: "Head office".to_string(),
+                    postcode: "BS7".to_string(),
+                    kwh: 125000.0,
+                },
+                Site {
+                    name: "Warehouse".to_string(),
+                    postcode: "RG10".to_string(),
+                    kwh: 48000.5,
+                },
+            ]
+        );
+
+        assert!(parse_sites("site,postcode,kwh\nHead office,BS7").is_err());
+        assert!(parse_sites("site,postcode,kwh\nHead office,BS7,lots").is_err());
+        assert!(parse_sites("site,postcode,kwh\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn write_audit() {
+        let site = Site {
+            name: "Head office".to_string(),
+            postcode: "BS7".to_string(),
+            kwh: 1000.0,
+        };
+        let audit = SiteAudit::new(site, 150.25);
+        assert_eq!(audit.emissions_kg, 150.25);
+        let mut quoted = audit.clone();
+        quoted.site.name = "Depot, \"East\"".to_string();
+
+        let mut output = Vec::new();
+        write_audit_csv(&mut output, &[audit, quoted]).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "site,postcode,kwh,mean_intensity,emissions_kg\nHead office,BS7,1000,150.2,150.2\n\
+             \"Depot, \"\"East\"\"\",BS7,1000,150.2,150.2\n"
+        );
+    }
+
+    #[test]
+    fn keep_going() {
+        let site = |name: &str, postcode: &str| Site {
+            name: name.to_string(),
+            postcode: postcode.to_string(),
+            kwh: 1000.0,
+        };
+        // invalid postcodes, rejected before querying the API
+        let sites = vec![site("Head office", "B"), site("Warehouse", "BS7 8A")];
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let options = QueryOptions::default();
+        let audit = audit_sites(&sites, "2024-01-01", &None, &options);
+        assert!(runtime.block_on(audit).is_err());
+
+        let audit = audit_sites_keep_going(&sites, "2024-01-01", &None, &options);
+        let outcome = runtime.block_on(audit);
+        assert_eq!(outcome.audits().count(), 0);
+        let failures: Vec<&str> = outcome.failures().map(|f| f.site.name.as_str()).collect();
+        assert_eq!(failures, vec!["Head office", "Warehouse"]);
+        assert!(outcome
+            .to_string()
+            .starts_with("0 sites audited, 2 failed\n  Head office: "));
+    }
+
+    #[test]
+    fn write_outcome() {
+        let site = Site {
+            name: "Head office".to_string(),
+            postcode: "BS7".to_string(),
+            kwh: 1000.0,
+        };
+        let outcome = AuditOutcome {
+            results: vec![
+                Ok(SiteAudit::new(site.clone(), 150.0)),
+                Err(SiteFailure {
+                    site: Site {
+                        postcode: "B".to_string(),
+                        ..site
+                    },
+                    error: "Invalid postcode \"B\", too short".to_string(),
+                }),
+            ],
+        };
+        assert_eq!(
+            outcome.to_string().lines().next(),
+            Some("1 sites audited, 1 failed")
+        );
+
+        let mut output = Vec::new();
+        write_audit_outcome_csv(&mut output, &outcome).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "site,postcode,kwh,mean_intensity,emissions_kg,error\n\
+             Head office,BS7,1000,150.0,150.0,\n\
+             Head office,B,1000,,,\"Invalid postcode \"\"B\"\", too short\"\n"
+        );
+    }
+}
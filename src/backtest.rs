@@ -0,0 +1,322 @@
+use chrono::{Duration, NaiveDateTime};
+
+use crate::{ApiError, Data, IntensityForDate, Result};
+
+/// Half-hour of history replayed by `backtest`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistoricalSlot {
+    pub start: NaiveDateTime,
+    /// Intensity forecast for the half-hour, what a strategy gets to see
+    pub forecast: i32,
+    /// Intensity measured for the half-hour, used to compute the emissions
+    pub actual: i32,
+}
+
+impl HistoricalSlot {
+    /// Slot from the data sent by the API, the forecast is used when there is no
+    /// actual value and the actual value when there is no forecast
+    pub fn from_data(data: &Data) -> Result<Self> {
+        let (start, actual) = data.intensity_for_date()?;
+        Ok(Self {
+            start,
+            forecast: data.intensity().forecast().unwrap_or(actual),
+            actual,
+        })
+    }
+}
+
+/// Job to schedule, running for a number of consecutive half-hours
+#[derive(Debug, Clone, PartialEq)]
+pub struct Job {
+    /// Earliest start of the job
+    pub release: NaiveDateTime,
+    /// Time by which the job must have completed
+    pub deadline: NaiveDateTime,
+    /// Number of half-hours the job runs for
+    pub slots: usize,
+    /// Electricity used by the job, spread evenly over its half-hours
+    pub kwh: f64,
+}
+
+impl Job {
+    /// Latest start allowing the job to complete by its deadline
+    pub fn latest_start(&self) -> NaiveDateTime {
+        self.deadline - Duration::minutes(30 * self.slots as i64)
+    }
+}
+
+/// Decision taken by a strategy for the current half-hour
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decision {
+    /// Starts the job now
+    Start,
+    /// Waits for the next half-hour
+    Wait,
+}
+
+/// Scheduling strategy evaluated by `backtest`
+///
+/// The strategy is asked every half-hour from the release of a job whether
+/// to start it, until it does or the deadline of the job has passed.
+pub trait Strategy {
+    /// Name of the strategy in the reports
+    fn name(&self) -> String;
+
+    /// Decides whether to start the job at `now`
+    ///
+    /// `forecast` holds the forecast intensities of the half-hours starting from
+    /// `now` up to the deadline of the job, or until the end of the history.
+    fn decide(&mut self, now: NaiveDateTime, job: &Job, forecast: &[IntensityForDate]) -> Decision;
+}
+
+/// Result of a job in a backtest
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobOutcome {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    /// Emissions of the job in kgCO2
+    pub emissions_kg: f64,
+    /// Emissions in kgCO2 had the job started on release
+    pub baseline_emissions_kg: f64,
+    /// The job completed after its deadline
+    pub missed_deadline: bool,
+}
+
+/// Result of a strategy over a set of jobs
+#[derive(Debug, Clone, PartialEq)]
+pub struct BacktestResult {
+    pub strategy: String,
+    /// One outcome per job, in the order of the jobs
+    pub outcomes: Vec<JobOutcome>,
+}
+
+impl BacktestResult {
+    /// Emissions of all the jobs, in kgCO2
+    pub fn total_emissions_kg(&self) -> f64 {
+        self.outcomes.iter().map(|o| o.emissions_kg).sum()
+    }
+
+    /// Emissions of all the jobs had they started on release, in kgCO2
+    pub fn baseline_emissions_kg(&self) -> f64 {
+        self.outcomes.iter().map(|o| o.baseline_emissions_kg).sum()
+    }
+
+    /// Emissions saved compared to starting the jobs on release, in %
+    pub fn savings_percent(&self) -> f64 {
+        let baseline = self.baseline_emissions_kg();
+        if baseline == 0.0 {
+            return 0.0;
+        }
+        (baseline - self.total_emissions_kg()) / baseline * 100.0
+    }
+
+    /// Number of jobs which completed after their deadline
+    pub fn missed_deadlines(&self) -> usize {
+        self.outcomes.iter().filter(|o| o.missed_deadline).count()
+    }
+}
+
+/// Replays the history through a strategy and measures the emissions of the jobs
+///
+/// The history must be made of consecutive half-hours in chronological order and
+/// include the release of every job. A job still waiting at its deadline, or when it
+/// would no longer fit in the history, is started regardless. Emissions are computed with the actual
+/// intensities and compared to running each job as soon as it is released.
+pub fn backtest(
+    strategy: &mut dyn Strategy,
+    history: &[HistoricalSlot],
+    jobs: &[Job],
+) -> Result<BacktestResult> {
+    let half_hour = Duration::minutes(30);
+    if history
+        .windows(2)
+        .any(|pair| pair[1].start - pair[0].start != half_hour)
+    {
+        return Err(ApiError::Error(
+            "The history must be made of consecutive half-hours".to_string(),
+        ));
+    }
+
+    let mut outcomes = Vec::with_capacity(jobs.len());
+    for job in jobs {
+        let release = history
+            .binary_search_by_key(&job.release, |slot| slot.start)
+            .map_err(|_| {
+                ApiError::Error(format!(
+                    "No history for the release of the job at {}",
+                    job.release
+                ))
+            })?;
+        if job.slots == 0 || release + job.slots > history.len() {
+            return Err(ApiError::Error(format!(
+                "Not enough history for the job released at {}",
+                job.release
+            )));
+        }
+        let last_start = history.len() - job.slots;
+
+        let mut start = release;
+        // past the deadline, the forecast would be empty
+        while start < last_start && history[start].start < job.deadline {
+            let now = history[start].start;
+            let forecast: Vec<IntensityForDate> = history[start..]
+                .iter()
+                .take_while(|slot| slot.start < job.deadline)
+                .map(|slot| (slot.start, slot.forecast))
+                .collect();
+            if strategy.decide(now, job, &forecast) == Decision::Start {
+                break;
+            }
+            start += 1;
+        }
+
+        let emissions = |first: usize| {
+            let kwh_per_slot = job.kwh / job.slots as f64;
+            history[first..first + job.slots]
+                .iter()
+                .map(|slot| f64::from(slot.actual) * kwh_per_slot / 1000.0)
+                .sum::<f64>()
+        };
+
+        let end = history[start].start + half_hour * job.slots as i32;
+        outcomes.push(JobOutcome {
+            start: history[start].start,
+            end,
+            emissions_kg: emissions(start),
+            baseline_emissions_kg: emissions(release),
+            missed_deadline: end > job.deadline,
+        });
+    }
+
+    Ok(BacktestResult {
+        strategy: strategy.name(),
+        outcomes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn half_hour(n: i64) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            + Duration::minutes(30 * n)
+    }
+
+    fn history(actuals: &[i32]) -> Vec<HistoricalSlot> {
+        actuals
+            .iter()
+            .enumerate()
+            .map(|(n, actual)| HistoricalSlot {
+                start: half_hour(n as i64),
+                forecast: actual + 10,
+                actual: *actual,
+            })
+            .collect()
+    }
+
+    /// Starts when the forecast of the current half-hour is the lowest
+    struct LowestForecast;
+
+    impl Strategy for LowestForecast {
+        fn name(&self) -> String {
+            "lowest".to_string()
+        }
+
+        fn decide(
+            &mut self,
+            _: NaiveDateTime,
+            job: &Job,
+            forecast: &[IntensityForDate],
+        ) -> Decision {
+            let candidates = forecast.len().saturating_sub(job.slots - 1).max(1);
+            let lowest = forecast[..candidates].iter().map(|(_, v)| v).min();
+            if lowest == Some(&forecast[0].1) {
+                Decision::Start
+            } else {
+                Decision::Wait
+            }
+        }
+    }
+
+    /// Never starts, checks that there is always a forecast to decide on
+    struct Procrastinator;
+
+    impl Strategy for Procrastinator {
+        fn name(&self) -> String {
+            "never".to_string()
+        }
+
+        fn decide(&mut self, _: NaiveDateTime, _: &Job, forecast: &[IntensityForDate]) -> Decision {
+            assert!(!forecast.is_empty());
+            Decision::Wait
+        }
+    }
+
+    #[test]
+    fn backtest_strategy() {
+        let history = history(&[300, 200, 100, 100, 250, 300]);
+        let job = Job {
+            release: half_hour(0),
+            deadline: half_hour(5),
+            slots: 2,
+            kwh: 10.0,
+        };
+        assert_eq!(job.latest_start(), half_hour(3));
+
+        let result = backtest(&mut LowestForecast, &history, std::slice::from_ref(&job)).unwrap();
+        assert_eq!(result.strategy, "lowest");
+        let outcome = &result.outcomes[0];
+        assert_eq!(outcome.start, half_hour(2));
+        assert_eq!(outcome.end, half_hour(4));
+        // 5 kWh at 100 gCO2/kWh twice
+        assert_eq!(outcome.emissions_kg, 1.0);
+        // 5 kWh at 300 then 200
+        assert_eq!(outcome.baseline_emissions_kg, 2.5);
+        assert!(!outcome.missed_deadline);
+        assert_eq!(result.savings_percent(), 60.0);
+        assert_eq!(result.missed_deadlines(), 0);
+
+        // forced to start at the end of the history
+        let result = backtest(&mut Procrastinator, &history, std::slice::from_ref(&job)).unwrap();
+        assert_eq!(result.outcomes[0].start, half_hour(4));
+        assert!(result.outcomes[0].missed_deadline);
+        assert_eq!(result.missed_deadlines(), 1);
+        assert!(result.savings_percent() < 0.0);
+
+        // forced to start at the deadline, before the end of the history
+        let longer = self::history(&[300, 200, 100, 100, 250, 300, 300, 300, 300]);
+        let result = backtest(&mut Procrastinator, &longer, &[job]).unwrap();
+        assert_eq!(result.outcomes[0].start, half_hour(5));
+        assert!(result.outcomes[0].missed_deadline);
+    }
+
+    #[test]
+    fn backtest_invalid() {
+        let job = Job {
+            release: half_hour(0),
+            deadline: half_hour(5),
+            slots: 2,
+            kwh: 10.0,
+        };
+        // gap
+        let mut gappy = history(&[100, 100, 100]);
+        gappy[2].start = half_hour(3);
+        assert!(backtest(&mut LowestForecast, &gappy, std::slice::from_ref(&job)).is_err());
+
+        // release not covered
+        let late = Job {
+            release: half_hour(10),
+            ..job.clone()
+        };
+        assert!(backtest(&mut LowestForecast, &history(&[100, 100]), &[late]).is_err());
+
+        // too short
+        assert!(backtest(&mut LowestForecast, &history(&[100]), &[job]).is_err());
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use futures::future::join_all;
+
+use crate::{
+    to_tuples, CarbonIntensityClient, DateRange, IntensityForDate, QueryOptions, QueryOutcome,
+    Result, Target,
+};
+
+/// Get the intensities of several targets over the same range
+///
+/// The dates are validated once, as for `get_intensities`, and the range split
+/// into the same windows for every target. The windows of all the targets are
+/// retrieved concurrently, within the limit of requests of the client. Each
+/// target is queried once if repeated, and fails on its own e.g. an unknown postcode.
+///
+/// ```no_run
+/// # use carbonintensity::{get_intensities_batch, Region, Target};
+/// # async fn run() -> carbonintensity::Result<()> {
+/// let targets = [Target::National, Target::Region(Region::London)];
+/// let results = get_intensities_batch(&targets, "2024-01-01", &Some("2024-02-01")).await?;
+/// let national = results[&Target::National].as_ref().unwrap();
+/// # Ok(())
+/// # }
+/// ```
+pub async fn get_intensities_batch(
+    targets: &[Target],
+    start: &str,
+    end: &Option<&str>,
+) -> Result<HashMap<Target, Result<Vec<IntensityForDate>>>> {
+    CarbonIntensityClient::shared()
+        .get_intensities_batch(targets, start, end)
+        .await
+}
+
+/// Same as `get_intensities_batch` but with control over the execution of the queries
+pub async fn get_intensities_batch_with_options(
+    targets: &[Target],
+    start: &str,
+    end: &Option<&str>,
+    options: &QueryOptions,
+) -> Result<HashMap<Target, Result<QueryOutcome>>> {
+    CarbonIntensityClient::shared()
+        .get_intensities_batch_with_options(targets, start, end, options)
+        .await
+}
+
+impl CarbonIntensityClient {
+    /// Intensities of several targets, see `get_intensities_batch()`
+    pub async fn get_intensities_batch(
+        &self,
+        targets: &[Target],
+        start: &str,
+        end: &Option<&str>,
+    ) -> Result<HashMap<Target, Result<Vec<IntensityForDate>>>> {
+        let outcomes = self
+            .get_intensities_batch_with_options(targets, start, end, &QueryOptions::default())
+            .await?;
+        Ok(outcomes
+            .into_iter()
+            .map(|(target, outcome)| (target, outcome.map(|outcome| outcome.intensities)))
+            .collect())
+    }
+
+    /// See `get_intensities_batch_with_options()`
+    ///
+    /// Returns an `ApiError` if the dates are invalid, the errors of each target
+    /// being in the map.
+    pub async fn get_intensities_batch_with_options(
+        &self,
+        targets: &[Target],
+        start: &str,
+        end: &Option<&str>,
+        options: &QueryOptions,
+    ) -> Result<HashMap<Target, Result<QueryOutcome>>> {
+        let range = DateRange::parse(start, end, options.clock())?;
+        Ok(self
+            .get_intensities_batch_in_range(targets, &range, options)
+            .await)
+    }
+
+    /// Intensities of several targets over a range already validated
+    pub(crate) async fn get_intensities_batch_in_range(
+        &self,
+        targets: &[Target],
+        range: &DateRange,
+        options: &QueryOptions,
+    ) -> HashMap<Target, Result<QueryOutcome>> {
+        let mut unique: Vec<&Target> = Vec::with_capacity(targets.len());
+        for target in targets {
+            if !unique.contains(&target) {
+                unique.push(target);
+            }
+        }
+
+        let outcomes = join_all(unique.iter().map(|target| async {
+            let outcome = self
+                .get_detailed_intensities_in_range(target, range, options)
+                .await?;
+            Ok(QueryOutcome {
+                intensities: to_tuples(outcome.intensities)?,
+                warnings: outcome.warnings,
+                report: outcome.report,
+            })
+        }))
+        .await;
+        unique.into_iter().cloned().zip(outcomes).collect()
+    }
+}
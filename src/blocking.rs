@@ -0,0 +1,311 @@
+//! Synchronous API, for programs which don't use async
+//!
+//! Mirrors the functions of the crate on top of `reqwest::blocking`, without
+//! an async runtime to set up. The windows of a range are requested one after
+//! the other. As with `reqwest::blocking`, these functions must not be called
+//! from within an async runtime.
+//!
+//! ```no_run
+//! use carbonintensity::{blocking, Region, Target};
+//!
+//! let intensity = blocking::get_intensity(&Target::Region(Region::London)).unwrap();
+//! println!("{intensity} gCO2/kWh");
+//! ```
+
+use std::sync::{Arc, LazyLock};
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    check_forecast, check_range_data, current_data, current_path, current_value, error_from_body,
+    first, forecast_bounds, mix::GenerationData, parse_body, range_path, regional_intensities,
+    report, runtime::Stopwatch, window_url, AllRegionsData, ApiError, Chunking, ClientBuilder,
+    Data, DateBound, DateRange, ExecutionReport, FuelMix, Intensity, IntensityForDate,
+    NationalData, PowerData, Provenance, QueryOptions, QueryOutcome, RegionalIntensity, Result,
+    RetryPolicy, Root, Target, Warning, WindowReport,
+};
+
+/// Blocking version of `carbonintensity::CarbonIntensityClient`
+#[derive(Debug, Clone)]
+pub struct CarbonIntensityClient {
+    http: reqwest::blocking::Client,
+    base_url: Arc<str>,
+    retry_policy: RetryPolicy,
+    chunking: Chunking,
+}
+
+impl Default for CarbonIntensityClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CarbonIntensityClient {
+    /// Client with the default configuration
+    ///
+    /// # Panics
+    ///
+    /// Like `reqwest::blocking::Client::new()`, if the TLS backend can't be initialised.
+    pub fn new() -> Self {
+        Self::from_builder(crate::CarbonIntensityClient::builder())
+            .expect("Error initialising the HTTP client")
+    }
+
+    /// Client with the default configuration used by the free functions, created on
+    /// first use, so that they share its connections
+    fn shared() -> &'static Self {
+        static SHARED: LazyLock<CarbonIntensityClient> = LazyLock::new(CarbonIntensityClient::new);
+        &SHARED
+    }
+
+    /// Blocking client configured with `CarbonIntensityClient::builder()`
+    ///
+    /// The base URL, timeout, user agent, retry policy and chunking are applied.
+    /// The caches, transport and concurrent requests only apply to the async client.
+    pub fn from_builder(builder: ClientBuilder) -> Result<Self> {
+        builder.chunking.validate()?;
+        // no timeout by default, as for the async client
+        let http = reqwest::blocking::Client::builder()
+            .user_agent(builder.user_agent)
+            .timeout(builder.timeout)
+            .build()?;
+        Ok(Self {
+            http,
+            base_url: builder.base_url,
+            retry_policy: builder.retry_policy,
+            chunking: builder.chunking,
+        })
+    }
+
+    /// Root of the API the requests are sent to, without a trailing slash
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// See `carbonintensity::get_intensity()`
+    pub fn get_intensity(&self, target: &Target) -> Result<i32> {
+        let intensity = self.get_current_intensity(target)?;
+        current_value(target, &intensity)
+    }
+
+    /// See `carbonintensity::get_current_intensity()`
+    pub fn get_current_intensity(&self, target: &Target) -> Result<Intensity> {
+        let url = format!("{}/{}", self.base_url, current_path(target)?);
+        let data = if *target != Target::National {
+            current_data(first(self.get_response::<Root>(&url)?.data)?)?
+        } else {
+            first(self.get_response::<NationalData>(&url)?.data)?
+        };
+        check_forecast(data.intensity)
+    }
+
+    /// See `carbonintensity::get_regional_intensity()`
+    pub fn get_regional_intensity(&self, target: &Target) -> Result<RegionalIntensity> {
+        if *target == Target::National {
+            return Err(ApiError::Error(
+                "A region or postcode is required".to_string(),
+            ));
+        }
+        let url = format!("{}/{}", self.base_url, current_path(target)?);
+        let region_data = first(self.get_response::<Root>(&url)?.data)?;
+        RegionalIntensity::try_from(region_data)
+    }
+
+    /// See `carbonintensity::get_all_regional_intensities()`
+    pub fn get_all_regional_intensities(&self) -> Result<Vec<RegionalIntensity>> {
+        let url = format!("{}/regional", self.base_url);
+        let data = self.get_response::<AllRegionsData>(&url)?;
+        Ok(regional_intensities(first(data.data)?))
+    }
+
+    /// See `carbonintensity::get_intensities()`
+    pub fn get_intensities(
+        &self,
+        target: &Target,
+        start: &str,
+        end: &Option<&str>,
+    ) -> Result<Vec<IntensityForDate>> {
+        let outcome =
+            self.get_intensities_with_options(target, start, end, &QueryOptions::default())?;
+        Ok(outcome.intensities)
+    }
+
+    /// See `carbonintensity::get_intensities_with_options()`
+    pub fn get_intensities_with_options(
+        &self,
+        target: &Target,
+        start: &str,
+        end: &Option<&str>,
+        options: &QueryOptions,
+    ) -> Result<QueryOutcome> {
+        let outcome = self.get_detailed_intensities(target, start, end, options)?;
+        Ok(QueryOutcome {
+            intensities: outcome
+                .intensities
+                .iter()
+                .map(Data::intensity_for_date)
+                .collect::<Result<_>>()?,
+            warnings: outcome.warnings,
+            report: outcome.report,
+        })
+    }
+
+    /// See `carbonintensity::get_detailed_intensities()`
+    pub fn get_detailed_intensities(
+        &self,
+        target: &Target,
+        start: &str,
+        end: &Option<&str>,
+        options: &QueryOptions,
+    ) -> Result<QueryOutcome<Data>> {
+        let start: DateBound = start.parse()?;
+        let end = end.map(str::parse::<DateBound>).transpose()?;
+        let range = DateRange::new(start, end, options.clock())?;
+        self.get_detailed_intensities_in_range(target, &range, options)
+    }
+
+    /// See `carbonintensity::get_detailed_intensities_in_range()`
+    ///
+    /// The deadline of the options is checked before each window, a request
+    /// in progress being bounded by the timeout of the client instead.
+    /// The windows being requested one after the other, the concurrency of the
+    /// execution report is 1.
+    pub fn get_detailed_intensities_in_range(
+        &self,
+        target: &Target,
+        range: &DateRange,
+        options: &QueryOptions,
+    ) -> Result<QueryOutcome<Data>> {
+        let path = range_path(target)?;
+
+        let started = Stopwatch::start();
+        let now = options.clock().now();
+        let ranges = range.windows(now, &self.chunking);
+        let base_url = options.base_url().unwrap_or(&self.base_url);
+
+        let mut outcome = QueryOutcome::default();
+        let mut windows = Vec::with_capacity(ranges.len());
+        for window @ &(start, end) in &ranges {
+            let expired = options
+                .deadline()
+                .is_some_and(|deadline| started.elapsed() >= deadline);
+            if expired {
+                outcome
+                    .warnings
+                    .push(Warning::DeadlineExceeded { start, end });
+                windows.push(WindowReport {
+                    start,
+                    end,
+                    records: 0,
+                    elapsed: None,
+                });
+                continue;
+            }
+
+            let requested = Stopwatch::start();
+            let url = window_url(base_url, target, &path, window, now);
+            let mut data = if *target == Target::National {
+                self.get_response::<NationalData>(&url)?.data
+            } else {
+                self.get_response::<PowerData>(&url)?.data.data
+            };
+            if options.provenance() {
+                Provenance::stamp(&mut data, &url);
+            }
+            windows.push(WindowReport {
+                start,
+                end,
+                records: data.len(),
+                elapsed: Some(requested.elapsed()),
+            });
+            outcome.intensities.extend(data);
+        }
+
+        let report_windows = ExecutionReport {
+            windows,
+            wall_time: std::time::Duration::ZERO,
+            max_concurrent_requests: 1,
+        };
+        outcome.report = report(Some(report_windows), started, options);
+        let end = ranges.last().map(|window| window.1);
+        check_range_data(&mut outcome, range, end, options)?;
+        Ok(outcome)
+    }
+
+    /// See `carbonintensity::get_forecast()`
+    pub fn get_forecast(&self, target: &Target, hours: u32) -> Result<Vec<Data>> {
+        let options = QueryOptions::default();
+        let (start, end) = forecast_bounds(hours, &options)?;
+        let outcome = self.get_detailed_intensities(target, &start, &Some(&end), &options)?;
+        Ok(outcome.intensities)
+    }
+
+    /// See `carbonintensity::get_generation_mix()`
+    pub fn get_generation_mix(&self, target: &Target) -> Result<FuelMix> {
+        if *target == Target::National {
+            let url = format!("{}/generation", self.base_url);
+            return self.get_response::<GenerationData>(&url)?.current_mix();
+        }
+
+        let url = format!("{}/{}", self.base_url, current_path(target)?);
+        let region_data = first(self.get_response::<Root>(&url)?.data)?;
+        FuelMix::try_from(&first(region_data.data)?)
+    }
+
+    /// Makes a GET request to the given URL and deserialises the JSON response,
+    /// retrying the transient failures according to the `RetryPolicy`
+    fn get_response<T>(&self, url: &str) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let mut retry = 0;
+        let body = loop {
+            match self.get_body(url) {
+                Err(error) if error.is_transient() && retry < self.retry_policy.max_retries() => {
+                    std::thread::sleep(self.retry_policy.delay(retry));
+                    retry += 1;
+                }
+                result => break result?,
+            }
+        };
+        parse_body(body.as_ref())
+    }
+
+    /// Body of a successful response, as received
+    fn get_body(&self, url: &str) -> Result<impl AsRef<[u8]>> {
+        #[cfg(debug_assertions)]
+        eprintln!("GET {url}");
+        let response = self.http.get(url).send()?;
+        let status = response.status();
+        let body = response.bytes()?;
+        if !status.is_success() {
+            let body = String::from_utf8_lossy(&body).into_owned();
+            return Err(error_from_body(status, body));
+        }
+        Ok(body)
+    }
+}
+
+/// Current carbon intensity for a target, see `carbonintensity::get_intensity()`
+pub fn get_intensity(target: &Target) -> Result<i32> {
+    CarbonIntensityClient::shared().get_intensity(target)
+}
+
+/// Current carbon intensity for a target with its index, see `carbonintensity::get_current_intensity()`
+pub fn get_current_intensity(target: &Target) -> Result<Intensity> {
+    CarbonIntensityClient::shared().get_current_intensity(target)
+}
+
+/// Intensities for a target in 30 minutes windows, see `carbonintensity::get_intensities()`
+pub fn get_intensities(
+    target: &Target,
+    start: &str,
+    end: &Option<&str>,
+) -> Result<Vec<IntensityForDate>> {
+    CarbonIntensityClient::shared().get_intensities(target, start, end)
+}
+
+/// Forecasts for a target over the coming hours, see `carbonintensity::get_forecast()`
+pub fn get_forecast(target: &Target, hours: u32) -> Result<Vec<Data>> {
+    CarbonIntensityClient::shared().get_forecast(target, hours)
+}
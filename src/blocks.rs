@@ -0,0 +1,240 @@
+use chrono::{Duration, NaiveDateTime, NaiveTime, TimeZone, Timelike};
+
+use crate::{ApiError, IntensityForDate, Result};
+
+const MINUTES_IN_DAY: i64 = 24 * 60;
+
+/// Division of the day into blocks of equal length
+///
+/// The blocks are in the local time of a timezone, e.g. the EFA blocks follow
+/// the time in the UK, see `aggregate_blocks()`.
+///
+/// ```
+/// # use chrono::{Duration, NaiveTime};
+/// # use carbonintensity::BlockDefinition;
+/// // 4-hour blocks starting at 23:00, i.e. EFA blocks
+/// let start = NaiveTime::from_hms_opt(23, 0, 0).unwrap();
+/// let blocks = BlockDefinition::new(start, Duration::hours(4)).unwrap();
+/// assert_eq!(blocks, BlockDefinition::efa());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockDefinition {
+    first_start: NaiveTime,
+    length_minutes: i64,
+}
+
+impl BlockDefinition {
+    /// Blocks of `length` starting at `first_start` each day
+    ///
+    /// The length must be a multiple of 30 minutes and divide a day evenly.
+    pub fn new(first_start: NaiveTime, length: Duration) -> Result<Self> {
+        let length_minutes = length.num_minutes();
+        if length_minutes <= 0 || length_minutes % 30 != 0 || MINUTES_IN_DAY % length_minutes != 0 {
+            return Err(ApiError::Error(format!(
+                "Invalid block length: {length_minutes} minutes"
+            )));
+        }
+        Ok(Self {
+            first_start,
+            length_minutes,
+        })
+    }
+
+    /// Electricity Forward Agreement blocks: six 4-hour blocks, the first one starting at 23:00
+    pub fn efa() -> Self {
+        Self {
+            first_start: NaiveTime::from_hms_opt(23, 0, 0).unwrap(),
+            length_minutes: 4 * 60,
+        }
+    }
+
+    /// Number of blocks in a day
+    pub fn blocks_per_day(&self) -> u32 {
+        (MINUTES_IN_DAY / self.length_minutes) as u32
+    }
+
+    /// Start of the block containing `time` and its number in the day, starting at 1
+    fn locate(&self, time: NaiveDateTime) -> (NaiveDateTime, u32) {
+        let first_start = i64::from(self.first_start.num_seconds_from_midnight() / 60);
+        let minute_of_day = i64::from(time.num_seconds_from_midnight() / 60);
+        let since_first_start = (minute_of_day - first_start).rem_euclid(MINUTES_IN_DAY);
+
+        let offset = since_first_start % self.length_minutes;
+        let start =
+            time.with_second(0).unwrap().with_nanosecond(0).unwrap() - Duration::minutes(offset);
+        let number = (since_first_start / self.length_minutes) as u32 + 1;
+        (start, number)
+    }
+}
+
+/// Intensities aggregated over a block
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockIntensity {
+    /// Start of the block in UTC
+    pub start: NaiveDateTime,
+    /// End of the block in UTC, the blocks spanning a change of the clocks
+    /// are an hour longer or shorter
+    pub end: NaiveDateTime,
+    /// Position of the block within the day, starting at 1
+    pub number: u32,
+    pub mean: f64,
+    pub min: i32,
+    pub max: i32,
+    /// Number of half-hours in the block which had a value
+    pub count: usize,
+}
+
+/// Aggregates intensities into the blocks of a `BlockDefinition`
+///
+/// The intensities are expected to be sorted by date, as returned by
+/// `get_intensities`, with their dates in UTC. The blocks are in the local time of
+/// `timezone`, e.g. `chrono_tz::Europe::London` for the EFA blocks, or `chrono::Local`.
+/// Blocks without any value are not returned.
+///
+/// ```
+/// # use chrono::{FixedOffset, NaiveDate};
+/// # use carbonintensity::{aggregate_blocks, BlockDefinition};
+/// // 22:00 UTC is 23:00 in the UK in summer, the start of the first EFA block
+/// let time = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap().and_hms_opt(22, 0, 0).unwrap();
+/// let bst = FixedOffset::east_opt(3600).unwrap();
+/// let blocks = aggregate_blocks(&[(time, 150)], &BlockDefinition::efa(), &bst);
+/// assert_eq!((blocks[0].start, blocks[0].number), (time, 1));
+/// ```
+pub fn aggregate_blocks<Tz: TimeZone>(
+    intensities: &[IntensityForDate],
+    blocks: &BlockDefinition,
+    timezone: &Tz,
+) -> Vec<BlockIntensity> {
+    let mut results: Vec<BlockIntensity> = Vec::new();
+    // start of the last block in local time
+    let mut last_start = None;
+
+    for &(time, value) in intensities {
+        let local = timezone.from_utc_datetime(&time).naive_local();
+        let (local_start, number) = blocks.locate(local);
+        match results.last_mut() {
+            Some(block) if last_start == Some(local_start) => {
+                let total = block.mean * block.count as f64 + f64::from(value);
+                block.count += 1;
+                block.mean = total / block.count as f64;
+                block.min = block.min.min(value);
+                block.max = block.max.max(value);
+            }
+            _ => {
+                last_start = Some(local_start);
+                let local_end = local_start + Duration::minutes(blocks.length_minutes);
+                // a local time skipped when the clocks go forward is taken
+                // at the offset of the intensity
+                let to_utc = |local_time: NaiveDateTime| {
+                    timezone
+                        .from_local_datetime(&local_time)
+                        .earliest()
+                        .map_or(time + (local_time - local), |date| date.naive_utc())
+                };
+                results.push(BlockIntensity {
+                    start: to_utc(local_start),
+                    end: to_utc(local_end),
+                    number,
+                    mean: f64::from(value),
+                    min: value,
+                    max: value,
+                    count: 1,
+                });
+            }
+        }
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, NaiveDate, Utc};
+
+    use super::*;
+
+    fn date_time(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn invalid_definitions() {
+        let midnight = NaiveTime::from_hms_opt(0, 0, 0).unwrap();
+        assert!(BlockDefinition::new(midnight, Duration::hours(5)).is_err());
+        assert!(BlockDefinition::new(midnight, Duration::minutes(45)).is_err());
+        assert!(BlockDefinition::new(midnight, Duration::zero()).is_err());
+        assert!(BlockDefinition::new(midnight, Duration::hours(24)).is_ok());
+        assert!(BlockDefinition::new(midnight, Duration::minutes(90)).is_ok());
+    }
+
+    #[test]
+    fn efa_blocks() {
+        let efa = BlockDefinition::efa();
+        assert_eq!(efa.blocks_per_day(), 6);
+
+        // 23:00 - 03:00 is block 1
+        assert_eq!(efa.locate(date_time(1, 23, 30)), (date_time(1, 23, 0), 1));
+        assert_eq!(efa.locate(date_time(2, 2, 30)), (date_time(1, 23, 0), 1));
+        // 03:00 - 07:00 is block 2
+        assert_eq!(efa.locate(date_time(2, 3, 0)), (date_time(2, 3, 0), 2));
+        // 19:00 - 23:00 is block 6
+        assert_eq!(efa.locate(date_time(2, 22, 30)), (date_time(2, 19, 0), 6));
+    }
+
+    #[test]
+    fn aggregate() {
+        let intensities = vec![
+            (date_time(1, 22, 0), 200),
+            (date_time(1, 22, 30), 210),
+            (date_time(1, 23, 0), 100),
+            (date_time(1, 23, 30), 120),
+            (date_time(2, 0, 0), 110),
+        ];
+        let blocks = aggregate_blocks(&intensities, &BlockDefinition::efa(), &Utc);
+
+        let expected = vec![
+            BlockIntensity {
+                start: date_time(1, 19, 0),
+                end: date_time(1, 23, 0),
+                number: 6,
+                mean: 205.0,
+                min: 200,
+                max: 210,
+                count: 2,
+            },
+            BlockIntensity {
+                start: date_time(1, 23, 0),
+                end: date_time(2, 3, 0),
+                number: 1,
+                mean: 110.0,
+                min: 100,
+                max: 120,
+                count: 3,
+            },
+        ];
+        assert_eq!(blocks, expected);
+
+        assert!(aggregate_blocks(&[], &BlockDefinition::efa(), &Utc).is_empty());
+    }
+
+    #[test]
+    fn aggregate_in_local_time() {
+        // 22:30 UTC is 23:30 in BST, in the first block which started at 22:00 UTC
+        let intensities = vec![(date_time(1, 21, 30), 200), (date_time(1, 22, 30), 100)];
+        let bst = FixedOffset::east_opt(3600).unwrap();
+        let blocks = aggregate_blocks(&intensities, &BlockDefinition::efa(), &bst);
+
+        let starts: Vec<_> = blocks
+            .iter()
+            .map(|block| (block.start, block.number))
+            .collect();
+        assert_eq!(
+            starts,
+            vec![(date_time(1, 18, 0), 6), (date_time(1, 22, 0), 1)]
+        );
+        assert_eq!(blocks[1].end, date_time(2, 2, 0));
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use bytes::Bytes;
+use chrono::NaiveDateTime;
+
+/// Body of a response, with the half-hour it was received in
+type Entry = (NaiveDateTime, Bytes);
+
+/// Bodies of the responses of the API, by URL
+///
+/// The API publishes new data every half-hour, a response is reused until the
+/// end of the half-hour in which it was received.
+#[derive(Debug, Default)]
+pub(crate) struct ResponseCache {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl ResponseCache {
+    /// Body received for the URL during the half-hour, if any
+    pub(crate) fn get(&self, url: &str, half_hour: NaiveDateTime) -> Option<Bytes> {
+        let entries = self.entries.lock().ok()?;
+        entries
+            .get(url)
+            .filter(|(received, _)| *received == half_hour)
+            .map(|(_, body)| body.clone())
+    }
+
+    /// Keeps the body until the end of the half-hour, dropping those of the previous ones
+    pub(crate) fn insert(&self, url: &str, body: Bytes, half_hour: NaiveDateTime) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.retain(|_, (received, _)| *received == half_hour);
+            entries.insert(url.to_string(), (half_hour, body));
+        }
+    }
+}
+
+/// Bodies of the responses for settled windows, one JSON file per URL in a directory
+///
+/// The data of the past no longer changes, a long range queried again only
+/// needs the windows which are not stored yet.
+#[derive(Debug)]
+pub(crate) struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    pub(crate) fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    /// Body stored for the URL, if any
+    pub(crate) fn get(&self, url: &str) -> Option<Vec<u8>> {
+        fs::read(self.path(url)).ok()
+    }
+
+    /// Stores the body, replacing the file in one go so that a file is never
+    /// read while partially written
+    pub(crate) fn insert(&self, url: &str, body: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(url);
+        let partial = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&partial, body)?;
+        fs::rename(&partial, path)
+    }
+
+    /// File of a URL, e.g. `api.carbonintensity.org.uk_intensity_2024-01-01T00_01Z_2024-01-14T00_01Z.json`
+    fn path(&self, url: &str) -> PathBuf {
+        let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+        let name: String = url
+            .trim_end_matches('/')
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '.' => c,
+                _ => '_',
+            })
+            .collect();
+        self.dir.join(format!("{name}.json"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn expiry() {
+        let half_hour = |minute| {
+            NaiveDate::from_ymd_opt(2024, 1, 1)
+                .unwrap()
+                .and_hms_opt(12, minute, 0)
+                .unwrap()
+        };
+        let cache = ResponseCache::default();
+        cache.insert("/intensity", Bytes::from_static(b"{}"), half_hour(0));
+        assert_eq!(
+            cache.get("/intensity", half_hour(0)).as_deref(),
+            Some(&b"{}"[..])
+        );
+        assert_eq!(cache.get("/regional", half_hour(0)), None);
+        assert_eq!(cache.get("/intensity", half_hour(30)), None);
+
+        // the responses of the previous half-hours are dropped
+        cache.insert("/regional", Bytes::from_static(b"[]"), half_hour(30));
+        assert_eq!(cache.entries.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn disk() {
+        let dir = std::env::temp_dir().join(format!("disk_cache_{}", std::process::id()));
+        let cache = DiskCache::new(&dir);
+        let url =
+            "https://api.carbonintensity.org.uk/intensity/2024-01-01T00:01Z/2024-01-14T00:01Z/";
+        assert_eq!(
+            cache.path(url),
+            dir.join(
+                "api.carbonintensity.org.uk_intensity_2024-01-01T00_01Z_2024-01-14T00_01Z.json"
+            )
+        );
+
+        assert_eq!(cache.get(url), None);
+        cache.insert(url, b"{}").unwrap();
+        assert_eq!(cache.get(url), Some(b"{}".to_vec()));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,555 @@
+#[cfg(feature = "cache")]
+use std::path::Path;
+use std::{
+    collections::hash_map::RandomState,
+    env,
+    hash::{BuildHasher, Hasher},
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+use tokio::sync::Semaphore;
+
+#[cfg(feature = "cache")]
+use crate::{
+    cache::{DiskCache, ResponseCache},
+    planner::start_of_half_hour,
+    Clock, SystemClock,
+};
+use crate::{
+    error_from_body, parse_body, runtime, ApiError, Chunking, QueryOptions, Result, Transport,
+    BASE_URL,
+};
+
+/// Overrides the default base URL when set, e.g. to go through a proxy
+pub const BASE_URL_VAR: &str = "CARBONINTENSITY_BASE_URL";
+
+/// Directory of the disk cache when set, see `ClientBuilder::with_disk_cache()`
+#[cfg(feature = "cache")]
+pub const CACHE_DIR_VAR: &str = "CARBONINTENSITY_CACHE_DIR";
+
+/// Requests sent at the same time by a client and its clones by default
+pub const MAX_CONCURRENT_REQUESTS: usize = 6;
+
+/// User agent sent by default, e.g. "carbonintensity-api/0.4.0"
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Retries of the requests which failed temporarily
+///
+/// A request is retried when it fails with a transient error, see `ApiError::is_transient()`.
+/// The delay doubles after each retry. With jitter, the default, each delay is drawn
+/// between half and all of it so that clients failing together don't retry together.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use carbonintensity::RetryPolicy;
+/// let policy = RetryPolicy::new(3, Duration::from_millis(200));
+/// assert_eq!(policy.max_retries(), 3);
+/// assert_eq!(RetryPolicy::default(), RetryPolicy::none());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    initial_backoff: Duration,
+    jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl RetryPolicy {
+    /// Retries up to `max_retries` times, waiting `initial_backoff` before the first retry
+    pub fn new(max_retries: u32, initial_backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            initial_backoff,
+            jitter: true,
+        }
+    }
+
+    /// Randomises the delays, enabled by default
+    pub fn with_jitter(mut self, enabled: bool) -> Self {
+        self.jitter = enabled;
+        self
+    }
+
+    /// Never retries, the default
+    pub fn none() -> Self {
+        Self::new(0, Duration::ZERO)
+    }
+
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    pub fn initial_backoff(&self) -> Duration {
+        self.initial_backoff
+    }
+
+    pub fn jitter(&self) -> bool {
+        self.jitter
+    }
+
+    /// Longest delay before a retry, `initial_backoff` for the first one (`retry` 0)
+    /// and doubling for each one after
+    fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2_u32.saturating_pow(retry))
+    }
+
+    /// Delay before a retry, randomised if jitter is enabled
+    pub(crate) fn delay(&self, retry: u32) -> Duration {
+        let backoff = self.backoff(retry);
+        if !self.jitter {
+            return backoff;
+        }
+        let half = backoff / 2;
+        let range = u64::try_from(half.as_nanos()).unwrap_or(u64::MAX);
+        let random = RandomState::new().build_hasher().finish();
+        half + Duration::from_nanos(
+            random
+                .checked_rem(range.saturating_add(1))
+                .unwrap_or(random),
+        )
+    }
+}
+
+/// Client of the Carbon Intensity API
+///
+/// Its methods mirror the free functions of the crate, which share a client with
+/// the default configuration, created on first use. A client reuses its connections
+/// across requests and is cheap to clone, the clones sharing the connections
+/// and the cache, if enabled with `ClientBuilder::with_cache()`.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use carbonintensity::{CarbonIntensityClient, RetryPolicy};
+/// let client = CarbonIntensityClient::builder()
+///     .with_timeout(Duration::from_secs(10))
+///     .with_retry_policy(RetryPolicy::new(2, Duration::from_millis(500)))
+///     .build()
+///     .unwrap();
+/// assert_eq!(client.base_url(), "https://api.carbonintensity.org.uk");
+/// ```
+#[derive(Debug, Clone)]
+pub struct CarbonIntensityClient {
+    transport: Arc<dyn Transport>,
+    base_url: Arc<str>,
+    /// Applied to each request in the browser, where reqwest has no timeout
+    #[cfg(target_arch = "wasm32")]
+    timeout: Option<Duration>,
+    retry_policy: RetryPolicy,
+    chunking: Chunking,
+    /// Permits of the requests in flight, shared by the clones
+    requests: Arc<Semaphore>,
+    max_concurrent_requests: usize,
+    #[cfg(feature = "cache")]
+    cache: Option<Arc<ResponseCache>>,
+    #[cfg(feature = "cache")]
+    disk_cache: Option<Arc<DiskCache>>,
+    #[cfg(feature = "cache")]
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for CarbonIntensityClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CarbonIntensityClient {
+    /// Client with the default configuration
+    ///
+    /// # Panics
+    ///
+    /// Like `reqwest::Client::new()`, if the TLS backend can't be initialised.
+    pub fn new() -> Self {
+        Self::builder()
+            .build()
+            .expect("Error initialising the HTTP client")
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    /// Client with the default configuration used by the free functions, created
+    /// on first use, so that they share its connections and limit of concurrent requests
+    ///
+    /// Its responses are not cached, see `ClientBuilder::with_cache()`.
+    pub(crate) fn shared() -> &'static Self {
+        static SHARED: LazyLock<CarbonIntensityClient> = LazyLock::new(CarbonIntensityClient::new);
+        &SHARED
+    }
+
+    /// Root of the API the requests are sent to, without a trailing slash
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
+    /// How the range queries are split into requests
+    pub fn chunking(&self) -> &Chunking {
+        &self.chunking
+    }
+
+    pub fn max_concurrent_requests(&self) -> usize {
+        self.max_concurrent_requests
+    }
+
+    /// Clock telling when the cached responses expire
+    #[cfg(feature = "middleware")]
+    pub(crate) fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    /// Root of the API for a range query, unless overridden in the options
+    pub(crate) fn base_url_for<'a>(&'a self, options: &'a QueryOptions) -> &'a str {
+        options.base_url().unwrap_or(&self.base_url)
+    }
+
+    /// Makes a GET request to the given URL
+    ///
+    /// Deserialise the JSON response as `T` and returns Ok<T> if all is well.
+    /// Returns an `ApiError` when the HTTP request failed or the response body
+    /// couldn't be deserialised as a `T` value. Transient failures are retried
+    /// according to the `RetryPolicy`.
+    pub(crate) async fn get_response<T>(&self, url: &str) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        self.fetch(url, false, true).await
+    }
+
+    /// Same as `get_response` for the window of a range query, stored in the
+    /// disk cache if there is one and the data of the window is settled
+    ///
+    /// The task of the window holds one of the `permits()` already, see `runtime::spawn_window()`.
+    pub(crate) async fn get_window_response<T>(&self, url: &str, settled: bool) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        self.fetch(url, settled, false).await
+    }
+
+    /// Permits of the requests in flight, one being held by each window of a range query
+    pub(crate) fn permits(&self) -> Arc<Semaphore> {
+        self.requests.clone()
+    }
+
+    /// Response to a request, from the caches if possible, waiting for a permit
+    /// for each attempt if `acquire` is set
+    #[cfg_attr(not(feature = "cache"), allow(unused_variables))]
+    async fn fetch<T>(&self, url: &str, settled: bool, acquire: bool) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        #[cfg(feature = "cache")]
+        let disk_cache = self.disk_cache.as_ref().filter(|_| settled);
+        #[cfg(feature = "cache")]
+        if let Some(body) = disk_cache.and_then(|cache| cache.get(url)) {
+            // downloaded again if the file is corrupted
+            if let Ok(parsed) = parse_body(&body) {
+                return Ok(parsed);
+            }
+        }
+
+        // the settled windows, e.g. of a backfill, aren't kept in memory
+        #[cfg(feature = "cache")]
+        let cache = self.cache.as_ref().filter(|_| !settled);
+        #[cfg(feature = "cache")]
+        let half_hour = start_of_half_hour(self.clock.now());
+        #[cfg(feature = "cache")]
+        if let Some(body) = cache.and_then(|cache| cache.get(url, half_hour)) {
+            return parse_body(body.as_ref());
+        }
+
+        let mut retry = 0;
+        let body = loop {
+            match self.get_body(url, acquire).await {
+                Err(error) if error.is_transient() && retry < self.retry_policy.max_retries => {
+                    runtime::sleep(self.retry_policy.delay(retry)).await;
+                    retry += 1;
+                }
+                result => break result?,
+            }
+        };
+
+        let parsed = parse_body(body.as_ref())?;
+        #[cfg(feature = "cache")]
+        if let Some(cache) = disk_cache {
+            // the response is still returned if it can't be stored
+            let _ = cache.insert(url, body.as_ref());
+        }
+        #[cfg(feature = "cache")]
+        if let Some(cache) = cache {
+            cache.insert(url, body, half_hour);
+        }
+        Ok(parsed)
+    }
+
+    /// Body of a successful response
+    async fn get_body(&self, url: &str, acquire: bool) -> Result<Bytes> {
+        // the retries of a request release its permit while they wait,
+        // those of a window keeping the permit of its task
+        let _permit = match acquire {
+            true => self.requests.acquire().await.ok(),
+            false => None,
+        };
+        #[cfg(debug_assertions)]
+        eprintln!("GET {url}");
+        #[cfg(not(target_arch = "wasm32"))]
+        let response = self.transport.get(url).await?;
+        #[cfg(target_arch = "wasm32")]
+        let response = match self.timeout {
+            Some(timeout) => runtime::timeout(timeout, self.transport.get(url))
+                .await
+                .ok_or_else(|| ApiError::Error(format!("Request timed out: {url}")))?,
+            None => self.transport.get(url).await,
+        }?;
+
+        if !response.status.is_success() {
+            let body = String::from_utf8_lossy(&response.body).into_owned();
+            return Err(error_from_body(response.status, body));
+        }
+
+        Ok(response.body)
+    }
+}
+
+/// Configuration of a `CarbonIntensityClient`
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    pub(crate) base_url: Arc<str>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) user_agent: String,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) chunking: Chunking,
+    max_concurrent_requests: usize,
+    #[cfg(feature = "cache")]
+    cache: bool,
+    #[cfg(feature = "cache")]
+    disk_cache: Option<Arc<DiskCache>>,
+    transport: Option<Arc<dyn Transport>>,
+    #[cfg(feature = "cache")]
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self {
+            base_url: default_base_url(env::var(BASE_URL_VAR).ok()),
+            timeout: None,
+            user_agent: USER_AGENT.to_string(),
+            retry_policy: RetryPolicy::default(),
+            chunking: Chunking::default(),
+            max_concurrent_requests: MAX_CONCURRENT_REQUESTS,
+            #[cfg(feature = "cache")]
+            cache: false,
+            #[cfg(feature = "cache")]
+            disk_cache: env::var_os(CACHE_DIR_VAR)
+                .filter(|dir| !dir.is_empty())
+                .map(|dir| Arc::new(DiskCache::new(Path::new(&dir)))),
+            transport: None,
+            #[cfg(feature = "cache")]
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+/// Base URL set in the environment if any, the public API otherwise
+fn default_base_url(from_env: Option<String>) -> Arc<str> {
+    match from_env {
+        Some(base_url) if !base_url.trim().is_empty() => {
+            Arc::from(base_url.trim().trim_end_matches('/'))
+        }
+        _ => Arc::from(BASE_URL),
+    }
+}
+
+impl ClientBuilder {
+    /// Root of the API, defaults to the value of `BASE_URL_VAR` if set
+    /// or to <https://api.carbonintensity.org.uk>
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = Arc::from(base_url.trim_end_matches('/'));
+        self
+    }
+
+    /// Time allowed for each request, from connecting to reading the whole response
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Defaults to the name and version of the crate
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Splits the range queries into windows of another length, or across years
+    ///
+    /// The windows are requested concurrently, shorter ones sending more requests.
+    pub fn with_chunking(mut self, chunking: Chunking) -> Self {
+        self.chunking = chunking;
+        self
+    }
+
+    /// Limits the requests sent at the same time by the client and its clones,
+    /// `MAX_CONCURRENT_REQUESTS` by default
+    ///
+    /// The windows of a long range are then retrieved a few at a time instead of
+    /// all at once, e.g. about 140 requests for 5 years.
+    pub fn with_max_concurrent_requests(mut self, max: usize) -> Self {
+        self.max_concurrent_requests = max;
+        self
+    }
+
+    /// Reuses the responses until the end of the half-hour in which they were received
+    ///
+    /// The API publishes new data every half-hour, so a dashboard refreshing more
+    /// often than that only needs to send requests when the data changes. The windows
+    /// of the range queries which are settled are not kept, see `with_disk_cache()`.
+    /// Disabled by default, including for the client shared by the free functions.
+    #[cfg(feature = "cache")]
+    pub fn with_cache(mut self, enabled: bool) -> Self {
+        self.cache = enabled;
+        self
+    }
+
+    /// Stores the responses of the range queries in a directory, for the windows
+    /// whose data no longer changes
+    ///
+    /// Running a query again then only downloads the windows not stored yet, e.g.
+    /// the last days of a year when updating an annual summary. Defaults to the
+    /// value of `CACHE_DIR_VAR` if set.
+    #[cfg(feature = "cache")]
+    pub fn with_disk_cache<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.disk_cache = Some(Arc::new(DiskCache::new(dir.as_ref())));
+        self
+    }
+
+    /// Clock telling when the responses kept by `with_cache()` expire, the `SystemClock` by default
+    #[cfg(feature = "cache")]
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Sends the requests with the given transport instead of a `reqwest::Client`,
+    /// e.g. a mock returning recorded responses or a client with custom middleware
+    ///
+    /// The user agent and, outside of the browser, the timeout are then left to the transport.
+    pub fn with_transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Returns an `ApiError::HttpError` if the HTTP client can't be initialised,
+    /// or an `ApiError::Error` if the windows of the chunking are too short or too long
+    /// or if no concurrent requests are allowed
+    pub fn build(self) -> Result<CarbonIntensityClient> {
+        self.chunking.validate()?;
+        if self.max_concurrent_requests == 0 {
+            return Err(ApiError::Error(
+                "At least one concurrent request must be allowed".to_string(),
+            ));
+        }
+        let transport = match self.transport {
+            Some(transport) => transport,
+            None => {
+                #[allow(unused_mut)]
+                let mut http = reqwest::Client::builder().user_agent(self.user_agent);
+                #[cfg(not(target_arch = "wasm32"))]
+                if let Some(timeout) = self.timeout {
+                    http = http.timeout(timeout);
+                }
+                Arc::new(http.build()?)
+            }
+        };
+        Ok(CarbonIntensityClient {
+            transport,
+            base_url: self.base_url,
+            #[cfg(target_arch = "wasm32")]
+            timeout: self.timeout,
+            retry_policy: self.retry_policy,
+            chunking: self.chunking,
+            requests: Arc::new(Semaphore::new(self.max_concurrent_requests)),
+            max_concurrent_requests: self.max_concurrent_requests,
+            #[cfg(feature = "cache")]
+            cache: self.cache.then(Arc::default),
+            #[cfg(feature = "cache")]
+            disk_cache: self.disk_cache,
+            #[cfg(feature = "cache")]
+            clock: self.clock,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(100)).with_jitter(false);
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(400));
+        assert_eq!(RetryPolicy::none().delay(5), Duration::ZERO);
+
+        let policy = policy.with_jitter(true);
+        for retry in 0..3 {
+            let delay = policy.delay(retry);
+            assert!(delay >= policy.backoff(retry) / 2 && delay <= policy.backoff(retry));
+        }
+    }
+
+    #[test]
+    fn builder() {
+        let client = CarbonIntensityClient::builder()
+            .with_base_url("http://localhost:8080/")
+            .with_retry_policy(RetryPolicy::new(1, Duration::ZERO))
+            .build()
+            .unwrap();
+        assert_eq!(client.base_url(), "http://localhost:8080");
+        assert_eq!(client.retry_policy().max_retries(), 1);
+
+        let options = QueryOptions::new();
+        assert_eq!(client.base_url_for(&options), "http://localhost:8080");
+        let options = options.with_base_url("http://mirror");
+        assert_eq!(client.base_url_for(&options), "http://mirror");
+
+        let chunking = Chunking::new().with_window(chrono::Duration::days(30));
+        let result = CarbonIntensityClient::builder()
+            .with_chunking(chunking)
+            .build();
+        assert!(matches!(result, Err(ApiError::Error(_))));
+        let result = CarbonIntensityClient::builder()
+            .with_max_concurrent_requests(0)
+            .build();
+        assert!(matches!(result, Err(ApiError::Error(_))));
+    }
+
+    #[test]
+    fn base_url_from_env() {
+        assert_eq!(&*default_base_url(None), BASE_URL);
+        assert_eq!(&*default_base_url(Some(" ".to_string())), BASE_URL);
+        assert_eq!(
+            &*default_base_url(Some("http://proxy:3128/ci/".to_string())),
+            "http://proxy:3128/ci"
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use std::fmt::Debug;
+
+use chrono::{NaiveDateTime, Utc};
+
+/// Source of the current date and time
+///
+/// Used when clamping dates in the future or when no end date is given.
+/// The default is the `SystemClock`, tests and simulations can use a
+/// `FixedClock` instead.
+pub trait Clock: Debug + Send + Sync {
+    /// Current date and time in UTC, as the dates of the API
+    fn now(&self) -> NaiveDateTime;
+}
+
+/// Clock returning the time of the system in UTC
+///
+/// ```
+/// # use carbonintensity::{Clock, SystemClock};
+/// let now = SystemClock.now();
+/// assert!((chrono::Utc::now().naive_utc() - now).num_seconds() < 5);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        Utc::now().naive_utc()
+    }
+}
+
+/// Clock always returning the same date and time
+///
+/// ```
+/// # use chrono::NaiveDate;
+/// # use carbonintensity::{Clock, FixedClock};
+/// let now = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+/// assert_eq!(FixedClock::new(now).now(), now);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock {
+    now: NaiveDateTime,
+}
+
+impl FixedClock {
+    pub fn new(now: NaiveDateTime) -> Self {
+        Self { now }
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> NaiveDateTime {
+        self.now
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::BTreeSet;
+
+use chrono::NaiveDateTime;
+
+use crate::{
+    ApiError, CarbonIntensityClient, IntensityForDate, QueryOptions, QueryOutcome, Region, Result,
+    Target, Warning,
+};
+
+/// Intensities of several regions aligned on the same half-hours, in gCO2/kWh
+#[derive(Debug)]
+pub struct RegionComparison {
+    /// Half-hours with data for at least one of the regions, in chronological order
+    pub times: Vec<NaiveDateTime>,
+    /// One row per region in the order requested, with a value per half-hour
+    /// or `None` if the region has no data for it
+    pub rows: Vec<(Region, Vec<Option<i32>>)>,
+    /// Problems which did not prevent returning results, with the region they concern
+    pub warnings: Vec<(Region, Warning)>,
+    /// Regions which could not be retrieved, left out of the rows
+    pub errors: Vec<(Region, ApiError)>,
+}
+
+impl RegionComparison {
+    /// Aligns the intensities of each region retrieved on the half-hours of all of them
+    fn new(results: Vec<(Region, Result<QueryOutcome>)>) -> Self {
+        let mut outcomes = Vec::with_capacity(results.len());
+        let mut errors = Vec::new();
+        for (region, result) in results {
+            match result {
+                Ok(outcome) => outcomes.push((region, outcome)),
+                Err(error) => errors.push((region, error)),
+            }
+        }
+
+        let times: Vec<NaiveDateTime> = outcomes
+            .iter()
+            .flat_map(|(_, outcome)| outcome.intensities.iter().map(|(time, _)| *time))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        let mut warnings = Vec::new();
+        let rows = outcomes
+            .into_iter()
+            .map(|(region, outcome)| {
+                warnings.extend(outcome.warnings.into_iter().map(|w| (region, w)));
+                (region, align(&times, &outcome.intensities))
+            })
+            .collect();
+        Self {
+            times,
+            rows,
+            warnings,
+            errors,
+        }
+    }
+
+    /// Values of a region for each half-hour, if it was compared
+    pub fn region(&self, region: Region) -> Option<&[Option<i32>]> {
+        self.rows
+            .iter()
+            .find(|(r, _)| *r == region)
+            .map(|(_, values)| values.as_slice())
+    }
+
+    /// Region with the lowest intensity for each half-hour, the first one
+    /// requested winning ties
+    pub fn lowest(&self) -> Vec<(NaiveDateTime, Region)> {
+        self.times
+            .iter()
+            .enumerate()
+            .filter_map(|(i, time)| {
+                self.rows
+                    .iter()
+                    .filter_map(|(region, values)| values[i].map(|value| (value, *region)))
+                    .reduce(|lowest, other| if other.0 < lowest.0 { other } else { lowest })
+                    .map(|(_, region)| (*time, region))
+            })
+            .collect()
+    }
+}
+
+/// Value of each half-hour of `times` in `intensities`, both in chronological order
+fn align(times: &[NaiveDateTime], intensities: &[IntensityForDate]) -> Vec<Option<i32>> {
+    let mut intensities = intensities.iter().peekable();
+    times
+        .iter()
+        .map(|time| {
+            while intensities.next_if(|(other, _)| other < time).is_some() {}
+            intensities
+                .next_if(|(other, _)| other == time)
+                .map(|(_, value)| *value)
+        })
+        .collect()
+}
+
+/// Intensities of several regions over a range, aligned on the same half-hours
+///
+/// The regions are retrieved concurrently as a batch, see `get_intensities_batch`,
+/// each one once if repeated. A region failing is reported in `errors` without
+/// failing the others. Returns an `ApiError` if the dates are invalid, as for
+/// `get_intensities`, or if no region could be retrieved.
+///
+/// ```no_run
+/// # use carbonintensity::{compare_regions, Region};
+/// # async fn run() -> carbonintensity::Result<()> {
+/// let regions = [Region::London, Region::NorthScotland];
+/// let comparison = compare_regions(&regions, "2024-01-01", &Some("2024-01-02")).await?;
+/// for (time, region) in comparison.lowest() {
+///     println!("{time}: {region}");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn compare_regions(
+    regions: &[Region],
+    start: &str,
+    end: &Option<&str>,
+) -> Result<RegionComparison> {
+    CarbonIntensityClient::shared()
+        .compare_regions(regions, start, end)
+        .await
+}
+
+/// Same as `compare_regions` but with control over the execution of the queries
+pub async fn compare_regions_with_options(
+    regions: &[Region],
+    start: &str,
+    end: &Option<&str>,
+    options: &QueryOptions,
+) -> Result<RegionComparison> {
+    CarbonIntensityClient::shared()
+        .compare_regions_with_options(regions, start, end, options)
+        .await
+}
+
+impl CarbonIntensityClient {
+    /// Intensities of several regions aligned on the same half-hours, see `compare_regions()`
+    pub async fn compare_regions(
+        &self,
+        regions: &[Region],
+        start: &str,
+        end: &Option<&str>,
+    ) -> Result<RegionComparison> {
+        self.compare_regions_with_options(regions, start, end, &QueryOptions::default())
+            .await
+    }
+
+    /// See `compare_regions_with_options()`
+    pub async fn compare_regions_with_options(
+        &self,
+        regions: &[Region],
+        start: &str,
+        end: &Option<&str>,
+        options: &QueryOptions,
+    ) -> Result<RegionComparison> {
+        let targets: Vec<Target> = regions.iter().map(|r| Target::Region(*r)).collect();
+        let mut outcomes = self
+            .get_intensities_batch_with_options(&targets, start, end, options)
+            .await?;
+        // in the order requested, the repeated regions being removed already
+        let results: Vec<(Region, Result<QueryOutcome>)> = regions
+            .iter()
+            .filter_map(|region| {
+                let outcome = outcomes.remove(&Target::Region(*region))?;
+                Some((*region, outcome))
+            })
+            .collect();
+        let mut comparison = RegionComparison::new(results);
+        if comparison.rows.is_empty() && !comparison.errors.is_empty() {
+            return Err(comparison.errors.swap_remove(0).1);
+        }
+        Ok(comparison)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn time(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn outcome(intensities: Vec<IntensityForDate>) -> Result<QueryOutcome> {
+        Ok(QueryOutcome {
+            intensities,
+            ..QueryOutcome::default()
+        })
+    }
+
+    #[test]
+    fn comparison() {
+        let comparison = RegionComparison::new(vec![
+            (
+                Region::London,
+                outcome(vec![(time(0, 0), 200), (time(0, 30), 150)]),
+            ),
+            (
+                Region::NorthScotland,
+                outcome(vec![(time(0, 30), 150), (time(1, 0), 20)]),
+            ),
+            (
+                Region::Wales,
+                Err(ApiError::Error("Unavailable".to_string())),
+            ),
+        ]);
+        assert_eq!(comparison.times, vec![time(0, 0), time(0, 30), time(1, 0)]);
+        assert_eq!(
+            comparison.region(Region::London),
+            Some(&[Some(200), Some(150), None][..])
+        );
+        assert_eq!(
+            comparison.region(Region::NorthScotland),
+            Some(&[None, Some(150), Some(20)][..])
+        );
+        // failed on its own
+        assert_eq!(comparison.region(Region::Wales), None);
+        assert!(matches!(comparison.errors[..], [(Region::Wales, _)]));
+        assert_eq!(
+            comparison.lowest(),
+            vec![
+                (time(0, 0), Region::London),
+                (time(0, 30), Region::London),
+                (time(1, 0), Region::NorthScotland),
+            ]
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use std::{error::Error, fmt::Display};
+
+use crate::Region;
+
+/// Distribution network operators, as named by the API, and other common names
+/// for the region they serve
+fn dno_regions() -> impl Iterator<Item = (&'static str, Region)> {
+    Region::ALL.into_iter().flat_map(|region| {
+        region
+            .dno_region()
+            .into_iter()
+            .chain(region.dno_aliases().iter().copied())
+            .map(move |name| (name, region))
+    })
+}
+
+/// Operators running the network of several regions
+const DNO_GROUPS: [(&str, &[Region]); 9] = [
+    (
+        "UKPN",
+        &[
+            Region::EastEngland,
+            Region::London,
+            Region::SouthEastEngland,
+        ],
+    ),
+    ("SSE", &[Region::NorthScotland, Region::SouthEngland]),
+    ("SSEN", &[Region::NorthScotland, Region::SouthEngland]),
+    (
+        "SP",
+        &[
+            Region::SouthScotland,
+            Region::NorthWalesMerseysideAndCheshire,
+        ],
+    ),
+    (
+        "SPEN",
+        &[
+            Region::SouthScotland,
+            Region::NorthWalesMerseysideAndCheshire,
+        ],
+    ),
+    ("NPG", &[Region::NorthEastEngland, Region::SouthYorkshire]),
+    (
+        "Northern Powergrid",
+        &[Region::NorthEastEngland, Region::SouthYorkshire],
+    ),
+    (
+        "WPD",
+        &[
+            Region::SouthWales,
+            Region::WestMidlands,
+            Region::EastMidlands,
+            Region::SouthWestEngland,
+        ],
+    ),
+    (
+        "NGED",
+        &[
+            Region::SouthWales,
+            Region::WestMidlands,
+            Region::EastMidlands,
+            Region::SouthWestEngland,
+        ],
+    ),
+];
+
+impl Region {
+    /// Region served by a distribution network operator (DNO)
+    ///
+    /// Accepts the names used by the API (e.g. "UKPN London") as well as the
+    /// usual abbreviations (e.g. "ENWL"), ignoring case, spaces and punctuation.
+    /// Operators serving several regions (e.g. "UKPN") are rejected with the list
+    /// of their regions.
+    ///
+    /// ```
+    /// # use carbonintensity::Region;
+    /// assert_eq!(Region::from_dno("ukpn london"), Ok(Region::London));
+    /// assert!(Region::from_dno("UKPN").is_err());
+    /// ```
+    pub fn from_dno(name: &str) -> Result<Region, DnoError> {
+        let key = normalise(name);
+
+        if let Some((_, region)) = dno_regions().find(|(dno, _)| normalise(dno) == key) {
+            return Ok(region);
+        }
+
+        match DNO_GROUPS.iter().find(|(dno, _)| normalise(dno) == key) {
+            Some((_, regions)) => Err(DnoError::Ambiguous {
+                name: name.to_string(),
+                regions: regions.to_vec(),
+            }),
+            None => Err(DnoError::Unknown(name.to_string())),
+        }
+    }
+}
+
+fn normalise(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DnoError {
+    /// Not the name of an operator
+    Unknown(String),
+    /// The operator serves several regions
+    Ambiguous { name: String, regions: Vec<Region> },
+}
+
+impl Error for DnoError {}
+
+impl Display for DnoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DnoError::Unknown(name) => write!(f, "Unknown network operator: {name}"),
+            DnoError::Ambiguous { name, regions } => {
+                let regions: Vec<String> = regions
+                    .iter()
+                    .map(|region| format!("{region} ({})", *region as u8))
+                    .collect();
+                write!(
+                    f,
+                    "{name} operates several regions, pick one of: {}",
+                    regions.join(", ")
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_dno() {
+        assert_eq!(
+            Region::from_dno("Scottish Hydro Electric Power Distribution"),
+            Ok(Region::NorthScotland)
+        );
+        assert_eq!(Region::from_dno("UKPN-London"), Ok(Region::London));
+        assert_eq!(Region::from_dno("enwl"), Ok(Region::NorthWestEngland));
+        assert_eq!(
+            Region::from_dno("NGED South West"),
+            Ok(Region::SouthWestEngland)
+        );
+        assert_eq!(
+            Region::from_dno("BS7"),
+            Err(DnoError::Unknown("BS7".to_string()))
+        );
+        assert_eq!(
+            Region::from_dno("ukpn"),
+            Err(DnoError::Ambiguous {
+                name: "ukpn".to_string(),
+                regions: vec![
+                    Region::EastEngland,
+                    Region::London,
+                    Region::SouthEastEngland
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn every_region_has_an_operator() {
+        // the last three regions are countries
+        for region in &Region::ALL[..14] {
+            assert!(dno_regions().any(|(_, r)| r == *region), "{region}");
+        }
+        assert_eq!(dno_regions().count(), 24);
+        // abbreviations don't contain digits so can't be mistaken for postcodes
+        let names = dno_regions().map(|(name, _)| name);
+        for name in names.chain(DNO_GROUPS.iter().map(|(name, _)| *name)) {
+            assert!(!name.chars().any(|c| c.is_ascii_digit()), "{name}");
+        }
+    }
+
+    #[test]
+    fn error_display() {
+        let error = Region::from_dno("SSEN").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "SSEN operates several regions, pick one of: North Scotland (1), South England (12)"
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use std::fmt::Display;
+
+use chrono::Duration;
+
+use crate::{plan_windows, QueryOptions, Result, Target};
+
+/// Approximate size of a half-hour in the JSON sent for a national query
+const NATIONAL_RECORD_BYTES: usize = 110;
+
+/// Approximate size of a half-hour for a region or postcode, with its generation mix
+const REGIONAL_RECORD_BYTES: usize = 450;
+
+/// Number of regions returned by the all-regions endpoint
+const REGIONS: usize = 17;
+
+/// Size of a range query, known before it is executed
+///
+/// ```
+/// # use chrono::NaiveDate;
+/// # use carbonintensity::{estimate_query, FixedClock, QueryOptions, Target};
+/// let now = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+/// let options = QueryOptions::new().with_clock(FixedClock::new(now));
+/// let estimate = estimate_query("2023-01-01", &Some("2024-01-01"), &options).unwrap();
+/// assert_eq!(estimate.requests, 29);
+/// assert_eq!(estimate.half_hours, 17521);
+/// assert!(estimate.approx_bytes(&Target::National) > 1_000_000);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueryEstimate {
+    /// Number of requests sent to the API, one per window
+    pub requests: usize,
+    /// Number of half-hours in the range, per target
+    pub half_hours: usize,
+}
+
+impl QueryEstimate {
+    /// Rough size of the responses for a target, in bytes
+    pub fn approx_bytes(&self, target: &Target) -> usize {
+        let record = match target {
+            Target::National => NATIONAL_RECORD_BYTES,
+            Target::Postcode(_) | Target::Region(_) => REGIONAL_RECORD_BYTES,
+        };
+        self.half_hours * record
+    }
+
+    /// Rough size of the responses when retrieving all the regions at once, in bytes
+    pub fn approx_bytes_all_regions(&self) -> usize {
+        self.half_hours * REGIONS * REGIONAL_RECORD_BYTES
+    }
+}
+
+impl Display for QueryEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} requests for {} half-hours",
+            self.requests, self.half_hours
+        )
+    }
+}
+
+/// Estimates how many requests and how much data a range query involves
+///
+/// The dates are validated and split as for `get_intensities`, see `plan_windows`.
+pub fn estimate_query(
+    start: &str,
+    end: &Option<&str>,
+    options: &QueryOptions,
+) -> Result<QueryEstimate> {
+    let windows = plan_windows(start, end, options)?;
+    let half_hours = match (windows.first(), windows.last()) {
+        (Some(first), Some(last)) if last.end >= first.start => {
+            ((last.end - first.start).num_minutes() / Duration::minutes(30).num_minutes()) as usize
+                + 1
+        }
+        _ => 0,
+    };
+    Ok(QueryEstimate {
+        requests: windows.len(),
+        half_hours,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::{FixedClock, Region};
+
+    #[test]
+    fn estimate() {
+        let now = NaiveDate::from_ymd_opt(2024, 6, 15)
+            .unwrap()
+            .and_hms_opt(12, 34, 0)
+            .unwrap();
+        let options = QueryOptions::new().with_clock(FixedClock::new(now));
+
+        let estimate = estimate_query("2024-06-14", &Some("2024-06-15"), &options).unwrap();
+        assert_eq!(
+            estimate,
+            QueryEstimate {
+                requests: 1,
+                half_hours: 49
+            }
+        );
+        assert_eq!(estimate.approx_bytes(&Target::National), 49 * 110);
+        assert_eq!(
+            estimate.approx_bytes(&Target::Region(Region::London)),
+            49 * 450
+        );
+        assert_eq!(estimate.approx_bytes_all_regions(), 49 * 17 * 450);
+        assert_eq!(estimate.to_string(), "1 requests for 49 half-hours");
+
+        // past and forecasts
+        let estimate = estimate_query("2024-06-01", &Some("2024-06-16"), &options).unwrap();
+        assert_eq!(estimate.requests, 3);
+        assert_eq!(estimate.half_hours, 15 * 48 + 1);
+
+        assert!(estimate_query("2024-06-01", &Some("2024-07-01"), &options).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use chrono::{Duration, NaiveDateTime};
+
+use crate::{
+    planner::FORECAST_HORIZON, to_tuples, ApiError, CarbonIntensityClient, IntensityForDate,
+    QueryOptions, Result, Target,
+};
+
+/// Contiguous window with the lowest average intensity, see `greenest_window`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GreenestWindow {
+    /// Start of the first half-hour of the window
+    pub start: NaiveDateTime,
+    /// End of the last half-hour of the window
+    pub end: NaiveDateTime,
+    /// Average intensity over the window, in gCO2/kWh
+    pub average: f64,
+}
+
+/// Finds the `slots` consecutive half-hours with the lowest average intensity
+///
+/// The intensities are expected in chronological order; no window is formed
+/// across a gap. The earliest window wins ties. Returns `None` if no window is
+/// long enough.
+///
+/// ```
+/// # use chrono::NaiveDate;
+/// # use carbonintensity::greenest_window;
+/// let time = |h, m| NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(h, m, 0).unwrap();
+/// let forecast = [(time(0, 0), 200), (time(0, 30), 120), (time(1, 0), 140), (time(1, 30), 190)];
+/// let window = greenest_window(&forecast, 2).unwrap();
+/// assert_eq!((window.start, window.end), (time(0, 30), time(1, 30)));
+/// assert_eq!(window.average, 130.0);
+/// ```
+pub fn greenest_window(intensities: &[IntensityForDate], slots: usize) -> Option<GreenestWindow> {
+    let (window, sum) = contiguous_windows(intensities, slots, |(time, _)| *time)
+        .map(|(_, window)| {
+            let sum: i64 = window.iter().map(|(_, value)| i64::from(*value)).sum();
+            (window, sum)
+        })
+        // the first of the lowest
+        .min_by_key(|(_, sum)| *sum)?;
+
+    Some(GreenestWindow {
+        start: window[0].0,
+        end: window[slots - 1].0 + Duration::minutes(30),
+        average: sum as f64 / slots as f64,
+    })
+}
+
+/// Runs of `slots` consecutive half-hours, with the position of their first one
+///
+/// The items are expected in chronological order, `time` giving the start of
+/// their half-hour; no run is formed across a gap. Shared by the searches for
+/// the best window to run a job in.
+pub(crate) fn contiguous_windows<T>(
+    items: &[T],
+    slots: usize,
+    time: impl Fn(&T) -> NaiveDateTime,
+) -> impl Iterator<Item = (usize, &[T])> {
+    let half_hour = Duration::minutes(30);
+    items
+        .windows(slots.max(1))
+        .enumerate()
+        .filter(move |(_, window)| {
+            slots > 0
+                && window
+                    .windows(2)
+                    .all(|pair| time(&pair[1]) - time(&pair[0]) == half_hour)
+        })
+}
+
+/// Finds the window of `duration` with the lowest forecast intensity for a target
+/// within the coming `horizon`, e.g. to schedule a job when the grid is greenest
+///
+/// The duration is rounded up to whole half-hours and the horizon to whole hours,
+/// up to the 48 hours forecast by the API. The window may start in the current
+/// half-hour. Returns an `ApiError::Error` if the horizon is shorter than the duration.
+pub async fn find_greenest_window(
+    target: &Target,
+    duration: Duration,
+    horizon: Duration,
+) -> Result<GreenestWindow> {
+    CarbonIntensityClient::shared()
+        .find_greenest_window(target, duration, horizon)
+        .await
+}
+
+/// Same as `find_greenest_window` but with control over the execution of the query
+pub async fn find_greenest_window_with_options(
+    target: &Target,
+    duration: Duration,
+    horizon: Duration,
+    options: &QueryOptions,
+) -> Result<GreenestWindow> {
+    CarbonIntensityClient::shared()
+        .find_greenest_window_with_options(target, duration, horizon, options)
+        .await
+}
+
+impl CarbonIntensityClient {
+    /// Greenest window for a job, see `find_greenest_window()`
+    pub async fn find_greenest_window(
+        &self,
+        target: &Target,
+        duration: Duration,
+        horizon: Duration,
+    ) -> Result<GreenestWindow> {
+        self.find_greenest_window_with_options(target, duration, horizon, &QueryOptions::default())
+            .await
+    }
+
+    /// See `find_greenest_window_with_options()`
+    pub async fn find_greenest_window_with_options(
+        &self,
+        target: &Target,
+        duration: Duration,
+        horizon: Duration,
+        options: &QueryOptions,
+    ) -> Result<GreenestWindow> {
+        if duration <= Duration::zero() || horizon < duration {
+            return Err(ApiError::Error(format!(
+                "Invalid window of {} minutes within {} minutes",
+                duration.num_minutes(),
+                horizon.num_minutes()
+            )));
+        }
+        let slots = usize::try_from((duration.num_minutes() + 29) / 30).unwrap_or(usize::MAX);
+        // the forecast doesn't go any further
+        let hours = ((horizon.num_minutes() + 59) / 60).min(FORECAST_HORIZON.num_hours());
+        let hours = u32::try_from(hours).unwrap_or(u32::MAX);
+
+        let outcome = self
+            .get_forecast_with_options(target, hours, options)
+            .await?;
+        let intensities = to_tuples(outcome.intensities)?;
+        greenest_window(&intensities, slots).ok_or_else(|| {
+            ApiError::Error(format!(
+                "No forecast for {slots} consecutive half-hours for {target}"
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn time(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn greenest() {
+        let intensities = [
+            (time(0, 0), 100),
+            (time(0, 30), 90),
+            // gap, the lowest pair is not contiguous
+            (time(2, 0), 40),
+            (time(2, 30), 250),
+            (time(3, 0), 200),
+            (time(3, 30), 120),
+        ];
+        let window = greenest_window(&intensities, 2).unwrap();
+        assert_eq!((window.start, window.end), (time(0, 0), time(1, 0)));
+        assert_eq!(window.average, 95.0);
+
+        let window = greenest_window(&intensities, 1).unwrap();
+        assert_eq!((window.start, window.end), (time(2, 0), time(2, 30)));
+
+        let window = greenest_window(&intensities, 4).unwrap();
+        assert_eq!(window.start, time(2, 0));
+        assert_eq!(window.average, 152.5);
+
+        // ties go to the earliest
+        let flat = [(time(0, 0), 100), (time(0, 30), 100)];
+        assert_eq!(greenest_window(&flat, 1).unwrap().start, time(0, 0));
+
+        assert_eq!(greenest_window(&intensities, 5), None);
+        let starts: Vec<usize> = contiguous_windows(&intensities, 3, |(time, _)| *time)
+            .map(|(start, _)| start)
+            .collect();
+        assert_eq!(starts, vec![2, 3]);
+        assert_eq!(greenest_window(&intensities, 0), None);
+        assert_eq!(greenest_window(&[], 1), None);
+    }
+}
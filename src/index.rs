@@ -0,0 +1,236 @@
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ApiError;
+
+/// Index of the carbon intensity, from very low to very high
+///
+/// An index added by a later version of the API is parsed as `Unknown`, rather
+/// than failing the whole response.
+///
+/// The known indices are ordered so that they can be compared, `Unknown` being
+/// neither lower nor higher than any of them
+///
+/// ```
+/// # use carbonintensity::IntensityIndex;
+/// assert!(IntensityIndex::VeryHigh > IntensityIndex::Moderate);
+/// assert!(!(IntensityIndex::Unknown >= IntensityIndex::High));
+/// assert!(!(IntensityIndex::Unknown <= IntensityIndex::High));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum IntensityIndex {
+    #[serde(rename = "very low")]
+    VeryLow,
+    #[serde(rename = "low")]
+    Low,
+    #[serde(rename = "moderate")]
+    Moderate,
+    #[serde(rename = "high")]
+    High,
+    #[serde(rename = "very high")]
+    VeryHigh,
+    /// Index not known to this version of the crate, not comparable to the others
+    #[serde(rename = "unknown", other)]
+    Unknown,
+}
+
+impl IntensityIndex {
+    /// All the known indexes, from very low to very high
+    pub const ALL: [IntensityIndex; 5] = [
+        IntensityIndex::VeryLow,
+        IntensityIndex::Low,
+        IntensityIndex::Moderate,
+        IntensityIndex::High,
+        IntensityIndex::VeryHigh,
+    ];
+
+    /// Rank of the index from 0 for very low to 4 for very high, `None` if unknown
+    pub fn severity(&self) -> Option<u8> {
+        match self {
+            IntensityIndex::VeryLow => Some(0),
+            IntensityIndex::Low => Some(1),
+            IntensityIndex::Moderate => Some(2),
+            IntensityIndex::High => Some(3),
+            IntensityIndex::VeryHigh => Some(4),
+            IntensityIndex::Unknown => None,
+        }
+    }
+}
+
+impl PartialOrd for IntensityIndex {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self.severity(), other.severity()) {
+            (Some(severity), Some(other)) => Some(severity.cmp(&other)),
+            _ => (self == other).then_some(Ordering::Equal),
+        }
+    }
+}
+
+impl Display for IntensityIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            IntensityIndex::VeryLow => "very low",
+            IntensityIndex::Low => "low",
+            IntensityIndex::Moderate => "moderate",
+            IntensityIndex::High => "high",
+            IntensityIndex::VeryHigh => "very high",
+            IntensityIndex::Unknown => "unknown",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Parses the index as sent by the API e.g. "very low"
+impl FromStr for IntensityIndex {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "very low" => Ok(Self::VeryLow),
+            "low" => Ok(Self::Low),
+            "moderate" => Ok(Self::Moderate),
+            "high" => Ok(Self::High),
+            "very high" => Ok(Self::VeryHigh),
+            _ => Err(ApiError::Error(format!("Invalid intensity index: {s}"))),
+        }
+    }
+}
+
+/// Lower bounds (in gCO2/kWh) of the index bands for a given year
+///
+/// Intensities below `low` are very low.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexThresholds {
+    pub year: i32,
+    pub low: i32,
+    pub moderate: i32,
+    pub high: i32,
+    pub very_high: i32,
+}
+
+impl IndexThresholds {
+    const fn new(year: i32, low: i32, moderate: i32, high: i32, very_high: i32) -> Self {
+        Self {
+            year,
+            low,
+            moderate,
+            high,
+            very_high,
+        }
+    }
+
+    /// Index of an intensity with these thresholds
+    pub fn classify(&self, intensity: i32) -> IntensityIndex {
+        if intensity >= self.very_high {
+            IntensityIndex::VeryHigh
+        } else if intensity >= self.high {
+            IntensityIndex::High
+        } else if intensity >= self.moderate {
+            IntensityIndex::Moderate
+        } else if intensity >= self.low {
+            IntensityIndex::Low
+        } else {
+            IntensityIndex::VeryLow
+        }
+    }
+}
+
+/// Index bands used by the API each year, lowered every year as the grid decarbonises
+///
+/// See the [methodology](https://github.com/carbon-intensity/methodology)
+/// of the Carbon Intensity API.
+pub const INDEX_THRESHOLDS: [IndexThresholds; 13] = [
+    IndexThresholds::new(2018, 80, 180, 280, 380),
+    IndexThresholds::new(2019, 75, 170, 270, 370),
+    IndexThresholds::new(2020, 70, 160, 260, 360),
+    IndexThresholds::new(2021, 65, 150, 250, 350),
+    IndexThresholds::new(2022, 60, 140, 240, 340),
+    IndexThresholds::new(2023, 55, 130, 230, 330),
+    IndexThresholds::new(2024, 50, 120, 220, 320),
+    IndexThresholds::new(2025, 45, 110, 210, 310),
+    IndexThresholds::new(2026, 40, 100, 200, 300),
+    IndexThresholds::new(2027, 35, 90, 190, 290),
+    IndexThresholds::new(2028, 30, 80, 180, 280),
+    IndexThresholds::new(2029, 25, 70, 170, 270),
+    IndexThresholds::new(2030, 20, 60, 160, 260),
+];
+
+/// Thresholds which applied for a given year
+///
+/// Years before 2018 use the thresholds of 2018, years after 2030 the ones of 2030.
+pub fn thresholds_for_year(year: i32) -> IndexThresholds {
+    let first = INDEX_THRESHOLDS[0].year;
+    let position = (year - first).clamp(0, INDEX_THRESHOLDS.len() as i32 - 1);
+    INDEX_THRESHOLDS[position as usize]
+}
+
+/// Index of an intensity with the thresholds which applied for a given year
+///
+/// ```
+/// # use carbonintensity::{classify, IntensityIndex};
+/// assert_eq!(classify(150, 2018), IntensityIndex::Low);
+/// assert_eq!(classify(150, 2024), IntensityIndex::Moderate);
+/// ```
+pub fn classify(intensity: i32, year: i32) -> IntensityIndex {
+    thresholds_for_year(year).classify(intensity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_display_from_str() {
+        for index in IntensityIndex::ALL {
+            assert_eq!(index.to_string().parse::<IntensityIndex>().unwrap(), index);
+            // serialised as sent by the API
+            let json = serde_json::to_string(&index).unwrap();
+            assert_eq!(json, format!("\"{index}\""));
+            assert_eq!(
+                serde_json::from_str::<IntensityIndex>(&json).unwrap(),
+                index
+            );
+        }
+        assert!("medium".parse::<IntensityIndex>().is_err());
+        assert_eq!(
+            serde_json::from_str::<IntensityIndex>("\"medium\"").unwrap(),
+            IntensityIndex::Unknown
+        );
+        assert_eq!(IntensityIndex::Unknown.severity(), None);
+        assert_eq!(
+            IntensityIndex::Unknown.partial_cmp(&IntensityIndex::VeryHigh),
+            None
+        );
+        assert!(IntensityIndex::ALL.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn thresholds_table() {
+        for (position, thresholds) in INDEX_THRESHOLDS.iter().enumerate() {
+            assert_eq!(thresholds.year, 2018 + position as i32);
+            assert!(thresholds.low < thresholds.moderate);
+            assert!(thresholds.moderate < thresholds.high);
+            assert!(thresholds.high < thresholds.very_high);
+        }
+        assert_eq!(thresholds_for_year(2010), INDEX_THRESHOLDS[0]);
+        assert_eq!(thresholds_for_year(2024).year, 2024);
+        assert_eq!(thresholds_for_year(2050), INDEX_THRESHOLDS[12]);
+    }
+
+    #[test]
+    fn classify_test() {
+        // bounds of 2024: 50, 120, 220, 320
+        assert_eq!(classify(0, 2024), IntensityIndex::VeryLow);
+        assert_eq!(classify(49, 2024), IntensityIndex::VeryLow);
+        assert_eq!(classify(50, 2024), IntensityIndex::Low);
+        assert_eq!(classify(120, 2024), IntensityIndex::Moderate);
+        assert_eq!(classify(220, 2024), IntensityIndex::High);
+        assert_eq!(classify(319, 2024), IntensityIndex::High);
+        assert_eq!(classify(320, 2024), IntensityIndex::VeryHigh);
+
+        // same value, different years
+        assert_eq!(classify(350, 2018), IntensityIndex::High);
+        assert_eq!(classify(350, 2021), IntensityIndex::VeryHigh);
+    }
+}
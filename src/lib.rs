@@ -1,19 +1,182 @@
 //! API for retrieving data from the Carbon Intensity API
 //! <https://api.carbonintensity.org.uk/>
-
-use futures::future;
-use std::sync::LazyLock;
-
-use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime};
-use reqwest::{Client, StatusCode};
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+//!
+//! The free functions, e.g. `get_current_intensity()`, mirror the methods of
+//! `CarbonIntensityClient` on a default client shared by the whole program.
+//! `get_intensity()` and `get_intensities()` are deprecated in favour of the
+//! methods of the same name, e.g. `CarbonIntensityClient::new().get_intensity(&target)`.
+
+use std::{
+    borrow::Cow,
+    sync::{Arc, LazyLock},
+};
+
+use chrono::{DateTime, DurationRound, NaiveDate, NaiveDateTime, Utc};
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
 use thiserror::Error;
 
+#[cfg(feature = "analysis")]
+mod annual;
+#[cfg(feature = "analysis")]
+mod audit;
+#[cfg(feature = "analysis")]
+mod backtest;
+#[cfg(feature = "analysis")]
+mod batch;
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub mod blocking;
+#[cfg(feature = "analysis")]
+mod blocks;
+#[cfg(feature = "cache")]
+mod cache;
+mod client;
+mod clock;
+#[cfg(feature = "analysis")]
+mod compare;
+mod dno;
+mod estimate;
+#[cfg(feature = "analysis")]
+mod greenest;
+mod index;
+#[cfg(feature = "analysis")]
+mod matrix;
+#[cfg(feature = "export")]
+mod metrics;
+#[cfg(feature = "middleware")]
+mod middleware;
+mod mix;
+#[cfg(feature = "analysis")]
+mod nowcast;
+#[cfg(feature = "analysis")]
+mod optimise;
+mod planner;
+mod postcode;
+#[cfg(feature = "postcode-regions")]
+mod postcode_regions;
+pub mod prelude;
+mod query;
+#[cfg(feature = "analysis")]
+mod ramp;
+mod range;
+mod record;
 mod region;
+#[cfg(feature = "analysis")]
+mod report;
+#[cfg(feature = "analysis")]
+mod rolling;
+mod runtime;
+#[cfg(feature = "analysis")]
+mod scope2;
+#[cfg(feature = "simulation")]
+mod simulation;
+#[cfg(feature = "export")]
+mod sink;
+mod stats;
+#[cfg(feature = "analysis")]
+mod strategies;
+#[cfg(feature = "analysis")]
+mod subscribe;
+#[cfg(feature = "analysis")]
+mod summary;
 mod target;
-
+#[cfg(feature = "analysis")]
+mod tariff;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+mod ticker;
+mod transport;
+mod wait;
+#[cfg(feature = "export")]
+mod widget;
+
+#[cfg(feature = "analysis")]
+pub use annual::{annual_summary, write_annual_summary, RegionSummary};
+#[cfg(feature = "analysis")]
+pub use audit::{
+    audit_sites, audit_sites_keep_going, parse_sites, write_audit_csv, write_audit_outcome_csv,
+    AuditOutcome, Site, SiteAudit, SiteFailure,
+};
+#[cfg(feature = "analysis")]
+pub use backtest::{backtest, BacktestResult, Decision, HistoricalSlot, Job, JobOutcome, Strategy};
+#[cfg(feature = "analysis")]
+pub use batch::{get_intensities_batch, get_intensities_batch_with_options};
+#[cfg(feature = "analysis")]
+pub use blocks::{aggregate_blocks, BlockDefinition, BlockIntensity};
+#[cfg(feature = "cache")]
+pub use client::CACHE_DIR_VAR;
+pub use client::{
+    CarbonIntensityClient, ClientBuilder, RetryPolicy, BASE_URL_VAR, MAX_CONCURRENT_REQUESTS,
+};
+pub use clock::{Clock, FixedClock, SystemClock};
+#[cfg(feature = "analysis")]
+pub use compare::{compare_regions, compare_regions_with_options, RegionComparison};
+pub use dno::DnoError;
+pub use estimate::{estimate_query, QueryEstimate};
+#[cfg(feature = "analysis")]
+pub use greenest::{
+    find_greenest_window, find_greenest_window_with_options, greenest_window, GreenestWindow,
+};
+pub use index::{classify, thresholds_for_year, IndexThresholds, IntensityIndex, INDEX_THRESHOLDS};
+#[cfg(feature = "analysis")]
+pub use matrix::{
+    intensity_matrix, write_matrix_csv, write_matrix_html, IntensityMatrix, MatrixMetric,
+};
+#[cfg(feature = "export")]
+pub use metrics::render_openmetrics;
+#[cfg(feature = "middleware")]
+pub use middleware::{GridIntensity, IntensityLayer, IntensityService, INTENSITY_HEADER};
+pub use mix::{
+    get_carbon_factors, get_generation_mix, get_generation_mixes, get_generation_mixes_in_range,
+    get_generation_mixes_with_options, Fuel, FuelMix,
+};
+#[cfg(feature = "analysis")]
+pub use nowcast::{get_nowcast, get_nowcast_with_options, Nowcast, NowcastSource};
+#[cfg(feature = "analysis")]
+pub use optimise::{optimise_windows, parse_prices, PriceForDate, WindowOption};
+pub use planner::{Chunking, PlannedWindow};
+pub use postcode::outward_code;
+pub use query::{
+    ExecutionReport, MixPolicy, NullPolicy, QueryOptions, QueryOutcome, Warning, WindowReport,
+};
+#[cfg(feature = "analysis")]
+pub use ramp::{ramp_rates, RampSummary};
+pub use range::{DateBound, DateRange, RangePreset};
+#[cfg(feature = "analysis")]
+pub use record::stream_intensities;
+pub use record::{get_intensity_records, get_intensity_records_with_options, IntensityRecord};
 pub use region::Region;
+#[cfg(feature = "analysis")]
+pub use report::{write_backtest_html, write_backtest_markdown};
+#[cfg(feature = "analysis")]
+pub use rolling::{rolling_annual_intensity, MonthlyIntensity, RollingAnnualIntensity};
+#[cfg(feature = "analysis")]
+pub use scope2::{
+    parse_monthly_consumption, scope2_report, write_scope2_csv, MonthlyConsumption, Scope2Line,
+    SCOPE2_SOURCE,
+};
+#[cfg(feature = "simulation")]
+pub use simulation::SyntheticProvider;
+#[cfg(feature = "export")]
+pub use sink::{CsvAppendSink, DateFormat, OutputFormat, OutputSink, WriterSink};
+pub use stats::{get_intensity_stats, get_intensity_stats_in_range, IntensityStats};
+#[cfg(feature = "analysis")]
+pub use strategies::{FixedWindow, PerfectForesight, RunImmediately, Threshold};
+#[cfg(feature = "analysis")]
+pub use subscribe::subscribe;
+#[cfg(feature = "analysis")]
+pub use summary::{summarise, summarise_with_percentiles, IntensitySummary, DEFAULT_PERCENTILES};
 pub use target::Target;
+#[cfg(feature = "analysis")]
+pub use tariff::{summarise_tariff, TariffSummary, TimeWindow};
+pub use ticker::HalfHourTicker;
+pub use transport::{Transport, TransportFuture, TransportResponse};
+pub use wait::wait_until_below;
+#[cfg(feature = "export")]
+pub use widget::{get_status_widget, StatusWidget, WidgetFormat};
+
+use planner::RangePlanner;
+use runtime::{join_window, spawn_window, Stopwatch, WindowTask};
 
 // oldest entry available for 2018-05-10 23:30:00
 static OLDEST_VALID_DATE: LazyLock<NaiveDateTime> = LazyLock::new(|| {
@@ -25,6 +188,7 @@ static OLDEST_VALID_DATE: LazyLock<NaiveDateTime> = LazyLock::new(|| {
 
 /// An error communicating with the Carbon Intensity API.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum ApiError {
     /// There was an error making the HTTP request.
     #[error("HTTP request error: {0}")]
@@ -32,6 +196,13 @@ pub enum ApiError {
     /// A REST API method returned an error status.
     #[error("REST error {status}: {body}")]
     RestError { status: StatusCode, body: String },
+    /// The API returned an error status along with a structured error payload.
+    #[error("API error {code}: {message}")]
+    Upstream {
+        status: StatusCode,
+        code: String,
+        message: String,
+    },
     /// There was an error parsing a URL from a string.
     #[error("Error parsing URL: {0}")]
     UrlParseError(#[from] url::ParseError),
@@ -43,29 +214,288 @@ pub enum ApiError {
     Error(String),
 }
 
+impl ApiError {
+    /// Whether the same request may succeed later: the connection failed or was
+    /// dropped, the request timed out, or the API answered with a server error
+    /// or 429 Too Many Requests
+    ///
+    /// These are the errors retried by a `CarbonIntensityClient` with a `RetryPolicy`.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ApiError::HttpError(error) => {
+                // connection errors are request errors in the browser
+                #[cfg(not(target_arch = "wasm32"))]
+                if error.is_connect() {
+                    return true;
+                }
+                error.is_timeout() || error.is_request() || error.is_body()
+            }
+            ApiError::RestError { status, .. } | ApiError::Upstream { status, .. } => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            _ => false,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, ApiError>;
 
 pub type IntensityForDate = (NaiveDateTime, i32);
 
+/// Names of the fuels sent by the API
+///
+/// They are repeated in every record, sharing them instead of allocating
+/// a string each time speeds up the parsing of long ranges.
+const KNOWN_NAMES: [&str; 9] = [
+    "biomass", "coal", "imports", "gas", "nuclear", "other", "hydro", "solar", "wind",
+];
+
+/// Deserialises one of `KNOWN_NAMES` without allocating, any other string as owned
+fn deserialize_name<'de, D>(deserializer: D) -> std::result::Result<Cow<'static, str>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct NameVisitor;
+
+    impl serde::de::Visitor<'_> for NameVisitor {
+        type Value = Cow<'static, str>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a string")
+        }
+
+        fn visit_str<E>(self, value: &str) -> std::result::Result<Self::Value, E> {
+            Ok(match KNOWN_NAMES.iter().find(|name| **name == value) {
+                Some(name) => Cow::Borrowed(name),
+                None => Cow::Owned(value.to_string()),
+            })
+        }
+    }
+
+    deserializer.deserialize_str(NameVisitor)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GenerationMix {
-    fuel: String,
+    #[serde(deserialize_with = "deserialize_name")]
+    fuel: Cow<'static, str>,
     perc: f64,
 }
 
+impl GenerationMix {
+    /// Name of the fuel e.g. "wind", "gas"
+    pub fn fuel(&self) -> &str {
+        &self.fuel
+    }
+
+    /// Percentage of the generation coming from the fuel
+    pub fn perc(&self) -> f64 {
+        self.perc
+    }
+
+    /// Share of the generation coming from the fuel
+    pub fn share(&self) -> MixShare {
+        MixShare::new(self.perc)
+    }
+}
+
+/// Share of the generation coming from a fuel, between 0 and 100%
+///
+/// ```
+/// # use carbonintensity::MixShare;
+/// let share = MixShare::new(45.1);
+/// assert_eq!(share.percent(), 45.1);
+/// assert_eq!(share.fraction(), 0.451);
+/// assert_eq!(MixShare::new(104.0).percent(), 100.0);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+pub struct MixShare(f64);
+
+impl MixShare {
+    /// Clamps the percentage between 0 and 100
+    pub fn new(percent: f64) -> Self {
+        Self(percent.clamp(0.0, 100.0))
+    }
+
+    pub fn percent(self) -> f64 {
+        self.0
+    }
+
+    /// Share between 0 and 1, e.g. to weight values by the generation
+    pub fn fraction(self) -> f64 {
+        self.0 / 100.0
+    }
+}
+
+/// Maximum difference (in percentage points) between the total of a generation
+/// mix and 100%, beyond which it is reported as inconsistent
+///
+/// The API rounds the percentages to one decimal, so the totals are usually a
+/// little off.
+pub const MIX_TOTAL_TOLERANCE: f64 = 1.0;
+
+/// Fuels considered when computing the renewable share of the generation
+const SURPLUS_FUELS: [&str; 3] = ["wind", "solar", "hydro"];
+
+/// Minimum share (in %) of wind, solar and hydro for a half-hour to be flagged as likely surplus
+pub const SURPLUS_MIN_RENEWABLE_SHARE: f64 = 60.0;
+
+/// Maximum intensity (in gCO2/kWh) for a half-hour to be flagged as likely surplus
+pub const SURPLUS_MAX_INTENSITY: i32 = 100;
+
+/// Highest plausible intensity (in gCO2/kWh), above even an all coal generation
+pub const MAX_PLAUSIBLE_INTENSITY: i32 = 1000;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Intensity {
-    forecast: i32,
-    index: String,
+    /// null for some half-hours, see `NullPolicy`
+    forecast: Option<i32>,
+    index: IntensityIndex,
     actual: Option<i32>,
 }
 
+impl Intensity {
+    /// Forecast intensity in gCO2/kWh
+    ///
+    /// `None` when the API sent no forecast, which the range queries only keep
+    /// for the half-hours with an actual value (see `NullPolicy`).
+    pub fn forecast(&self) -> Option<i32> {
+        self.forecast
+    }
+
+    /// Actual intensity in gCO2/kWh, only available at the national level
+    pub fn actual(&self) -> Option<i32> {
+        self.actual
+    }
+
+    /// Returns the first of the forecast and actual values which is negative or
+    /// above `MAX_PLAUSIBLE_INTENSITY`, if any
+    pub fn implausible_value(&self) -> Option<i32> {
+        self.forecast
+            .into_iter()
+            .chain(self.actual)
+            .find(|value| !(0..=MAX_PLAUSIBLE_INTENSITY).contains(value))
+    }
+
+    /// Actual intensity if available, the forecast otherwise
+    pub(crate) fn value(&self) -> Option<i32> {
+        self.actual.or(self.forecast)
+    }
+
+    /// Index of the intensity e.g. `IntensityIndex::Moderate`
+    pub fn index(&self) -> IntensityIndex {
+        self.index
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Data {
     from: String,
     to: String,
     intensity: Intensity,
     generationmix: Option<Vec<GenerationMix>>,
+    #[serde(skip)]
+    provenance: Option<Provenance>,
+}
+
+impl Data {
+    /// Start of the half-hour as sent by the API e.g. "2023-01-01T00:30Z"
+    pub fn from(&self) -> &str {
+        &self.from
+    }
+
+    /// End of the half-hour as sent by the API
+    pub fn to(&self) -> &str {
+        &self.to
+    }
+
+    pub fn intensity(&self) -> &Intensity {
+        &self.intensity
+    }
+
+    /// Generation mix, not available at the national level
+    pub fn generation_mix(&self) -> Option<&[GenerationMix]> {
+        self.generationmix.as_deref()
+    }
+
+    /// Sum of the percentages of the generation mix, around 100
+    ///
+    /// Returns `None` if the generation mix is not known.
+    pub fn mix_total(&self) -> Option<f64> {
+        let mix = self.generationmix.as_ref()?;
+        Some(mix.iter().map(|m| m.perc).sum())
+    }
+
+    /// Origin of the record, if requested with `QueryOptions::with_provenance()`
+    pub fn provenance(&self) -> Option<&Provenance> {
+        self.provenance.as_ref()
+    }
+
+    /// Start date and intensity, using the actual value if available
+    pub fn intensity_for_date(&self) -> Result<IntensityForDate> {
+        let start_date = parse_date(&self.from)?;
+        let intensity = self
+            .intensity
+            .value()
+            .ok_or_else(|| ApiError::Error(format!("No intensity sent for {}", self.from)))?;
+        Ok((start_date, intensity))
+    }
+
+    /// Percentage of the generation coming from wind, solar and hydro
+    ///
+    /// Returns `None` if the generation mix is not known.
+    pub fn renewable_share(&self) -> Option<f64> {
+        let mix = self.generationmix.as_ref()?;
+        let share = mix
+            .iter()
+            .filter(|m| SURPLUS_FUELS.contains(&m.fuel()))
+            .map(|m| m.perc)
+            .sum();
+        Some(share)
+    }
+
+    /// Heuristic flagging half-hours where renewable generation was likely
+    /// in surplus (and possibly curtailed)
+    ///
+    /// This is the case when the renewable share is at least
+    /// `SURPLUS_MIN_RENEWABLE_SHARE` and the intensity at most `SURPLUS_MAX_INTENSITY`.
+    pub fn is_likely_surplus(&self) -> bool {
+        self.renewable_share()
+            .is_some_and(|share| share >= SURPLUS_MIN_RENEWABLE_SHARE)
+            && self
+                .intensity
+                .value()
+                .is_some_and(|intensity| intensity <= SURPLUS_MAX_INTENSITY)
+    }
+}
+
+/// Origin of a record, to trace the values of a report back to the API
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    /// URL of the request which returned the record
+    pub url: Arc<str>,
+    /// Time the response was received
+    pub fetched_at: DateTime<Utc>,
+    /// The intensity was measured, otherwise it is a forecast
+    pub actual: bool,
+    /// The forecast was missing and interpolated, see `NullPolicy`
+    pub interpolated: bool,
+}
+
+impl Provenance {
+    /// Marks the data as coming from a response to `url` received now
+    fn stamp(data: &mut [Data], url: &str) {
+        let url: Arc<str> = Arc::from(url);
+        let fetched_at = Utc::now();
+        for data in data {
+            data.provenance = Some(Provenance {
+                url: url.clone(),
+                fetched_at,
+                actual: data.intensity.actual.is_some(),
+                interpolated: false,
+            });
+        }
+    }
 }
 
 #[serde_with::skip_serializing_none]
@@ -78,6 +508,41 @@ pub struct RegionData {
     data: Vec<Data>,
 }
 
+/// Current intensity of a region, with the details of the region
+#[derive(Debug)]
+pub struct RegionalIntensity {
+    pub region: Region,
+    /// Name of the region as sent by the API e.g. "South West England"
+    pub shortname: String,
+    /// Distribution network operator e.g. "WPD South West", not sent for every query
+    pub dno_region: Option<String>,
+    pub intensity: Intensity,
+}
+
+impl TryFrom<RegionData> for RegionalIntensity {
+    type Error = ApiError;
+
+    fn try_from(region_data: RegionData) -> Result<Self> {
+        let region = region_data
+            .regionid
+            .to_string()
+            .parse::<Region>()
+            .map_err(|error| ApiError::Error(format!("{error}: {}", region_data.regionid)))?;
+        let intensity = region_data
+            .data
+            .into_iter()
+            .next()
+            .ok_or_else(|| ApiError::Error("No intensity data found".to_string()))?
+            .intensity;
+        Ok(Self {
+            region,
+            shortname: region_data.shortname,
+            dno_region: region_data.dnoregion,
+            intensity: check_forecast(intensity)?,
+        })
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct Root {
     data: Vec<RegionData>,
@@ -93,6 +558,46 @@ struct NationalData {
     data: Vec<Data>,
 }
 
+/// Intensities of every region, one entry per half-hour
+#[derive(Debug, Deserialize)]
+struct AllRegionsData {
+    data: Vec<RegionsForPeriod>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RegionsForPeriod {
+    from: String,
+    to: String,
+    regions: Vec<RegionIntensity>,
+    #[serde(skip)]
+    url: Option<Arc<str>>,
+    #[serde(skip)]
+    fetched_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RegionIntensity {
+    regionid: i32,
+    /// Only sent by /regional
+    dnoregion: Option<String>,
+    shortname: Option<String>,
+    intensity: Intensity,
+    generationmix: Option<Vec<GenerationMix>>,
+}
+
+/// Error payload sent by the API e.g.
+/// `{"error":{"code":"400 Bad Request","message":"Please enter a valid postcode"}}`
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: ErrorDetails,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorDetails {
+    code: String,
+    message: String,
+}
+
 static BASE_URL: &str = "https://api.carbonintensity.org.uk";
 
 /// Current carbon intensity for a target (e.g. a region)
@@ -101,13 +606,76 @@ static BASE_URL: &str = "https://api.carbonintensity.org.uk";
 /// - <https://api.carbonintensity.org.uk/regional/postcode/>
 /// - <https://api.carbonintensity.org.uk/regional/regionid/>
 /// - <https://api.carbonintensity.org.uk/intensity>
+#[deprecated(
+    since = "0.4.0",
+    note = "use CarbonIntensityClient::get_intensity() instead, e.g. `CarbonIntensityClient::new().get_intensity(&target)`"
+)]
 pub async fn get_intensity(target: &Target) -> Result<i32> {
+    CarbonIntensityClient::shared().get_intensity(target).await
+}
+
+/// Current carbon intensity for a target, with its index
+///
+/// Same as `get_intensity` but returns the full `Intensity`
+/// as sent by the API.
+pub async fn get_current_intensity(target: &Target) -> Result<Intensity> {
+    CarbonIntensityClient::shared()
+        .get_current_intensity(target)
+        .await
+}
+
+/// Current carbon intensity for a region or postcode, with the region it belongs to
+///
+/// Useful to find out which DNO region a postcode maps to. Returns an error
+/// for the national target.
+pub async fn get_regional_intensity(target: &Target) -> Result<RegionalIntensity> {
+    CarbonIntensityClient::shared()
+        .get_regional_intensity(target)
+        .await
+}
+
+/// Current carbon intensity of every region, with the details of the regions
+///
+/// Uses <https://api.carbonintensity.org.uk/regional>, a single request for the
+/// whole country. The regions are ordered by id, those without a forecast for
+/// the current half-hour are left out.
+pub async fn get_all_regional_intensities() -> Result<Vec<RegionalIntensity>> {
+    CarbonIntensityClient::shared()
+        .get_all_regional_intensities()
+        .await
+}
+
+/// Regions of a period with a forecast, ordered by id
+fn regional_intensities(period: RegionsForPeriod) -> Vec<RegionalIntensity> {
+    let mut intensities: Vec<RegionalIntensity> = period
+        .regions
+        .into_iter()
+        .filter_map(|region_intensity| {
+            let region = region_intensity
+                .regionid
+                .to_string()
+                .parse::<Region>()
+                .ok()?;
+            Some(RegionalIntensity {
+                region,
+                shortname: region_intensity
+                    .shortname
+                    .unwrap_or_else(|| region.short_name().to_string()),
+                dno_region: region_intensity.dnoregion,
+                intensity: check_forecast(region_intensity.intensity).ok()?,
+            })
+        })
+        .collect();
+    intensities.sort_by_key(|intensity| intensity.region as u8);
+    intensities
+}
+
+/// Path of the endpoint returning the current intensity for a target
+fn current_path(target: &Target) -> Result<String> {
     let path = match target {
         Target::Postcode(postcode) => {
-            if postcode.len() < 2 || postcode.len() > 4 {
-                return Err(ApiError::Error("Invalid postcode".to_string()));
-            }
-            format!("regional/postcode/{postcode}")
+            let outward = outward_code(postcode)?;
+            format!("regional/postcode/{outward}")
         }
         &Target::Region(region) => {
             let region_id = region as u8;
@@ -115,13 +683,7 @@ pub async fn get_intensity(target: &Target) -> Result<i32> {
         }
         &Target::National => "intensity".to_string(),
     };
-
-    let url = format!("{BASE_URL}/{path}");
-    if *target != Target::National {
-        get_intensity_for_url(&url).await
-    } else {
-        get_intensity_for_url_national(&url).await
-    }
+    Ok(path)
 }
 
 fn parse_date(date: &str) -> std::result::Result<NaiveDateTime, chrono::ParseError> {
@@ -135,43 +697,49 @@ fn parse_date(date: &str) -> std::result::Result<NaiveDateTime, chrono::ParseErr
 /// Normalises the start and end dates
 /// returns ranges that are acceptable by the API
 /// both in their duration and string representation
-fn normalise_dates(start: &str, end: &Option<&str>) -> Result<Vec<(NaiveDateTime, NaiveDateTime)>> {
-    let start_date = parse_date(start)?;
-
-    let now = Local::now().naive_local();
-
-    // if the end is not set - use now
-    let end_date = match end {
-        None => now,
-        Some(end_date) => parse_date(end_date)?,
-    };
-
-    let start_date = validate_date(start_date);
-    let end_date = validate_date(end_date);
-
-    //  split into ranges
-    let mut ranges = Vec::new();
-
-    let duration = Duration::days(13);
-    let mut current = start_date;
-    loop {
-        let mut next_end = current + duration;
-        // break the end of year boundary
-        let new_year_d = NaiveDate::from_ymd_opt(current.year() + 1, 1, 1).unwrap();
-        let new_year = NaiveDateTime::new(new_year_d, NaiveTime::from_hms_opt(0, 0, 0).unwrap());
-        if next_end >= new_year {
-            next_end = new_year;
-        }
-        if next_end >= end_date {
-            ranges.push((current, end_date));
-            break;
-        } else {
-            ranges.push((current, next_end));
-        }
+///
+/// Dates in the future are accepted up to the forecast horizon,
+/// the future part of the range is then a separate forecast window.
+fn normalise_dates(
+    start: &str,
+    end: &Option<&str>,
+    clock: &dyn Clock,
+    chunking: &Chunking,
+) -> Result<Vec<(NaiveDateTime, NaiveDateTime)>> {
+    Ok(DateRange::parse(start, end, clock)?.windows(clock.now(), chunking))
+}
 
-        current = next_end;
-    }
-    Ok(ranges)
+/// Splits a range into the windows requested by the range queries of this crate
+///
+/// The dates are validated as for `get_intensities`. With the default `Chunking`,
+/// each window lasts at most 13 days and never spans two years. The part of the
+/// range after the current half-hour is a separate forecast window. This allows to fetch or store the
+/// windows independently while reusing the same splitting.
+///
+/// ```
+/// # use chrono::NaiveDate;
+/// # use carbonintensity::{plan_windows, FixedClock, QueryOptions};
+/// let now = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+/// let options = QueryOptions::new().with_clock(FixedClock::new(now));
+/// let windows = plan_windows("2023-12-20", &Some("2024-01-10"), &options).unwrap();
+/// assert_eq!(windows.len(), 2);
+/// assert_eq!(windows[0].url_bounds(), ("2023-12-20T00:01Z".to_string(), "2024-01-01T00:01Z".to_string()));
+/// ```
+pub fn plan_windows(
+    start: &str,
+    end: &Option<&str>,
+    options: &QueryOptions,
+) -> Result<Vec<PlannedWindow>> {
+    let now = options.clock().now();
+    let windows = normalise_dates(start, end, options.clock(), &Chunking::default())?
+        .into_iter()
+        .map(|window| PlannedWindow {
+            start: window.0,
+            end: window.1,
+            forecast: RangePlanner.is_forecast(&window, now),
+        })
+        .collect();
+    Ok(windows)
 }
 
 /// Get intensities for a given target (region or postcode) in 30 minutes windows
@@ -183,18 +751,508 @@ fn normalise_dates(start: &str, end: &Option<&str>) -> Result<Vec<(NaiveDateTime
 /// - https://api.carbonintensity.org.uk/regional/intensity/2023-05-15/2023-05-20/postcode/RG10
 /// - https://api.carbonintensity.org.uk/regional/intensity/2023-05-15/2023-05-20/regionid/13
 /// - https://api.carbonintensity.org.uk/intensity/2023-05-15/2023-05-20/
+#[deprecated(
+    since = "0.4.0",
+    note = "use CarbonIntensityClient::get_intensities() instead, e.g. `CarbonIntensityClient::new().get_intensities(&target, start, end)`"
+)]
 pub async fn get_intensities(
     target: &Target,
     start: &str,
     end: &Option<&str>,
 ) -> Result<Vec<IntensityForDate>> {
+    CarbonIntensityClient::shared()
+        .get_intensities(target, start, end)
+        .await
+}
+
+/// Same as `get_intensities` but with control over the execution of the query
+///
+/// When a deadline is set, the windows which haven't been retrieved in time
+/// are cancelled and whatever data was obtained is returned, along with
+/// a warning for each missing window.
+pub async fn get_intensities_with_options(
+    target: &Target,
+    start: &str,
+    end: &Option<&str>,
+    options: &QueryOptions,
+) -> Result<QueryOutcome> {
+    CarbonIntensityClient::shared()
+        .get_intensities_with_options(target, start, end, options)
+        .await
+}
+
+/// Get the data for a given target in 30 minutes windows
+///
+/// Same as `get_intensities_with_options` but returns the data as sent by
+/// the API, including the index and generation mix.
+pub async fn get_detailed_intensities(
+    target: &Target,
+    start: &str,
+    end: &Option<&str>,
+    options: &QueryOptions,
+) -> Result<QueryOutcome<Data>> {
+    CarbonIntensityClient::shared()
+        .get_detailed_intensities(target, start, end, options)
+        .await
+}
+
+/// Same as `get_intensities` with the dates as chrono values, e.g. `NaiveDate` or `DateTime<Utc>`
+///
+/// ```no_run
+/// # use chrono::{Duration, NaiveDate};
+/// # use carbonintensity::{get_intensities_between, Target};
+/// # async fn run() -> carbonintensity::Result<()> {
+/// let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+/// let end = start + Duration::days(7);
+/// let week = get_intensities_between(&Target::National, start, Some(end.into())).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn get_intensities_between(
+    target: &Target,
+    start: impl Into<DateBound>,
+    end: Option<DateBound>,
+) -> Result<Vec<IntensityForDate>> {
+    CarbonIntensityClient::shared()
+        .get_intensities_between(target, start, end)
+        .await
+}
+
+/// Same as `get_detailed_intensities` with the dates as chrono values
+pub async fn get_detailed_intensities_between(
+    target: &Target,
+    start: impl Into<DateBound>,
+    end: Option<DateBound>,
+    options: &QueryOptions,
+) -> Result<QueryOutcome<Data>> {
+    CarbonIntensityClient::shared()
+        .get_detailed_intensities_between(target, start, end, options)
+        .await
+}
+
+/// Same as `get_detailed_intensities` for a range validated beforehand
+pub async fn get_detailed_intensities_in_range(
+    target: &Target,
+    range: &DateRange,
+    options: &QueryOptions,
+) -> Result<QueryOutcome<Data>> {
+    CarbonIntensityClient::shared()
+        .get_detailed_intensities_in_range(target, range, options)
+        .await
+}
+
+/// Forecasts for a target over the coming hours, from the current half-hour
+///
+/// `hours` is between 1 and 48, the horizon of the forecasts. The half-hours
+/// to come are retrieved as forward periods, using one of
+/// - https://api.carbonintensity.org.uk/regional/intensity/2024-06-15T12:01Z/fw48h/postcode/RG10
+/// - https://api.carbonintensity.org.uk/regional/intensity/2024-06-15T12:01Z/fw48h/regionid/13
+/// - https://api.carbonintensity.org.uk/intensity/2024-06-15T12:01Z/fw48h
+pub async fn get_forecast(target: &Target, hours: u32) -> Result<Vec<Data>> {
+    CarbonIntensityClient::shared()
+        .get_forecast(target, hours)
+        .await
+}
+
+/// Same as `get_forecast` but with control over the execution of the query
+pub async fn get_forecast_with_options(
+    target: &Target,
+    hours: u32,
+    options: &QueryOptions,
+) -> Result<QueryOutcome<Data>> {
+    CarbonIntensityClient::shared()
+        .get_forecast_with_options(target, hours, options)
+        .await
+}
+
+/// Get the intensities of every region in 30 minutes windows
+///
+/// Uses <https://api.carbonintensity.org.uk/regional/intensity/2023-05-15/2023-05-20>
+/// which returns all the regions at once, so this needs far fewer requests than
+/// querying each region separately. The series are ordered by region id.
+pub async fn get_intensities_all_regions(
+    start: &str,
+    end: &Option<&str>,
+) -> Result<Vec<(Region, Vec<IntensityForDate>)>> {
+    CarbonIntensityClient::shared()
+        .get_intensities_all_regions(start, end)
+        .await
+}
+
+/// Same as `get_intensities_all_regions` but returns the data as sent by the API,
+/// with control over the execution of the query
+pub async fn get_detailed_intensities_all_regions(
+    start: &str,
+    end: &Option<&str>,
+    options: &QueryOptions,
+) -> Result<QueryOutcome<(Region, Vec<Data>)>> {
+    CarbonIntensityClient::shared()
+        .get_detailed_intensities_all_regions(start, end, options)
+        .await
+}
+
+/// Same as `get_detailed_intensities_all_regions` for a range validated beforehand,
+/// e.g. with `DateRange::within_horizon()` for a period in progress
+pub async fn get_detailed_intensities_all_regions_in_range(
+    range: &DateRange,
+    options: &QueryOptions,
+) -> Result<QueryOutcome<(Region, Vec<Data>)>> {
+    CarbonIntensityClient::shared()
+        .get_detailed_intensities_all_regions_in_range(range, options)
+        .await
+}
+
+impl CarbonIntensityClient {
+    /// Current carbon intensity for a target, see `get_intensity()`
+    pub async fn get_intensity(&self, target: &Target) -> Result<i32> {
+        let intensity = self.get_current_intensity(target).await?;
+        current_value(target, &intensity)
+    }
+
+    /// Current carbon intensity for a target with its index, see `get_current_intensity()`
+    pub async fn get_current_intensity(&self, target: &Target) -> Result<Intensity> {
+        check_forecast(self.get_current_data(target).await?.intensity)
+    }
+
+    /// Current half-hour for a target, with its dates
+    pub(crate) async fn get_current_data(&self, target: &Target) -> Result<Data> {
+        let url = format!("{}/{}", self.base_url(), current_path(target)?);
+        if *target != Target::National {
+            get_data_for_url(self, &url).await
+        } else {
+            get_data_for_url_national(self, &url).await
+        }
+    }
+
+    /// Current carbon intensity for a region or postcode, see `get_regional_intensity()`
+    pub async fn get_regional_intensity(&self, target: &Target) -> Result<RegionalIntensity> {
+        if *target == Target::National {
+            return Err(ApiError::Error(
+                "A region or postcode is required".to_string(),
+            ));
+        }
+        let url = format!("{}/{}", self.base_url(), current_path(target)?);
+        let region_data = get_region_data_for_url(self, &url).await?;
+        RegionalIntensity::try_from(region_data)
+    }
+
+    /// Current carbon intensity of every region, see `get_all_regional_intensities()`
+    pub async fn get_all_regional_intensities(&self) -> Result<Vec<RegionalIntensity>> {
+        let url = format!("{}/regional", self.base_url());
+        let data = self.get_response::<AllRegionsData>(&url).await?;
+        Ok(regional_intensities(first(data.data)?))
+    }
+
+    /// Intensities for a target in 30 minutes windows, see `get_intensities()`
+    pub async fn get_intensities(
+        &self,
+        target: &Target,
+        start: &str,
+        end: &Option<&str>,
+    ) -> Result<Vec<IntensityForDate>> {
+        let outcome = self
+            .get_intensities_with_options(target, start, end, &QueryOptions::default())
+            .await?;
+        Ok(outcome.intensities)
+    }
+
+    /// See `get_intensities_with_options()`
+    pub async fn get_intensities_with_options(
+        &self,
+        target: &Target,
+        start: &str,
+        end: &Option<&str>,
+        options: &QueryOptions,
+    ) -> Result<QueryOutcome> {
+        let outcome = self
+            .get_detailed_intensities(target, start, end, options)
+            .await?;
+        Ok(QueryOutcome {
+            intensities: to_tuples(outcome.intensities)?,
+            warnings: outcome.warnings,
+            report: outcome.report,
+        })
+    }
+
+    /// Intensities for a target in 30 minutes windows, see `get_intensities_between()`
+    pub async fn get_intensities_between(
+        &self,
+        target: &Target,
+        start: impl Into<DateBound>,
+        end: Option<DateBound>,
+    ) -> Result<Vec<IntensityForDate>> {
+        let outcome = self
+            .get_detailed_intensities_between(target, start, end, &QueryOptions::default())
+            .await?;
+        to_tuples(outcome.intensities)
+    }
+
+    /// Data for a target in 30 minutes windows, see `get_detailed_intensities()`
+    pub async fn get_detailed_intensities(
+        &self,
+        target: &Target,
+        start: &str,
+        end: &Option<&str>,
+        options: &QueryOptions,
+    ) -> Result<QueryOutcome<Data>> {
+        let start: DateBound = start.parse()?;
+        let end = end.map(str::parse::<DateBound>).transpose()?;
+        self.get_detailed_intensities_between(target, start, end, options)
+            .await
+    }
+
+    /// See `get_detailed_intensities_between()`
+    pub async fn get_detailed_intensities_between(
+        &self,
+        target: &Target,
+        start: impl Into<DateBound>,
+        end: Option<DateBound>,
+        options: &QueryOptions,
+    ) -> Result<QueryOutcome<Data>> {
+        let range = DateRange::new(start, end, options.clock())?;
+        self.get_detailed_intensities_in_range(target, &range, options)
+            .await
+    }
+
+    /// See `get_detailed_intensities_in_range()`
+    pub async fn get_detailed_intensities_in_range(
+        &self,
+        target: &Target,
+        range: &DateRange,
+        options: &QueryOptions,
+    ) -> Result<QueryOutcome<Data>> {
+        let path = range_path(target)?;
+
+        let started = Stopwatch::start();
+        let now = options.clock().now();
+        let ranges = range.windows(now, self.chunking());
+
+        let provenance = options.provenance();
+        let base_url = self.base_url_for(options);
+        // Spawns concurrent tasks...
+        let tasks: Vec<_> = ranges
+            .iter()
+            .map(|window| {
+                let url = window_url(base_url, target, &path, window, now);
+                let settled = RangePlanner.is_settled(window, now);
+                let national = *target == Target::National;
+                let client = self.clone();
+                spawn_window(self.permits(), async move {
+                    get_window_data(&client, &url, national, settled, provenance).await
+                })
+            })
+            .collect();
+
+        let end = ranges.last().map(|window| window.1);
+        let mut outcome = collect_windows(
+            tasks,
+            ranges,
+            self.max_concurrent_requests(),
+            options.deadline(),
+            started,
+        )
+        .await?;
+        outcome.report = report(outcome.report, started, options);
+        check_range_data(&mut outcome, range, end, options)?;
+        Ok(outcome)
+    }
+
+    /// Forecasts for a target over the coming hours, see `get_forecast()`
+    pub async fn get_forecast(&self, target: &Target, hours: u32) -> Result<Vec<Data>> {
+        let outcome = self
+            .get_forecast_with_options(target, hours, &QueryOptions::default())
+            .await?;
+        Ok(outcome.intensities)
+    }
+
+    /// See `get_forecast_with_options()`
+    pub async fn get_forecast_with_options(
+        &self,
+        target: &Target,
+        hours: u32,
+        options: &QueryOptions,
+    ) -> Result<QueryOutcome<Data>> {
+        let (start, end) = forecast_bounds(hours, options)?;
+        self.get_detailed_intensities(target, &start, &Some(&end), options)
+            .await
+    }
+
+    /// Intensities of all the regions, see `get_intensities_all_regions()`
+    pub async fn get_intensities_all_regions(
+        &self,
+        start: &str,
+        end: &Option<&str>,
+    ) -> Result<Vec<(Region, Vec<IntensityForDate>)>> {
+        let outcome = self
+            .get_detailed_intensities_all_regions(start, end, &QueryOptions::default())
+            .await?;
+        outcome
+            .intensities
+            .into_iter()
+            .map(|(region, data)| Ok((region, to_tuples(data)?)))
+            .collect()
+    }
+
+    /// See `get_detailed_intensities_all_regions()`
+    pub async fn get_detailed_intensities_all_regions(
+        &self,
+        start: &str,
+        end: &Option<&str>,
+        options: &QueryOptions,
+    ) -> Result<QueryOutcome<(Region, Vec<Data>)>> {
+        let range = DateRange::parse(start, end, options.clock())?;
+        self.get_detailed_intensities_all_regions_in_range(&range, options)
+            .await
+    }
+
+    /// See `get_detailed_intensities_all_regions_in_range()`
+    pub async fn get_detailed_intensities_all_regions_in_range(
+        &self,
+        range: &DateRange,
+        options: &QueryOptions,
+    ) -> Result<QueryOutcome<(Region, Vec<Data>)>> {
+        let started = Stopwatch::start();
+        let now = options.clock().now();
+        let ranges = range.windows(now, self.chunking());
+
+        let provenance = options.provenance();
+        let base_url = self.base_url_for(options);
+        let tasks: Vec<_> = ranges
+            .iter()
+            .map(|window| {
+                let (start_date, end_date) = window_bounds(window, now);
+                let settled = RangePlanner.is_settled(window, now);
+                let url = format!("{base_url}/regional/intensity/{start_date}/{end_date}");
+                let client = self.clone();
+                spawn_window(self.permits(), async move {
+                    let mut periods = client
+                        .get_window_response::<AllRegionsData>(&url, settled)
+                        .await?
+                        .data;
+                    if provenance {
+                        let (url, fetched_at): (Arc<str>, _) = (Arc::from(url), Utc::now());
+                        for period in periods.iter_mut() {
+                            period.url = Some(url.clone());
+                            period.fetched_at = Some(fetched_at);
+                        }
+                    }
+                    Ok(periods)
+                })
+            })
+            .collect();
+
+        let end = ranges.last().map(|window| window.1);
+        let mut outcome = collect_windows(
+            tasks,
+            ranges,
+            self.max_concurrent_requests(),
+            options.deadline(),
+            started,
+        )
+        .await?;
+        let report = report(outcome.report, started, options);
+        if let Some(end) = end {
+            outcome
+                .intensities
+                .retain(|period| parse_date(&period.from).map_or(true, |from| from <= end));
+        }
+        let mut warnings = outcome.warnings;
+        warnings.extend(horizon_warning(range));
+        let mut intensities = by_region(outcome.intensities);
+        for (_, data) in intensities.iter_mut() {
+            discard_implausible(data, &mut warnings);
+            check_mixes(data, options.mix_policy(), &mut warnings);
+            apply_null_policy(data, options.null_policy(), &mut warnings)?;
+        }
+        intensities.retain(|(_, data)| !data.is_empty());
+        Ok(QueryOutcome {
+            intensities,
+            warnings,
+            report,
+        })
+    }
+}
+
+/// Value of the current intensity: the actual one when the API sent it,
+/// only for the national target, the forecast otherwise
+fn current_value(target: &Target, intensity: &Intensity) -> Result<i32> {
+    let value = if *target != Target::National {
+        intensity.forecast
+    } else {
+        intensity.value()
+    };
+    value.ok_or_else(|| ApiError::Error(format!("No intensity sent for {target}")))
+}
+
+/// Start and end of the half-hours forecast over the coming hours
+fn forecast_bounds(hours: u32, options: &QueryOptions) -> Result<(String, String)> {
+    let horizon = planner::FORECAST_HORIZON.num_hours();
+    if hours == 0 || i64::from(hours) > horizon {
+        return Err(ApiError::Error(format!(
+            "Invalid number of hours: {hours}, expected between 1 and {horizon}"
+        )));
+    }
+    let start = options
+        .clock()
+        .now()
+        .duration_trunc(chrono::Duration::minutes(30))
+        .map_err(|error| ApiError::Error(error.to_string()))?;
+    // start of the last half-hour
+    let end = start + chrono::Duration::hours(hours.into()) - chrono::Duration::minutes(30);
+    let format = |time: NaiveDateTime| time.format("%Y-%m-%dT%H:%MZ").to_string();
+    Ok((format(start), format(end)))
+}
+
+/// Checks the data retrieved for a range, `end` being the start of its last window
+///
+/// Removes the half-hours after the range and those with implausible values,
+/// then applies the policies of the options.
+fn check_range_data(
+    outcome: &mut QueryOutcome<Data>,
+    range: &DateRange,
+    end: Option<NaiveDateTime>,
+    options: &QueryOptions,
+) -> Result<()> {
+    if let Some(end) = end {
+        discard_after(&mut outcome.intensities, end);
+    }
+    outcome.warnings.extend(horizon_warning(range));
+    discard_implausible(&mut outcome.intensities, &mut outcome.warnings);
+    check_mixes(
+        &mut outcome.intensities,
+        options.mix_policy(),
+        &mut outcome.warnings,
+    );
+    apply_null_policy(
+        &mut outcome.intensities,
+        options.null_policy(),
+        &mut outcome.warnings,
+    )
+}
+
+/// Warning for the part of a range moved back to the forecast horizon
+fn horizon_warning(range: &DateRange) -> Option<Warning> {
+    range
+        .beyond_horizon()
+        .map(|(start, end)| Warning::BeyondHorizon { start, end })
+}
+
+/// Bounds of a window in the URLs, forward periods for the forecast windows
+fn window_bounds(window: &(NaiveDateTime, NaiveDateTime), now: NaiveDateTime) -> (String, String) {
+    PlannedWindow {
+        start: window.0,
+        end: window.1,
+        forecast: RangePlanner.is_forecast(window, now),
+    }
+    .url_bounds()
+}
+
+/// Path identifying the target in the URLs of the range queries
+fn range_path(target: &Target) -> Result<String> {
     let path = match target {
         Target::Postcode(postcode) => {
-            if postcode.len() < 2 || postcode.len() > 4 {
-                return Err(ApiError::Error("Invalid postcode".to_string()));
-            }
-
-            format!("postcode/{postcode}")
+            let outward = outward_code(postcode)?;
+            format!("postcode/{outward}")
         }
         &Target::Region(region) => {
             let region_id = region as u8;
@@ -202,149 +1260,416 @@ pub async fn get_intensities(
         }
         &Target::National => "intensity".to_string(),
     };
+    Ok(path)
+}
+
+/// URL of a window of a range query, `path` being the one of the target
+fn window_url(
+    base_url: &str,
+    target: &Target,
+    path: &str,
+    window: &(NaiveDateTime, NaiveDateTime),
+    now: NaiveDateTime,
+) -> String {
+    let (start_date, end_date) = window_bounds(window, now);
+    if *target != Target::National {
+        format!("{base_url}/regional/intensity/{start_date}/{end_date}/{path}")
+    } else {
+        format!("{base_url}/{path}/{start_date}/{end_date}/")
+    }
+}
 
-    let ranges = normalise_dates(start, end)?;
+/// Data of a window of a range query, stamped with its provenance if requested
+async fn get_window_data(
+    client: &CarbonIntensityClient,
+    url: &str,
+    national: bool,
+    settled: bool,
+    provenance: bool,
+) -> Result<Vec<Data>> {
+    let mut data = if national {
+        get_intensities_for_url_national(client, url, settled)
+            .await?
+            .data
+    } else {
+        get_intensities_for_url(client, url, settled).await?.data
+    };
+    if provenance {
+        Provenance::stamp(&mut data, url);
+    }
+    Ok(data)
+}
 
-    // Spawns concurrent tasks...
-    let tasks: Vec<_> = ranges
-        .into_iter()
-        .map(|(start_date, end_date)| {
-            // shift dates by one minute
-            let start_date = start_date + Duration::minutes(1);
-            let end_date = end_date + Duration::minutes(1);
-            // format dates
-            let start_date = start_date.format("%Y-%m-%dT%H:%MZ");
-            let end_date = end_date.format("%Y-%m-%dT%H:%MZ");
-
-            if *target != Target::National {
-                let url = format!("{BASE_URL}/regional/intensity/{start_date}/{end_date}/{path}");
-
-                tokio::spawn(async move {
-                    let region_data = get_intensities_for_url(&url).await?;
-                    to_tuples(region_data.data)
-                })
-            } else {
-                let url = format!("{BASE_URL}/{path}/{start_date}/{end_date}/");
+/// Removes the half-hours starting after the end of the range,
+/// returned by the forward queries of the forecast windows
+fn discard_after(data: &mut Vec<Data>, end: NaiveDateTime) {
+    data.retain(|data| parse_date(&data.from).map_or(true, |from| from <= end));
+}
 
-                tokio::spawn(async move {
-                    let national_data = get_intensities_for_url_national(&url).await?;
-                    to_tuples(national_data.data)
-                })
+/// Removes the half-hours with implausible values, reporting them as warnings
+/// so that they don't skew averages
+fn discard_implausible(data: &mut Vec<Data>, warnings: &mut Vec<Warning>) {
+    data.retain(|data| match data.intensity.implausible_value() {
+        None => true,
+        Some(value) => {
+            warnings.push(Warning::ImplausibleIntensity {
+                from: data.from.clone(),
+                value,
+            });
+            false
+        }
+    });
+}
+
+/// Reports the generation mixes which don't add up to 100%,
+/// scaling them if required by the policy
+fn check_mixes(data: &mut [Data], policy: MixPolicy, warnings: &mut Vec<Warning>) {
+    for data in data.iter_mut() {
+        let Some(total) = data.mix_total() else {
+            continue;
+        };
+        if (total - 100.0).abs() <= MIX_TOTAL_TOLERANCE {
+            continue;
+        }
+        let renormalised = policy == MixPolicy::Renormalise && total > 0.0;
+        if renormalised {
+            for mix in data.generationmix.iter_mut().flatten() {
+                mix.perc *= 100.0 / total;
             }
-        })
+        }
+        warnings.push(Warning::InconsistentMix {
+            from: data.from.clone(),
+            total,
+            renormalised,
+        });
+    }
+}
+
+/// Handles the half-hours without a forecast according to the policy
+///
+/// Interpolates linearly between the nearest half-hours with a forecast, or
+/// copies the nearest one at the start or end of the data. The half-hours with
+/// an actual value are kept when skipped and don't fail the query, only those
+/// with neither value being missing.
+fn apply_null_policy(
+    data: &mut Vec<Data>,
+    policy: NullPolicy,
+    warnings: &mut Vec<Warning>,
+) -> Result<()> {
+    let known: Vec<(usize, i32)> = data
+        .iter()
+        .enumerate()
+        .filter_map(|(position, data)| data.intensity.forecast.map(|f| (position, f)))
         .collect();
+    if known.len() == data.len() {
+        return Ok(());
+    }
 
-    let tasks_results = future::try_join_all(tasks).await?;
-    tasks_results
-        .into_iter()
-        .collect::<Result<Vec<_>>>() // convert to single Result
-        .map(|nested_tuples| nested_tuples.into_iter().flatten().collect())
+    match policy {
+        NullPolicy::Error => {
+            if let Some(data) = data.iter().find(|data| is_missing(&data.intensity)) {
+                return Err(ApiError::Error(format!(
+                    "No intensity sent for {}",
+                    data.from
+                )));
+            }
+        }
+        NullPolicy::Interpolate if !known.is_empty() => {
+            for (position, data) in data.iter_mut().enumerate() {
+                if !is_missing(&data.intensity) {
+                    continue;
+                }
+                let next = known.partition_point(|(known, _)| *known < position);
+                let forecast = match (next.checked_sub(1).map(|p| known[p]), known.get(next)) {
+                    (Some((before, a)), Some(&(after, b))) => {
+                        let ratio = (position - before) as f64 / (after - before) as f64;
+                        (f64::from(a) + f64::from(b - a) * ratio).round() as i32
+                    }
+                    (Some((_, value)), None) | (None, Some(&(_, value))) => value,
+                    // known is not empty
+                    (None, None) => continue,
+                };
+                data.intensity.forecast = Some(forecast);
+                if let Some(provenance) = data.provenance.as_mut() {
+                    provenance.interpolated = true;
+                }
+                warnings.push(Warning::NullIntensity {
+                    from: data.from.clone(),
+                    interpolated: true,
+                });
+            }
+        }
+        // nothing to interpolate from
+        NullPolicy::Skip | NullPolicy::Interpolate => {
+            data.retain(|data| {
+                if !is_missing(&data.intensity) {
+                    return true;
+                }
+                warnings.push(Warning::NullIntensity {
+                    from: data.from.clone(),
+                    interpolated: false,
+                });
+                false
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Neither the forecast nor the actual intensity was sent
+fn is_missing(intensity: &Intensity) -> bool {
+    intensity.forecast.is_none() && intensity.actual.is_none()
+}
+
+/// Reshapes the periods returned for all the regions into one series per region
+///
+/// Ignores the regions which are not listed in `Region`.
+fn by_region(periods: Vec<RegionsForPeriod>) -> Vec<(Region, Vec<Data>)> {
+    let mut series: Vec<(Region, Vec<Data>)> = Region::ALL
+        .iter()
+        .map(|region| (*region, Vec::new()))
+        .collect();
+
+    for period in periods {
+        for region_intensity in period.regions {
+            let Ok(region) = region_intensity.regionid.to_string().parse::<Region>() else {
+                continue;
+            };
+            let provenance = period
+                .url
+                .clone()
+                .zip(period.fetched_at)
+                .map(|(url, fetched_at)| Provenance {
+                    url,
+                    fetched_at,
+                    actual: region_intensity.intensity.actual.is_some(),
+                    interpolated: false,
+                });
+            series[region as usize - 1].1.push(Data {
+                from: period.from.clone(),
+                to: period.to.clone(),
+                intensity: region_intensity.intensity,
+                generationmix: region_intensity.generationmix,
+                provenance,
+            });
+        }
+    }
+
+    series.retain(|(_, data)| !data.is_empty());
+    series
+}
+
+/// Waits for the tasks retrieving each window
+///
+/// Tasks run concurrently, waiting for them in order keeps the results
+/// sorted by date. The tasks still running when the deadline expires are
+/// cancelled and reported as warnings. The report of the windows is always
+/// filled in, the wall time being left to the caller.
+async fn collect_windows<T>(
+    tasks: Vec<WindowTask<T>>,
+    ranges: Vec<(NaiveDateTime, NaiveDateTime)>,
+    max_concurrent_requests: usize,
+    deadline: Option<std::time::Duration>,
+    started: Stopwatch,
+) -> Result<QueryOutcome<T>> {
+    let mut outcome = QueryOutcome::default();
+    let mut windows = Vec::with_capacity(ranges.len());
+
+    for (task, (start, end)) in tasks.into_iter().zip(ranges) {
+        let remaining = deadline.map(|deadline| deadline.saturating_sub(started.elapsed()));
+        let Some(result) = join_window(task, remaining).await else {
+            outcome
+                .warnings
+                .push(Warning::DeadlineExceeded { start, end });
+            windows.push(WindowReport {
+                start,
+                end,
+                records: 0,
+                elapsed: None,
+            });
+            continue;
+        };
+        let (data, elapsed) = result?;
+        windows.push(WindowReport {
+            start,
+            end,
+            records: data.len(),
+            elapsed: Some(elapsed),
+        });
+        outcome.intensities.extend(data);
+    }
+
+    outcome.report = Some(ExecutionReport {
+        windows,
+        wall_time: std::time::Duration::ZERO,
+        max_concurrent_requests,
+    });
+    Ok(outcome)
+}
+
+/// Completes the report with the wall time, if requested in the options
+fn report(
+    report: Option<ExecutionReport>,
+    started: Stopwatch,
+    options: &QueryOptions,
+) -> Option<ExecutionReport> {
+    report
+        .filter(|_| options.execution_report())
+        .map(|report| ExecutionReport {
+            wall_time: started.elapsed(),
+            ..report
+        })
 }
 
 /// converts the values from JSON into a simpler
 /// representation Vec<DateTime, float>
 fn to_tuples(data: Vec<Data>) -> Result<Vec<IntensityForDate>> {
-    data.into_iter()
-        .map(|datum| {
-            let start_date = parse_date(&datum.from)?;
-            let intensity = datum.intensity.actual.unwrap_or(datum.intensity.forecast);
-            Ok((start_date, intensity))
-        })
-        .collect()
+    data.iter().map(Data::intensity_for_date).collect()
 }
 
 /// Returns a date within a valid date
 ///
 /// Datetimes older than 2018-05-10 23:30:00 are invalid.
-/// Also, datetimes in the future are invalid.
+/// Also, datetimes beyond the 48 hours forecast by the API are invalid.
 ///
 /// - if a datetime is too old, returns the oldest valid date
-/// - if a datetime is in the future, returns now
+/// - if a datetime is beyond the forecast horizon, returns an error
 /// - otherwise returns the input datetime
-fn validate_date(date: NaiveDateTime) -> NaiveDateTime {
-    let now = Local::now().naive_local();
-
+fn validate_date(date: NaiveDateTime, now: NaiveDateTime) -> Result<NaiveDateTime> {
     // check if date is too old
     if date < *OLDEST_VALID_DATE {
-        return *OLDEST_VALID_DATE;
+        return Ok(*OLDEST_VALID_DATE);
     }
-    // check that the date is not in the future
-    if date > now {
-        return now;
+    // check that the date is within the forecasts
+    if date > now + planner::FORECAST_HORIZON {
+        return Err(ApiError::Error(format!(
+            "{date} is beyond the forecast horizon of {} hours",
+            planner::FORECAST_HORIZON.num_hours()
+        )));
     }
 
-    date
+    Ok(date)
 }
 
-async fn get_intensities_for_url(url: &str) -> Result<RegionData> {
-    let PowerData { data } = get_response(url).await?;
+async fn get_intensities_for_url(
+    client: &CarbonIntensityClient,
+    url: &str,
+    settled: bool,
+) -> Result<RegionData> {
+    let PowerData { data } = client.get_window_response(url, settled).await?;
     Ok(data)
 }
 
-async fn get_intensities_for_url_national(url: &str) -> Result<NationalData> {
-    let data = get_response::<NationalData>(url).await?;
+async fn get_intensities_for_url_national(
+    client: &CarbonIntensityClient,
+    url: &str,
+    settled: bool,
+) -> Result<NationalData> {
+    let data = client
+        .get_window_response::<NationalData>(url, settled)
+        .await?;
     Ok(data)
 }
 
-/// Retrieves the intensity value from a structure
-async fn get_intensity_for_url(url: &str) -> Result<i32> {
-    let result = get_instant_data(url).await?;
-
-    let intensity = result
-        .data
-        .first()
-        .ok_or_else(|| ApiError::Error("No data found".to_string()))?
-        .data
-        .first()
-        .ok_or_else(|| ApiError::Error("No intensity data found".to_string()))?
-        .intensity
-        .forecast;
+/// Retrieves the data of the current half-hour from a structure
+async fn get_data_for_url(client: &CarbonIntensityClient, url: &str) -> Result<Data> {
+    current_data(get_region_data_for_url(client, url).await?)
+}
 
-    Ok(intensity)
+/// Retrieves the data of the first region from a structure
+async fn get_region_data_for_url(client: &CarbonIntensityClient, url: &str) -> Result<RegionData> {
+    first(get_instant_data(client, url).await?.data)
 }
 
-/// Retrieves the intensity value from a structure
-async fn get_intensity_for_url_national(url: &str) -> Result<i32> {
-    let result = get_response::<NationalData>(url).await?;
+/// Retrieves the data of the current half-hour from a structure
+async fn get_data_for_url_national(client: &CarbonIntensityClient, url: &str) -> Result<Data> {
+    first(client.get_response::<NationalData>(url).await?.data)
+}
 
-    let intensity = result
+/// Data of the current half-hour of a region
+fn current_data(region_data: RegionData) -> Result<Data> {
+    region_data
         .data
-        .first()
-        .ok_or_else(|| ApiError::Error("No data found".to_string()))?
-        .intensity
-        .actual
-        .unwrap();
+        .into_iter()
+        .next()
+        .ok_or_else(|| ApiError::Error("No intensity data found".to_string()))
+}
 
-    Ok(intensity)
+/// First item of a response, e.g. the current half-hour
+fn first<T>(data: Vec<T>) -> Result<T> {
+    data.into_iter()
+        .next()
+        .ok_or_else(|| ApiError::Error("No data found".to_string()))
+}
+
+/// Field of a CSV line, quoted if it contains a comma, a quote or a line break
+#[cfg(feature = "analysis")]
+fn csv_field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// Returns an error if the API sent no forecast for the current intensity
+fn check_forecast(intensity: Intensity) -> Result<Intensity> {
+    match intensity.forecast {
+        Some(_) => Ok(intensity),
+        None => Err(ApiError::Error(
+            "No forecast sent for the current intensity".to_string(),
+        )),
+    }
 }
 
 // Internal method to handle the querying and parsing
-async fn get_instant_data(url: &str) -> Result<Root> {
-    get_response::<Root>(url).await
+async fn get_instant_data(client: &CarbonIntensityClient, url: &str) -> Result<Root> {
+    client.get_response::<Root>(url).await
 }
 
-/// Makes a GET request to the given URL
+/// Deserialises a response body
 ///
-/// Deserialise the JSON response as `T` and returns Ok<T> if all is well.
-/// Returns an `ApiError` when the HTTP request failed or the response body
-/// couldn't be deserialised as a `T` value.
-async fn get_response<T>(url: &str) -> Result<T>
+/// With the `simd-json` feature, the body is parsed with SIMD instructions
+/// if the CPU supports them, which is faster on the large responses of long ranges.
+/// As it parses in place, the body is copied into a buffer reused by the thread,
+/// leaving it intact for the caches without allocating for each response.
+///
+/// Public for the benchmarks only, not part of the API.
+#[doc(hidden)]
+pub fn parse_body<T>(body: &[u8]) -> Result<T>
 where
     T: DeserializeOwned,
 {
-    let client = Client::new();
-    #[cfg(debug_assertions)]
-    eprintln!("GET {url}");
-    let response = client.get(url).send().await?;
+    #[cfg(feature = "simd-json")]
+    let parsed = {
+        thread_local! {
+            static BUFFER: std::cell::RefCell<Vec<u8>> = const { std::cell::RefCell::new(Vec::new()) };
+        }
+        BUFFER.with_borrow_mut(|buffer| {
+            buffer.clear();
+            buffer.extend_from_slice(body);
+            simd_json::serde::from_slice(buffer)
+        })
+    };
+    #[cfg(not(feature = "simd-json"))]
+    let parsed = serde_json::from_slice(body);
 
-    let status = response.status();
-    if !status.is_success() {
-        let body = response.text().await?;
-        return Err(ApiError::RestError { status, body });
-    }
+    parsed.map_err(|error| ApiError::Error(format!("Error parsing response: {error}")))
+}
 
-    let target = response.json::<T>().await?;
-    Ok(target)
+/// Builds an `ApiError::Upstream` if the body contains an error payload,
+/// an `ApiError::RestError` with the raw body otherwise
+fn error_from_body(status: StatusCode, body: String) -> ApiError {
+    match serde_json::from_str::<ErrorResponse>(&body) {
+        Ok(ErrorResponse {
+            error: ErrorDetails { code, message },
+        }) => ApiError::Upstream {
+            status,
+            code,
+            message,
+        },
+        Err(_) => ApiError::RestError { status, body },
+    }
 }
 
 #[cfg(test)]
@@ -352,7 +1677,7 @@ mod tests {
 
     use std::str::FromStr;
 
-    use chrono::{Days, Months, SubsecRound};
+    use chrono::{Months, NaiveTime};
 
     use super::*;
 
@@ -362,21 +1687,22 @@ mod tests {
                 from: from.to_string(),
                 to: to.to_string(),
                 intensity: Intensity {
-                    forecast: intensity,
-                    index: "very high".to_string(),
+                    forecast: Some(intensity),
+                    index: IntensityIndex::VeryHigh,
                     actual: None,
                 },
+                provenance: None,
                 generationmix: Option::from(vec![
                     GenerationMix {
-                        fuel: "gas".to_string(),
+                        fuel: "gas".into(),
                         perc: 80.0,
                     },
                     GenerationMix {
-                        fuel: "wind".to_string(),
+                        fuel: "wind".into(),
                         perc: 10.0,
                     },
                     GenerationMix {
-                        fuel: "other".to_string(),
+                        fuel: "other".into(),
                         perc: 10.0,
                     },
                 ]),
@@ -391,6 +1717,53 @@ mod tests {
             .and_time(NaiveTime::from_hms_opt(0, 0, 0).unwrap())
     }
 
+    #[test]
+    fn regional_intensities_test() {
+        let json_str = r#"{"data":[{"from":"2024-01-01T12:00Z","to":"2024-01-01T12:30Z","regions":[
+            {"regionid":13,"dnoregion":"UKPN London","shortname":"London","intensity":{"forecast":180,"index":"moderate"},"generationmix":[]},
+            {"regionid":1,"dnoregion":"Scottish Hydro Electric Power Distribution","shortname":"North Scotland","intensity":{"forecast":0,"index":"very low"},"generationmix":[]},
+            {"regionid":2,"dnoregion":"SP Distribution","shortname":"South Scotland","intensity":{"forecast":null,"index":"very low"},"generationmix":[]},
+            {"regionid":99,"shortname":"Unknown","intensity":{"forecast":10,"index":"very low"}}
+        ]}]}"#;
+        let AllRegionsData { data } = serde_json::from_str(json_str).unwrap();
+        let intensities = regional_intensities(data.into_iter().next().unwrap());
+        let regions: Vec<Region> = intensities.iter().map(|i| i.region).collect();
+        assert_eq!(regions, vec![Region::NorthScotland, Region::London]);
+        assert_eq!(intensities[1].shortname, "London");
+        assert_eq!(intensities[1].dno_region.as_deref(), Some("UKPN London"));
+        assert_eq!(intensities[1].intensity.forecast(), Some(180));
+    }
+
+    #[test]
+    fn get_forecast_test() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        for hours in [0, 49] {
+            let result = runtime.block_on(get_forecast(&Target::National, hours));
+            assert!(matches!(result, Err(ApiError::Error(_))));
+        }
+    }
+
+    #[test]
+    fn parse_body_test() {
+        let body = br#"[{"from":"2024-01-01T00:00Z","to":"2024-01-01T00:30Z",
+            "intensity":{"forecast":120,"index":"very\u0020low"},
+            "generationmix":[{"fuel":"wind","perc":60.0},{"fuel":"tidal","perc":40.0}]}]"#;
+        let data: Vec<Data> = parse_body(body).unwrap();
+        assert_eq!(data[0].intensity().index(), IntensityIndex::VeryLow);
+        let mix = data[0].generation_mix().unwrap();
+        assert_eq!(mix[0].fuel(), "wind");
+        assert_eq!(mix[1].fuel(), "tidal");
+
+        // the known names are shared
+        assert!(matches!(mix[0].fuel, Cow::Borrowed(_)));
+        assert!(matches!(mix[1].fuel, Cow::Owned(_)));
+
+        let result = parse_body::<Vec<Data>>(b"[{\"from\":");
+        assert!(matches!(result, Err(ApiError::Error(_))));
+    }
+
     #[test]
     fn to_tuples_test() {
         // One of the dates is invalid
@@ -417,6 +1790,41 @@ mod tests {
         assert_eq!(result.unwrap(), expected);
     }
 
+    #[test]
+    fn likely_surplus_test() {
+        // mostly gas
+        let data = Data::test_data("2024-01-01", "2024-02-01", 90);
+        assert_eq!(data.renewable_share(), Some(10.0));
+        assert!(!data.is_likely_surplus());
+
+        let mut data = Data::test_data("2024-01-01", "2024-02-01", 40);
+        data.generationmix = Some(vec![
+            GenerationMix {
+                fuel: "wind".into(),
+                perc: 55.0,
+            },
+            GenerationMix {
+                fuel: "solar".into(),
+                perc: 10.0,
+            },
+            GenerationMix {
+                fuel: "gas".into(),
+                perc: 35.0,
+            },
+        ]);
+        assert_eq!(data.renewable_share(), Some(65.0));
+        assert!(data.is_likely_surplus());
+
+        // high share but intensity too high
+        data.intensity.forecast = Some(150);
+        assert!(!data.is_likely_surplus());
+
+        // unknown mix
+        data.generationmix = None;
+        assert_eq!(data.renewable_share(), None);
+        assert!(!data.is_likely_surplus());
+    }
+
     #[test]
     fn deserialise_power_data_test() {
         let json_str = r#"
@@ -427,14 +1835,277 @@ mod tests {
             serde_json::from_str(json_str);
     }
 
+    #[test]
+    fn all_regions_by_region() {
+        let json_str = r#"{"data":[
+            {"from":"2024-01-01T00:00Z","to":"2024-01-01T00:30Z","regions":[
+                {"regionid":1,"dnoregion":"Scottish Hydro Electric Power Distribution","shortname":"North Scotland","intensity":{"forecast":10,"index":"very low"},"generationmix":[{"fuel":"wind","perc":90}]},
+                {"regionid":13,"dnoregion":"UKPN London","shortname":"London","intensity":{"forecast":150,"index":"moderate"}},
+                {"regionid":18,"shortname":"GB","intensity":{"forecast":120,"index":"moderate"}}]},
+            {"from":"2024-01-01T00:30Z","to":"2024-01-01T01:00Z","regions":[
+                {"regionid":13,"dnoregion":"UKPN London","shortname":"London","intensity":{"forecast":160,"index":"moderate"}},
+                {"regionid":1,"dnoregion":"Scottish Hydro Electric Power Distribution","shortname":"North Scotland","intensity":{"forecast":20,"index":"very low"}}]}
+        ]}"#;
+        let AllRegionsData { data } = serde_json::from_str(json_str).unwrap();
+        let series = by_region(data);
+
+        assert_eq!(series.len(), 2);
+        let (region, data) = &series[0];
+        assert_eq!(*region, Region::NorthScotland);
+        let forecasts: Vec<_> = data
+            .iter()
+            .filter_map(|d| d.intensity().forecast())
+            .collect();
+        assert_eq!(forecasts, vec![10, 20]);
+        assert_eq!(data[0].renewable_share(), Some(90.0));
+        assert_eq!(data[1].from(), "2024-01-01T00:30Z");
+
+        let (region, data) = &series[1];
+        assert_eq!(*region, Region::London);
+        assert_eq!(data.len(), 2);
+    }
+
+    #[test]
+    fn implausible_intensities() {
+        let json_str = r#"[
+            {"from":"2024-01-01T00:00Z","to":"2024-01-01T00:30Z","intensity":{"forecast":150,"actual":140,"index":"moderate"}},
+            {"from":"2024-01-01T00:30Z","to":"2024-01-01T01:00Z","intensity":{"forecast":-3,"index":"very low"}},
+            {"from":"2024-01-01T01:00Z","to":"2024-01-01T01:30Z","intensity":{"forecast":150,"actual":4000,"index":"very high"}},
+            {"from":"2024-01-01T01:30Z","to":"2024-01-01T02:00Z","intensity":{"forecast":1000,"actual":0,"index":"very high"}}
+        ]"#;
+        let mut data: Vec<Data> = serde_json::from_str(json_str).unwrap();
+        let mut warnings = Vec::new();
+        discard_implausible(&mut data, &mut warnings);
+
+        let froms: Vec<&str> = data.iter().map(Data::from).collect();
+        assert_eq!(froms, vec!["2024-01-01T00:00Z", "2024-01-01T01:30Z"]);
+        assert_eq!(
+            warnings,
+            vec![
+                Warning::ImplausibleIntensity {
+                    from: "2024-01-01T00:30Z".to_string(),
+                    value: -3
+                },
+                Warning::ImplausibleIntensity {
+                    from: "2024-01-01T01:00Z".to_string(),
+                    value: 4000
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn inconsistent_mixes() {
+        let json_str = r#"[
+            {"from":"2024-01-01T00:00Z","to":"2024-01-01T00:30Z","intensity":{"forecast":150,"index":"moderate"},
+             "generationmix":[{"fuel":"wind","perc":40.1},{"fuel":"gas","perc":60.3}]},
+            {"from":"2024-01-01T00:30Z","to":"2024-01-01T01:00Z","intensity":{"forecast":150,"index":"moderate"},
+             "generationmix":[{"fuel":"wind","perc":60},{"fuel":"gas","perc":60}]},
+            {"from":"2024-01-01T01:00Z","to":"2024-01-01T01:30Z","intensity":{"forecast":150,"index":"moderate"}}
+        ]"#;
+        let mut data: Vec<Data> = serde_json::from_str(json_str).unwrap();
+        let mut warnings = Vec::new();
+        check_mixes(&mut data, MixPolicy::Keep, &mut warnings);
+        assert_eq!(
+            warnings,
+            vec![Warning::InconsistentMix {
+                from: "2024-01-01T00:30Z".to_string(),
+                total: 120.0,
+                renormalised: false
+            }]
+        );
+        assert_eq!(data[1].renewable_share(), Some(60.0));
+        assert_eq!(data[1].mix_total(), Some(120.0));
+
+        let mut warnings = Vec::new();
+        check_mixes(&mut data, MixPolicy::Renormalise, &mut warnings);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(data[1].mix_total(), Some(100.0));
+        let shares: Vec<f64> = data[1]
+            .generation_mix()
+            .unwrap()
+            .iter()
+            .map(|m| m.share().percent())
+            .collect();
+        assert_eq!(shares, vec![50.0, 50.0]);
+        // within the tolerance, left as is
+        assert_eq!(data[0].generation_mix().unwrap()[0].perc(), 40.1);
+    }
+
+    fn null_data() -> Vec<Data> {
+        let json_str = r#"[
+            {"from":"2024-01-01T00:00Z","to":"2024-01-01T00:30Z","intensity":{"forecast":null,"index":"moderate"}},
+            {"from":"2024-01-01T00:30Z","to":"2024-01-01T01:00Z","intensity":{"forecast":100,"index":"low"}},
+            {"from":"2024-01-01T01:00Z","to":"2024-01-01T01:30Z","intensity":{"forecast":null,"index":"low"}},
+            {"from":"2024-01-01T01:30Z","to":"2024-01-01T02:00Z","intensity":{"forecast":null,"index":"low"}},
+            {"from":"2024-01-01T02:00Z","to":"2024-01-01T02:30Z","intensity":{"forecast":130,"index":"moderate"}}
+        ]"#;
+        serde_json::from_str(json_str).unwrap()
+    }
+
+    #[test]
+    fn null_policy() {
+        // skip
+        let mut data = null_data();
+        let mut warnings = Vec::new();
+        apply_null_policy(&mut data, NullPolicy::Skip, &mut warnings).unwrap();
+        let forecasts: Vec<_> = data
+            .iter()
+            .filter_map(|d| d.intensity().forecast())
+            .collect();
+        assert_eq!(forecasts, vec![100, 130]);
+        assert_eq!(warnings.len(), 3);
+        assert_eq!(
+            warnings[0],
+            Warning::NullIntensity {
+                from: "2024-01-01T00:00Z".to_string(),
+                interpolated: false
+            }
+        );
+
+        // error
+        let mut data = null_data();
+        let result = apply_null_policy(&mut data, NullPolicy::Error, &mut Vec::new());
+        assert!(matches!(result, Err(ApiError::Error(_))));
+
+        // interpolate, the first one copied from the next
+        let mut data = null_data();
+        let mut warnings = Vec::new();
+        apply_null_policy(&mut data, NullPolicy::Interpolate, &mut warnings).unwrap();
+        let forecasts: Vec<_> = data
+            .iter()
+            .filter_map(|d| d.intensity().forecast())
+            .collect();
+        assert_eq!(forecasts, vec![100, 100, 110, 120, 130]);
+        assert_eq!(warnings.len(), 3);
+
+        // nothing to interpolate from
+        let mut data = null_data();
+        data.retain(|d| d.intensity.forecast.is_none());
+        let mut warnings = Vec::new();
+        apply_null_policy(&mut data, NullPolicy::Interpolate, &mut warnings).unwrap();
+        assert!(data.is_empty());
+        assert_eq!(warnings.len(), 3);
+
+        // no nulls
+        let mut data = null_data();
+        data.retain(|d| d.intensity.forecast.is_some());
+        apply_null_policy(&mut data, NullPolicy::Error, &mut Vec::new()).unwrap();
+        assert_eq!(data.len(), 2);
+
+        // only the forecast missing, kept with its actual value
+        let mut data = null_data();
+        data[0].intensity.actual = Some(95);
+        let mut warnings = Vec::new();
+        apply_null_policy(&mut data, NullPolicy::Skip, &mut warnings).unwrap();
+        let intensities: Vec<i32> = data
+            .iter()
+            .map(|d| d.intensity_for_date().unwrap().1)
+            .collect();
+        assert_eq!(intensities, vec![95, 100, 130]);
+        assert_eq!(warnings.len(), 2);
+        let mut data = null_data();
+        data.iter_mut().for_each(|d| d.intensity.actual = Some(95));
+        apply_null_policy(&mut data, NullPolicy::Error, &mut Vec::new()).unwrap();
+
+        // nor interpolated
+        let mut data = null_data();
+        data[0].intensity.actual = Some(95);
+        let mut warnings = Vec::new();
+        apply_null_policy(&mut data, NullPolicy::Interpolate, &mut warnings).unwrap();
+        assert_eq!(data[0].intensity().forecast(), None);
+        assert_eq!(warnings.len(), 2);
+        assert!(!warnings.iter().any(|w| matches!(w,
+            Warning::NullIntensity { from, .. } if from == data[0].from())));
+    }
+
+    #[test]
+    fn provenance() {
+        let url =
+            "https://api.carbonintensity.org.uk/intensity/2024-01-01T00:00Z/2024-01-01T02:30Z";
+        let mut data = null_data();
+        data[1].intensity.actual = Some(98);
+        Provenance::stamp(&mut data, url);
+        apply_null_policy(&mut data, NullPolicy::Interpolate, &mut Vec::new()).unwrap();
+
+        let provenance: Vec<_> = data.iter().map(|d| d.provenance().unwrap()).collect();
+        assert!(provenance.iter().all(|p| &*p.url == url));
+        assert!(provenance
+            .iter()
+            .all(|p| p.fetched_at == provenance[0].fetched_at));
+        let actual: Vec<bool> = provenance.iter().map(|p| p.actual).collect();
+        assert_eq!(actual, vec![false, true, false, false, false]);
+        let interpolated: Vec<bool> = provenance.iter().map(|p| p.interpolated).collect();
+        assert_eq!(interpolated, vec![true, false, true, true, false]);
+    }
+
+    #[test]
+    fn regional_intensity_from_region_data() {
+        let json_str = r#"{"data":[{"regionid":11,"dnoregion":"WPD South West","shortname":"South West England","postcode":"BS7","data":[{"from":"2024-01-01T12:00Z","to":"2024-01-01T12:30Z","intensity":{"forecast":152,"index":"moderate"}}]}]}"#;
+        let Root { data } = serde_json::from_str(json_str).unwrap();
+        let region_data = data.into_iter().next().unwrap();
+        let regional = RegionalIntensity::try_from(region_data).unwrap();
+        assert_eq!(regional.region, Region::SouthWestEngland);
+        assert_eq!(regional.shortname, "South West England");
+        assert_eq!(regional.dno_region.as_deref(), Some("WPD South West"));
+        assert_eq!(regional.intensity.forecast(), Some(152));
+
+        // unknown region
+        let json_str = r#"{"data":[{"regionid":18,"shortname":"GB","data":[]}]}"#;
+        let Root { data } = serde_json::from_str(json_str).unwrap();
+        let region_data = data.into_iter().next().unwrap();
+        assert!(RegionalIntensity::try_from(region_data).is_err());
+    }
+
+    #[test]
+    fn error_from_body_test() {
+        let body =
+            r#"{"error":{"code":"400 Bad Request","message":"Please enter a valid postcode"}}"#;
+        let error = error_from_body(StatusCode::BAD_REQUEST, body.to_string());
+        assert!(matches!(
+            &error,
+            ApiError::Upstream { status, code, message }
+                if *status == StatusCode::BAD_REQUEST
+                    && code == "400 Bad Request"
+                    && message == "Please enter a valid postcode"
+        ));
+        assert_eq!(
+            error.to_string(),
+            "API error 400 Bad Request: Please enter a valid postcode"
+        );
+
+        // not a structured payload
+        let body = "<html>Bad Gateway</html>";
+        let error = error_from_body(StatusCode::BAD_GATEWAY, body.to_string());
+        assert!(matches!(
+            error,
+            ApiError::RestError { status, body }
+                if status == StatusCode::BAD_GATEWAY && body == "<html>Bad Gateway</html>"
+        ));
+    }
+
+    #[test]
+    fn is_transient_test() {
+        let error = |status| error_from_body(status, String::new());
+        assert!(error(StatusCode::BAD_GATEWAY).is_transient());
+        assert!(error(StatusCode::TOO_MANY_REQUESTS).is_transient());
+        assert!(!error(StatusCode::BAD_REQUEST).is_transient());
+        assert!(!ApiError::Error("Invalid postcode".to_string()).is_transient());
+    }
+
     #[test]
     fn normalise_dates_invalid() {
         // Invalid start date
-        let result = normalise_dates("not a date", &None);
+        let result = normalise_dates("not a date", &None, &SystemClock, &Chunking::default());
         assert!(matches!(result, Err(ApiError::DateParseError(_))));
 
         // Invalid end date
-        let result = normalise_dates("2024-01-01", &Some("not a date"));
+        let result = normalise_dates(
+            "2024-01-01",
+            &Some("not a date"),
+            &SystemClock,
+            &Chunking::default(),
+        );
         assert!(matches!(result, Err(ApiError::DateParseError(_))));
     }
 
@@ -446,7 +2117,12 @@ mod tests {
             .unwrap();
 
         // Start date too old
-        let result = normalise_dates("1111-01-01", &Some("2018-05-15"));
+        let result = normalise_dates(
+            "1111-01-01",
+            &Some("2018-05-15"),
+            &SystemClock,
+            &Chunking::default(),
+        );
         assert!(result.is_ok());
 
         let ranges = result.unwrap();
@@ -458,30 +2134,49 @@ mod tests {
 
     #[test]
     fn normalise_dates_future() {
-        // End date in the future
-        let now = Local::now().naive_local();
-        let five_days = Days::new(5);
-        let five_days_ago = now.checked_sub_days(five_days).unwrap().date();
-        let in_five_days = now.checked_add_days(five_days).unwrap().date();
-
-        let result = normalise_dates(&five_days_ago.to_string(), &Some(&in_five_days.to_string()));
-        assert!(result.is_ok());
-
+        let now = test_date_time("2024-06-15") + chrono::Duration::minutes(754);
+        let clock = FixedClock::new(now);
+
+        // End date within the forecasts
+        let result = normalise_dates(
+            "2024-06-10",
+            &Some("2024-06-16"),
+            &clock,
+            &Chunking::default(),
+        );
         let ranges = result.unwrap();
-        assert_eq!(ranges.len(), 1);
-
-        let (start, end) = ranges[0];
-        let expected_start = five_days_ago.and_hms_opt(0, 0, 0).unwrap();
-        // start unchanged
-        assert_eq!(start, expected_start);
-        // end became now because it was in the future
-        assert_eq!(end.trunc_subsecs(0), now.trunc_subsecs(0));
+        assert_eq!(ranges.len(), 2);
+
+        // past up to the current half-hour
+        let current = test_date_time("2024-06-15") + chrono::Duration::minutes(750);
+        assert_eq!(ranges[0], (test_date_time("2024-06-10"), current));
+        // then forecasts
+        let next = current + chrono::Duration::minutes(30);
+        assert_eq!(ranges[1], (next, test_date_time("2024-06-16")));
+
+        // End date beyond the forecasts
+        let result = normalise_dates(
+            "2024-06-10",
+            &Some("2024-06-20"),
+            &clock,
+            &Chunking::default(),
+        );
+        assert!(matches!(result, Err(ApiError::Error(_))));
+
+        // no end date - uses now
+        let result = normalise_dates("2024-06-10", &None, &clock, &Chunking::default());
+        assert_eq!(result.unwrap(), vec![(test_date_time("2024-06-10"), now)]);
     }
 
     #[test]
     fn normalise_dates_splitting() {
         // Ranges splitting logic
-        let result = normalise_dates("2022-12-01", &Some("2023-01-01"));
+        let result = normalise_dates(
+            "2022-12-01",
+            &Some("2023-01-01"),
+            &SystemClock,
+            &Chunking::default(),
+        );
         assert!(result.is_ok());
         let ranges = result.unwrap();
         let expected = vec![
@@ -498,7 +2193,12 @@ mod tests {
         // The API doesn't cope well with ranges spanning more than one year.
         // If end_date is in a different year the API would use year end as
         // end_date and don't return any values beyond that datetime.
-        let result = normalise_dates("2022-12-31", &Some("2023-01-02"));
+        let result = normalise_dates(
+            "2022-12-31",
+            &Some("2023-01-02"),
+            &SystemClock,
+            &Chunking::default(),
+        );
         assert!(result.is_ok());
         let ranges = result.unwrap();
         let expected = vec![
@@ -508,33 +2208,63 @@ mod tests {
         assert_eq!(ranges, expected);
     }
 
+    #[test]
+    fn plan_windows_test() {
+        let now = test_date_time("2024-06-15") + chrono::Duration::minutes(754);
+        let options = QueryOptions::new().with_clock(FixedClock::new(now));
+        let windows = plan_windows("2024-06-10", &Some("2024-06-16"), &options).unwrap();
+        let next = test_date_time("2024-06-15") + chrono::Duration::minutes(780);
+        assert_eq!(
+            windows,
+            vec![
+                PlannedWindow {
+                    start: test_date_time("2024-06-10"),
+                    end: next - chrono::Duration::minutes(30),
+                    forecast: false
+                },
+                PlannedWindow {
+                    start: next,
+                    end: test_date_time("2024-06-16"),
+                    forecast: true
+                }
+            ]
+        );
+        assert_eq!(
+            windows[1].url_bounds(),
+            ("2024-06-15T13:01Z".to_string(), "fw24h".to_string())
+        );
+
+        assert!(plan_windows("2024-06-10", &Some("2024-06-20"), &options).is_err());
+    }
+
     #[test]
     fn validate_date_test() {
+        let now = test_date_time("2024-08-01");
+
         // valid dates just returned as-is
         let just_a_day = test_date_time("2024-07-30");
-        let datetime = validate_date(just_a_day);
-        assert_eq!(datetime.trunc_subsecs(0), just_a_day.trunc_subsecs(0));
+        let datetime = validate_date(just_a_day, now).unwrap();
+        assert_eq!(datetime, just_a_day);
 
-        // future dates turns into now
-        let future = Local::now()
-            .naive_local()
-            .checked_add_months(Months::new(2))
-            .unwrap();
-        let datetime = validate_date(future);
-        let now = Local::now().naive_local();
-        assert_eq!(datetime.trunc_subsecs(0), now.trunc_subsecs(0));
+        // future dates within the forecasts are fine
+        let tomorrow = test_date_time("2024-08-02");
+        assert_eq!(validate_date(tomorrow, now).unwrap(), tomorrow);
+
+        // beyond the forecasts
+        let future = now.checked_add_months(Months::new(2)).unwrap();
+        assert!(validate_date(future, now).is_err());
 
         // oldest is fine
         let oldest_date = NaiveDate::from_ymd_opt(2018, 5, 10)
             .unwrap()
             .and_hms_opt(23, 30, 0)
             .unwrap();
-        let datetime = validate_date(oldest_date);
-        assert_eq!(datetime.trunc_subsecs(0), oldest_date.trunc_subsecs(0));
+        let datetime = validate_date(oldest_date, now).unwrap();
+        assert_eq!(datetime, oldest_date);
 
         // just too old - turn into the oldest valid date
         let old = test_date_time("1980-12-31");
-        let datetime = validate_date(old);
+        let datetime = validate_date(old, now).unwrap();
         assert_eq!(datetime, oldest_date);
     }
 }
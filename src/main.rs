@@ -1,82 +1,859 @@
-use std::process;
+mod update;
 
-use carbonintensity::{get_intensities, get_intensity, ApiError, Target};
-use chrono::NaiveDateTime;
-use clap::Parser;
-use std::io::Write;
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process,
+    time::Duration,
+};
+
+use carbonintensity::{
+    annual_summary, audit_sites, audit_sites_keep_going, estimate_query, get_current_intensity,
+    get_detailed_intensities, get_intensities_with_options, get_regional_intensity,
+    get_status_widget, intensity_matrix, parse_monthly_consumption, parse_sites, ramp_rates,
+    scope2_report, summarise_with_percentiles, write_annual_summary, write_audit_csv,
+    write_audit_outcome_csv, write_matrix_csv, write_matrix_html, write_scope2_csv, ApiError,
+    CsvAppendSink, Data, DateFormat, DnoError, MatrixMetric, OutputFormat, OutputSink,
+    QueryEstimate, QueryOptions, QueryOutcome, RampSummary, RangePreset, Region, Result, Target,
+    WidgetFormat, WriterSink, DEFAULT_PERCENTILES,
+};
+use clap::{Parser, Subcommand, ValueEnum};
+
+/// Number of requests a range can take without confirmation in a terminal
+const DEFAULT_MAX_REQUESTS: usize = 50;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 /// CLI for the CarbonIntensity API.
 ///
 /// Dates can be specified either is ISO-8601 (`2022-08-21T09:00:00Z`) or simply
-/// YYYY-MM-DD. If no end date is specified, it will be set to 14 days from the start date.
+/// YYYY-MM-DD, or relative to now: `now`, `today`, `yesterday` or an offset like
+/// `now-7d`, `now-24h` or `now-30m`. If no end date is specified, it will be set to 14 days from the start date.
 struct Args {
-    #[clap(short, long)]
-    pub start_date: Option<String>,
-    #[clap(short, long)]
-    pub end_date: Option<String>,
+    /// writes the results to a file instead of the standard output
+    #[clap(short, long, global = true)]
+    pub output: Option<PathBuf>,
+
+    /// maximum number of seconds spent retrieving a range,
+    /// partial results are returned if it expires
+    #[clap(short, long, global = true)]
+    pub timeout: Option<u64>,
+
+    /// format of the dates in the output: unix, unix-ms or a strftime
+    /// pattern e.g. "%Y-%m-%dT%H:%M:%SZ"
+    #[clap(long, global = true)]
+    pub date_format: Option<DateFormat>,
+
+    /// number of requests to the API a range can take without confirmation,
+    /// larger ones are refused unless confirmed in a terminal [default: 50 in a terminal,
+    /// no limit otherwise e.g. from cron]
+    #[clap(long, global = true)]
+    pub max_requests: Option<usize>,
+
+    /// returns the current intensity at the national level if not set
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+enum Command {
+    /// Current intensity
+    Now {
+        /// numerical value for a region (1-17), name of its network operator
+        /// (e.g. "UKPN London") or UK postcode, in full or its first part
+        /// returns data at the national level if not set
+        #[arg(default_value_t=Target::National, value_parser = parse_target)]
+        target: Target,
+
+        /// format of the output, badge generates a shields.io endpoint JSON,
+        /// waybar, i3blocks and polybar the snippets expected by those status bars
+        #[clap(short, long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// Intensities over a period in 30 minutes windows
+    History {
+        /// numerical value for a region (1-17), name of its network operator
+        /// (e.g. "UKPN London") or UK postcode, in full or its first part
+        /// returns data at the national level if not set
+        #[arg(default_value_t=Target::National, value_parser = parse_target)]
+        target: Target,
+
+        #[clap(short, long)]
+        #[arg(required_unless_present_any = ["append_to", "range"])]
+        start_date: Option<String>,
+        #[clap(short, long)]
+        end_date: Option<String>,
+        /// common period instead of the dates: today, yesterday, this-week,
+        /// last-month or ytd, the days starting at midnight local time
+        #[clap(short, long)]
+        #[arg(conflicts_with_all = ["start_date", "end_date"])]
+        range: Option<RangePreset>,
+
+        /// only returns the half-hours where renewable generation was likely in surplus,
+        /// for a region or a postcode as the national intensities have no generation mix
+        #[clap(long)]
+        surplus_only: bool,
+
+        /// format of the output
+        #[clap(short, long, value_enum, default_value_t = HistoryFormat::Text)]
+        format: HistoryFormat,
+
+        /// prints how the requests were executed on the standard error
+        #[clap(long)]
+        report: bool,
+
+        /// appends the intensities to a CSV file, only retrieving the half-hours
+        /// more recent than the ones already in the file for the target
+        #[clap(long)]
+        #[arg(conflicts_with_all = ["output", "surplus_only", "date_format", "format", "range"])]
+        append_to: Option<PathBuf>,
+    },
+    /// Average intensity and estimated emissions of sites over a period,
+    /// written as CSV
+    Audit {
+        /// CSV file with a header then one line per site: name, postcode
+        /// and kWh consumed over the period
+        #[clap(long)]
+        postcodes: PathBuf,
+
+        #[clap(short, long)]
+        #[arg(required_unless_present = "range")]
+        start_date: Option<String>,
+        #[clap(short, long)]
+        end_date: Option<String>,
+        /// common period instead of the dates: today, yesterday, this-week,
+        /// last-month or ytd, the days starting at midnight local time
+        #[clap(short, long)]
+        #[arg(conflicts_with_all = ["start_date", "end_date"])]
+        range: Option<RangePreset>,
+
+        /// records the sites which can't be audited (e.g. invalid postcodes)
+        /// in an error column instead of stopping, with a summary on the standard error
+        #[clap(long)]
+        keep_going: bool,
+    },
+    /// Location-based Scope 2 emissions of sites per month, written as CSV
+    Scope2 {
+        /// CSV file with a header then one line per site and month: name,
+        /// postcode, month (YYYY-MM) and kWh consumed
+        #[clap(long)]
+        consumption: PathBuf,
+    },
+    /// Mean, min and max intensity and renewable share of every region
+    /// over a year
+    Annual {
+        /// calendar year e.g. 2024
+        year: i32,
+    },
+    /// Changes of intensity from one half-hour to the next over a period,
+    /// summarised to show how volatile it is
+    Ramp {
+        /// numerical value for a region (1-17), name of its network operator
+        /// (e.g. "UKPN London") or UK postcode, in full or its first part
+        /// returns data at the national level if not set
+        #[arg(default_value_t=Target::National, value_parser = parse_target)]
+        target: Target,
+
+        #[clap(short, long)]
+        #[arg(required_unless_present = "range")]
+        start_date: Option<String>,
+        #[clap(short, long)]
+        end_date: Option<String>,
+        /// common period instead of the dates: today, yesterday, this-week,
+        /// last-month or ytd, the days starting at midnight local time
+        #[clap(short, long)]
+        #[arg(conflicts_with_all = ["start_date", "end_date"])]
+        range: Option<RangePreset>,
+
+        /// writes the change of each half-hour instead of the summary
+        #[clap(long)]
+        series: bool,
+    },
+    /// Minimum, maximum, mean, median and percentiles of the intensity over a period
+    Stats {
+        /// numerical value for a region (1-17), name of its network operator
+        /// (e.g. "UKPN London") or UK postcode, in full or its first part
+        /// returns data at the national level if not set
+        #[arg(default_value_t=Target::National, value_parser = parse_target)]
+        target: Target,
+
+        #[clap(short, long)]
+        #[arg(required_unless_present = "range")]
+        start_date: Option<String>,
+        #[clap(short, long)]
+        end_date: Option<String>,
+        /// common period instead of the dates: today, yesterday, this-week,
+        /// last-month or ytd, the days starting at midnight local time
+        #[clap(short, long)]
+        #[arg(conflicts_with_all = ["start_date", "end_date"])]
+        range: Option<RangePreset>,
+
+        /// percentiles to compute, between 0 and 100
+        #[clap(short, long, value_delimiter = ',', default_values_t = DEFAULT_PERCENTILES)]
+        #[arg(value_parser = parse_percentile)]
+        percentiles: Vec<f64>,
+    },
+    /// Daily intensity of every region over a period, one line per region
+    /// and one column per day
+    Matrix {
+        #[clap(short, long)]
+        #[arg(required_unless_present = "range")]
+        start_date: Option<String>,
+        #[clap(short, long)]
+        end_date: Option<String>,
+        /// common period instead of the dates: today, yesterday, this-week,
+        /// last-month or ytd, the days starting at midnight local time
+        #[clap(short, long)]
+        #[arg(conflicts_with_all = ["start_date", "end_date"])]
+        range: Option<RangePreset>,
+
+        /// statistic of the half-hours of each day
+        #[clap(short, long, value_enum, default_value_t = Metric::Mean)]
+        metric: Metric,
+
+        /// format of the output, html generates a heatmap
+        #[clap(short, long, value_enum, default_value_t = MatrixFormat::Csv)]
+        format: MatrixFormat,
+    },
+    /// Replaces this binary with the latest release from GitHub
+    #[cfg(feature = "self-update")]
+    SelfUpdate,
+}
 
-    /// numerical value for a region (1-17) or first part of a UK postcode
-    /// returns data at the national level if not set
-    #[clap()]
-    #[arg(default_value_t=Target::National)]
-    pub target: Target,
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    Text,
+    Badge,
+    Json,
+    Waybar,
+    I3blocks,
+    Polybar,
+}
+
+impl Format {
+    /// Status bar format, with the next green window
+    fn widget(self) -> Option<WidgetFormat> {
+        match self {
+            Format::Waybar => Some(WidgetFormat::Waybar),
+            Format::I3blocks => Some(WidgetFormat::I3blocks),
+            Format::Polybar => Some(WidgetFormat::Polybar),
+            Format::Text | Format::Badge | Format::Json => None,
+        }
+    }
+}
+
+impl From<Format> for OutputFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Text => OutputFormat::Text,
+            Format::Badge => OutputFormat::Badge,
+            Format::Json => OutputFormat::Json,
+            // written by run_widget
+            Format::Waybar | Format::I3blocks | Format::Polybar => OutputFormat::Text,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Metric {
+    Mean,
+    Min,
+    Max,
+}
+
+impl From<Metric> for MatrixMetric {
+    fn from(metric: Metric) -> Self {
+        match metric {
+            Metric::Mean => MatrixMetric::Mean,
+            Metric::Min => MatrixMetric::Min,
+            Metric::Max => MatrixMetric::Max,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum MatrixFormat {
+    Csv,
+    Html,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum HistoryFormat {
+    Text,
+    Json,
+    Ndjson,
+}
+
+impl From<HistoryFormat> for OutputFormat {
+    fn from(format: HistoryFormat) -> Self {
+        match format {
+            HistoryFormat::Text => OutputFormat::Text,
+            HistoryFormat::Json => OutputFormat::Json,
+            HistoryFormat::Ndjson => OutputFormat::Ndjson,
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
 
-    let target: Target = args.target;
+    // only worth it when a person reads the output
+    let notice = io::stderr()
+        .is_terminal()
+        .then(|| tokio::spawn(update::new_version_notice()));
 
-    // look for a range if a date was specified
-    if let Some(start_date) = &args.start_date {
-        let end_date: Option<&str> = args.end_date.as_deref();
+    match &args.command {
+        None => run_now(&Target::National, Format::Text, &args).await,
+        Some(Command::Now { target, format }) => run_now(target, *format, &args).await,
+        Some(Command::History {
+            target,
+            start_date,
+            end_date,
+            surplus_only,
+            format,
+            report,
+            range,
+            append_to,
+        }) => {
+            let options = query_options(&args).with_execution_report(*report);
+            match append_to {
+                Some(path) => {
+                    let end_date = end_date.as_deref();
+                    run_append(path, target, start_date, &end_date, &options, &args).await
+                }
+                None => {
+                    let (start_date, end_date) = period(start_date, end_date, *range);
+                    let (surplus_only, format) = (*surplus_only, (*format).into());
+                    run_history(
+                        target,
+                        &start_date,
+                        &end_date.as_deref(),
+                        surplus_only,
+                        format,
+                        &options,
+                        &args,
+                    )
+                    .await
+                }
+            }
+        }
+        Some(Command::Audit {
+            postcodes,
+            start_date,
+            end_date,
+            range,
+            keep_going,
+        }) => {
+            let (start_date, end_date) = period(start_date, end_date, *range);
+            let end_date = end_date.as_deref();
+            run_audit(postcodes, &start_date, &end_date, *keep_going, &args).await
+        }
+        Some(Command::Scope2 { consumption }) => run_scope2(consumption, &args).await,
+        Some(Command::Annual { year }) => run_annual(*year, &args).await,
+        Some(Command::Ramp {
+            target,
+            start_date,
+            end_date,
+            range,
+            series,
+        }) => {
+            let (start_date, end_date) = period(start_date, end_date, *range);
+            run_ramp(target, &start_date, &end_date.as_deref(), *series, &args).await
+        }
+        Some(Command::Stats {
+            target,
+            start_date,
+            end_date,
+            range,
+            percentiles,
+        }) => {
+            let (start_date, end_date) = period(start_date, end_date, *range);
+            run_stats(
+                target,
+                &start_date,
+                &end_date.as_deref(),
+                percentiles,
+                &args,
+            )
+            .await
+        }
+        Some(Command::Matrix {
+            start_date,
+            end_date,
+            range,
+            metric,
+            format,
+        }) => {
+            let (start_date, end_date) = period(start_date, end_date, *range);
+            run_matrix(&start_date, &end_date.as_deref(), *metric, *format, &args).await
+        }
+        #[cfg(feature = "self-update")]
+        Some(Command::SelfUpdate) => run_self_update().await,
+    }
 
-        let result = get_intensities(&target, start_date, &end_date).await;
-        handle_results(result);
-    } else {
-        let result = get_intensity(&target).await;
-        handle_result(result, &target);
+    // doesn't hold the command back for long if GitHub is slow
+    if let Some(notice) = notice {
+        if let Ok(Ok(Some(notice))) = tokio::time::timeout(Duration::from_secs(1), notice).await {
+            eprintln!("{notice}");
+        }
     }
 }
 
-#[allow(clippy::explicit_write)]
-fn handle_results(result: Result<Vec<(NaiveDateTime, i32)>, ApiError>) {
-    if let Ok(results) = result {
-        for (time, value) in results {
-            writeln!(std::io::stdout(), "{}, {}", time, value).unwrap_or_default();
+/// Parses a target, rejecting the network operators which serve several regions
+fn parse_target(s: &str) -> std::result::Result<Target, DnoError> {
+    if let Err(error @ DnoError::Ambiguous { .. }) = Region::from_dno(s) {
+        return Err(error);
+    }
+    Ok(Target::from(s.to_string()))
+}
+
+fn parse_percentile(s: &str) -> std::result::Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(percentile) if (0.0..=100.0).contains(&percentile) => Ok(percentile),
+        _ => Err(format!("{s} is not a percentile between 0 and 100")),
+    }
+}
+
+/// Start and end dates, given explicitly or as a range
+fn period(
+    start_date: &Option<String>,
+    end_date: &Option<String>,
+    range: Option<RangePreset>,
+) -> (String, Option<String>) {
+    match range {
+        Some(range) => {
+            let (start, end) = range.resolve(chrono::Local::now());
+            (start, Some(end))
         }
-    } else {
-        eprintln!("{}", result.unwrap_err());
-        process::exit(1);
+        // the start date is required by clap without a range
+        None => (start_date.clone().unwrap_or_default(), end_date.clone()),
     }
 }
 
-#[allow(clippy::explicit_write)]
-fn handle_result(result: Result<i32, ApiError>, target: &Target) {
-    if result.is_ok() {
-        writeln!(
-            std::io::stdout(),
-            "Carbon intensity for {}: {:?}",
-            target,
-            result.unwrap()
-        )
-        .unwrap_or_default();
+fn query_options(args: &Args) -> QueryOptions {
+    let mut options = QueryOptions::new();
+    if let Some(timeout) = args.timeout {
+        options = options.with_deadline(Duration::from_secs(timeout));
+    }
+    options
+}
+
+/// Sink writing to the output file if one was set, to the standard output otherwise
+fn output_sink(format: OutputFormat, args: &Args) -> Box<dyn OutputSink> {
+    let date_format = args.date_format.clone().unwrap_or_default();
+    match &args.output {
+        Some(path) => match WriterSink::create_file(path) {
+            Ok(sink) => Box::new(sink.with_format(format).with_date_format(date_format)),
+            Err(error) => exit_with_error(error),
+        },
+        None => Box::new(
+            WriterSink::stdout()
+                .with_format(format)
+                .with_date_format(date_format),
+        ),
+    }
+}
+
+/// Prints the current intensity and next green window for a status bar
+async fn run_widget(target: &Target, format: WidgetFormat, args: &Args) {
+    let widget = get_status_widget(target)
+        .await
+        .unwrap_or_else(|error| exit_with_error(error));
+    let mut writer = output_writer(args);
+    handle_io_result(
+        widget
+            .write(&mut writer, format)
+            .and_then(|_| writer.flush()),
+    );
+}
+
+/// Writer for the output file if one was set, the standard output otherwise
+fn output_writer(args: &Args) -> Box<dyn Write> {
+    match &args.output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(error) => exit_with_error(error),
+        },
+        None => Box::new(io::stdout()),
+    }
+}
+
+/// Prints the current intensity, along with the region for a postcode
+async fn run_now(target: &Target, format: Format, args: &Args) {
+    if let Some(widget) = format.widget() {
+        return run_widget(target, widget, args).await;
+    }
+
+    let mut sink = output_sink(format.into(), args);
+
+    let written = if let Target::Postcode(_) = target {
+        let regional = get_regional_intensity(target)
+            .await
+            .unwrap_or_else(|error| exit_with_error(error));
+        sink.write_region(target, &regional)
+            .and_then(|_| sink.write_intensity(target, &regional.intensity))
+    } else {
+        let intensity = get_current_intensity(target)
+            .await
+            .unwrap_or_else(|error| exit_with_error(error));
+        sink.write_intensity(target, &intensity)
+    };
+
+    handle_io_result(written.and_then(|_| sink.flush()));
+}
+
+/// Prints the intensities over a period
+async fn run_history(
+    target: &Target,
+    start_date: &str,
+    end_date: &Option<&str>,
+    surplus_only: bool,
+    format: OutputFormat,
+    options: &QueryOptions,
+    args: &Args,
+) {
+    if surplus_only && *target == Target::National {
+        exit_with_error(ApiError::Error(
+            "--surplus-only needs a region or a postcode, the national intensities have no generation mix"
+                .to_string(),
+        ));
+    }
+    check_budget(
+        start_date,
+        end_date,
+        1,
+        &target.to_string(),
+        |estimate| estimate.approx_bytes(target),
+        args,
+    );
+    let result = if surplus_only {
+        get_surplus_intensities(target, start_date, end_date, options).await
     } else {
-        eprintln!("{}", result.unwrap_err());
+        get_intensities_with_options(target, start_date, end_date, options).await
+    };
+    let outcome = result.unwrap_or_else(|error| exit_with_error(error));
+    print_diagnostics(&outcome);
+
+    let mut sink = output_sink(format, args);
+    handle_io_result(
+        sink.write_intensities(target, &outcome.intensities)
+            .and_then(|_| sink.flush()),
+    );
+}
+
+/// Appends the intensities more recent than the ones already in the file
+async fn run_append(
+    path: &Path,
+    target: &Target,
+    start_date: &Option<String>,
+    end_date: &Option<&str>,
+    options: &QueryOptions,
+    args: &Args,
+) {
+    let latest = CsvAppendSink::latest(path, target).unwrap_or_else(|error| exit_with_error(error));
+
+    let start_date = match latest {
+        Some(latest) => (latest + chrono::Duration::minutes(30))
+            .format("%Y-%m-%dT%H:%MZ")
+            .to_string(),
+        None => match start_date {
+            Some(start_date) => start_date.clone(),
+            None => {
+                exit_with_error("A start date is required when the file has no data for the target")
+            }
+        },
+    };
+
+    check_budget(
+        &start_date,
+        end_date,
+        1,
+        &target.to_string(),
+        |estimate| estimate.approx_bytes(target),
+        args,
+    );
+    let outcome = get_intensities_with_options(target, &start_date, end_date, options)
+        .await
+        .unwrap_or_else(|error| exit_with_error(error));
+    print_diagnostics(&outcome);
+
+    let intensities: Vec<_> = outcome
+        .intensities
+        .into_iter()
+        .filter(|(time, _)| latest.map_or(true, |latest| *time > latest))
+        .collect();
+
+    let mut sink = CsvAppendSink::open(path).unwrap_or_else(|error| exit_with_error(error));
+    handle_io_result(
+        sink.write_intensities(target, &intensities)
+            .and_then(|_| sink.flush()),
+    );
+}
+
+/// Audits the sites listed in a CSV file
+async fn run_audit(
+    sites_file: &Path,
+    start_date: &str,
+    end_date: &Option<&str>,
+    keep_going: bool,
+    args: &Args,
+) {
+    let csv = fs::read_to_string(sites_file).unwrap_or_else(|error| exit_with_error(error));
+    let sites = parse_sites(&csv).unwrap_or_else(|error| exit_with_error(error));
+    let description = format!("{} sites", sites.len());
+    let size = |estimate: &QueryEstimate| {
+        let postcode = Target::Postcode(String::new());
+        sites.len() * estimate.approx_bytes(&postcode)
+    };
+    check_budget(start_date, end_date, sites.len(), &description, size, args);
+
+    let options = query_options(args);
+    if keep_going {
+        let outcome = audit_sites_keep_going(&sites, start_date, end_date, &options).await;
+        let mut writer = output_writer(args);
+        handle_io_result(
+            write_audit_outcome_csv(&mut writer, &outcome).and_then(|_| writer.flush()),
+        );
+        eprintln!("{outcome}");
+        return;
+    }
+
+    let audits = audit_sites(&sites, start_date, end_date, &options)
+        .await
+        .unwrap_or_else(|error| exit_with_error(error));
+
+    let mut writer = output_writer(args);
+    handle_io_result(write_audit_csv(&mut writer, &audits).and_then(|_| writer.flush()));
+}
+
+/// Computes the Scope 2 emissions of the sites listed in a CSV file
+async fn run_scope2(consumption_file: &Path, args: &Args) {
+    let csv = fs::read_to_string(consumption_file).unwrap_or_else(|error| exit_with_error(error));
+    let consumption =
+        parse_monthly_consumption(&csv).unwrap_or_else(|error| exit_with_error(error));
+    let lines = scope2_report(&consumption, &query_options(args))
+        .await
+        .unwrap_or_else(|error| exit_with_error(error));
+
+    let mut writer = output_writer(args);
+    handle_io_result(write_scope2_csv(&mut writer, &lines).and_then(|_| writer.flush()));
+}
+
+/// Summarises every region over a year
+async fn run_annual(year: i32, args: &Args) {
+    let summaries = annual_summary(year, &query_options(args))
+        .await
+        .unwrap_or_else(|error| exit_with_error(error));
+
+    let mut writer = output_writer(args);
+    handle_io_result(write_annual_summary(&mut writer, &summaries).and_then(|_| writer.flush()));
+}
+
+/// Prints the changes between half-hours, or their summary
+async fn run_ramp(
+    target: &Target,
+    start_date: &str,
+    end_date: &Option<&str>,
+    series: bool,
+    args: &Args,
+) {
+    check_budget(
+        start_date,
+        end_date,
+        1,
+        &target.to_string(),
+        |estimate| estimate.approx_bytes(target),
+        args,
+    );
+    let outcome = get_intensities_with_options(target, start_date, end_date, &query_options(args))
+        .await
+        .unwrap_or_else(|error| exit_with_error(error));
+    print_diagnostics(&outcome);
+    let ramps = ramp_rates(&outcome.intensities);
+
+    if series {
+        let mut sink = output_sink(OutputFormat::Text, args);
+        handle_io_result(
+            sink.write_intensities(target, &ramps)
+                .and_then(|_| sink.flush()),
+        );
+        return;
+    }
+
+    let summary = RampSummary::new(&ramps)
+        .unwrap_or_else(|| exit_with_error(format!("Not enough data for {target}")));
+    let mut writer = output_writer(args);
+    handle_io_result(write!(writer, "{summary}").and_then(|_| writer.flush()));
+}
+
+/// Prints the distribution of the intensities over a period
+async fn run_stats(
+    target: &Target,
+    start_date: &str,
+    end_date: &Option<&str>,
+    percentiles: &[f64],
+    args: &Args,
+) {
+    check_budget(
+        start_date,
+        end_date,
+        1,
+        &target.to_string(),
+        |estimate| estimate.approx_bytes(target),
+        args,
+    );
+    let outcome = get_intensities_with_options(target, start_date, end_date, &query_options(args))
+        .await
+        .unwrap_or_else(|error| exit_with_error(error));
+    print_diagnostics(&outcome);
+
+    let summary = summarise_with_percentiles(&outcome.intensities, percentiles)
+        .unwrap_or_else(|| exit_with_error(format!("No data for {target}")));
+    let mut writer = output_writer(args);
+    handle_io_result(write!(writer, "{summary}").and_then(|_| writer.flush()));
+}
+
+/// Prints the daily intensities of every region as CSV or an HTML heatmap
+async fn run_matrix(
+    start_date: &str,
+    end_date: &Option<&str>,
+    metric: Metric,
+    format: MatrixFormat,
+    args: &Args,
+) {
+    check_budget(
+        start_date,
+        end_date,
+        1,
+        "all the regions",
+        QueryEstimate::approx_bytes_all_regions,
+        args,
+    );
+    let matrix = intensity_matrix(start_date, end_date, metric.into(), &query_options(args))
+        .await
+        .unwrap_or_else(|error| exit_with_error(error));
+
+    let mut writer = output_writer(args);
+    let written = match format {
+        MatrixFormat::Csv => write_matrix_csv(&mut writer, &matrix),
+        MatrixFormat::Html => write_matrix_html(&mut writer, &matrix),
+    };
+    handle_io_result(written.and_then(|_| writer.flush()));
+}
+
+/// Stops before a range needing more requests than allowed by `--max-requests`,
+/// unless the user confirms it in a terminal
+///
+/// Without `--max-requests`, the limit is `DEFAULT_MAX_REQUESTS` in a terminal
+/// and there is none otherwise, so that the scheduled jobs aren't refused.
+///
+/// `per_window` is the number of requests sent for each window of the range,
+/// e.g. one per site for an audit, and `size` the rough size of all the responses.
+fn check_budget(
+    start_date: &str,
+    end_date: &Option<&str>,
+    per_window: usize,
+    description: &str,
+    size: impl Fn(&QueryEstimate) -> usize,
+    args: &Args,
+) {
+    // invalid dates are reported by the query itself
+    let Ok(estimate) = estimate_query(start_date, end_date, &QueryOptions::default()) else {
+        return;
+    };
+    let interactive = io::stdin().is_terminal() && io::stderr().is_terminal();
+    let max_requests = match args.max_requests {
+        Some(max_requests) => max_requests,
+        None if interactive => DEFAULT_MAX_REQUESTS,
+        None => return,
+    };
+    let requests = estimate.requests * per_window;
+    if requests <= max_requests {
+        return;
+    }
+
+    let message = format!(
+        "Retrieving {} half-hours for {description} needs {requests} requests (about {:.1} MB), \
+        more than the {} allowed by --max-requests",
+        estimate.half_hours,
+        size(&estimate) as f64 / 1_000_000.0,
+        max_requests
+    );
+    if !interactive {
+        exit_with_error(message);
+    }
+    eprint!("{message}. Continue? [y/N] ");
+    let mut answer = String::new();
+    let confirmed =
+        io::stdin().read_line(&mut answer).is_ok() && matches!(answer.trim(), "y" | "Y" | "yes");
+    if !confirmed {
         process::exit(1);
     }
 }
 
+/// Retrieves the intensities of the half-hours flagged as likely surplus
+async fn get_surplus_intensities(
+    target: &Target,
+    start_date: &str,
+    end_date: &Option<&str>,
+    options: &QueryOptions,
+) -> Result<QueryOutcome> {
+    let outcome = get_detailed_intensities(target, start_date, end_date, options).await?;
+    let intensities = outcome
+        .intensities
+        .iter()
+        .filter(|data| data.is_likely_surplus())
+        .map(Data::intensity_for_date)
+        .collect::<Result<_>>()?;
+    Ok(QueryOutcome {
+        intensities,
+        warnings: outcome.warnings,
+        report: outcome.report,
+    })
+}
+
+/// Prints the warnings and the execution report, if any, on the standard error
+fn print_diagnostics<T>(outcome: &QueryOutcome<T>) {
+    for warning in &outcome.warnings {
+        eprintln!("Warning: {warning}");
+    }
+    if let Some(report) = &outcome.report {
+        eprint!("{report}");
+    }
+}
+
+/// Reports IO errors, ignoring broken pipes (e.g. output piped into `head`)
+fn handle_io_result(result: io::Result<()>) {
+    if let Err(error) = result {
+        if error.kind() != io::ErrorKind::BrokenPipe {
+            exit_with_error(error);
+        }
+    }
+}
+
+#[cfg(feature = "self-update")]
+async fn run_self_update() {
+    match update::self_update().await {
+        Ok(Some(version)) => println!("Updated to version {version}"),
+        Ok(None) => println!("Already up to date"),
+        Err(error) => exit_with_error(error),
+    }
+}
+
+fn exit_with_error(error: impl std::fmt::Display) -> ! {
+    eprintln!("{error}");
+    process::exit(1);
+}
+
 #[cfg(test)]
 mod tests {
     use clap::Parser;
 
-    use carbonintensity::Region;
+    use carbonintensity::{DateFormat, RangePreset, Region, DEFAULT_PERCENTILES};
 
-    use crate::{Args, Target};
+    use crate::{Args, Command, Format, HistoryFormat, MatrixFormat, Metric, Target};
 
     fn parsed_args(args: Vec<&str>) -> Result<Args, clap::Error> {
         let args = ["carbonintensity-api"].iter().chain(args.iter());
@@ -85,40 +862,389 @@ mod tests {
 
     #[test]
     fn cli_valid_arguments() {
+        // current national intensity by default
+        let args = parsed_args(vec![]).unwrap();
+        assert_eq!(args.command, None);
+
         // single postcode
-        let args: Args = parsed_args(vec!["bs7"]).unwrap();
-        assert_eq!(args.target, Target::Postcode("bs7".to_string()));
+        let args: Args = parsed_args(vec!["now", "bs7"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Now {
+                target: Target::Postcode("bs7".to_string()),
+                format: Format::Text
+            })
+        );
 
         // single region id
-        let args = parsed_args(vec!["13"]).unwrap();
-        assert_eq!(args.target, Target::Region(Region::London));
+        let args = parsed_args(vec!["now", "13"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Now {
+                target: Target::Region(Region::London),
+                format: Format::Text
+            })
+        );
 
-        // start date  / postcode
-        let args = parsed_args(vec!["--start-date", "2024-05-06", "BS7"]).unwrap();
-        assert_eq!(args.start_date, Some("2024-05-06".to_string()));
-        assert_eq!(args.target, Target::Postcode("BS7".to_string()));
+        // network operator
+        let args = parsed_args(vec!["now", "UKPN London"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Now {
+                target: Target::Region(Region::London),
+                format: Format::Text
+            })
+        );
+        assert!(parsed_args(vec!["now", "UKPN"]).is_err());
+
+        // national
+        let args = parsed_args(vec!["now"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Now {
+                target: Target::National,
+                format: Format::Text
+            })
+        );
 
-        // start date / region id
-        let args = parsed_args(vec!["--start-date", "2024-05-06", "16"]).unwrap();
-        assert_eq!(args.start_date, Some("2024-05-06".to_string()));
-        assert_eq!(args.target, Target::Region(Region::Scotland));
+        // badge
+        let args = parsed_args(vec!["now", "--format", "badge", "13"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Now {
+                target: Target::Region(Region::London),
+                format: Format::Badge
+            })
+        );
+        assert!(parsed_args(vec!["history", "--format", "badge", "-s", "2024-05-06"]).is_err());
 
-        // start date / end date
+        // status bars
+        let args = parsed_args(vec!["now", "--format", "i3blocks", "13"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Now {
+                target: Target::Region(Region::London),
+                format: Format::I3blocks
+            })
+        );
+        assert!(parsed_args(vec!["history", "--format", "waybar", "-s", "2024-05-06"]).is_err());
+
+        // start date  / postcode
+        let args = parsed_args(vec!["history", "--start-date", "2024-05-06", "BS7"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::History {
+                target: Target::Postcode("BS7".to_string()),
+                start_date: Some("2024-05-06".to_string()),
+                end_date: None,
+                range: None,
+                surplus_only: false,
+                format: HistoryFormat::Text,
+                report: false,
+                append_to: None,
+            })
+        );
+
+        // start date / end date / region id
         let args = parsed_args(vec![
+            "history",
             "--start-date",
             "2024-05-06",
             "--end-date",
             "2024-07-08",
-            "BS7",
+            "16",
         ])
         .unwrap();
-        assert_eq!(args.start_date, Some("2024-05-06".to_string()));
-        assert_eq!(args.end_date, Some("2024-07-08".to_string()));
-        assert_eq!(args.target, Target::Postcode("BS7".to_string()));
+        assert_eq!(
+            args.command,
+            Some(Command::History {
+                target: Target::Region(Region::Scotland),
+                start_date: Some("2024-05-06".to_string()),
+                end_date: Some("2024-07-08".to_string()),
+                range: None,
+                surplus_only: false,
+                format: HistoryFormat::Text,
+                report: false,
+                append_to: None,
+            })
+        );
+
+        // relative dates, after now or attached to the option if starting with a hyphen
+        let args = parsed_args(vec!["history", "-s", "now-24h", "-e=-1h", "BS7"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::History { start_date: Some(start), end_date: Some(end), .. })
+                if start == "now-24h" && end == "-1h"
+        ));
+        // not the next option as the value of the start date
+        assert!(parsed_args(vec!["history", "-s", "-e", "-1h", "BS7"]).is_err());
+
+        // start date required
+        assert!(parsed_args(vec!["history", "BS7"]).is_err());
 
         // short names
-        parsed_args(vec!["-s 2024-05-06", "-e 2024-05-06", "BS7"]).unwrap();
-        parsed_args(vec!["-s 2024-05-06", "BS7"]).unwrap();
-        parsed_args(vec!["-e 2024-05-06", "BS7"]).unwrap();
+        parsed_args(vec!["history", "-s 2024-05-06", "-e 2024-05-06", "BS7"]).unwrap();
+        parsed_args(vec!["history", "-s 2024-05-06", "BS7"]).unwrap();
+
+        // global options, before or after the subcommand
+        let args = parsed_args(vec!["--output", "out.txt", "now", "BS7"]).unwrap();
+        assert_eq!(args.output, Some("out.txt".into()));
+        let args = parsed_args(vec!["history", "-s", "2024-05-06", "--timeout", "10"]).unwrap();
+        assert_eq!(args.timeout, Some(10));
+        assert_eq!(args.max_requests, None);
+        let args = parsed_args(vec!["matrix", "-r", "ytd", "--max-requests", "200"]).unwrap();
+        assert_eq!(args.max_requests, Some(200));
+
+        // JSON output
+        let args = parsed_args(vec!["history", "-s", "2024-05-06", "-f", "ndjson"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::History {
+                format: HistoryFormat::Ndjson,
+                ..
+            })
+        ));
+        let args = parsed_args(vec!["now", "--format", "json"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Now {
+                format: Format::Json,
+                ..
+            })
+        ));
+
+        // execution report
+        let args = parsed_args(vec!["history", "-s", "2024-05-06", "--report"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::History { report: true, .. })
+        ));
+
+        // date format
+        let args =
+            parsed_args(vec!["history", "-s", "2024-05-06", "--date-format", "unix"]).unwrap();
+        assert_eq!(args.date_format, Some(DateFormat::Unix));
+        let args = parsed_args(vec![
+            "--date-format",
+            "%d/%m/%Y %H:%M",
+            "history",
+            "-s",
+            "2024-05-06",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.date_format,
+            Some(DateFormat::Pattern("%d/%m/%Y %H:%M".to_string()))
+        );
+        assert!(parsed_args(vec!["history", "-s", "2024-05-06", "--date-format", "%Q"]).is_err());
+        assert!(parsed_args(vec![
+            "history",
+            "--append-to",
+            "data.csv",
+            "--date-format",
+            "unix"
+        ])
+        .is_err());
+
+        // surplus only
+        let args = parsed_args(vec!["history", "--surplus-only", "-s", "2024-05-06"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::History {
+                surplus_only: true,
+                ..
+            })
+        ));
+        assert!(parsed_args(vec!["now", "--surplus-only"]).is_err());
+
+        // audit
+        let args = parsed_args(vec![
+            "audit",
+            "--postcodes",
+            "sites.csv",
+            "-s",
+            "2024-01-01",
+            "-e",
+            "2024-12-31",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Audit {
+                postcodes: "sites.csv".into(),
+                start_date: Some("2024-01-01".to_string()),
+                end_date: Some("2024-12-31".to_string()),
+                range: None,
+                keep_going: false,
+            })
+        );
+        assert!(parsed_args(vec!["audit", "--postcodes", "sites.csv"]).is_err());
+        let args = parsed_args(vec![
+            "audit",
+            "--postcodes",
+            "sites.csv",
+            "-s",
+            "2024-01-01",
+            "--keep-going",
+        ])
+        .unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Audit {
+                keep_going: true,
+                ..
+            })
+        ));
+
+        // range instead of the dates
+        let args = parsed_args(vec![
+            "audit",
+            "--postcodes",
+            "sites.csv",
+            "-r",
+            "last-month",
+        ])
+        .unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Audit {
+                start_date: None,
+                range: Some(RangePreset::LastMonth),
+                ..
+            })
+        ));
+        let args = parsed_args(vec!["history", "--range", "ytd", "bs7"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::History {
+                range: Some(RangePreset::YearToDate),
+                ..
+            })
+        ));
+        assert!(parsed_args(vec!["matrix", "-r", "today"]).is_ok());
+        assert!(parsed_args(vec!["history", "-r", "today", "-s", "2024-01-01"]).is_err());
+        assert!(parsed_args(vec!["history", "-r", "today", "-e", "2024-01-01"]).is_err());
+        assert!(parsed_args(vec!["history", "-r", "today", "--append-to", "a.csv"]).is_err());
+        assert!(parsed_args(vec!["history", "-r", "last-week"]).is_err());
+
+        // scope 2
+        let args = parsed_args(vec!["scope2", "--consumption", "monthly.csv"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Scope2 {
+                consumption: "monthly.csv".into()
+            })
+        );
+        assert!(parsed_args(vec!["scope2"]).is_err());
+
+        // annual
+        let args = parsed_args(vec!["annual", "2024"]).unwrap();
+        assert_eq!(args.command, Some(Command::Annual { year: 2024 }));
+        assert!(parsed_args(vec!["annual"]).is_err());
+        assert!(parsed_args(vec!["annual", "last"]).is_err());
+
+        // ramp
+        let args = parsed_args(vec!["ramp", "-r", "last-month", "13"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Ramp {
+                target: Target::Region(Region::London),
+                start_date: None,
+                end_date: None,
+                range: Some(RangePreset::LastMonth),
+                series: false,
+            })
+        );
+        let args = parsed_args(vec!["ramp", "-s", "2024-01-01", "--series"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Ramp {
+                target: Target::National,
+                series: true,
+                ..
+            })
+        ));
+        assert!(parsed_args(vec!["ramp"]).is_err());
+
+        // stats
+        let args = parsed_args(vec!["stats", "-r", "ytd", "-p", "50,99.5"]).unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Stats {
+                target: Target::National,
+                start_date: None,
+                end_date: None,
+                range: Some(RangePreset::YearToDate),
+                percentiles: vec![50.0, 99.5],
+            })
+        );
+        let args = parsed_args(vec!["stats", "-s", "now-7d"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Stats { percentiles, .. }) if percentiles == DEFAULT_PERCENTILES
+        ));
+        assert!(parsed_args(vec!["stats", "-r", "ytd", "-p", "101"]).is_err());
+
+        // matrix
+        let args = parsed_args(vec![
+            "matrix",
+            "-s",
+            "2024-01-01",
+            "-m",
+            "max",
+            "-f",
+            "html",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.command,
+            Some(Command::Matrix {
+                start_date: Some("2024-01-01".to_string()),
+                end_date: None,
+                range: None,
+                metric: Metric::Max,
+                format: MatrixFormat::Html
+            })
+        );
+        let args = parsed_args(vec!["matrix", "-s", "2024-01-01", "-e", "2024-02-01"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Matrix {
+                metric: Metric::Mean,
+                format: MatrixFormat::Csv,
+                ..
+            })
+        ));
+        assert!(parsed_args(vec!["matrix"]).is_err());
+        assert!(parsed_args(vec!["matrix", "-s", "2024-01-01", "-m", "median"]).is_err());
+
+        // self-update
+        #[cfg(feature = "self-update")]
+        {
+            let args = parsed_args(vec!["self-update"]).unwrap();
+            assert_eq!(args.command, Some(Command::SelfUpdate));
+        }
+        #[cfg(not(feature = "self-update"))]
+        assert!(parsed_args(vec!["self-update"]).is_err());
+
+        // append, the start date is then optional
+        let args = parsed_args(vec!["history", "--append-to", "bs7.csv", "BS7"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::History {
+                append_to: Some(_),
+                start_date: None,
+                ..
+            })
+        ));
+        assert!(parsed_args(vec![
+            "history",
+            "--append-to",
+            "bs7.csv",
+            "-o",
+            "out.txt",
+            "BS7"
+        ])
+        .is_err());
     }
 }
@@ -0,0 +1,291 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::{ApiError, CarbonIntensityClient, Data, DateRange, QueryOptions, Region, Result};
+
+/// Daily statistic in the cells of an `IntensityMatrix`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MatrixMetric {
+    #[default]
+    Mean,
+    Min,
+    Max,
+}
+
+impl MatrixMetric {
+    /// Returns `None` if there are no values
+    fn apply(self, values: &[i32]) -> Option<f64> {
+        match self {
+            MatrixMetric::Mean => {
+                let total: i64 = values.iter().copied().map(i64::from).sum();
+                (!values.is_empty()).then(|| total as f64 / values.len() as f64)
+            }
+            MatrixMetric::Min => values.iter().min().map(|v| f64::from(*v)),
+            MatrixMetric::Max => values.iter().max().map(|v| f64::from(*v)),
+        }
+    }
+}
+
+/// Intensity of every region for each day of a range, in gCO2/kWh
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntensityMatrix {
+    pub metric: MatrixMetric,
+    /// Consecutive days, the columns of the matrix
+    pub days: Vec<NaiveDate>,
+    /// One row per region, ordered by region id, with a value per day
+    /// or `None` if the day has no data
+    pub rows: Vec<(Region, Vec<Option<f64>>)>,
+}
+
+impl IntensityMatrix {
+    /// Groups the half-hours of each region starting before `end` by the day they start
+    fn from_data(
+        regions: &[(Region, Vec<Data>)],
+        end: NaiveDateTime,
+        metric: MatrixMetric,
+    ) -> Result<Self> {
+        let mut by_day: Vec<(Region, BTreeMap<NaiveDate, Vec<i32>>)> = Vec::new();
+        for &region in Region::ALL.iter() {
+            let mut days: BTreeMap<NaiveDate, Vec<i32>> = BTreeMap::new();
+            let data = regions
+                .iter()
+                .find(|(r, _)| *r == region)
+                .map(|(_, data)| data.as_slice())
+                .unwrap_or_default();
+            for item in data {
+                let (start, value) = item.intensity_for_date()?;
+                // the range includes the half-hour starting at its end
+                if start < end {
+                    days.entry(start.date()).or_default().push(value);
+                }
+            }
+            by_day.push((region, days));
+        }
+
+        let first = by_day.iter().filter_map(|(_, d)| d.keys().next()).min();
+        let last = by_day
+            .iter()
+            .filter_map(|(_, d)| d.keys().next_back())
+            .max();
+        let days: Vec<NaiveDate> = match (first, last) {
+            (Some(&first), Some(&last)) => first.iter_days().take_while(|d| *d <= last).collect(),
+            _ => Vec::new(),
+        };
+
+        let rows = by_day
+            .into_iter()
+            .map(|(region, values)| {
+                let cells = days
+                    .iter()
+                    .map(|day| values.get(day).and_then(|v| metric.apply(v)))
+                    .collect();
+                (region, cells)
+            })
+            .collect();
+        Ok(Self { metric, days, rows })
+    }
+
+    /// Lowest and highest values of the matrix
+    fn bounds(&self) -> Option<(f64, f64)> {
+        let values = self
+            .rows
+            .iter()
+            .flat_map(|(_, cells)| cells.iter().flatten());
+        values.fold(None, |bounds, &value| match bounds {
+            None => Some((value, value)),
+            Some((min, max)) => Some((f64::min(min, value), f64::max(max, value))),
+        })
+    }
+}
+
+/// Computes a statistic of the intensity of every region for each day of a range
+///
+/// All the regions are retrieved at once with the all-regions endpoint,
+/// the windows of the range concurrently. Days are in UTC.
+pub async fn intensity_matrix(
+    start: &str,
+    end: &Option<&str>,
+    metric: MatrixMetric,
+    options: &QueryOptions,
+) -> Result<IntensityMatrix> {
+    CarbonIntensityClient::shared()
+        .intensity_matrix(start, end, metric, options)
+        .await
+}
+
+impl CarbonIntensityClient {
+    /// Daily statistic of the intensity of every region, see `intensity_matrix()`
+    pub async fn intensity_matrix(
+        &self,
+        start: &str,
+        end: &Option<&str>,
+        metric: MatrixMetric,
+        options: &QueryOptions,
+    ) -> Result<IntensityMatrix> {
+        let range = DateRange::parse(start, end, options.clock())?;
+        let outcome = self
+            .get_detailed_intensities_all_regions_in_range(&range, options)
+            .await?;
+        if outcome.is_partial() {
+            let missing: Vec<String> = outcome.warnings.iter().map(ToString::to_string).collect();
+            return Err(ApiError::Error(format!(
+                "Incomplete data: {}",
+                missing.join(", ")
+            )));
+        }
+        IntensityMatrix::from_data(&outcome.intensities, range.end(), metric)
+    }
+}
+
+/// Writes the matrix as CSV, one line per region and one column per day
+///
+/// Days without data are left empty.
+pub fn write_matrix_csv<W: Write>(writer: &mut W, matrix: &IntensityMatrix) -> io::Result<()> {
+    write!(writer, "id,region")?;
+    for day in &matrix.days {
+        write!(writer, ",{day}")?;
+    }
+    writeln!(writer)?;
+
+    for (region, cells) in &matrix.rows {
+        // some names contain a comma
+        write!(writer, "{},\"{region}\"", *region as u8)?;
+        for cell in cells {
+            match cell {
+                Some(value) => write!(writer, ",{value:.1}")?,
+                None => write!(writer, ",")?,
+            }
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Writes the matrix as a standalone HTML page, with the cells coloured
+/// from green for the lowest value to red for the highest
+pub fn write_matrix_html<W: Write>(writer: &mut W, matrix: &IntensityMatrix) -> io::Result<()> {
+    let metric = match matrix.metric {
+        MatrixMetric::Mean => "Mean",
+        MatrixMetric::Min => "Minimum",
+        MatrixMetric::Max => "Maximum",
+    };
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(writer, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(writer, "<title>Regional intensity</title>\n</head>\n<body>")?;
+    writeln!(writer, "<h1>{metric} daily intensity (gCO2/kWh)</h1>")?;
+    writeln!(writer, "<table>")?;
+    write!(writer, "<tr><th>Region</th>")?;
+    for day in &matrix.days {
+        write!(writer, "<th>{}</th>", day.format("%d/%m"))?;
+    }
+    writeln!(writer, "</tr>")?;
+
+    let (min, max) = matrix.bounds().unwrap_or((0.0, 0.0));
+    for (region, cells) in &matrix.rows {
+        write!(writer, "<tr><td>{region}</td>")?;
+        for cell in cells {
+            match cell {
+                Some(value) => {
+                    let scale = (value - min) / (max - min).max(f64::EPSILON);
+                    let hue = 120.0 * (1.0 - scale);
+                    write!(
+                        writer,
+                        "<td style=\"background-color:hsl({hue:.0},70%,60%)\">{value:.0}</td>"
+                    )?;
+                }
+                None => write!(writer, "<td></td>")?,
+            }
+        }
+        writeln!(writer, "</tr>")?;
+    }
+    writeln!(writer, "</table>")?;
+    writeln!(writer, "</body>\n</html>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn end() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, 4)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    }
+
+    fn regions() -> Vec<(Region, Vec<Data>)> {
+        vec![
+            (
+                Region::NorthScotland,
+                vec![
+                    Data::fixture("2024-01-01T00:00Z", 10),
+                    Data::fixture("2024-01-01T12:00Z", 30),
+                    Data::fixture("2024-01-03T00:00Z", 50),
+                ],
+            ),
+            (
+                Region::London,
+                vec![Data::fixture("2024-01-02T23:30Z", 200)],
+            ),
+        ]
+    }
+
+    #[test]
+    fn matrix() {
+        let matrix = IntensityMatrix::from_data(&regions(), end(), MatrixMetric::Mean).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        assert_eq!(matrix.days, vec![day(1), day(2), day(3)]);
+        assert_eq!(matrix.rows.len(), Region::ALL.len());
+        assert_eq!(
+            matrix.rows[0],
+            (Region::NorthScotland, vec![Some(20.0), None, Some(50.0)])
+        );
+        assert_eq!(
+            matrix.rows[Region::London as usize - 1],
+            (Region::London, vec![None, Some(200.0), None])
+        );
+        assert_eq!(matrix.rows[1], (Region::SouthScotland, vec![None; 3]));
+        assert_eq!(matrix.bounds(), Some((20.0, 200.0)));
+
+        let matrix = IntensityMatrix::from_data(&regions(), end(), MatrixMetric::Max).unwrap();
+        assert_eq!(matrix.rows[0].1, vec![Some(30.0), None, Some(50.0)]);
+        let matrix = IntensityMatrix::from_data(&regions(), end(), MatrixMetric::Min).unwrap();
+        assert_eq!(matrix.rows[0].1, vec![Some(10.0), None, Some(50.0)]);
+
+        // the half-hour starting at the end of the range
+        let mut regions = regions();
+        regions[1].1.push(Data::fixture("2024-01-04T00:00Z", 100));
+        let matrix = IntensityMatrix::from_data(&regions, end(), MatrixMetric::Mean).unwrap();
+        assert_eq!(matrix.days.len(), 3);
+
+        let matrix = IntensityMatrix::from_data(&[], end(), MatrixMetric::Mean).unwrap();
+        assert!(matrix.days.is_empty());
+        assert_eq!(matrix.bounds(), None);
+    }
+
+    #[test]
+    fn write() {
+        let matrix = IntensityMatrix::from_data(&regions(), end(), MatrixMetric::Mean).unwrap();
+
+        let mut output = Vec::new();
+        write_matrix_csv(&mut output, &matrix).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), Region::ALL.len() + 1);
+        assert_eq!(lines[0], "id,region,2024-01-01,2024-01-02,2024-01-03");
+        assert_eq!(lines[1], "1,\"North Scotland\",20.0,,50.0");
+        assert_eq!(lines[6], "6,\"North Wales, Merseyside and Cheshire\",,,");
+
+        let mut output = Vec::new();
+        write_matrix_html(&mut output, &matrix).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("<h1>Mean daily intensity (gCO2/kWh)</h1>"));
+        assert!(output.contains("<th>01/01</th><th>02/01</th><th>03/01</th>"));
+        assert!(output.contains("<td style=\"background-color:hsl(120,70%,60%)\">20</td>"));
+        assert!(output.contains("<td style=\"background-color:hsl(0,70%,60%)\">200</td>"));
+    }
+}
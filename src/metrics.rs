@@ -0,0 +1,143 @@
+use std::fmt::Write;
+
+use crate::{sink::csv_key, Intensity, IntensityIndex, Target};
+
+/// Renders the current intensities of targets as OpenMetrics text
+///
+/// The output can be served as is from a `/metrics` endpoint and scraped by
+/// Prometheus. Each target is identified by a `target` label: `national`, the
+/// postcode in uppercase or the region id.
+///
+/// ```
+/// # use carbonintensity::{render_openmetrics, Target};
+/// let text = render_openmetrics(&[]);
+/// assert!(text.ends_with("# EOF\n"));
+/// ```
+pub fn render_openmetrics(snapshot: &[(Target, Intensity)]) -> String {
+    let mut text = String::new();
+
+    family(
+        &mut text,
+        "carbonintensity_forecast_gco2_per_kwh",
+        "Forecast carbon intensity of the current half-hour",
+    );
+    for (target, intensity) in snapshot {
+        if let Some(forecast) = intensity.forecast() {
+            sample(
+                &mut text,
+                "carbonintensity_forecast_gco2_per_kwh",
+                target,
+                None,
+                forecast,
+            );
+        }
+    }
+
+    family(
+        &mut text,
+        "carbonintensity_actual_gco2_per_kwh",
+        "Actual carbon intensity of the current half-hour, only known at the national level",
+    );
+    for (target, intensity) in snapshot {
+        if let Some(actual) = intensity.actual() {
+            sample(
+                &mut text,
+                "carbonintensity_actual_gco2_per_kwh",
+                target,
+                None,
+                actual,
+            );
+        }
+    }
+
+    family(
+        &mut text,
+        "carbonintensity_index",
+        "Index of the current half-hour, 1 for the current index and 0 for the others",
+    );
+    for (target, intensity) in snapshot {
+        for index in IntensityIndex::ALL {
+            let value = i32::from(intensity.index() == index);
+            sample(
+                &mut text,
+                "carbonintensity_index",
+                target,
+                Some(&index.to_string()),
+                value,
+            );
+        }
+    }
+
+    text.push_str("# EOF\n");
+    text
+}
+
+fn family(text: &mut String, name: &str, help: &str) {
+    // writing to a String can't fail
+    let _ = writeln!(text, "# TYPE {name} gauge");
+    let _ = writeln!(text, "# HELP {name} {help}");
+}
+
+fn sample(text: &mut String, name: &str, target: &Target, index: Option<&str>, value: i32) {
+    let target = escape(&csv_key(target));
+    let _ = match index {
+        None => writeln!(text, "{name}{{target=\"{target}\"}} {value}"),
+        Some(index) => writeln!(
+            text,
+            "{name}{{target=\"{target}\",index=\"{}\"}} {value}",
+            escape(index)
+        ),
+    };
+}
+
+/// Escapes a label value
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Region;
+
+    #[test]
+    fn render() {
+        let snapshot = vec![
+            (
+                Target::National,
+                Intensity {
+                    forecast: Some(150),
+                    index: IntensityIndex::Moderate,
+                    actual: Some(145),
+                },
+            ),
+            (
+                Target::Region(Region::London),
+                Intensity {
+                    forecast: Some(90),
+                    index: IntensityIndex::Low,
+                    actual: None,
+                },
+            ),
+        ];
+        let text = render_openmetrics(&snapshot);
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert!(lines.contains(&r#"carbonintensity_forecast_gco2_per_kwh{target="national"} 150"#));
+        assert!(lines.contains(&r#"carbonintensity_forecast_gco2_per_kwh{target="13"} 90"#));
+        assert!(lines.contains(&r#"carbonintensity_actual_gco2_per_kwh{target="national"} 145"#));
+        assert!(!text.contains(r#"carbonintensity_actual_gco2_per_kwh{target="13"}"#));
+        assert!(lines.contains(&r#"carbonintensity_index{target="13",index="low"} 1"#));
+        assert!(lines.contains(&r#"carbonintensity_index{target="13",index="high"} 0"#));
+        assert_eq!(lines.iter().filter(|l| l.starts_with("# TYPE")).count(), 3);
+        assert_eq!(lines.last(), Some(&"# EOF"));
+    }
+
+    #[test]
+    fn escape_label() {
+        assert_eq!(escape(r#"a"b\c"#), r#"a\"b\\c"#);
+    }
+}
@@ -0,0 +1,296 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use chrono::NaiveDateTime;
+use http::{HeaderValue, Request, Response};
+use tokio::sync::Mutex;
+use tower::{Layer, Service};
+
+use crate::{
+    planner::start_of_half_hour,
+    runtime::{self, Stopwatch},
+    CarbonIntensityClient, IntensityIndex, Target,
+};
+
+/// Header added to the responses, holding the current intensity in gCO2/kWh
+pub const INTENSITY_HEADER: &str = "x-grid-carbon-intensity";
+
+/// Current intensity, added as an extension to the requests by `IntensityLayer`
+///
+/// Handlers can get it (e.g. with axum's `Extension` extractor) to degrade
+/// expensive features when the grid is dirty, like serving lower quality videos.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GridIntensity {
+    /// Intensity in gCO2/kWh
+    pub intensity: i32,
+    pub index: IntensityIndex,
+}
+
+/// Default time allowed to retrieve the intensity, the requests waiting for it
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Time during which a failure to retrieve the intensity is remembered,
+/// the requests going through without it instead of retrying
+const FAILURE_TTL: Duration = Duration::from_secs(30);
+
+/// Outcome of the last retrieval of the intensity
+#[derive(Debug)]
+enum Entry {
+    /// Intensity with the half-hour it applies to
+    Fetched(NaiveDateTime, GridIntensity),
+    /// Time since the retrieval failed
+    Failed(Stopwatch),
+}
+
+/// Last retrieval, locked while retrieving so that concurrent requests wait for it
+type Cached = Arc<Mutex<Option<Entry>>>;
+
+/// Tower layer exposing the current intensity of a target to the wrapped service
+///
+/// The intensity is added as a `GridIntensity` extension to each request and in
+/// the `INTENSITY_HEADER` of each response. It is retrieved at most once per
+/// half-hour and shared by the clones of the service, the requests arriving while
+/// it is retrieved waiting for it rather than sending their own. If it can't be
+/// retrieved within the timeout, 2 seconds by default, requests go through without
+/// it and it isn't retried for 30 seconds.
+///
+/// ```
+/// # use carbonintensity::{IntensityLayer, Region, Target};
+/// let layer = IntensityLayer::new(Target::Region(Region::London));
+/// // e.g. axum::Router::new().route(...).layer(layer)
+/// ```
+#[derive(Debug, Clone)]
+pub struct IntensityLayer {
+    target: Target,
+    fetcher: Fetcher,
+}
+
+impl IntensityLayer {
+    pub fn new(target: Target) -> Self {
+        Self {
+            target,
+            fetcher: Fetcher {
+                client: CarbonIntensityClient::shared().clone(),
+                timeout: DEFAULT_TIMEOUT,
+                cached: Arc::default(),
+            },
+        }
+    }
+
+    /// Sets the client retrieving the intensity, e.g. with a cache or a transport
+    pub fn with_client(mut self, client: CarbonIntensityClient) -> Self {
+        self.fetcher.client = client;
+        self
+    }
+
+    /// Sets the time allowed to retrieve the intensity, including the retries of the client
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.fetcher.timeout = timeout;
+        self
+    }
+}
+
+/// Retrieves the intensity for the services of a layer
+#[derive(Debug, Clone)]
+struct Fetcher {
+    client: CarbonIntensityClient,
+    timeout: Duration,
+    cached: Cached,
+}
+
+impl<S> Layer<S> for IntensityLayer {
+    type Service = IntensityService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IntensityService {
+            inner,
+            target: self.target.clone(),
+            fetcher: self.fetcher.clone(),
+        }
+    }
+}
+
+/// Service created by `IntensityLayer`
+#[derive(Debug, Clone)]
+pub struct IntensityService<S> {
+    inner: S,
+    target: Target,
+    fetcher: Fetcher,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for IntensityService<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        // the clone may not be ready, use the instance which was polled
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let target = self.target.clone();
+        let fetcher = self.fetcher.clone();
+
+        Box::pin(async move {
+            let grid = fetcher.current(&target).await;
+            if let Some(grid) = &grid {
+                request.extensions_mut().insert(grid.clone());
+            }
+            let mut response = inner.call(request).await?;
+            if let Some(grid) = grid {
+                response
+                    .headers_mut()
+                    .insert(INTENSITY_HEADER, HeaderValue::from(grid.intensity));
+            }
+            Ok(response)
+        })
+    }
+}
+
+impl Fetcher {
+    /// Intensity of the current half-hour, from the cache if already retrieved
+    async fn current(&self, target: &Target) -> Option<GridIntensity> {
+        let half_hour = start_of_half_hour(self.client.clock().now());
+
+        let mut cached = self.cached.lock().await;
+        match cached.as_ref() {
+            Some(Entry::Fetched(time, grid)) if *time == half_hour => return Some(grid.clone()),
+            Some(Entry::Failed(failed)) if failed.elapsed() < FAILURE_TTL => return None,
+            _ => {}
+        }
+
+        let intensity = runtime::timeout(self.timeout, self.client.get_current_intensity(target))
+            .await
+            .and_then(Result::ok);
+        let grid = intensity.and_then(|intensity| {
+            Some(GridIntensity {
+                intensity: intensity.value()?,
+                index: intensity.index(),
+            })
+        });
+        let Some(grid) = grid else {
+            *cached = Some(Entry::Failed(Stopwatch::start()));
+            return None;
+        };
+        *cached = Some(Entry::Fetched(half_hour, grid.clone()));
+        Some(grid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+    use crate::{Clock, Region, RetryPolicy, SystemClock, Transport, TransportFuture};
+
+    /// Counts the requests, answered after a delay
+    #[derive(Debug)]
+    struct Slow {
+        requests: Arc<AtomicUsize>,
+    }
+
+    impl Transport for Slow {
+        fn get<'a>(&'a self, _url: &'a str) -> TransportFuture<'a> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                runtime::sleep(Duration::from_secs(5)).await;
+                Err(crate::ApiError::Error("too late".to_string()))
+            })
+        }
+    }
+
+    /// Responds with the intensity found in the request extension
+    #[derive(Clone)]
+    struct Echo;
+
+    impl Service<Request<()>> for Echo {
+        type Response = Response<String>;
+        type Error = Infallible;
+        type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: Request<()>) -> Self::Future {
+            let body = request
+                .extensions()
+                .get::<GridIntensity>()
+                .map(|grid| grid.index.to_string())
+                .unwrap_or_default();
+            std::future::ready(Ok(Response::new(body)))
+        }
+    }
+
+    #[test]
+    fn cached_intensity() {
+        let layer = IntensityLayer::new(Target::Region(Region::London));
+        let half_hour = start_of_half_hour(SystemClock.now());
+        let grid = GridIntensity {
+            intensity: 123,
+            index: IntensityIndex::Moderate,
+        };
+        *layer.fetcher.cached.try_lock().unwrap() = Some(Entry::Fetched(half_hour, grid));
+
+        let mut service = layer.layer(Echo);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let response = runtime.block_on(service.call(Request::new(()))).unwrap();
+
+        assert_eq!(response.body(), "moderate");
+        assert_eq!(response.headers()[INTENSITY_HEADER], "123");
+    }
+
+    #[test]
+    fn failed_intensity() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let client = CarbonIntensityClient::builder()
+            .with_transport(Slow {
+                requests: requests.clone(),
+            })
+            .with_retry_policy(RetryPolicy::new(0, Duration::ZERO))
+            .build()
+            .unwrap();
+        let layer = IntensityLayer::new(Target::National)
+            .with_client(client)
+            .with_timeout(Duration::from_millis(50));
+        let service = layer.layer(Echo);
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        // the requests arriving together wait for the same retrieval
+        let responses: Vec<_> = (0..3)
+            .map(|_| runtime.spawn(service.clone().call(Request::new(()))))
+            .collect();
+        for response in responses {
+            let response = runtime.block_on(response).unwrap().unwrap();
+            assert_eq!(response.body(), "");
+            assert!(response.headers().get(INTENSITY_HEADER).is_none());
+        }
+        // the failure is remembered
+        runtime
+            .block_on(service.clone().call(Request::new(())))
+            .unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,411 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+
+use crate::{
+    collect_windows, current_path, first, get_region_data_for_url, parse_date,
+    planner::RangePlanner,
+    report,
+    runtime::{spawn_window, Stopwatch},
+    ApiError, CarbonIntensityClient, Data, DateRange, GenerationMix, MixShare, QueryOptions,
+    QueryOutcome, Result, Target,
+};
+
+/// Fuel of a generation mix
+///
+/// Fuels sent by the API which are not listed here are counted as `Other`.
+///
+/// ```
+/// # use carbonintensity::Fuel;
+/// assert_eq!(Fuel::from("wind"), Fuel::Wind);
+/// assert_eq!(Fuel::from("tidal"), Fuel::Other);
+/// assert_eq!(Fuel::Imports.to_string(), "imports");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fuel {
+    Biomass,
+    Coal,
+    Imports,
+    Gas,
+    Nuclear,
+    Other,
+    Hydro,
+    Solar,
+    Wind,
+}
+
+impl Fuel {
+    /// All the fuels, in the order used by the API
+    pub const ALL: [Fuel; 9] = [
+        Fuel::Biomass,
+        Fuel::Coal,
+        Fuel::Imports,
+        Fuel::Gas,
+        Fuel::Nuclear,
+        Fuel::Other,
+        Fuel::Hydro,
+        Fuel::Solar,
+        Fuel::Wind,
+    ];
+}
+
+impl Display for Fuel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Fuel::Biomass => "biomass",
+            Fuel::Coal => "coal",
+            Fuel::Imports => "imports",
+            Fuel::Gas => "gas",
+            Fuel::Nuclear => "nuclear",
+            Fuel::Other => "other",
+            Fuel::Hydro => "hydro",
+            Fuel::Solar => "solar",
+            Fuel::Wind => "wind",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Parses the name of the fuel as sent by the API e.g. "wind"
+impl From<&str> for Fuel {
+    fn from(s: &str) -> Self {
+        Fuel::ALL
+            .into_iter()
+            .find(|fuel| fuel.to_string() == s)
+            .unwrap_or(Fuel::Other)
+    }
+}
+
+/// Generation mix of a half-hour, by fuel
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuelMix {
+    /// Start of the half-hour
+    pub from: NaiveDateTime,
+    /// End of the half-hour
+    pub to: NaiveDateTime,
+    /// Share of each fuel, in the order sent by the API
+    pub shares: Vec<(Fuel, MixShare)>,
+}
+
+impl FuelMix {
+    fn new(from: &str, to: &str, mix: &[GenerationMix]) -> Result<Self> {
+        Ok(Self {
+            from: parse_date(from)?,
+            to: parse_date(to)?,
+            shares: mix
+                .iter()
+                .map(|m| (Fuel::from(m.fuel()), m.share()))
+                .collect(),
+        })
+    }
+
+    /// Share of a fuel, zero if the API sent none
+    pub fn share(&self, fuel: Fuel) -> MixShare {
+        let percent = self
+            .shares
+            .iter()
+            .filter(|(f, _)| *f == fuel)
+            .map(|(_, share)| share.percent())
+            .sum();
+        MixShare::new(percent)
+    }
+}
+
+/// Builds the mix of a half-hour, failing if the API sent none
+impl TryFrom<&Data> for FuelMix {
+    type Error = ApiError;
+
+    fn try_from(data: &Data) -> Result<Self> {
+        let mix = data
+            .generation_mix()
+            .ok_or_else(|| ApiError::Error(format!("No generation mix for {}", data.from())))?;
+        FuelMix::new(data.from(), data.to(), mix)
+    }
+}
+
+/// Mix of the current half-hour at the national level
+#[derive(Debug, Deserialize)]
+struct NationalGeneration {
+    from: String,
+    to: String,
+    generationmix: Vec<GenerationMix>,
+}
+
+/// The national endpoint sends a single object where others send a list
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct GenerationData {
+    data: OneOrMany<NationalGeneration>,
+}
+
+impl GenerationData {
+    /// Generation mix of the current half-hour
+    pub(crate) fn current_mix(self) -> Result<FuelMix> {
+        let generation = match self.data {
+            OneOrMany::One(generation) => generation,
+            OneOrMany::Many(generations) => first(generations)?,
+        };
+        FuelMix::new(&generation.from, &generation.to, &generation.generationmix)
+    }
+}
+
+/// Current generation mix for a target
+///
+/// Uses one of
+/// - <https://api.carbonintensity.org.uk/regional/postcode/>
+/// - <https://api.carbonintensity.org.uk/regional/regionid/>
+/// - <https://api.carbonintensity.org.uk/generation>
+pub async fn get_generation_mix(target: &Target) -> Result<FuelMix> {
+    CarbonIntensityClient::shared()
+        .get_generation_mix(target)
+        .await
+}
+
+#[derive(Debug, Deserialize)]
+struct FactorsData {
+    data: Vec<BTreeMap<String, i32>>,
+}
+
+/// Carbon intensity of each type of generation, in gCO2/kWh
+///
+/// The types are those published by the API e.g. "Gas (Combined Cycle)" or
+/// "French Imports", more detailed than the fuels of the generation mixes.
+/// Uses <https://api.carbonintensity.org.uk/intensity/factors>
+pub async fn get_carbon_factors() -> Result<BTreeMap<String, i32>> {
+    CarbonIntensityClient::shared().get_carbon_factors().await
+}
+
+/// Generation mixes of a target in 30 minutes windows
+///
+/// Dates are given as for `get_intensities`. The range is split into windows
+/// requested concurrently, using one of
+/// - https://api.carbonintensity.org.uk/regional/intensity/2023-05-15/2023-05-20/postcode/RG10
+/// - https://api.carbonintensity.org.uk/regional/intensity/2023-05-15/2023-05-20/regionid/13
+/// - https://api.carbonintensity.org.uk/generation/2023-05-15/2023-05-20
+///
+/// The national mixes are only published for the past, the future half-hours
+/// of the range are ignored for the national target.
+pub async fn get_generation_mixes(
+    target: &Target,
+    start: &str,
+    end: &Option<&str>,
+) -> Result<Vec<FuelMix>> {
+    CarbonIntensityClient::shared()
+        .get_generation_mixes(target, start, end)
+        .await
+}
+
+/// Same as `get_generation_mixes` but with control over the execution of the query
+pub async fn get_generation_mixes_with_options(
+    target: &Target,
+    start: &str,
+    end: &Option<&str>,
+    options: &QueryOptions,
+) -> Result<QueryOutcome<FuelMix>> {
+    CarbonIntensityClient::shared()
+        .get_generation_mixes_with_options(target, start, end, options)
+        .await
+}
+
+/// Same as `get_generation_mixes_with_options` for a range validated beforehand
+pub async fn get_generation_mixes_in_range(
+    target: &Target,
+    range: &DateRange,
+    options: &QueryOptions,
+) -> Result<QueryOutcome<FuelMix>> {
+    CarbonIntensityClient::shared()
+        .get_generation_mixes_in_range(target, range, options)
+        .await
+}
+
+impl CarbonIntensityClient {
+    /// Current generation mix for a target, see `get_generation_mix()`
+    pub async fn get_generation_mix(&self, target: &Target) -> Result<FuelMix> {
+        if *target == Target::National {
+            let url = format!("{}/generation", self.base_url());
+            return self
+                .get_response::<GenerationData>(&url)
+                .await?
+                .current_mix();
+        }
+
+        let url = format!("{}/{}", self.base_url(), current_path(target)?);
+        let data = first(get_region_data_for_url(self, &url).await?.data)?;
+        FuelMix::try_from(&data)
+    }
+
+    /// Carbon intensity of each type of generation, see `get_carbon_factors()`
+    pub async fn get_carbon_factors(&self) -> Result<BTreeMap<String, i32>> {
+        let url = format!("{}/intensity/factors", self.base_url());
+        self.get_response::<FactorsData>(&url)
+            .await?
+            .data
+            .into_iter()
+            .next()
+            .ok_or_else(|| ApiError::Error("No data found".to_string()))
+    }
+
+    /// Generation mixes of a target in 30 minutes windows, see `get_generation_mixes()`
+    pub async fn get_generation_mixes(
+        &self,
+        target: &Target,
+        start: &str,
+        end: &Option<&str>,
+    ) -> Result<Vec<FuelMix>> {
+        let outcome = self
+            .get_generation_mixes_with_options(target, start, end, &QueryOptions::default())
+            .await?;
+        Ok(outcome.intensities)
+    }
+
+    /// See `get_generation_mixes_with_options()`
+    pub async fn get_generation_mixes_with_options(
+        &self,
+        target: &Target,
+        start: &str,
+        end: &Option<&str>,
+        options: &QueryOptions,
+    ) -> Result<QueryOutcome<FuelMix>> {
+        let range = DateRange::parse(start, end, options.clock())?;
+        self.get_generation_mixes_in_range(target, &range, options)
+            .await
+    }
+
+    /// See `get_generation_mixes_in_range()`
+    pub async fn get_generation_mixes_in_range(
+        &self,
+        target: &Target,
+        range: &DateRange,
+        options: &QueryOptions,
+    ) -> Result<QueryOutcome<FuelMix>> {
+        if *target != Target::National {
+            let outcome = self
+                .get_detailed_intensities_in_range(target, range, options)
+                .await?;
+            return Ok(QueryOutcome {
+                intensities: outcome
+                    .intensities
+                    .iter()
+                    .map(FuelMix::try_from)
+                    .collect::<Result<_>>()?,
+                warnings: outcome.warnings,
+                report: outcome.report,
+            });
+        }
+
+        let started = Stopwatch::start();
+        let now = options.clock().now();
+        let ranges: Vec<_> = range
+            .windows(now, self.chunking())
+            .into_iter()
+            .filter(|window| !RangePlanner.is_forecast(window, now))
+            .collect();
+
+        let base_url = self.base_url_for(options);
+        let tasks: Vec<_> = ranges
+            .iter()
+            .map(|window| {
+                let (start_date, end_date) = RangePlanner.query_bounds(window);
+                let settled = RangePlanner.is_settled(window, now);
+                let url = format!("{base_url}/generation/{start_date}/{end_date}");
+                let client = self.clone();
+                spawn_window(self.permits(), async move {
+                    match client
+                        .get_window_response::<GenerationData>(&url, settled)
+                        .await?
+                        .data
+                    {
+                        OneOrMany::One(generation) => Ok(vec![generation]),
+                        OneOrMany::Many(generations) => Ok(generations),
+                    }
+                })
+            })
+            .collect();
+
+        let end = ranges.last().map(|window| window.1);
+        let outcome = collect_windows(
+            tasks,
+            ranges,
+            self.max_concurrent_requests(),
+            options.deadline(),
+            started,
+        )
+        .await?;
+        let mut mixes = outcome
+            .intensities
+            .iter()
+            .map(|generation| {
+                FuelMix::new(&generation.from, &generation.to, &generation.generationmix)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(end) = end {
+            mixes.retain(|mix| mix.from <= end);
+        }
+        Ok(QueryOutcome {
+            intensities: mixes,
+            warnings: outcome.warnings,
+            report: report(outcome.report, started, options),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuels() {
+        for fuel in Fuel::ALL {
+            assert_eq!(Fuel::from(fuel.to_string().as_str()), fuel);
+        }
+        assert_eq!(Fuel::from("tidal"), Fuel::Other);
+    }
+
+    #[test]
+    fn mix() {
+        let json_str = r#"{"from":"2024-01-01T00:00Z","to":"2024-01-01T00:30Z",
+            "intensity":{"forecast":120,"index":"moderate"},
+            "generationmix":[{"fuel":"gas","perc":40.0},{"fuel":"wind","perc":50.0},
+            {"fuel":"other","perc":4.0},{"fuel":"tidal","perc":6.0}]}"#;
+        let data: Data = serde_json::from_str(json_str).unwrap();
+        let mix = FuelMix::try_from(&data).unwrap();
+        assert_eq!(mix.from, parse_date("2024-01-01T00:00Z").unwrap());
+        assert_eq!(mix.shares[0], (Fuel::Gas, MixShare::new(40.0)));
+        assert_eq!(mix.share(Fuel::Wind).percent(), 50.0);
+        assert_eq!(mix.share(Fuel::Other).percent(), 10.0);
+        assert_eq!(mix.share(Fuel::Coal).percent(), 0.0);
+
+        let json_str = r#"{"from":"2024-01-01T00:00Z","to":"2024-01-01T00:30Z",
+            "intensity":{"forecast":120,"actual":118,"index":"moderate"}}"#;
+        let data: Data = serde_json::from_str(json_str).unwrap();
+        assert!(FuelMix::try_from(&data).is_err());
+    }
+
+    #[test]
+    fn factors() {
+        let json_str = r#"{"data":[{"Biomass":120,"Coal":937,"Dutch Imports":474,
+            "Gas (Combined Cycle)":394,"Wind":0}]}"#;
+        let FactorsData { data } = serde_json::from_str(json_str).unwrap();
+        assert_eq!(data[0].len(), 5);
+        assert_eq!(data[0]["Gas (Combined Cycle)"], 394);
+    }
+
+    #[test]
+    fn national_generation() {
+        let object = r#"{"data":{"from":"2024-01-01T00:00Z","to":"2024-01-01T00:30Z",
+            "generationmix":[{"fuel":"wind","perc":100.0}]}}"#;
+        let generation: GenerationData = serde_json::from_str(object).unwrap();
+        assert!(matches!(generation.data, OneOrMany::One(_)));
+
+        let list = format!(r#"{{"data":[{}]}}"#, &object[8..object.len() - 1]);
+        let generation: GenerationData = serde_json::from_str(&list).unwrap();
+        assert!(matches!(generation.data, OneOrMany::Many(g) if g.len() == 1));
+    }
+}
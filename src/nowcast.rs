@@ -0,0 +1,160 @@
+use std::fmt::Display;
+
+use chrono::Duration;
+
+use crate::{ApiError, CarbonIntensityClient, Intensity, QueryOptions, Result, Target};
+
+/// Origin of the value of a `Nowcast`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NowcastSource {
+    /// Actual national value for the current half-hour
+    Actual,
+    /// Forecast for the current half-hour, no recent actual value was available
+    Forecast,
+    /// Forecast for the current half-hour, corrected by how far the most recent
+    /// national actual value was from its forecast
+    Blended,
+}
+
+impl Display for NowcastSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            NowcastSource::Actual => "actual",
+            NowcastSource::Forecast => "forecast",
+            NowcastSource::Blended => "forecast adjusted with the latest national actual",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Best estimate of the current intensity, with its provenance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Nowcast {
+    /// Intensity in gCO2/kWh
+    pub intensity: i32,
+    pub source: NowcastSource,
+}
+
+/// Best estimate of the intensity right now for a target
+///
+/// Uses the actual national value when it has been published for the current
+/// half-hour. Otherwise, the forecast for the target is scaled by the ratio between
+/// the most recent national actual value and its forecast, so that a systematic
+/// error of the forecast is corrected.
+pub async fn get_nowcast(target: &Target) -> Result<Nowcast> {
+    CarbonIntensityClient::shared().get_nowcast(target).await
+}
+
+/// Same as `get_nowcast()`, the recent national values are those of the last
+/// two hours in UTC according to the clock of the options
+pub async fn get_nowcast_with_options(target: &Target, options: &QueryOptions) -> Result<Nowcast> {
+    CarbonIntensityClient::shared()
+        .get_nowcast_with_options(target, options)
+        .await
+}
+
+impl CarbonIntensityClient {
+    /// Best estimate of the current intensity, see `get_nowcast()`
+    pub async fn get_nowcast(&self, target: &Target) -> Result<Nowcast> {
+        self.get_nowcast_with_options(target, &QueryOptions::default())
+            .await
+    }
+
+    /// See `get_nowcast_with_options()`
+    pub async fn get_nowcast_with_options(
+        &self,
+        target: &Target,
+        options: &QueryOptions,
+    ) -> Result<Nowcast> {
+        let since = (options.clock().now() - Duration::hours(2))
+            .format("%Y-%m-%dT%H:%MZ")
+            .to_string();
+
+        let (current, recent) = futures::try_join!(
+            self.get_current_intensity(target),
+            self.get_detailed_intensities(&Target::National, &since, &None, options),
+        )?;
+
+        let latest_actual = recent
+            .intensities
+            .iter()
+            .rev()
+            .map(|data| data.intensity())
+            .find(|intensity| intensity.actual().is_some());
+
+        if *target == Target::National {
+            if let Some(actual) = current.actual() {
+                return Ok(Nowcast {
+                    intensity: actual,
+                    source: NowcastSource::Actual,
+                });
+            }
+        }
+
+        let forecast = current
+            .forecast()
+            .ok_or_else(|| ApiError::Error(format!("No forecast sent for {target}")))?;
+        Ok(blend(forecast, latest_actual))
+    }
+}
+
+/// Scales the forecast by the error of the latest national value
+fn blend(forecast: i32, latest_national: Option<&Intensity>) -> Nowcast {
+    let ratio = latest_national.and_then(|national| {
+        let (actual, forecast) = (national.actual()?, national.forecast()?);
+        (forecast > 0).then(|| f64::from(actual) / f64::from(forecast))
+    });
+
+    match ratio {
+        Some(ratio) => Nowcast {
+            intensity: (f64::from(forecast) * ratio).round() as i32,
+            source: NowcastSource::Blended,
+        },
+        None => Nowcast {
+            intensity: forecast,
+            source: NowcastSource::Forecast,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IntensityIndex;
+
+    fn intensity(forecast: i32, actual: Option<i32>) -> Intensity {
+        Intensity {
+            forecast: Some(forecast),
+            index: IntensityIndex::Moderate,
+            actual,
+        }
+    }
+
+    #[test]
+    fn blend_test() {
+        // national actual 10% above its forecast
+        let national = intensity(200, Some(220));
+        assert_eq!(
+            blend(100, Some(&national)),
+            Nowcast {
+                intensity: 110,
+                source: NowcastSource::Blended
+            }
+        );
+
+        // no actual value
+        let national = intensity(200, None);
+        assert_eq!(
+            blend(100, Some(&national)),
+            Nowcast {
+                intensity: 100,
+                source: NowcastSource::Forecast
+            }
+        );
+        assert_eq!(blend(100, None).source, NowcastSource::Forecast);
+
+        // can't compute a ratio
+        let national = intensity(0, Some(10));
+        assert_eq!(blend(100, Some(&national)).source, NowcastSource::Forecast);
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, NaiveDateTime};
+
+use crate::{greenest::contiguous_windows, ApiError, IntensityForDate, Result};
+
+/// Price of electricity for the half-hour starting at a given time, e.g. in p/kWh
+pub type PriceForDate = (NaiveDateTime, f64);
+
+/// Parses a half-hourly price series from CSV
+///
+/// Each line holds the start of the half-hour followed by the price, e.g.
+/// `2024-01-01T00:00Z,15.12`. A header line is tolerated, as are extra columns
+/// between the date and the price (the price is the last column), which is how
+/// Agile tariff exports are usually laid out.
+pub fn parse_prices(csv: &str) -> Result<Vec<PriceForDate>> {
+    let mut prices = Vec::new();
+    for (number, line) in csv.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let columns: Vec<&str> = line
+            .split(',')
+            .map(|c| c.trim().trim_matches('"'))
+            .collect();
+        let (first, last) = (columns[0], columns[columns.len() - 1]);
+
+        let price = last.parse::<f64>();
+        let time = parse_price_date(first);
+        match (time, price) {
+            (Some(time), Ok(price)) if columns.len() > 1 => prices.push((time, price)),
+            // header
+            _ if number == 0 => continue,
+            _ => {
+                return Err(ApiError::Error(format!(
+                    "Invalid price on line {}: {line}",
+                    number + 1
+                )))
+            }
+        }
+    }
+    Ok(prices)
+}
+
+fn parse_price_date(date: &str) -> Option<NaiveDateTime> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Some(date.naive_utc());
+    }
+    ["%Y-%m-%dT%H:%MZ", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+}
+
+/// Candidate window for running a job
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowOption {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    /// Average price over the window
+    pub mean_price: f64,
+    /// Average intensity over the window, in gCO2/kWh
+    pub mean_intensity: f64,
+    /// Weighted combination of the normalised price and intensity, lower is better
+    pub score: f64,
+}
+
+/// Finds the windows minimising a combination of cost and carbon for a job
+///
+/// The job lasts `slots` half-hours. Only windows where every half-hour has both
+/// a price and an intensity are considered. `carbon_weight` (between 0 and 1) sets
+/// the importance of carbon relative to cost: 0 only looks at the price, 1 only at
+/// the intensity.
+///
+/// Returns the windows which are not beaten on both price and intensity by another
+/// window (the Pareto front), best score first.
+pub fn optimise_windows(
+    intensities: &[IntensityForDate],
+    prices: &[PriceForDate],
+    slots: usize,
+    carbon_weight: f64,
+) -> Vec<WindowOption> {
+    if slots == 0 {
+        return Vec::new();
+    }
+    let carbon_weight = carbon_weight.clamp(0.0, 1.0);
+    let prices: HashMap<NaiveDateTime, f64> = prices.iter().copied().collect();
+
+    // half-hours with both values, in chronological order
+    let mut points: Vec<(NaiveDateTime, f64, f64)> = intensities
+        .iter()
+        .filter_map(|(time, intensity)| {
+            prices
+                .get(time)
+                .map(|price| (*time, *price, f64::from(*intensity)))
+        })
+        .collect();
+    points.sort_by_key(|(time, _, _)| *time);
+
+    let half_hour = Duration::minutes(30);
+    let mut candidates: Vec<WindowOption> = contiguous_windows(&points, slots, |point| point.0)
+        .map(|(_, window)| WindowOption {
+            start: window[0].0,
+            end: window[slots - 1].0 + half_hour,
+            mean_price: window.iter().map(|p| p.1).sum::<f64>() / slots as f64,
+            mean_intensity: window.iter().map(|p| p.2).sum::<f64>() / slots as f64,
+            score: 0.0,
+        })
+        .collect();
+
+    // normalise both dimensions before weighting them
+    let range = |values: Vec<f64>| {
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        (min, (max - min).max(f64::EPSILON))
+    };
+    let (min_price, price_range) = range(candidates.iter().map(|c| c.mean_price).collect());
+    let (min_intensity, intensity_range) =
+        range(candidates.iter().map(|c| c.mean_intensity).collect());
+    for candidate in &mut candidates {
+        let price = (candidate.mean_price - min_price) / price_range;
+        let intensity = (candidate.mean_intensity - min_intensity) / intensity_range;
+        candidate.score = (1.0 - carbon_weight) * price + carbon_weight * intensity;
+    }
+
+    let mut front: Vec<WindowOption> = candidates
+        .iter()
+        .filter(|candidate| {
+            !candidates.iter().any(|other| {
+                other.mean_price <= candidate.mean_price
+                    && other.mean_intensity <= candidate.mean_intensity
+                    && (other.mean_price < candidate.mean_price
+                        || other.mean_intensity < candidate.mean_intensity)
+            })
+        })
+        .cloned()
+        .collect();
+    front.sort_by(|a, b| a.score.total_cmp(&b.score).then(a.start.cmp(&b.start)));
+    front
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn half_hour(n: i64) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            + Duration::minutes(30 * n)
+    }
+
+    #[test]
+    fn parse_prices_test() {
+        let csv = "\
+timestamp,price
+2024-01-01T00:00Z,15.5
+\"2024-01-01T00:30:00Z\",\"2024-01-01T01:00:00Z\",A,\"Eastern England\",12.25
+
+2024-01-01 01:00,10
+";
+        let prices = parse_prices(csv).unwrap();
+        assert_eq!(
+            prices,
+            vec![
+                (half_hour(0), 15.5),
+                (half_hour(1), 12.25),
+                (half_hour(2), 10.0)
+            ]
+        );
+
+        assert!(parse_prices("2024-01-01T00:00Z,15.5\nnot a date,12").is_err());
+        assert!(parse_prices("2024-01-01T00:00Z,15.5\n2024-01-01T00:30Z,cheap").is_err());
+    }
+
+    #[test]
+    fn optimise() {
+        // cheap but dirty at the start, clean but expensive at the end
+        let intensities: Vec<_> = [300, 300, 200, 200, 100, 100]
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (half_hour(i as i64), *v))
+            .collect();
+        let prices: Vec<_> = [5.0, 5.0, 10.0, 10.0, 30.0, 30.0]
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (half_hour(i as i64), *v))
+            .collect();
+
+        // only carbon matters
+        let windows = optimise_windows(&intensities, &prices, 2, 1.0);
+        assert_eq!(windows[0].start, half_hour(4));
+        assert_eq!(windows[0].end, half_hour(6));
+        assert_eq!(windows[0].mean_intensity, 100.0);
+
+        // only cost matters
+        let windows = optimise_windows(&intensities, &prices, 2, 0.0);
+        assert_eq!(windows[0].start, half_hour(0));
+        assert_eq!(windows[0].mean_price, 5.0);
+
+        // every window is a trade-off between cost and carbon
+        assert_eq!(windows.len(), 5);
+
+        assert!(optimise_windows(&intensities, &prices, 0, 0.5).is_empty());
+        assert!(optimise_windows(&intensities, &prices, 7, 0.5).is_empty());
+    }
+
+    #[test]
+    fn optimise_skips_gaps() {
+        let intensities = vec![(half_hour(0), 100), (half_hour(2), 100)];
+        let prices = vec![(half_hour(0), 10.0), (half_hour(2), 10.0)];
+        assert!(optimise_windows(&intensities, &prices, 2, 0.5).is_empty());
+
+        // missing price
+        let intensities = vec![(half_hour(0), 100), (half_hour(1), 100)];
+        let prices = vec![(half_hour(0), 10.0)];
+        assert!(optimise_windows(&intensities, &prices, 2, 0.5).is_empty());
+    }
+}
@@ -0,0 +1,425 @@
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+
+use crate::{ApiError, Result};
+
+/// Length of the windows the ranges are split into by default, kept below
+/// the 14 days accepted by the API
+const WINDOW: Duration = Duration::days(13);
+
+/// Longest window accepted by the API for a single query
+pub(crate) const MAX_WINDOW: Duration = Duration::days(14);
+
+/// How far ahead the API forecasts
+pub(crate) const FORECAST_HORIZON: Duration = Duration::hours(48);
+
+/// Delay after which the data of a half-hour no longer changes, the actual
+/// intensities being published after the forecasts
+const SETTLED_AFTER: Duration = Duration::days(1);
+
+/// Format of the dates in the URLs
+const QUERY_FORMAT: &str = "%Y-%m-%dT%H:%MZ";
+
+/// How the ranges are split into windows, each retrieved with a request
+///
+/// By default, the windows last 13 days and never span two years, as the API
+/// stops at the end of the year of the start date. Shorter windows send more,
+/// smaller requests, e.g. to get the first results of a long range sooner.
+///
+/// ```
+/// # use chrono::Duration;
+/// # use carbonintensity::{CarbonIntensityClient, Chunking};
+/// let chunking = Chunking::new().with_window(Duration::days(7));
+/// let client = CarbonIntensityClient::builder()
+///     .with_chunking(chunking)
+///     .build()
+///     .unwrap();
+/// assert_eq!(client.chunking().window(), Duration::days(7));
+/// assert!(client.chunking().year_split());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chunking {
+    window: Duration,
+    year_split: bool,
+}
+
+impl Default for Chunking {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Chunking {
+    /// Windows of 13 days split at the start of each year
+    pub fn new() -> Self {
+        Self {
+            window: WINDOW,
+            year_split: true,
+        }
+    }
+
+    /// Longest duration of a window, from 30 minutes to the 14 days accepted by the API
+    ///
+    /// Checked by `ClientBuilder::build()`.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Splits the windows at the start of each year, enabled by default
+    ///
+    /// Only to be disabled for a server which, unlike the API, returns the
+    /// data of a window spanning two years e.g. a mirror of the API.
+    pub fn with_year_split(mut self, enabled: bool) -> Self {
+        self.year_split = enabled;
+        self
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    pub fn year_split(&self) -> bool {
+        self.year_split
+    }
+
+    /// Returns an `ApiError::Error` if the window is too short or too long for the API
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.window < Duration::minutes(30) || self.window > MAX_WINDOW {
+            return Err(ApiError::Error(format!(
+                "Windows must last between 30 minutes and {} days",
+                MAX_WINDOW.num_days()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Splits a range of dates into windows which can be queried from the API
+///
+/// With the default `Chunking`, the windows produced
+/// - are contiguous: each one starts where the previous one ended, without gaps or overlaps
+/// - last at most 14 days
+/// - never span over two years, as the API stops at the end of the year of the start date
+///   (see <https://github.com/jnioche/carbonintensity-api/issues/6>)
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct RangePlanner;
+
+impl RangePlanner {
+    /// Splits the range between `start` and `end` into windows
+    ///
+    /// If `end` is not after `start`, a single window `(start, end)` is returned.
+    pub(crate) fn plan(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+        chunking: &Chunking,
+    ) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+        let mut windows = Vec::new();
+
+        let mut current = start;
+        loop {
+            let mut next_end = current + chunking.window;
+            if chunking.year_split {
+                next_end = next_end.min(start_of_next_year(current));
+            }
+            if next_end >= end {
+                windows.push((current, end));
+                break;
+            }
+            windows.push((current, next_end));
+            current = next_end;
+        }
+        windows
+    }
+
+    /// Splits the range between `start` and `end` into windows, the part of the range
+    /// after the current half-hour being a single forecast window
+    ///
+    /// The forecast window is the last one and starts after the current half-hour,
+    /// see `is_forecast`. The range is expected to end within `FORECAST_HORIZON`.
+    pub(crate) fn plan_with_horizon(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+        now: NaiveDateTime,
+        chunking: &Chunking,
+    ) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+        let current = start_of_half_hour(now);
+        if start > current {
+            return vec![(start, end)];
+        }
+        let next = current + Duration::minutes(30);
+        if end <= next {
+            return self.plan(start, end, chunking);
+        }
+        let mut windows = self.plan(start, current, chunking);
+        windows.push((next, end));
+        windows
+    }
+
+    /// Returns `true` if the window starts after the current half-hour
+    /// and should be retrieved with `forecast_bounds`
+    pub(crate) fn is_forecast(
+        &self,
+        window: &(NaiveDateTime, NaiveDateTime),
+        now: NaiveDateTime,
+    ) -> bool {
+        window.0 > start_of_half_hour(now)
+    }
+
+    /// Returns `true` if the data of the whole window is final, so that the
+    /// response can be stored
+    pub(crate) fn is_settled(
+        &self,
+        window: &(NaiveDateTime, NaiveDateTime),
+        now: NaiveDateTime,
+    ) -> bool {
+        window.1 + SETTLED_AFTER <= now
+    }
+
+    /// Formats the bounds of a forecast window for a query
+    ///
+    /// The end is given as a forward period of 24 or 48 hours (`fw24h`, `fw48h`) from
+    /// the start, the half-hours returned after the end of the window must be discarded.
+    pub(crate) fn forecast_bounds(
+        &self,
+        window: &(NaiveDateTime, NaiveDateTime),
+    ) -> (String, String) {
+        let (start, end) = window;
+        let forward = if *end - *start <= Duration::hours(24) {
+            "fw24h"
+        } else {
+            "fw48h"
+        };
+        let start = (*start + Duration::minutes(1)).format(QUERY_FORMAT);
+        (start.to_string(), forward.to_string())
+    }
+
+    /// Formats the bounds of a window for a query
+    ///
+    /// The API returns the half-hour *ending* at the start date, dates are
+    /// shifted by one minute so that the half-hours returned are the ones
+    /// starting within the window.
+    pub(crate) fn query_bounds(&self, window: &(NaiveDateTime, NaiveDateTime)) -> (String, String) {
+        let (start, end) = window;
+        let start = (*start + Duration::minutes(1)).format(QUERY_FORMAT);
+        let end = (*end + Duration::minutes(1)).format(QUERY_FORMAT);
+        (start.to_string(), end.to_string())
+    }
+}
+
+/// Window of a range, retrieved with a single request to the API
+///
+/// See `plan_windows`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlannedWindow {
+    /// Start of the first half-hour of the window
+    pub start: NaiveDateTime,
+    /// Start of the last half-hour of the window
+    pub end: NaiveDateTime,
+    /// The window starts after the current half-hour and is retrieved
+    /// as a forward period of forecasts
+    pub forecast: bool,
+}
+
+impl PlannedWindow {
+    /// Bounds to use in the URL of the request e.g. `/intensity/{start}/{end}`
+    ///
+    /// The start is shifted by a minute, as the API returns the half-hour ending
+    /// at the start date. For the forecast windows, the end is a forward period
+    /// (`fw24h` or `fw48h`) and the half-hours returned after the end of the
+    /// window must be discarded.
+    pub fn url_bounds(&self) -> (String, String) {
+        let window = (self.start, self.end);
+        if self.forecast {
+            RangePlanner.forecast_bounds(&window)
+        } else {
+            RangePlanner.query_bounds(&window)
+        }
+    }
+}
+
+/// Start of the half-hour containing the date
+pub(crate) fn start_of_half_hour(date: NaiveDateTime) -> NaiveDateTime {
+    let minute = date.minute() - date.minute() % 30;
+    date.date().and_hms_opt(date.hour(), minute, 0).unwrap()
+}
+
+fn start_of_next_year(date: NaiveDateTime) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(date.year() + 1, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveTime;
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn date_time(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDateTime::new(date.parse().unwrap(), time.parse::<NaiveTime>().unwrap())
+    }
+
+    #[test]
+    fn plan_single_window() {
+        let start = date_time("2024-03-01", "10:00:00");
+        let end = date_time("2024-03-02", "10:00:00");
+        assert_eq!(
+            RangePlanner.plan(start, end, &Chunking::default()),
+            vec![(start, end)]
+        );
+
+        // empty or inverted ranges are passed through
+        assert_eq!(
+            RangePlanner.plan(end, end, &Chunking::default()),
+            vec![(end, end)]
+        );
+        assert_eq!(
+            RangePlanner.plan(end, start, &Chunking::default()),
+            vec![(end, start)]
+        );
+    }
+
+    #[test]
+    fn plan_with_chunking() {
+        let start = date_time("2023-12-20", "00:00:00");
+        let end = date_time("2024-01-10", "00:00:00");
+        let weekly = Chunking::new().with_window(Duration::days(7));
+        let expected = vec![
+            (start, date_time("2023-12-27", "00:00:00")),
+            (
+                date_time("2023-12-27", "00:00:00"),
+                date_time("2024-01-01", "00:00:00"),
+            ),
+            (
+                date_time("2024-01-01", "00:00:00"),
+                date_time("2024-01-08", "00:00:00"),
+            ),
+            (date_time("2024-01-08", "00:00:00"), end),
+        ];
+        assert_eq!(RangePlanner.plan(start, end, &weekly), expected);
+
+        let unsplit = weekly.with_year_split(false);
+        let windows = RangePlanner.plan(start, end, &unsplit);
+        assert_eq!(windows.len(), 3);
+        assert_eq!(
+            windows[1],
+            (
+                date_time("2023-12-27", "00:00:00"),
+                date_time("2024-01-03", "00:00:00")
+            )
+        );
+
+        assert!(Chunking::default().validate().is_ok());
+        assert!(weekly.with_window(MAX_WINDOW).validate().is_ok());
+        assert!(weekly.with_window(Duration::days(15)).validate().is_err());
+        assert!(weekly
+            .with_window(Duration::minutes(10))
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn plan_around_dst_change() {
+        // clocks went forward on 2024-03-31, naive dates are unaffected
+        let start = date_time("2024-03-25", "00:00:00");
+        let end = date_time("2024-04-10", "00:00:00");
+        let expected = vec![
+            (start, date_time("2024-04-07", "00:00:00")),
+            (date_time("2024-04-07", "00:00:00"), end),
+        ];
+        assert_eq!(
+            RangePlanner.plan(start, end, &Chunking::default()),
+            expected
+        );
+    }
+
+    #[test]
+    fn query_bounds_shifted() {
+        let window = (
+            date_time("2023-12-31", "23:30:00"),
+            date_time("2024-01-01", "00:00:00"),
+        );
+        let (start, end) = RangePlanner.query_bounds(&window);
+        assert_eq!(start, "2023-12-31T23:31Z");
+        assert_eq!(end, "2024-01-01T00:01Z");
+    }
+
+    #[test]
+    fn plan_with_horizon() {
+        let now = date_time("2024-06-15", "12:34");
+        let current = date_time("2024-06-15", "12:30");
+        let next = date_time("2024-06-15", "13:00");
+
+        // all in the past
+        let start = date_time("2024-06-14", "00:00");
+        let end = date_time("2024-06-15", "12:00");
+        let windows = RangePlanner.plan_with_horizon(start, end, now, &Chunking::default());
+        assert_eq!(windows, vec![(start, end)]);
+        assert!(!RangePlanner.is_forecast(&windows[0], now));
+        // the actual intensities may still change
+        assert!(!RangePlanner.is_settled(&windows[0], now));
+        let day_before = (start, date_time("2024-06-14", "12:00"));
+        assert!(RangePlanner.is_settled(&day_before, now));
+
+        // ends within the current half-hour
+        let end = date_time("2024-06-15", "12:45");
+        let windows = RangePlanner.plan_with_horizon(start, end, now, &Chunking::default());
+        assert_eq!(windows, vec![(start, end)]);
+
+        // past then forecast
+        let end = date_time("2024-06-16", "12:00");
+        let windows = RangePlanner.plan_with_horizon(start, end, now, &Chunking::default());
+        assert_eq!(windows, vec![(start, current), (next, end)]);
+        assert!(!RangePlanner.is_forecast(&windows[0], now));
+        assert!(RangePlanner.is_forecast(&windows[1], now));
+        assert_eq!(
+            RangePlanner.forecast_bounds(&windows[1]),
+            ("2024-06-15T13:01Z".to_string(), "fw24h".to_string())
+        );
+
+        // only forecast
+        let start = date_time("2024-06-15", "18:00");
+        let end = date_time("2024-06-17", "12:00");
+        let windows = RangePlanner.plan_with_horizon(start, end, now, &Chunking::default());
+        assert_eq!(windows, vec![(start, end)]);
+        assert!(RangePlanner.is_forecast(&windows[0], now));
+        assert_eq!(RangePlanner.forecast_bounds(&windows[0]).1, "fw48h");
+    }
+
+    fn any_date_time() -> impl Strategy<Value = NaiveDateTime> {
+        // from 2018-01-01 to 2030-12-31 with a minute precision
+        (0i64..13 * 366 * 24 * 60)
+            .prop_map(|minutes| date_time("2018-01-01", "00:00:00") + Duration::minutes(minutes))
+    }
+
+    proptest! {
+        #[test]
+        fn plan_invariants(a in any_date_time(), b in any_date_time()) {
+            let (start, end) = if a < b { (a, b) } else { (b, a) };
+            prop_assume!(start < end);
+
+            let windows = RangePlanner.plan(start, end, &Chunking::default());
+
+            // covers the whole range
+            prop_assert_eq!(windows.first().unwrap().0, start);
+            prop_assert_eq!(windows.last().unwrap().1, end);
+
+            for (window_start, window_end) in &windows {
+                // not empty and not too long
+                prop_assert!(window_start < window_end);
+                prop_assert!(*window_end - *window_start <= MAX_WINDOW);
+                // within a single year
+                prop_assert!(*window_end <= start_of_next_year(*window_start));
+            }
+
+            // no gaps, no overlaps
+            for pair in windows.windows(2) {
+                prop_assert_eq!(pair[0].1, pair[1].0);
+            }
+        }
+    }
+}
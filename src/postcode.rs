@@ -0,0 +1,140 @@
+use crate::{ApiError, Result};
+
+/// Letters allowed in each position of the outward code, the area being one or
+/// two letters followed by the district
+const FIRST: &str = "ABCDEFGHIJKLMNOPRSTUWYZ";
+const SECOND: &str = "ABCDEFGHKLMNOPQRSTUVWXY";
+/// Letter ending the district of the areas with one letter e.g. W1A
+const THIRD: &str = "ABCDEFGHJKPSTUW";
+/// Letter ending the district of the areas with two letters e.g. SW1A
+const FOURTH: &str = "ABEHMNPRVWXY";
+/// Letters of the unit, at the end of the inward code
+const UNIT: &str = "ABDEFGHJLNPQRSTUWXYZ";
+
+/// Outward code of a UK postcode, as expected by the API
+///
+/// Accepts an outward code (e.g. "BS7") or a full postcode (e.g. "BS7 8AB"),
+/// with or without the space, in any case. The outward code is validated
+/// against the formats of the UK postcodes and returned in uppercase.
+///
+/// ```
+/// # use carbonintensity::outward_code;
+/// assert_eq!(outward_code("bs7").unwrap(), "BS7");
+/// assert_eq!(outward_code("BS7 8AB").unwrap(), "BS7");
+/// assert_eq!(outward_code("sw1a1aa").unwrap(), "SW1A");
+/// assert!(outward_code("BS7 8").is_err());
+/// ```
+pub fn outward_code(postcode: &str) -> Result<String> {
+    let invalid = || ApiError::Error(format!("Invalid postcode \"{postcode}\""));
+
+    let parts: Vec<&str> = postcode.split_whitespace().collect();
+    let compact = parts.concat().to_ascii_uppercase();
+    if !compact.is_ascii() {
+        return Err(invalid());
+    }
+    // the inward code is always a digit and two letters
+    let outward = match compact.len() {
+        2..=4 => &compact[..],
+        5..=7 => {
+            let (outward, inward) = compact.split_at(compact.len() - 3);
+            if !is_inward(inward) {
+                return Err(invalid());
+            }
+            outward
+        }
+        _ => return Err(invalid()),
+    };
+    // a space is only allowed between the outward and inward codes
+    match parts[..] {
+        [_] => {}
+        [first, _] if first.eq_ignore_ascii_case(outward) => {}
+        _ => return Err(invalid()),
+    }
+
+    if is_outward(outward) {
+        Ok(outward.to_string())
+    } else {
+        Err(invalid())
+    }
+}
+
+/// Whether a string in uppercase is in one of the formats of the outward codes:
+/// A9, A99, AA9, AA99, A9A or AA9A
+fn is_outward(outward: &str) -> bool {
+    let letter = |c: char, allowed: &str| allowed.contains(c);
+    let digit = |c: char| c.is_ascii_digit();
+    let chars: Vec<char> = outward.chars().collect();
+    match chars[..] {
+        [a, b] => letter(a, FIRST) && digit(b),
+        [a, b, c] if digit(b) => letter(a, FIRST) && (digit(c) || letter(c, THIRD)),
+        [a, b, c] => letter(a, FIRST) && letter(b, SECOND) && digit(c),
+        [a, b, c, d] => {
+            letter(a, FIRST) && letter(b, SECOND) && digit(c) && (digit(d) || letter(d, FOURTH))
+        }
+        _ => false,
+    }
+}
+
+/// Whether a string in uppercase is an inward code e.g. 8AB
+fn is_inward(inward: &str) -> bool {
+    let chars: Vec<char> = inward.chars().collect();
+    match chars[..] {
+        [sector, a, b] => sector.is_ascii_digit() && UNIT.contains(a) && UNIT.contains(b),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outward_codes() {
+        for (postcode, expected) in [
+            ("BS7", "BS7"),
+            ("bs7", "BS7"),
+            (" RG10 ", "RG10"),
+            ("M1", "M1"),
+            ("B33", "B33"),
+            ("W1A", "W1A"),
+            ("SW1A", "SW1A"),
+            ("BS7 8AB", "BS7"),
+            ("bs78ab", "BS7"),
+            ("SW1A 1AA", "SW1A"),
+            ("M1 1AE", "M1"),
+            ("EC1A  1BB", "EC1A"),
+        ] {
+            assert_eq!(outward_code(postcode).unwrap(), expected, "{postcode}");
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        for postcode in [
+            "",
+            "B",
+            "BS",
+            "7BS",
+            "BS7A8",
+            "BSS7",
+            "QS7",
+            "BI7",
+            "W1I",
+            "SW1C",
+            "BS7 8",
+            "BS7 8CB",
+            "BS7 AAB",
+            "BS7 8AB X",
+            "B S7",
+            "BS7 8 AB",
+            "ÉS7",
+            "BS7-8AB",
+        ] {
+            assert!(outward_code(postcode).is_err(), "{postcode}");
+        }
+        assert_eq!(
+            outward_code("BS7 8").unwrap_err().to_string(),
+            "Error: Invalid postcode \"BS7 8\""
+        );
+    }
+}
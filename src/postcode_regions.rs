@@ -0,0 +1,199 @@
+use crate::{outward_code, Region, Result, Target};
+
+/// Postcode areas lying within the network of a single operator
+///
+/// The areas straddling the boundary of two regions (e.g. GL, KT or SY) and
+/// those outside Great Britain are left out.
+const AREAS: [(&str, Region); 90] = [
+    ("AB", Region::NorthScotland),
+    ("DD", Region::NorthScotland),
+    ("HS", Region::NorthScotland),
+    ("IV", Region::NorthScotland),
+    ("KW", Region::NorthScotland),
+    ("PH", Region::NorthScotland),
+    ("ZE", Region::NorthScotland),
+    ("DG", Region::SouthScotland),
+    ("EH", Region::SouthScotland),
+    ("G", Region::SouthScotland),
+    ("KA", Region::SouthScotland),
+    ("KY", Region::SouthScotland),
+    ("ML", Region::SouthScotland),
+    ("TD", Region::SouthScotland),
+    ("BB", Region::NorthWestEngland),
+    ("BL", Region::NorthWestEngland),
+    ("CA", Region::NorthWestEngland),
+    ("FY", Region::NorthWestEngland),
+    ("LA", Region::NorthWestEngland),
+    ("M", Region::NorthWestEngland),
+    ("OL", Region::NorthWestEngland),
+    ("PR", Region::NorthWestEngland),
+    ("WN", Region::NorthWestEngland),
+    ("DH", Region::NorthEastEngland),
+    ("DL", Region::NorthEastEngland),
+    ("NE", Region::NorthEastEngland),
+    ("SR", Region::NorthEastEngland),
+    ("TS", Region::NorthEastEngland),
+    ("BD", Region::SouthYorkshire),
+    ("HD", Region::SouthYorkshire),
+    ("HG", Region::SouthYorkshire),
+    ("HU", Region::SouthYorkshire),
+    ("HX", Region::SouthYorkshire),
+    ("LS", Region::SouthYorkshire),
+    ("WF", Region::SouthYorkshire),
+    ("YO", Region::SouthYorkshire),
+    ("CH", Region::NorthWalesMerseysideAndCheshire),
+    ("L", Region::NorthWalesMerseysideAndCheshire),
+    ("LL", Region::NorthWalesMerseysideAndCheshire),
+    ("CF", Region::SouthWales),
+    ("NP", Region::SouthWales),
+    ("SA", Region::SouthWales),
+    ("B", Region::WestMidlands),
+    ("CV", Region::WestMidlands),
+    ("DY", Region::WestMidlands),
+    ("HR", Region::WestMidlands),
+    ("TF", Region::WestMidlands),
+    ("WR", Region::WestMidlands),
+    ("WS", Region::WestMidlands),
+    ("WV", Region::WestMidlands),
+    ("DE", Region::EastMidlands),
+    ("LE", Region::EastMidlands),
+    ("LN", Region::EastMidlands),
+    ("NG", Region::EastMidlands),
+    ("NN", Region::EastMidlands),
+    ("AL", Region::EastEngland),
+    ("CB", Region::EastEngland),
+    ("CM", Region::EastEngland),
+    ("CO", Region::EastEngland),
+    ("IP", Region::EastEngland),
+    ("LU", Region::EastEngland),
+    ("NR", Region::EastEngland),
+    ("SG", Region::EastEngland),
+    ("SS", Region::EastEngland),
+    ("BA", Region::SouthWestEngland),
+    ("BS", Region::SouthWestEngland),
+    ("EX", Region::SouthWestEngland),
+    ("PL", Region::SouthWestEngland),
+    ("TA", Region::SouthWestEngland),
+    ("TQ", Region::SouthWestEngland),
+    ("TR", Region::SouthWestEngland),
+    ("BH", Region::SouthEngland),
+    ("OX", Region::SouthEngland),
+    ("PO", Region::SouthEngland),
+    ("RG", Region::SouthEngland),
+    ("SL", Region::SouthEngland),
+    ("SO", Region::SouthEngland),
+    ("E", Region::London),
+    ("EC", Region::London),
+    ("N", Region::London),
+    ("NW", Region::London),
+    ("SE", Region::London),
+    ("SW", Region::London),
+    ("W", Region::London),
+    ("WC", Region::London),
+    ("BN", Region::SouthEastEngland),
+    ("CT", Region::SouthEastEngland),
+    ("ME", Region::SouthEastEngland),
+    ("RH", Region::SouthEastEngland),
+    ("TN", Region::SouthEastEngland),
+];
+
+/// Districts in a different region from the rest of their area
+const DISTRICTS: [(&str, Region); 1] = [
+    // Berwick-upon-Tweed
+    ("TD15", Region::NorthEastEngland),
+];
+
+impl Region {
+    /// Region of a postcode from the mapping bundled with the crate, without
+    /// querying the API
+    ///
+    /// The postcode is validated as for `outward_code()`. Returns `None` for
+    /// the postcodes of the areas straddling the boundary of two regions, which
+    /// can only be resolved by the API (see `get_regional_intensity()`). The
+    /// mapping is by postcode area and may differ from the API near the boundaries.
+    ///
+    /// ```
+    /// # use carbonintensity::Region;
+    /// assert_eq!(Region::from_postcode("SW1A 1AA").unwrap(), Some(Region::London));
+    /// assert_eq!(Region::from_postcode("bs7").unwrap(), Some(Region::SouthWestEngland));
+    /// assert_eq!(Region::from_postcode("GL1").unwrap(), None);
+    /// ```
+    pub fn from_postcode(postcode: &str) -> Result<Option<Region>> {
+        let outward = outward_code(postcode)?;
+        // the letters before the district number
+        let area = &outward[..outward.find(|c: char| c.is_ascii_digit()).unwrap_or(0)];
+        let region = DISTRICTS
+            .iter()
+            .find(|(district, _)| *district == outward)
+            .or_else(|| AREAS.iter().find(|(a, _)| *a == area))
+            .map(|(_, region)| *region);
+        Ok(region)
+    }
+}
+
+impl Target {
+    /// Replaces a postcode with its region when known from the bundled mapping,
+    /// see `Region::from_postcode()`, saving the API the lookup
+    ///
+    /// Other targets and the postcodes which can't be resolved offline are
+    /// returned as they are. Returns an `ApiError` if the postcode is invalid.
+    pub fn resolve_offline(&self) -> Result<Target> {
+        match self {
+            Target::Postcode(postcode) => {
+                Ok(Region::from_postcode(postcode)?.map_or_else(|| self.clone(), Target::Region))
+            }
+            _ => Ok(self.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions() {
+        for (postcode, region) in [
+            ("AB10", Some(Region::NorthScotland)),
+            ("G1 1AA", Some(Region::SouthScotland)),
+            ("M1", Some(Region::NorthWestEngland)),
+            ("W1A 1AA", Some(Region::London)),
+            ("EC1A", Some(Region::London)),
+            ("e14", Some(Region::London)),
+            ("TD1", Some(Region::SouthScotland)),
+            ("TD15 1AA", Some(Region::NorthEastEngland)),
+            ("CF10", Some(Region::SouthWales)),
+            ("GL1", None),
+            ("KT1", None),
+            ("BT1", None),
+        ] {
+            assert_eq!(
+                Region::from_postcode(postcode).unwrap(),
+                region,
+                "{postcode}"
+            );
+        }
+        assert!(Region::from_postcode("B").is_err());
+
+        // every area starts an outward code
+        for (area, _) in AREAS {
+            assert!(outward_code(&format!("{area}1")).is_ok(), "{area}");
+        }
+    }
+
+    #[test]
+    fn resolve() {
+        let bs7 = Target::Postcode("BS7 8AB".to_string());
+        assert_eq!(
+            bs7.resolve_offline().unwrap(),
+            Target::Region(Region::SouthWestEngland)
+        );
+        let gl1 = Target::Postcode("GL1".to_string());
+        assert_eq!(gl1.resolve_offline().unwrap(), gl1);
+        assert_eq!(
+            Target::National.resolve_offline().unwrap(),
+            Target::National
+        );
+        assert!(Target::Postcode("B".to_string()).resolve_offline().is_err());
+    }
+}
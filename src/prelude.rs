@@ -0,0 +1,25 @@
+//! Recommended imports for most uses of the crate
+//!
+//! The items re-exported here are the stable surface of the API: they only
+//! change with a major version, whereas the rest of the crate may grow or be
+//! reorganised. Items behind features (analysis, export...) are not included.
+//! `ApiError`, `NullPolicy` and `Warning` are non-exhaustive, new variants being
+//! added in minor versions.
+//!
+//! ```
+//! use carbonintensity::prelude::*;
+//!
+//! let target = Target::from("13".to_string());
+//! assert_eq!(target, Target::Region(Region::London));
+//! let options = QueryOptions::new().with_null_policy(NullPolicy::Interpolate);
+//! assert_eq!(options.null_policy(), NullPolicy::Interpolate);
+//! ```
+
+pub use crate::{
+    get_current_intensity, get_detailed_intensities, get_intensities_between,
+    get_intensities_with_options, ApiError, CarbonIntensityClient, ClientBuilder, Data, DateBound,
+    Intensity, IntensityForDate, NullPolicy, QueryOptions, QueryOutcome, Region, Result,
+    RetryPolicy, Target, Warning,
+};
+#[allow(deprecated)]
+pub use crate::{get_intensities, get_intensity};
@@ -0,0 +1,393 @@
+use std::{fmt::Display, sync::Arc, time::Duration};
+
+use chrono::NaiveDateTime;
+
+use crate::{Clock, IntensityForDate, SystemClock};
+
+/// Options controlling how a range query is executed
+///
+/// ```
+/// # use std::time::Duration;
+/// # use carbonintensity::QueryOptions;
+/// let options = QueryOptions::new().with_deadline(Duration::from_secs(10));
+/// assert_eq!(options.deadline(), Some(Duration::from_secs(10)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct QueryOptions {
+    deadline: Option<Duration>,
+    clock: Arc<dyn Clock>,
+    null_policy: NullPolicy,
+    execution_report: bool,
+    provenance: bool,
+    mix_policy: MixPolicy,
+    base_url: Option<Arc<str>>,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        Self {
+            deadline: None,
+            clock: Arc::new(SystemClock),
+            null_policy: NullPolicy::default(),
+            execution_report: false,
+            provenance: false,
+            mix_policy: MixPolicy::default(),
+            base_url: None,
+        }
+    }
+}
+
+impl QueryOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Clock used to determine the current date, e.g. to clamp dates in the future
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    /// Overall time allowed for the query
+    ///
+    /// Windows which haven't been fetched when the deadline expires are
+    /// cancelled and reported as warnings in the `QueryOutcome`.
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    /// What to do with the half-hours for which the API sent no forecast
+    pub fn with_null_policy(mut self, null_policy: NullPolicy) -> Self {
+        self.null_policy = null_policy;
+        self
+    }
+
+    pub fn null_policy(&self) -> NullPolicy {
+        self.null_policy
+    }
+
+    /// Returns an `ExecutionReport` in the `QueryOutcome`
+    pub fn with_execution_report(mut self, enabled: bool) -> Self {
+        self.execution_report = enabled;
+        self
+    }
+
+    pub fn execution_report(&self) -> bool {
+        self.execution_report
+    }
+
+    /// Records the origin of each `Data` returned, see `Data::provenance()`
+    pub fn with_provenance(mut self, enabled: bool) -> Self {
+        self.provenance = enabled;
+        self
+    }
+
+    pub fn provenance(&self) -> bool {
+        self.provenance
+    }
+
+    /// What to do with the generation mixes which don't add up to 100%
+    pub fn with_mix_policy(mut self, mix_policy: MixPolicy) -> Self {
+        self.mix_policy = mix_policy;
+        self
+    }
+
+    pub fn mix_policy(&self) -> MixPolicy {
+        self.mix_policy
+    }
+
+    /// Root of the API the ranges are requested from, without a trailing slash
+    ///
+    /// Overrides the base URL of the `CarbonIntensityClient` for this query, e.g.
+    /// to point to a mirror or to a server replaying recorded responses.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = Some(Arc::from(base_url.trim_end_matches('/')));
+        self
+    }
+
+    pub fn base_url(&self) -> Option<&str> {
+        self.base_url.as_deref()
+    }
+}
+
+/// How a range query was executed
+///
+/// The range is split into windows, each window being a single request to the API,
+/// which are requested concurrently up to the limit of the client. The results are
+/// always returned in chronological order, whatever the order in which the requests complete.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionReport {
+    /// One entry per window, in chronological order
+    pub windows: Vec<WindowReport>,
+    /// Time taken by the whole query
+    pub wall_time: Duration,
+    /// Limit of the requests sent at the same time by the client
+    pub max_concurrent_requests: usize,
+}
+
+impl ExecutionReport {
+    /// Number of requests sent at the same time, at most
+    pub fn concurrency(&self) -> usize {
+        self.windows.len().min(self.max_concurrent_requests)
+    }
+
+    /// Number of windows which were retrieved
+    pub fn fetched(&self) -> usize {
+        self.windows.iter().filter(|w| w.elapsed.is_some()).count()
+    }
+}
+
+impl Display for ExecutionReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} of {} windows fetched in {:.2?}, {} at a time",
+            self.fetched(),
+            self.windows.len(),
+            self.wall_time,
+            self.concurrency()
+        )?;
+        for window in &self.windows {
+            match window.elapsed {
+                Some(elapsed) => writeln!(
+                    f,
+                    "{} to {}: {} records in {elapsed:.2?}",
+                    window.start, window.end, window.records
+                )?,
+                None => writeln!(f, "{} to {}: cancelled", window.start, window.end)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Execution of the request for a window of a range query
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowReport {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    /// Number of records sent by the API, before any filtering
+    pub records: usize,
+    /// Time taken by the request, `None` if cancelled when the deadline expired
+    pub elapsed: Option<Duration>,
+}
+
+/// Handling of the half-hours for which the API sent a null forecast
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[non_exhaustive]
+pub enum NullPolicy {
+    /// Discards the half-hour unless it has an actual value, reported with a warning
+    #[default]
+    Skip,
+    /// Fails the query, unless the half-hour has an actual value
+    Error,
+    /// Interpolates the forecast from the nearest half-hours with one, unless the
+    /// half-hour has an actual value, reported with a warning
+    Interpolate,
+}
+
+/// Handling of the generation mixes whose percentages don't add up to 100%,
+/// give or take `MIX_TOTAL_TOLERANCE`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MixPolicy {
+    /// Keeps the mix as sent, reported with a warning
+    #[default]
+    Keep,
+    /// Scales the percentages so that they add up to 100%, reported with a warning
+    Renormalise,
+}
+
+/// Results of a range query, possibly partial
+#[derive(Debug)]
+pub struct QueryOutcome<T = IntensityForDate> {
+    /// Intensities retrieved, in chronological order
+    pub intensities: Vec<T>,
+    /// Problems which did not prevent returning results
+    pub warnings: Vec<Warning>,
+    /// How the query was executed, if requested with `QueryOptions::with_execution_report()`
+    pub report: Option<ExecutionReport>,
+}
+
+impl<T> Default for QueryOutcome<T> {
+    fn default() -> Self {
+        Self {
+            intensities: Vec::new(),
+            warnings: Vec::new(),
+            report: None,
+        }
+    }
+}
+
+impl<T> QueryOutcome<T> {
+    /// Returns `true` if some of the data could not be retrieved
+    pub fn is_partial(&self) -> bool {
+        self.warnings
+            .iter()
+            .any(|warning| matches!(warning, Warning::DeadlineExceeded { .. }))
+    }
+}
+
+/// Non fatal issue encountered while running a query
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// The window was not fetched before the deadline expired
+    DeadlineExceeded {
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    },
+    /// The range was moved back to the forecast horizon, there is no data
+    /// yet after it, see `DateRange::within_horizon()`
+    BeyondHorizon {
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    },
+    /// The value sent for the half-hour is physically implausible,
+    /// the half-hour was discarded
+    ImplausibleIntensity {
+        /// Start of the half-hour as sent by the API
+        from: String,
+        value: i32,
+    },
+    /// The API sent no forecast for the half-hour, see `NullPolicy`
+    NullIntensity {
+        /// Start of the half-hour as sent by the API
+        from: String,
+        /// The forecast was interpolated, otherwise the half-hour was discarded
+        interpolated: bool,
+    },
+    /// The percentages of the generation mix don't add up to 100%, see `MixPolicy`
+    InconsistentMix {
+        /// Start of the half-hour as sent by the API
+        from: String,
+        /// Sum of the percentages as sent
+        total: f64,
+        renormalised: bool,
+    },
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::DeadlineExceeded { start, end } => {
+                write!(f, "Deadline exceeded, no data from {start} to {end}")
+            }
+            Warning::BeyondHorizon { start, end } => {
+                write!(
+                    f,
+                    "Beyond the forecast horizon, no data from {start} to {end}"
+                )
+            }
+            Warning::ImplausibleIntensity { from, value } => {
+                write!(
+                    f,
+                    "Implausible intensity of {value} gCO2/kWh at {from}, discarded"
+                )
+            }
+            Warning::NullIntensity { from, interpolated } => {
+                let action = if *interpolated {
+                    "interpolated"
+                } else {
+                    "discarded"
+                };
+                write!(f, "No intensity at {from}, {action}")
+            }
+            Warning::InconsistentMix {
+                from,
+                total,
+                renormalised,
+            } => {
+                write!(f, "Generation mix at {from} adds up to {total:.1}%")?;
+                if *renormalised {
+                    write!(f, ", renormalised")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn outcome_is_partial() {
+        let mut outcome: QueryOutcome = QueryOutcome::default();
+        assert!(!outcome.is_partial());
+
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        outcome.warnings.push(Warning::DeadlineExceeded {
+            start: date.and_hms_opt(0, 0, 0).unwrap(),
+            end: date.and_hms_opt(12, 0, 0).unwrap(),
+        });
+        assert!(outcome.is_partial());
+        assert_eq!(
+            outcome.warnings[0].to_string(),
+            "Deadline exceeded, no data from 2024-01-01 00:00:00 to 2024-01-01 12:00:00"
+        );
+    }
+
+    #[test]
+    fn execution_report() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let report = ExecutionReport {
+            windows: vec![
+                WindowReport {
+                    start: date.and_hms_opt(0, 0, 0).unwrap(),
+                    end: date.and_hms_opt(12, 0, 0).unwrap(),
+                    records: 24,
+                    elapsed: Some(Duration::from_millis(250)),
+                },
+                WindowReport {
+                    start: date.and_hms_opt(12, 0, 0).unwrap(),
+                    end: date.and_hms_opt(23, 30, 0).unwrap(),
+                    records: 0,
+                    elapsed: None,
+                },
+            ],
+            wall_time: Duration::from_secs(2),
+            max_concurrent_requests: 6,
+        };
+        assert_eq!(report.concurrency(), 2);
+        assert_eq!(report.fetched(), 1);
+        assert_eq!(
+            report.to_string(),
+            "1 of 2 windows fetched in 2.00s, 2 at a time\n\
+             2024-01-01 00:00:00 to 2024-01-01 12:00:00: 24 records in 250.00ms\n\
+             2024-01-01 12:00:00 to 2024-01-01 23:30:00: cancelled\n"
+        );
+
+        // capped by the limit of the client
+        let report = ExecutionReport {
+            max_concurrent_requests: 1,
+            ..report
+        };
+        assert_eq!(report.concurrency(), 1);
+    }
+
+    #[test]
+    fn implausible_not_partial() {
+        let mut outcome: QueryOutcome = QueryOutcome::default();
+        outcome.warnings.push(Warning::ImplausibleIntensity {
+            from: "2024-01-01T00:30Z".to_string(),
+            value: -5,
+        });
+        assert!(!outcome.is_partial());
+        assert_eq!(
+            outcome.warnings[0].to_string(),
+            "Implausible intensity of -5 gCO2/kWh at 2024-01-01T00:30Z, discarded"
+        );
+    }
+}
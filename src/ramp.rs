@@ -0,0 +1,144 @@
+use std::fmt::Display;
+
+use chrono::Duration;
+
+use crate::IntensityForDate;
+
+/// Changes of intensity from one half-hour to the next, in gCO2/kWh
+///
+/// Each change is dated with the half-hour it leads to. The intensities are
+/// expected in chronological order; no change is computed across a gap.
+///
+/// ```
+/// # use chrono::NaiveDate;
+/// # use carbonintensity::ramp_rates;
+/// let time = |m| NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, m, 0).unwrap();
+/// let ramps = ramp_rates(&[(time(0), 200), (time(30), 180)]);
+/// assert_eq!(ramps, vec![(time(30), -20)]);
+/// ```
+pub fn ramp_rates(intensities: &[IntensityForDate]) -> Vec<IntensityForDate> {
+    intensities
+        .windows(2)
+        .filter(|pair| pair[1].0 - pair[0].0 == Duration::minutes(30))
+        .map(|pair| (pair[1].0, pair[1].1 - pair[0].1))
+        .collect()
+}
+
+/// Statistics of the changes between consecutive half-hours
+///
+/// Indicates how volatile the intensity is, e.g. to decide how often a
+/// scheduler needs to re-evaluate its decisions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RampSummary {
+    /// Number of changes
+    pub count: usize,
+    /// Mean of the absolute changes, in gCO2/kWh per half-hour
+    pub mean_abs: f64,
+    /// Standard deviation of the changes
+    pub std_dev: f64,
+    /// 95th percentile of the absolute changes
+    pub p95_abs: i32,
+    /// Largest increase, with the half-hour it led to
+    pub max_rise: IntensityForDate,
+    /// Largest decrease, as a negative change
+    pub max_fall: IntensityForDate,
+}
+
+impl RampSummary {
+    /// Summarises changes as returned by `ramp_rates`
+    ///
+    /// Returns `None` if there are none.
+    pub fn new(ramps: &[IntensityForDate]) -> Option<Self> {
+        let max_rise = *ramps.iter().max_by_key(|(_, change)| *change)?;
+        let max_fall = *ramps.iter().min_by_key(|(_, change)| *change)?;
+
+        let count = ramps.len();
+        let changes: Vec<f64> = ramps.iter().map(|(_, c)| f64::from(*c)).collect();
+        let mean = changes.iter().sum::<f64>() / count as f64;
+        let variance = changes.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / count as f64;
+        let mean_abs = changes.iter().map(|c| c.abs()).sum::<f64>() / count as f64;
+
+        let mut absolute: Vec<i32> = ramps.iter().map(|(_, c)| c.abs()).collect();
+        absolute.sort_unstable();
+        // nearest rank
+        let rank = (count as f64 * 0.95).ceil() as usize;
+        let p95_abs = absolute[rank.clamp(1, count) - 1];
+
+        Some(Self {
+            count,
+            mean_abs,
+            std_dev: variance.sqrt(),
+            p95_abs,
+            max_rise,
+            max_fall,
+        })
+    }
+}
+
+impl Display for RampSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "changes: {}", self.count)?;
+        writeln!(f, "mean absolute change: {:.1}", self.mean_abs)?;
+        writeln!(f, "standard deviation: {:.1}", self.std_dev)?;
+        writeln!(f, "95th percentile of absolute changes: {}", self.p95_abs)?;
+        writeln!(
+            f,
+            "largest rise: {:+} at {}",
+            self.max_rise.1, self.max_rise.0
+        )?;
+        writeln!(
+            f,
+            "largest fall: {:+} at {}",
+            self.max_fall.1, self.max_fall.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    use super::*;
+
+    fn time(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn rates() {
+        let intensities = [
+            (time(0, 0), 200),
+            (time(0, 30), 180),
+            (time(1, 0), 190),
+            // gap
+            (time(2, 0), 100),
+            (time(2, 30), 100),
+        ];
+        assert_eq!(
+            ramp_rates(&intensities),
+            vec![(time(0, 30), -20), (time(1, 0), 10), (time(2, 30), 0)]
+        );
+        assert!(ramp_rates(&intensities[..1]).is_empty());
+    }
+
+    #[test]
+    fn summary() {
+        assert_eq!(RampSummary::new(&[]), None);
+
+        let ramps = [(time(0, 30), -20), (time(1, 0), 10), (time(1, 30), 40)];
+        let summary = RampSummary::new(&ramps).unwrap();
+        assert_eq!(summary.count, 3);
+        assert_eq!(summary.mean_abs, 70.0 / 3.0);
+        // mean of 10, deviations of -30, 0 and 30
+        assert_eq!(summary.std_dev, 600.0_f64.sqrt());
+        assert_eq!(summary.p95_abs, 40);
+        assert_eq!(summary.max_rise, (time(1, 30), 40));
+        assert_eq!(summary.max_fall, (time(0, 30), -20));
+        assert!(summary
+            .to_string()
+            .contains("largest fall: -20 at 2024-01-01 00:30:00\n"));
+    }
+}
@@ -0,0 +1,500 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{
+    DateTime, Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc,
+};
+
+use crate::{
+    parse_date,
+    planner::{RangePlanner, FORECAST_HORIZON},
+    validate_date, ApiError, Chunking, Clock, Result,
+};
+
+/// Format of the dates returned by `RangePreset::resolve`
+const QUERY_FORMAT: &str = "%Y-%m-%dT%H:%MZ";
+
+/// Common periods which can be queried without composing the dates
+///
+/// Parsed from `today`, `yesterday`, `this-week`, `last-month` or `ytd`.
+///
+/// ```
+/// # use chrono::{FixedOffset, TimeZone};
+/// # use carbonintensity::RangePreset;
+/// let now = FixedOffset::east_opt(3600).unwrap().with_ymd_and_hms(2024, 3, 15, 10, 20, 0).unwrap();
+/// let range: RangePreset = "yesterday".parse().unwrap();
+/// let (start, end) = range.resolve(now);
+/// assert_eq!(start, "2024-03-13T23:00Z");
+/// assert_eq!(end, "2024-03-14T23:00Z");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RangePreset {
+    /// From midnight to now
+    Today,
+    /// The whole previous day
+    Yesterday,
+    /// From Monday midnight to now
+    ThisWeek,
+    /// The whole previous calendar month
+    LastMonth,
+    /// From the 1st of January to now
+    YearToDate,
+}
+
+impl Display for RangePreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            RangePreset::Today => "today",
+            RangePreset::Yesterday => "yesterday",
+            RangePreset::ThisWeek => "this-week",
+            RangePreset::LastMonth => "last-month",
+            RangePreset::YearToDate => "ytd",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl FromStr for RangePreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "today" => Ok(Self::Today),
+            "yesterday" => Ok(Self::Yesterday),
+            "this-week" => Ok(Self::ThisWeek),
+            "last-month" => Ok(Self::LastMonth),
+            "ytd" => Ok(Self::YearToDate),
+            _ => Err(format!(
+                "Invalid range: {s}, expected today, yesterday, this-week, last-month or ytd"
+            )),
+        }
+    }
+}
+
+impl RangePreset {
+    /// Start and end of the period, as UTC dates accepted by the API
+    ///
+    /// The days start at midnight in the timezone of `now`, so that e.g.
+    /// `Yesterday` is the day as experienced locally, even over a change of
+    /// daylight saving time. The periods in progress end at `now`.
+    pub fn resolve<Tz: TimeZone>(self, now: DateTime<Tz>) -> (String, String) {
+        let timezone = now.timezone();
+        let today = now.date_naive();
+        let start_of = |date: NaiveDate| midnight(&timezone, date);
+        let now = now.with_timezone(&Utc);
+
+        let (start, end) = match self {
+            RangePreset::Today => (start_of(today), now),
+            RangePreset::Yesterday => (start_of(today - Duration::days(1)), start_of(today)),
+            RangePreset::ThisWeek => {
+                let monday = today - Duration::days(today.weekday().num_days_from_monday().into());
+                (start_of(monday), now)
+            }
+            RangePreset::LastMonth => {
+                let first = today.with_day(1).unwrap_or(today);
+                (start_of(first - Months::new(1)), start_of(first))
+            }
+            RangePreset::YearToDate => {
+                let first = today.with_ordinal(1).unwrap_or(today);
+                (start_of(first), now)
+            }
+        };
+        (
+            start.format(QUERY_FORMAT).to_string(),
+            end.format(QUERY_FORMAT).to_string(),
+        )
+    }
+}
+
+/// Start of a day in a timezone, in UTC
+///
+/// The earliest time is used if midnight happens twice, the first existing
+/// time after it if it is skipped by a change of daylight saving time.
+fn midnight<Tz: TimeZone>(timezone: &Tz, date: NaiveDate) -> DateTime<Utc> {
+    let mut time = date.and_time(NaiveTime::MIN);
+    for _ in 0..24 {
+        if let Some(start) = timezone.from_local_datetime(&time).earliest() {
+            return start.with_timezone(&Utc);
+        }
+        time += Duration::hours(1);
+    }
+    // no timezone skips a whole day
+    date.and_time(NaiveTime::MIN).and_utc()
+}
+
+/// Start or end of a range query, in UTC
+///
+/// Converted from the chrono types, so that callers with dates at hand don't
+/// have to format them, or parsed from the strings accepted by `get_intensities`.
+/// A `NaiveDate` is the midnight starting the day.
+///
+/// ```
+/// # use chrono::{FixedOffset, NaiveDate, TimeZone};
+/// # use carbonintensity::DateBound;
+/// let day = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+/// let paris = FixedOffset::east_opt(3600).unwrap().with_ymd_and_hms(2024, 3, 15, 1, 0, 0).unwrap();
+/// assert_eq!(DateBound::from(day), DateBound::from(paris));
+/// assert_eq!(DateBound::from(day), "2024-03-15T00:00Z".parse().unwrap());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DateBound(NaiveDateTime);
+
+impl DateBound {
+    /// Parses a date as `from_str`, or an expression relative to `now`
+    ///
+    /// The expressions are `now`, `today` and `yesterday`, the last two at midnight,
+    /// or an offset in minutes, hours, days or weeks e.g. `-30m`, `-24h`, `-7d` or `-2w`.
+    /// Offsets without a sign are in the past, `+` is for the forecasts e.g. `+12h`.
+    /// They can follow `now`, e.g. `now-24h`, which doesn't start with a hyphen
+    /// as expected for the values of command line options.
+    ///
+    /// ```
+    /// # use chrono::NaiveDate;
+    /// # use carbonintensity::DateBound;
+    /// let now = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap().and_hms_opt(10, 20, 0).unwrap();
+    /// let bound = DateBound::parse_relative("-24h", now).unwrap();
+    /// assert_eq!(bound, "2024-03-14T10:20Z".parse().unwrap());
+    /// assert_eq!(DateBound::parse_relative("yesterday", now).unwrap(), "2024-03-14".parse().unwrap());
+    /// ```
+    pub fn parse_relative(s: &str, now: NaiveDateTime) -> Result<Self> {
+        match relative_date(s.trim(), now) {
+            Some(date) => Ok(Self(date)),
+            None => s.parse(),
+        }
+    }
+
+    pub fn datetime(self) -> NaiveDateTime {
+        self.0
+    }
+}
+
+/// Date of an expression relative to `now`, see `DateBound::parse_relative`
+fn relative_date(expression: &str, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let midnight = now.date().and_time(NaiveTime::MIN);
+    match expression {
+        "now" => return Some(now),
+        "today" => return Some(midnight),
+        "yesterday" => return Some(midnight - Duration::days(1)),
+        _ => {}
+    }
+
+    let expression = expression
+        .strip_prefix("now")
+        .filter(|offset| offset.starts_with(['+', '-']))
+        .unwrap_or(expression);
+    let (sign, offset) = match expression.strip_prefix('+') {
+        Some(offset) => (1, offset),
+        None => (-1, expression.strip_prefix('-').unwrap_or(expression)),
+    };
+    let unit = offset.chars().last()?;
+    let amount: i64 = offset[..offset.len() - unit.len_utf8()].parse().ok()?;
+    let offset = match unit {
+        'm' => Duration::try_minutes(amount),
+        'h' => Duration::try_hours(amount),
+        'd' => Duration::try_days(amount),
+        'w' => Duration::try_weeks(amount),
+        _ => None,
+    }?;
+    now.checked_add_signed(offset * sign)
+}
+
+impl From<NaiveDateTime> for DateBound {
+    fn from(datetime: NaiveDateTime) -> Self {
+        Self(datetime)
+    }
+}
+
+impl From<NaiveDate> for DateBound {
+    fn from(date: NaiveDate) -> Self {
+        Self(date.and_time(NaiveTime::MIN))
+    }
+}
+
+impl<Tz: TimeZone> From<DateTime<Tz>> for DateBound {
+    fn from(datetime: DateTime<Tz>) -> Self {
+        Self(datetime.naive_utc())
+    }
+}
+
+/// Parses YYYY-MM-DDThh:mmZ or YYYY-MM-DD
+impl FromStr for DateBound {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(Self(parse_date(s)?))
+    }
+}
+
+/// Range of a query, validated against the data the API can serve
+///
+/// A start before the oldest data, on 2018-05-10 23:30, is moved to it, as is
+/// an end, which `is_clamped()` reports. The end defaults to now and can be up
+/// to 48 hours later, the horizon of the forecasts. Aggregates over periods in
+/// progress, e.g. the current year, build the range with `within_horizon()` to
+/// move a later end back to the horizon instead. A range is built once and
+/// reused across queries, e.g. for the intensities, generation mixes and statistics of a period.
+///
+/// ```
+/// # use chrono::NaiveDate;
+/// # use carbonintensity::{DateRange, FixedClock};
+/// let now = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+/// let clock = FixedClock::new(now);
+/// let range = DateRange::parse("2017-01-01", &Some("2018-06-01"), &clock).unwrap();
+/// assert!(range.is_clamped());
+/// assert_eq!(range.start().to_string(), "2018-05-10 23:30:00");
+/// assert!(DateRange::parse("2024-05-01", &Some("2024-06-10"), &clock).is_err());
+///
+/// let june = NaiveDate::from_ymd_opt(2024, 7, 1).unwrap();
+/// let range = DateRange::within_horizon(NaiveDate::from_ymd_opt(2024, 6, 1).unwrap(), Some(june.into()), &clock).unwrap();
+/// assert_eq!(range.end().to_string(), "2024-06-03 00:00:00");
+/// assert!(range.is_clamped());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange {
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    clamped: bool,
+    /// End requested beyond the forecast horizon, if moved back to it
+    beyond_horizon: Option<NaiveDateTime>,
+}
+
+impl DateRange {
+    /// Range from `start` to `end`, or to the current time of the clock
+    ///
+    /// Returns an `ApiError::Error` if a date is beyond the forecast horizon.
+    pub fn new(
+        start: impl Into<DateBound>,
+        end: Option<DateBound>,
+        clock: &dyn Clock,
+    ) -> Result<Self> {
+        let now = clock.now();
+        let requested = (
+            start.into().datetime(),
+            end.map_or(now, DateBound::datetime),
+        );
+        let start = validate_date(requested.0, now)?;
+        let end = validate_date(requested.1, now)?;
+        Ok(Self {
+            start,
+            end,
+            clamped: (start, end) != requested,
+            beyond_horizon: None,
+        })
+    }
+
+    /// Same as `new` but the dates beyond the forecast horizon are moved back to it,
+    /// which `is_clamped()` and `beyond_horizon()` report
+    pub fn within_horizon(
+        start: impl Into<DateBound>,
+        end: Option<DateBound>,
+        clock: &dyn Clock,
+    ) -> Result<Self> {
+        let horizon = clock.now() + FORECAST_HORIZON;
+        let requested = end.map(DateBound::datetime);
+        let start = start.into().datetime().min(horizon);
+        let end = requested.map(|end| DateBound::from(end.min(horizon)));
+        let mut range = Self::new(start, end, clock)?;
+        if let Some(requested) = requested.filter(|requested| *requested > horizon) {
+            range.clamped = true;
+            range.beyond_horizon = Some(requested);
+        }
+        Ok(range)
+    }
+
+    /// Same as `new` with the dates as accepted by `get_intensities`, e.g. "2024-01-01T00:30Z",
+    /// or relative to the current time of the clock e.g. "-24h", see `DateBound::parse_relative()`
+    pub fn parse(start: &str, end: &Option<&str>, clock: &dyn Clock) -> Result<Self> {
+        let (start, end) = parse_bounds(start, end, clock)?;
+        Self::new(start, end, clock)
+    }
+
+    /// Same as `within_horizon` with the dates as accepted by `parse`
+    pub fn parse_within_horizon(
+        start: &str,
+        end: &Option<&str>,
+        clock: &dyn Clock,
+    ) -> Result<Self> {
+        let (start, end) = parse_bounds(start, end, clock)?;
+        Self::within_horizon(start, end, clock)
+    }
+
+    pub fn start(&self) -> NaiveDateTime {
+        self.start
+    }
+
+    pub fn end(&self) -> NaiveDateTime {
+        self.end
+    }
+
+    /// A date was moved to the oldest data available, or back to the forecast horizon
+    pub fn is_clamped(&self) -> bool {
+        self.clamped
+    }
+
+    /// Part of the requested range after the forecast horizon, without data,
+    /// if built with `within_horizon()`
+    pub fn beyond_horizon(&self) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        self.beyond_horizon.map(|requested| (self.end, requested))
+    }
+
+    /// Windows requested for the range, the part after `now` being a forecast window
+    pub(crate) fn windows(
+        &self,
+        now: NaiveDateTime,
+        chunking: &Chunking,
+    ) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+        RangePlanner.plan_with_horizon(self.start, self.end, now, chunking)
+    }
+}
+
+/// Start and end of `DateRange::parse`, relative to the current time of the clock
+fn parse_bounds(
+    start: &str,
+    end: &Option<&str>,
+    clock: &dyn Clock,
+) -> Result<(DateBound, Option<DateBound>)> {
+    let now = clock.now();
+    let start = DateBound::parse_relative(start, now)?;
+    let end = end
+        .map(|end| DateBound::parse_relative(end, now))
+        .transpose()?;
+    Ok((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::FixedOffset;
+
+    use super::*;
+
+    #[test]
+    fn parse() {
+        for name in ["today", "yesterday", "this-week", "last-month", "ytd"] {
+            let range: RangePreset = name.parse().unwrap();
+            assert_eq!(range.to_string(), name);
+        }
+        assert!("last-week".parse::<RangePreset>().is_err());
+    }
+
+    #[test]
+    fn date_bound() {
+        let bound: DateBound = "2024-03-15T10:20Z".parse().unwrap();
+        let local = FixedOffset::west_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(2024, 3, 15, 9, 20, 0)
+            .unwrap();
+        assert_eq!(bound, DateBound::from(local));
+        assert_eq!(bound.datetime(), local.naive_utc());
+        assert!(matches!(
+            "15/03/2024".parse::<DateBound>(),
+            Err(ApiError::DateParseError(_))
+        ));
+    }
+
+    #[test]
+    fn relative() {
+        let now = NaiveDate::from_ymd_opt(2024, 3, 15)
+            .unwrap()
+            .and_hms_opt(10, 20, 0)
+            .unwrap();
+        let relative = |s| DateBound::parse_relative(s, now).map(DateBound::datetime);
+        let date = |s| parse_date(s).unwrap();
+
+        assert_eq!(relative("now").unwrap(), now);
+        assert_eq!(relative("today").unwrap(), date("2024-03-15"));
+        assert_eq!(relative("-7d").unwrap(), date("2024-03-08T10:20Z"));
+        assert_eq!(relative("7d").unwrap(), date("2024-03-08T10:20Z"));
+        assert_eq!(relative("-2w").unwrap(), date("2024-03-01T10:20Z"));
+        assert_eq!(relative("-30m").unwrap(), date("2024-03-15T09:50Z"));
+        assert_eq!(relative("+12h").unwrap(), date("2024-03-15T22:20Z"));
+        assert_eq!(relative("now-7d").unwrap(), date("2024-03-08T10:20Z"));
+        assert_eq!(relative("now+12h").unwrap(), date("2024-03-15T22:20Z"));
+        for invalid in ["-7y", "-d", "d", "-1.5h", "tomorrow", "", "now7d", "now-"] {
+            assert!(
+                matches!(relative(invalid), Err(ApiError::DateParseError(_))),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn date_range() {
+        let now = NaiveDate::from_ymd_opt(2024, 6, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let clock = crate::FixedClock::new(now);
+        let date = |s| parse_date(s).unwrap();
+
+        let range = DateRange::parse("2024-05-01", &None, &clock).unwrap();
+        assert_eq!(range.end(), now);
+        assert!(!range.is_clamped());
+
+        // both ends before the oldest data
+        let range = DateRange::parse("2010-01-01", &Some("2011-01-01"), &clock).unwrap();
+        assert_eq!(range.start(), range.end());
+        assert!(range.is_clamped());
+
+        let tomorrow = now + Duration::days(1);
+        let range = DateRange::new(now.date(), Some(tomorrow.into()), &clock).unwrap();
+        assert_eq!(range.windows(now, &Chunking::default()).len(), 2);
+        assert!(DateRange::parse("2024-01-01", &Some("01/06/2024"), &clock).is_err());
+
+        // the current year, up to the forecasts
+        let range =
+            DateRange::parse_within_horizon("2024-01-01", &Some("2025-01-01"), &clock).unwrap();
+        assert_eq!(range.end(), now + Duration::hours(48));
+        assert!(range.is_clamped());
+        assert_eq!(
+            range.beyond_horizon(),
+            Some((range.end(), date("2025-01-01")))
+        );
+        assert!(DateRange::parse("2024-01-01", &Some("2025-01-01"), &clock).is_err());
+        let range = DateRange::parse_within_horizon("2024-05-01", &None, &clock).unwrap();
+        assert_eq!((range.end(), range.beyond_horizon()), (now, None));
+        assert!(!range.is_clamped());
+    }
+
+    #[test]
+    fn resolve() {
+        // Friday
+        let now = Utc.with_ymd_and_hms(2024, 3, 15, 10, 20, 0).unwrap();
+        let resolve = |range: RangePreset| range.resolve(now);
+        let range = |start: &str, end: &str| (start.to_string(), end.to_string());
+
+        assert_eq!(
+            resolve(RangePreset::Today),
+            range("2024-03-15T00:00Z", "2024-03-15T10:20Z")
+        );
+        assert_eq!(
+            resolve(RangePreset::Yesterday),
+            range("2024-03-14T00:00Z", "2024-03-15T00:00Z")
+        );
+        assert_eq!(
+            resolve(RangePreset::ThisWeek),
+            range("2024-03-11T00:00Z", "2024-03-15T10:20Z")
+        );
+        assert_eq!(
+            resolve(RangePreset::LastMonth),
+            range("2024-02-01T00:00Z", "2024-03-01T00:00Z")
+        );
+        assert_eq!(
+            resolve(RangePreset::YearToDate),
+            range("2024-01-01T00:00Z", "2024-03-15T10:20Z")
+        );
+
+        // January, last month is in the previous year
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            RangePreset::LastMonth.resolve(now),
+            range("2023-12-01T00:00Z", "2024-01-01T00:00Z")
+        );
+
+        // local midnight, the evening before in UTC for timezones east of it
+        let bst = FixedOffset::east_opt(3600).unwrap();
+        let now = bst.with_ymd_and_hms(2024, 6, 1, 0, 30, 0).unwrap();
+        assert_eq!(
+            RangePreset::Today.resolve(now),
+            range("2024-05-31T23:00Z", "2024-05-31T23:30Z")
+        );
+    }
+}
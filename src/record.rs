@@ -0,0 +1,281 @@
+use chrono::NaiveDateTime;
+
+#[cfg(feature = "analysis")]
+use futures::{stream, Stream, StreamExt};
+
+#[cfg(feature = "analysis")]
+use crate::{
+    apply_null_policy, check_mixes, discard_after, discard_implausible, get_window_data,
+    range_path, window_url, DateRange, RangePlanner,
+};
+use crate::{
+    parse_date, ApiError, CarbonIntensityClient, Data, Fuel, IntensityForDate, IntensityIndex,
+    MixShare, QueryOptions, QueryOutcome, Result, Target,
+};
+
+/// Windows retrieved ahead of the consumer of a stream of intensities
+#[cfg(feature = "analysis")]
+const STREAMED_WINDOWS: usize = 2;
+
+/// Intensity of a half-hour, with its dates parsed
+///
+/// Same content as `Data`, in types which don't need to be parsed again
+/// by every consumer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntensityRecord {
+    /// Start of the half-hour
+    pub from: NaiveDateTime,
+    /// End of the half-hour
+    pub to: NaiveDateTime,
+    /// Forecast intensity in gCO2/kWh, `None` when the API sent none
+    pub forecast: Option<i32>,
+    /// Actual intensity in gCO2/kWh, only available at the national level
+    pub actual: Option<i32>,
+    pub index: IntensityIndex,
+    /// Share of each fuel in the order sent by the API, not available at the national level
+    pub generationmix: Option<Vec<(Fuel, MixShare)>>,
+}
+
+impl IntensityRecord {
+    /// Intensity in gCO2/kWh, using the actual value if available
+    ///
+    /// The records converted from a `Data` always have one of the two values, 0 otherwise.
+    pub fn intensity(&self) -> i32 {
+        self.actual.or(self.forecast).unwrap_or_default()
+    }
+
+    /// Start date and intensity, as returned by `get_intensities`
+    pub fn intensity_for_date(&self) -> IntensityForDate {
+        (self.from, self.intensity())
+    }
+}
+
+impl TryFrom<&Data> for IntensityRecord {
+    type Error = ApiError;
+
+    fn try_from(data: &Data) -> Result<Self> {
+        // fails if neither the forecast nor the actual intensity was sent
+        let (from, _) = data.intensity_for_date()?;
+        let intensity = data.intensity();
+        Ok(Self {
+            from,
+            to: parse_date(data.to())?,
+            forecast: intensity.forecast(),
+            actual: intensity.actual(),
+            index: intensity.index(),
+            generationmix: data.generation_mix().map(|mix| {
+                mix.iter()
+                    .map(|m| (Fuel::from(m.fuel()), m.share()))
+                    .collect()
+            }),
+        })
+    }
+}
+
+/// Get the intensities for a given target in 30 minutes windows, with their
+/// index and generation mix
+///
+/// Same query as `get_intensities`, returning an `IntensityRecord` per half-hour.
+pub async fn get_intensity_records(
+    target: &Target,
+    start: &str,
+    end: &Option<&str>,
+) -> Result<Vec<IntensityRecord>> {
+    CarbonIntensityClient::shared()
+        .get_intensity_records(target, start, end)
+        .await
+}
+
+/// Same as `get_intensity_records` but with control over the execution of the query
+pub async fn get_intensity_records_with_options(
+    target: &Target,
+    start: &str,
+    end: &Option<&str>,
+    options: &QueryOptions,
+) -> Result<QueryOutcome<IntensityRecord>> {
+    CarbonIntensityClient::shared()
+        .get_intensity_records_with_options(target, start, end, options)
+        .await
+}
+
+/// Stream of the intensity records of a target over a range, yielded as each
+/// window of 13 days is received
+///
+/// Unlike `get_intensity_records`, which returns once the whole range is in
+/// memory, the records of a range spanning years can be written out as they come.
+///
+/// ```no_run
+/// # use carbonintensity::{stream_intensities, DateRange, SystemClock, Target};
+/// use futures::StreamExt;
+///
+/// # async fn run() -> carbonintensity::Result<()> {
+/// let range = DateRange::parse("2020-01-01", &Some("2024-01-01"), &SystemClock)?;
+/// let mut records = std::pin::pin!(stream_intensities(&Target::National, &range));
+/// while let Some(record) = records.next().await {
+///     let record = record?;
+///     println!("{},{}", record.from, record.intensity());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "analysis")]
+pub fn stream_intensities(
+    target: &Target,
+    range: &DateRange,
+) -> impl Stream<Item = Result<IntensityRecord>> {
+    CarbonIntensityClient::shared().stream_intensities(target, range)
+}
+
+impl CarbonIntensityClient {
+    /// Intensity records for a target in 30 minutes windows, see `get_intensity_records()`
+    pub async fn get_intensity_records(
+        &self,
+        target: &Target,
+        start: &str,
+        end: &Option<&str>,
+    ) -> Result<Vec<IntensityRecord>> {
+        let outcome = self
+            .get_intensity_records_with_options(target, start, end, &QueryOptions::default())
+            .await?;
+        Ok(outcome.intensities)
+    }
+
+    /// See `get_intensity_records_with_options()`
+    pub async fn get_intensity_records_with_options(
+        &self,
+        target: &Target,
+        start: &str,
+        end: &Option<&str>,
+        options: &QueryOptions,
+    ) -> Result<QueryOutcome<IntensityRecord>> {
+        let outcome = self
+            .get_detailed_intensities(target, start, end, options)
+            .await?;
+        Ok(QueryOutcome {
+            intensities: outcome
+                .intensities
+                .iter()
+                .map(IntensityRecord::try_from)
+                .collect::<Result<_>>()?,
+            warnings: outcome.warnings,
+            report: outcome.report,
+        })
+    }
+
+    /// Stream of the intensity records over a range, see `stream_intensities()`
+    #[cfg(feature = "analysis")]
+    pub fn stream_intensities(
+        &self,
+        target: &Target,
+        range: &DateRange,
+    ) -> impl Stream<Item = Result<IntensityRecord>> {
+        self.stream_intensities_with_options(target, range, &QueryOptions::default())
+    }
+
+    /// Same as `stream_intensities` but with control over the execution of the query
+    ///
+    /// The half-hours reported as warnings by `get_intensity_records_with_options`
+    /// are handled according to the policies of the options, without the warnings
+    /// being returned. Nulls are interpolated within a window only. The deadline
+    /// and the execution report don't apply to a stream.
+    #[cfg(feature = "analysis")]
+    pub fn stream_intensities_with_options(
+        &self,
+        target: &Target,
+        range: &DateRange,
+        options: &QueryOptions,
+    ) -> impl Stream<Item = Result<IntensityRecord>> {
+        let now = options.clock().now();
+        let windows = range.windows(now, self.chunking());
+        let end = windows.last().map(|window| window.1);
+        let requests: Vec<Result<(String, bool)>> = match range_path(target) {
+            Ok(path) => {
+                let base_url = self.base_url_for(options);
+                windows
+                    .iter()
+                    .map(|window| {
+                        let url = window_url(base_url, target, &path, window, now);
+                        Ok((url, RangePlanner.is_settled(window, now)))
+                    })
+                    .collect()
+            }
+            Err(error) => vec![Err(error)],
+        };
+
+        let client = self.clone();
+        let national = *target == Target::National;
+        let options = options.clone();
+        stream::iter(requests)
+            .map(move |request| {
+                let client = client.clone();
+                let options = options.clone();
+                async move {
+                    let (url, settled) = request?;
+                    let provenance = options.provenance();
+                    let data =
+                        get_window_data(&client, &url, national, settled, provenance).await?;
+                    window_records(data, end, &options)
+                }
+            })
+            .buffered(STREAMED_WINDOWS)
+            .flat_map(|records| {
+                let records: Vec<Result<IntensityRecord>> = match records {
+                    Ok(records) => records.into_iter().map(Ok).collect(),
+                    Err(error) => vec![Err(error)],
+                };
+                stream::iter(records)
+            })
+    }
+}
+
+/// Records of a window, processed as the data of a whole range would be
+#[cfg(feature = "analysis")]
+fn window_records(
+    mut data: Vec<Data>,
+    end: Option<NaiveDateTime>,
+    options: &QueryOptions,
+) -> Result<Vec<IntensityRecord>> {
+    let mut warnings = Vec::new();
+    if let Some(end) = end {
+        discard_after(&mut data, end);
+    }
+    discard_implausible(&mut data, &mut warnings);
+    check_mixes(&mut data, options.mix_policy(), &mut warnings);
+    apply_null_policy(&mut data, options.null_policy(), &mut warnings)?;
+    data.iter().map(IntensityRecord::try_from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record() {
+        let json_str = r#"{"from":"2024-01-01T00:00Z","to":"2024-01-01T00:30Z",
+            "intensity":{"forecast":120,"index":"moderate"},
+            "generationmix":[{"fuel":"gas","perc":40.0},{"fuel":"tidal","perc":60.0}]}"#;
+        let data: Data = serde_json::from_str(json_str).unwrap();
+        let record = IntensityRecord::try_from(&data).unwrap();
+        assert_eq!(record.from, parse_date("2024-01-01T00:00Z").unwrap());
+        assert_eq!(record.to, parse_date("2024-01-01T00:30Z").unwrap());
+        assert_eq!(record.index, IntensityIndex::Moderate);
+        assert_eq!(
+            record.generationmix,
+            Some(vec![
+                (Fuel::Gas, MixShare::new(40.0)),
+                (Fuel::Other, MixShare::new(60.0))
+            ])
+        );
+        assert_eq!(
+            record.intensity_for_date(),
+            data.intensity_for_date().unwrap()
+        );
+
+        let json_str = r#"{"from":"2024-01-01T00:00Z","to":"2024-01-01T00:30Z",
+            "intensity":{"forecast":120,"actual":118,"index":"moderate"}}"#;
+        let data: Data = serde_json::from_str(json_str).unwrap();
+        let record = IntensityRecord::try_from(&data).unwrap();
+        assert_eq!(record.intensity(), 118);
+        assert_eq!(record.generationmix, None);
+    }
+}
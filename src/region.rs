@@ -1,25 +1,89 @@
 use std::{error::Error, fmt::Display, num::ParseIntError, str::FromStr};
 
-/// Region
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Region {
-    NorthScotland = 1,
-    SouthScotland = 2,
-    NorthWestEngland = 3,
-    NorthEastEngland = 4,
-    SouthYorkshire = 5,
-    NorthWalesMerseysideAndCheshire = 6,
-    SouthWales = 7,
-    WestMidlands = 8,
-    EastMidlands = 9,
-    EastEngland = 10,
-    SouthWestEngland = 11,
-    SouthEngland = 12,
-    London = 13,
-    SouthEastEngland = 14,
-    England = 15,
-    Scotland = 16,
-    Wales = 17,
+/// Declares the regions from a single table: variant, id, name, short name as
+/// sent by the API, the distribution network operator as named by the API, the
+/// other common names of the operator and the grid supply point (GSP) group
+///
+/// Generates the `Region` enum, `Region::ALL` and the lookups used by the
+/// `FromStr` and `Display` implementations, so that they can't get out of sync.
+macro_rules! regions {
+    ($($variant:ident = $id:literal, $name:literal, $short:literal, $dno:expr, [$($alias:literal),*], $gsp:expr;)+) => {
+        /// Region
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Region {
+            $($variant = $id,)+
+        }
+
+        impl Region {
+            /// Every region, ordered by id
+            pub const ALL: [Region; [$($id),+].len()] = [$(Self::$variant),+];
+
+            fn from_id(id: u8) -> Option<Self> {
+                match id {
+                    $($id => Some(Self::$variant),)+
+                    _ => None,
+                }
+            }
+
+            /// Name of the region e.g. "South West England"
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$variant => $name,)+
+                }
+            }
+
+            /// Name of the region as in the `shortname` of the responses of the API
+            /// e.g. "Yorkshire" for `SouthYorkshire`
+            pub fn short_name(&self) -> &'static str {
+                match self {
+                    $(Self::$variant => $short,)+
+                }
+            }
+
+            /// Distribution network operator as named by the API e.g. "WPD South West",
+            /// `None` for England, Scotland and Wales
+            pub fn dno_region(&self) -> Option<&'static str> {
+                match self {
+                    $(Self::$variant => $dno,)+
+                }
+            }
+
+            /// Other common names of the distribution network operator e.g. "NGED South West"
+            pub fn dno_aliases(&self) -> &'static [&'static str] {
+                match self {
+                    $(Self::$variant => &[$($alias),*],)+
+                }
+            }
+
+            /// Grid supply point group of the region as used by the electricity
+            /// settlement e.g. "_L", `None` for England, Scotland and Wales
+            pub fn gsp_group(&self) -> Option<&'static str> {
+                match self {
+                    $(Self::$variant => $gsp,)+
+                }
+            }
+        }
+    };
+}
+
+regions! {
+    NorthScotland = 1, "North Scotland", "North Scotland", Some("Scottish Hydro Electric Power Distribution"), ["SHEPD"], Some("_P");
+    SouthScotland = 2, "South Scotland", "South Scotland", Some("SP Distribution"), [], Some("_N");
+    NorthWestEngland = 3, "North West England", "North West England", Some("Electricity North West"), ["ENWL"], Some("_G");
+    NorthEastEngland = 4, "North East England", "North East England", Some("NPG North East"), ["Northern Powergrid North East"], Some("_F");
+    SouthYorkshire = 5, "South Yorkshire", "Yorkshire", Some("NPG Yorkshire"), ["Northern Powergrid Yorkshire"], Some("_M");
+    NorthWalesMerseysideAndCheshire = 6, "North Wales, Merseyside and Cheshire", "North Wales & Merseyside", Some("SP Manweb"), [], Some("_D");
+    SouthWales = 7, "South Wales", "South Wales", Some("WPD South Wales"), ["NGED South Wales"], Some("_K");
+    WestMidlands = 8, "West Midlands", "West Midlands", Some("WPD West Midlands"), ["NGED West Midlands"], Some("_E");
+    EastMidlands = 9, "East Midlands", "East Midlands", Some("WPD East Midlands"), ["NGED East Midlands"], Some("_B");
+    EastEngland = 10, "East England", "East England", Some("UKPN East"), [], Some("_A");
+    SouthWestEngland = 11, "South West England", "South West England", Some("WPD South West"), ["NGED South West"], Some("_L");
+    SouthEngland = 12, "South England", "South England", Some("SSE South"), ["SSEN South", "SEPD"], Some("_H");
+    London = 13, "London", "London", Some("UKPN London"), [], Some("_C");
+    SouthEastEngland = 14, "South East England", "South East England", Some("UKPN South East"), [], Some("_J");
+    England = 15, "England", "England", None, [], None;
+    Scotland = 16, "Scotland", "Scotland", None, [], None;
+    Wales = 17, "Wales", "Wales", None, [], None;
 }
 
 impl FromStr for Region {
@@ -27,54 +91,13 @@ impl FromStr for Region {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let region_id = s.parse::<u8>()?;
-
-        let region = match region_id {
-            1 => Self::NorthScotland,
-            2 => Self::SouthScotland,
-            3 => Self::NorthWestEngland,
-            4 => Self::NorthEastEngland,
-            5 => Self::SouthYorkshire,
-            6 => Self::NorthWalesMerseysideAndCheshire,
-            7 => Self::SouthWales,
-            8 => Self::WestMidlands,
-            9 => Self::EastMidlands,
-            10 => Self::EastEngland,
-            11 => Self::SouthWestEngland,
-            12 => Self::SouthEngland,
-            13 => Self::London,
-            14 => Self::SouthEastEngland,
-            15 => Self::England,
-            16 => Self::Scotland,
-            17 => Self::Wales,
-            _ => return Err(RegionError::OutsideRange),
-        };
-
-        Ok(region)
+        Self::from_id(region_id).ok_or(RegionError::OutsideRange)
     }
 }
 
 impl Display for Region {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let s = match self {
-            Region::NorthScotland => "North Scotland",
-            Region::SouthScotland => "South Scotland",
-            Region::NorthWestEngland => "North West England",
-            Region::NorthEastEngland => "North East England",
-            Region::SouthYorkshire => "South Yorkshire",
-            Region::NorthWalesMerseysideAndCheshire => "North Wales, Merseyside and Cheshire",
-            Region::SouthWales => "South Wales",
-            Region::WestMidlands => "West Midlands",
-            Region::EastMidlands => "East Midlands",
-            Region::EastEngland => "East England",
-            Region::SouthWestEngland => "South West England",
-            Region::SouthEngland => "South England",
-            Region::London => "London",
-            Region::SouthEastEngland => "South East England",
-            Region::England => "England",
-            Region::Scotland => "Scotland",
-            Region::Wales => "Wales",
-        };
-        write!(f, "{}", s)
+        write!(f, "{}", self.name())
     }
 }
 
@@ -88,13 +111,14 @@ impl Error for RegionError {}
 
 impl Display for RegionError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let message = match self {
-            RegionError::ParseError => "Failed to parse region id",
-            RegionError::OutsideRange => {
-                "Region id outside allowed range. Must be between 1 and 17 (inclusive)"
-            }
-        };
-        write!(f, "{}", message)
+        match self {
+            RegionError::ParseError => write!(f, "Failed to parse region id"),
+            RegionError::OutsideRange => write!(
+                f,
+                "Region id outside allowed range. Must be between 1 and {} (inclusive)",
+                Region::ALL.len()
+            ),
+        }
     }
 }
 
@@ -131,6 +155,32 @@ mod tests {
         assert_eq!(15_u8, Region::England as u8);
         assert_eq!(16_u8, Region::Scotland as u8);
         assert_eq!(17_u8, Region::Wales as u8);
+
+        for (position, region) in Region::ALL.iter().enumerate() {
+            assert_eq!(*region as usize, position + 1);
+            assert_eq!((*region as u8).to_string().parse::<Region>(), Ok(*region));
+        }
+    }
+
+    #[test]
+    fn metadata() {
+        assert_eq!(Region::SouthWestEngland.name(), "South West England");
+        assert_eq!(
+            Region::SouthWestEngland.dno_region(),
+            Some("WPD South West")
+        );
+        assert_eq!(Region::SouthWestEngland.dno_aliases(), &["NGED South West"]);
+        assert_eq!(Region::Wales.dno_region(), None);
+        assert!(Region::London.dno_aliases().is_empty());
+        assert_eq!(Region::SouthWestEngland.short_name(), "South West England");
+        assert_eq!(Region::SouthYorkshire.short_name(), "Yorkshire");
+        assert_eq!(
+            Region::NorthWalesMerseysideAndCheshire.short_name(),
+            "North Wales & Merseyside"
+        );
+        assert_eq!(Region::SouthWestEngland.gsp_group(), Some("_L"));
+        assert_eq!(Region::London.gsp_group(), Some("_C"));
+        assert_eq!(Region::Wales.gsp_group(), None);
     }
 
     #[test]
@@ -155,6 +205,8 @@ mod tests {
         assert_eq!("15".parse::<Region>(), Ok(Region::England));
         assert_eq!("16".parse::<Region>(), Ok(Region::Scotland));
         assert_eq!("17".parse::<Region>(), Ok(Region::Wales));
+        assert_eq!("0".parse::<Region>(), Err(RegionError::OutsideRange));
+        assert_eq!("18".parse::<Region>(), Err(RegionError::OutsideRange));
     }
 
     #[test]
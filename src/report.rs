@@ -0,0 +1,197 @@
+use std::io::{self, Write};
+
+use crate::BacktestResult;
+
+/// Width in characters of the longest bar in the markdown charts
+const BAR_WIDTH: f64 = 40.0;
+
+/// Writes the comparison of backtest results as markdown
+///
+/// The table lists the emissions of each strategy, the savings compared to
+/// running the jobs as soon as they are released and the missed deadlines,
+/// followed by a bar chart of the emissions.
+pub fn write_backtest_markdown<W: Write>(
+    writer: &mut W,
+    results: &[BacktestResult],
+) -> io::Result<()> {
+    writeln!(writer, "# Backtest")?;
+    writeln!(writer)?;
+    writeln!(
+        writer,
+        "| Strategy | Emissions (kgCO2) | Savings | Missed deadlines |"
+    )?;
+    writeln!(writer, "|---|---:|---:|---:|")?;
+    for result in results {
+        writeln!(
+            writer,
+            "| {} | {:.1} | {:.1}% | {} / {} |",
+            escape_cell(&result.strategy),
+            result.total_emissions_kg(),
+            result.savings_percent(),
+            result.missed_deadlines(),
+            result.outcomes.len()
+        )?;
+    }
+
+    writeln!(writer)?;
+    writeln!(writer, "```text")?;
+    let max = max_emissions(results);
+    let name_width = results.iter().map(|r| r.strategy.len()).max().unwrap_or(0);
+    for result in results {
+        let emissions = result.total_emissions_kg();
+        let length = (emissions / max * BAR_WIDTH).round() as usize;
+        writeln!(
+            writer,
+            "{:<name_width$} {} {emissions:.1}",
+            result.strategy,
+            "█".repeat(length)
+        )?;
+    }
+    writeln!(writer, "```")
+}
+
+/// Writes the comparison of backtest results as a standalone HTML page
+///
+/// Same content as `write_backtest_markdown`, the chart is drawn in SVG.
+pub fn write_backtest_html<W: Write>(writer: &mut W, results: &[BacktestResult]) -> io::Result<()> {
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(writer, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(writer, "<title>Backtest</title>\n</head>\n<body>")?;
+    writeln!(writer, "<h1>Backtest</h1>")?;
+    writeln!(writer, "<table>")?;
+    writeln!(
+        writer,
+        "<tr><th>Strategy</th><th>Emissions (kgCO2)</th><th>Savings</th><th>Missed deadlines</th></tr>"
+    )?;
+    for result in results {
+        writeln!(
+            writer,
+            "<tr><td>{}</td><td>{:.1}</td><td>{:.1}%</td><td>{} / {}</td></tr>",
+            escape(&result.strategy),
+            result.total_emissions_kg(),
+            result.savings_percent(),
+            result.missed_deadlines(),
+            result.outcomes.len()
+        )?;
+    }
+    writeln!(writer, "</table>")?;
+
+    let (bar_height, label_width, chart_width) = (24, 160, 400.0);
+    let max = max_emissions(results);
+    writeln!(
+        writer,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">",
+        label_width + chart_width as usize + 80,
+        bar_height * results.len()
+    )?;
+    for (position, result) in results.iter().enumerate() {
+        let emissions = result.total_emissions_kg();
+        let width = emissions / max * chart_width;
+        let y = position * bar_height;
+        writeln!(
+            writer,
+            "<text x=\"0\" y=\"{}\">{}</text>",
+            y + 16,
+            escape(&result.strategy)
+        )?;
+        writeln!(
+            writer,
+            "<rect x=\"{label_width}\" y=\"{}\" width=\"{width:.1}\" height=\"{}\" fill=\"seagreen\"/>",
+            y + 4,
+            bar_height - 8
+        )?;
+        writeln!(
+            writer,
+            "<text x=\"{:.1}\" y=\"{}\">{emissions:.1}</text>",
+            label_width as f64 + width + 4.0,
+            y + 16
+        )?;
+    }
+    writeln!(writer, "</svg>")?;
+    writeln!(writer, "</body>\n</html>")
+}
+
+/// Highest emissions of the results, used to scale the charts
+fn max_emissions(results: &[BacktestResult]) -> f64 {
+    results
+        .iter()
+        .map(BacktestResult::total_emissions_kg)
+        .fold(0.0, f64::max)
+        .max(f64::EPSILON)
+}
+
+/// Escapes the pipes which would end a cell of a markdown table
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::JobOutcome;
+
+    fn result(strategy: &str, emissions_kg: f64, missed_deadline: bool) -> BacktestResult {
+        let time = NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        BacktestResult {
+            strategy: strategy.to_string(),
+            outcomes: vec![JobOutcome {
+                start: time,
+                end: time,
+                emissions_kg,
+                baseline_emissions_kg: 10.0,
+                missed_deadline,
+            }],
+        }
+    }
+
+    #[test]
+    fn markdown() {
+        let results = vec![result("naive", 10.0, false), result("night", 5.0, true)];
+        let mut output = Vec::new();
+        write_backtest_markdown(&mut output, &results).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("| naive | 10.0 | 0.0% | 0 / 1 |\n"));
+        assert!(output.contains("| night | 5.0 | 50.0% | 1 / 1 |\n"));
+        assert!(output.contains(&format!("naive {} 10.0\n", "█".repeat(40))));
+        assert!(output.contains(&format!("night {} 5.0\n", "█".repeat(20))));
+
+        // the pipes of the names don't split the cells, the chart is left as is
+        let results = vec![result("day|night", 10.0, false)];
+        let mut output = Vec::new();
+        write_backtest_markdown(&mut output, &results).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("| day\\|night | 10.0 | 0.0% | 0 / 1 |\n"));
+        assert!(output.contains("day|night █"));
+    }
+
+    #[test]
+    fn html() {
+        let results = vec![result("<naive>", 10.0, false), result("night", 5.0, true)];
+        let mut output = Vec::new();
+        write_backtest_html(&mut output, &results).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.starts_with("<!DOCTYPE html>"));
+        assert!(output.contains("<td>&lt;naive&gt;</td><td>10.0</td><td>0.0%</td><td>0 / 1</td>"));
+        assert!(output.contains("width=\"400.0\""));
+        assert!(output.contains("width=\"200.0\""));
+        assert!(output.trim_end().ends_with("</html>"));
+
+        // no results
+        let mut output = Vec::new();
+        write_backtest_html(&mut output, &[]).unwrap();
+    }
+}
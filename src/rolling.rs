@@ -0,0 +1,197 @@
+use std::collections::VecDeque;
+
+use chrono::{Datelike, Months, NaiveDate};
+
+use crate::{ApiError, CarbonIntensityClient, IntensityForDate, QueryOptions, Result, Target};
+
+/// Sum of the intensities of a calendar month
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MonthlyIntensity {
+    /// First day of the month
+    pub month: NaiveDate,
+    pub sum: i64,
+    /// Number of half-hours
+    pub count: usize,
+}
+
+impl MonthlyIntensity {
+    /// Mean intensity over the month, in gCO2/kWh
+    pub fn mean(&self) -> f64 {
+        self.sum as f64 / self.count as f64
+    }
+}
+
+/// Average intensity over the trailing 12 months, as used for
+/// location-based Scope 2 reporting
+///
+/// The intensities are kept as monthly sums, so the average can be moved
+/// forward by pushing the intensities of the new month only; the months
+/// falling out of the window are dropped.
+///
+/// ```
+/// # use chrono::NaiveDate;
+/// # use carbonintensity::RollingAnnualIntensity;
+/// let time = |m| NaiveDate::from_ymd_opt(2024, m, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+/// let mut rolling = RollingAnnualIntensity::new();
+/// rolling.push(&[(time(1), 200), (time(2), 100)]);
+/// assert_eq!(rolling.mean(), Some(150.0));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RollingAnnualIntensity {
+    /// In chronological order
+    months: VecDeque<MonthlyIntensity>,
+}
+
+impl RollingAnnualIntensity {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds intensities to the months they fall in
+    ///
+    /// Only the 12 months up to the most recent one are kept.
+    pub fn push(&mut self, intensities: &[IntensityForDate]) {
+        for (time, value) in intensities {
+            let month = time.date().with_day(1).unwrap_or(time.date());
+            let position = self.months.partition_point(|m| m.month < month);
+            match self.months.get_mut(position) {
+                Some(existing) if existing.month == month => {
+                    existing.sum += i64::from(*value);
+                    existing.count += 1;
+                }
+                _ => self.months.insert(
+                    position,
+                    MonthlyIntensity {
+                        month,
+                        sum: i64::from(*value),
+                        count: 1,
+                    },
+                ),
+            }
+        }
+
+        if let Some(latest) = self.months.back().map(|m| m.month) {
+            let first = latest - Months::new(11);
+            self.months.retain(|m| m.month >= first);
+        }
+    }
+
+    /// Mean intensity over the half-hours of the trailing 12 months, in gCO2/kWh
+    ///
+    /// Returns `None` if there is no data.
+    pub fn mean(&self) -> Option<f64> {
+        let count: usize = self.months.iter().map(|m| m.count).sum();
+        let sum: i64 = self.months.iter().map(|m| m.sum).sum();
+        (count > 0).then(|| sum as f64 / count as f64)
+    }
+
+    /// Months within the window, in chronological order
+    pub fn months(&self) -> impl Iterator<Item = &MonthlyIntensity> {
+        self.months.iter()
+    }
+}
+
+/// Average intensity of a target over the 12 complete months before `month`
+///
+/// `month` can be any day of the month following the period e.g. the current
+/// date. The result can then be moved forward month by month with
+/// `RollingAnnualIntensity::push`.
+pub async fn rolling_annual_intensity(
+    target: &Target,
+    month: NaiveDate,
+    options: &QueryOptions,
+) -> Result<RollingAnnualIntensity> {
+    CarbonIntensityClient::shared()
+        .rolling_annual_intensity(target, month, options)
+        .await
+}
+
+impl CarbonIntensityClient {
+    /// Average intensity over the trailing 12 months, see `rolling_annual_intensity()`
+    pub async fn rolling_annual_intensity(
+        &self,
+        target: &Target,
+        month: NaiveDate,
+        options: &QueryOptions,
+    ) -> Result<RollingAnnualIntensity> {
+        let end = month.with_day(1).unwrap_or(month);
+        let start = end - Months::new(12);
+        let outcome = self
+            .get_intensities_with_options(
+                target,
+                &start.to_string(),
+                &Some(&end.to_string()),
+                options,
+            )
+            .await?;
+        if outcome.is_partial() {
+            return Err(ApiError::Error(
+                "Incomplete data for the trailing 12 months".to_string(),
+            ));
+        }
+
+        // the range includes the half-hour starting at the end
+        let intensities: Vec<_> = outcome
+            .intensities
+            .into_iter()
+            .filter(|(time, _)| time.date() < end)
+            .collect();
+        let mut rolling = RollingAnnualIntensity::new();
+        rolling.push(&intensities);
+        Ok(rolling)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+
+    fn time(year: i32, month: u32, day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn rolling_mean() {
+        let mut rolling = RollingAnnualIntensity::new();
+        assert_eq!(rolling.mean(), None);
+
+        rolling.push(&[
+            (time(2023, 1, 10), 300),
+            (time(2023, 6, 1), 100),
+            (time(2023, 6, 2), 200),
+        ]);
+        assert_eq!(rolling.mean(), Some(200.0));
+        assert_eq!(rolling.months().count(), 2);
+
+        // January 2023 drops out
+        rolling.push(&[(time(2024, 1, 5), 150)]);
+        assert_eq!(rolling.mean(), Some(150.0));
+        let months: Vec<_> = rolling.months().copied().collect();
+        assert_eq!(
+            months,
+            vec![
+                MonthlyIntensity {
+                    month: NaiveDate::from_ymd_opt(2023, 6, 1).unwrap(),
+                    sum: 300,
+                    count: 2
+                },
+                MonthlyIntensity {
+                    month: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                    sum: 150,
+                    count: 1
+                },
+            ]
+        );
+        assert_eq!(months[0].mean(), 150.0);
+
+        // out of order
+        rolling.push(&[(time(2023, 9, 1), 150)]);
+        let months: Vec<_> = rolling.months().map(|m| m.month.month()).collect();
+        assert_eq!(months, vec![6, 9, 1]);
+    }
+}
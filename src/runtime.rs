@@ -0,0 +1,206 @@
+//! Timers and concurrent tasks, on tokio or on the event loop of the browser
+//! when compiled to wasm32
+
+use std::{future::Future, sync::Arc, time::Duration};
+
+use tokio::sync::Semaphore;
+
+use crate::Result;
+
+/// Measures the time elapsed since its creation
+///
+/// `std::time::Instant` is not available in the browser, where `Date.now()` is used instead.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    started: std::time::Instant,
+    /// Milliseconds since the epoch
+    #[cfg(target_arch = "wasm32")]
+    started: f64,
+}
+
+impl Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn start() -> Self {
+        Self {
+            started: std::time::Instant::now(),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn start() -> Self {
+        Self {
+            started: js_sys::Date::now(),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn elapsed(&self) -> Duration {
+        Duration::from_secs_f64((js_sys::Date::now() - self.started).max(0.0) / 1000.0)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+/// Waits with `setTimeout`, there is no tokio timer in the browser
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn sleep(duration: Duration) {
+    let millis = duration.as_millis().min(i32::MAX as u128) as f64;
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        let global = js_sys::global();
+        if let Ok(set_timeout) = js_sys::Reflect::get(&global, &"setTimeout".into()) {
+            let set_timeout = js_sys::Function::from(set_timeout);
+            let _ = set_timeout.call2(&global, &resolve, &millis.into());
+        }
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
+/// Task retrieving a window, returning the data and the time taken
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) type WindowTask<T> = tokio::task::JoinHandle<Result<(Vec<T>, Duration)>>;
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) type WindowTask<T> = tokio::sync::oneshot::Receiver<Result<(Vec<T>, Duration)>>;
+
+/// Spawns the retrieval of a window once one of the `permits` is available,
+/// timing it from then for the `ExecutionReport`
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn spawn_window<T, F>(permits: Arc<Semaphore>, future: F) -> WindowTask<T>
+where
+    T: Send + 'static,
+    F: Future<Output = Result<Vec<T>>> + Send + 'static,
+{
+    tokio::spawn(timed(permits, future))
+}
+
+/// Spawns the retrieval of a window on the event loop of the browser, where
+/// the futures don't need to be sent between threads
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn spawn_window<T, F>(permits: Arc<Semaphore>, future: F) -> WindowTask<T>
+where
+    T: 'static,
+    F: Future<Output = Result<Vec<T>>> + 'static,
+{
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    wasm_bindgen_futures::spawn_local(async move {
+        let _ = sender.send(timed(permits, future).await);
+    });
+    receiver
+}
+
+async fn timed<T, F>(permits: Arc<Semaphore>, future: F) -> Result<(Vec<T>, Duration)>
+where
+    F: Future<Output = Result<Vec<T>>>,
+{
+    let _permit = permits.acquire_owned().await.ok();
+    let started = Stopwatch::start();
+    let data = future.await?;
+    Ok((data, started.elapsed()))
+}
+
+/// Waits for a window for at most `timeout`, if set
+///
+/// Returns `None` if the timeout expired, the task being cancelled.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn join_window<T>(
+    mut task: WindowTask<T>,
+    timeout: Option<Duration>,
+) -> Option<Result<(Vec<T>, Duration)>> {
+    let result = match timeout {
+        None => (&mut task).await,
+        Some(timeout) => match tokio::time::timeout(timeout, &mut task).await {
+            Ok(result) => result,
+            Err(_) => {
+                task.abort();
+                return None;
+            }
+        },
+    };
+    Some(
+        result
+            .map_err(crate::ApiError::from)
+            .and_then(|result| result),
+    )
+}
+
+/// Waits for a window for at most `timeout`, if set
+///
+/// Returns `None` if the timeout expired, the result of the task being ignored.
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn join_window<T>(
+    task: WindowTask<T>,
+    timeout: Option<Duration>,
+) -> Option<Result<(Vec<T>, Duration)>> {
+    let result = match timeout {
+        None => task.await,
+        Some(timeout) => self::timeout(timeout, task).await?,
+    };
+    Some(
+        result
+            .map_err(|_| {
+                crate::ApiError::Error("The retrieval of a window was interrupted".to_string())
+            })
+            .and_then(|result| result),
+    )
+}
+
+/// Output of the future if it completes within `duration`
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    tokio::time::timeout(duration, future).await.ok()
+}
+
+/// Output of the future if it completes within `duration`
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    use std::{pin::pin, task::Poll};
+
+    let mut future = pin!(future);
+    let mut timer = pin!(sleep(duration));
+    std::future::poll_fn(|context| {
+        if let Poll::Ready(output) = future.as_mut().poll(context) {
+            return Poll::Ready(Some(output));
+        }
+        timer.as_mut().poll(context).map(|()| None)
+    })
+    .await
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_timeout() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let permits = Arc::new(Semaphore::new(1));
+            let task = spawn_window(permits.clone(), async { Ok(vec![1, 2]) });
+            let (data, _) = join_window(task, Some(Duration::from_secs(5)))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(data, vec![1, 2]);
+
+            let task = spawn_window(permits, async {
+                sleep(Duration::from_secs(5)).await;
+                Ok(vec![3])
+            });
+            assert!(join_window(task, Some(Duration::from_millis(10)))
+                .await
+                .is_none());
+        });
+    }
+}
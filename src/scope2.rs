@@ -0,0 +1,239 @@
+use std::io::{self, Write};
+
+use chrono::{Months, NaiveDate};
+
+use crate::{
+    csv_field, ApiError, CarbonIntensityClient, DateRange, QueryOptions, QueryOutcome, Result,
+    Target,
+};
+
+/// Source of the intensities noted in the Scope 2 reports
+pub const SCOPE2_SOURCE: &str = "Carbon Intensity API regional forecast";
+
+/// Electricity consumed by a site over a calendar month
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonthlyConsumption {
+    pub site: String,
+    pub postcode: String,
+    /// First day of the month
+    pub month: NaiveDate,
+    pub kwh: f64,
+}
+
+/// Parses monthly consumption figures from CSV
+///
+/// Expects a header line followed by lines with the name of the site,
+/// its postcode, the month and the consumption in kWh e.g.
+///
+/// ```text
+/// site,postcode,month,kwh
+/// Head office,BS7,2024-01,10400
+/// Head office,BS7,2024-02,9800.5
+/// ```
+pub fn parse_monthly_consumption(csv: &str) -> Result<Vec<MonthlyConsumption>> {
+    csv.lines()
+        .enumerate()
+        // skip the header
+        .skip(1)
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            let invalid = || {
+                ApiError::Error(format!(
+                    "Invalid consumption on line {}: {line}",
+                    number + 1
+                ))
+            };
+            let columns: Vec<&str> = line.split(',').map(str::trim).collect();
+            let [site, postcode, month, kwh] = columns[..] else {
+                return Err(invalid());
+            };
+            Ok(MonthlyConsumption {
+                site: site.to_string(),
+                postcode: postcode.to_string(),
+                month: NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d")
+                    .map_err(|_| invalid())?,
+                kwh: kwh.parse().map_err(|_| invalid())?,
+            })
+        })
+        .collect()
+}
+
+/// Location-based Scope 2 emissions of a site for a month
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scope2Line {
+    pub consumption: MonthlyConsumption,
+    /// Mean intensity over the month, in gCO2/kWh
+    pub mean_intensity: f64,
+    /// Emissions in kgCO2, assuming a flat consumption over the month
+    pub emissions_kg: f64,
+    /// Where the intensities come from
+    pub source: &'static str,
+    /// Date the intensities were retrieved
+    pub vintage: NaiveDate,
+}
+
+impl Scope2Line {
+    fn new(consumption: MonthlyConsumption, mean_intensity: f64, vintage: NaiveDate) -> Self {
+        let emissions_kg = consumption.kwh * mean_intensity / 1000.0;
+        Self {
+            consumption,
+            mean_intensity,
+            emissions_kg,
+            source: SCOPE2_SOURCE,
+            vintage,
+        }
+    }
+}
+
+/// Computes the location-based Scope 2 emissions of each site and month
+///
+/// Uses the mean regional intensity of the postcode over the month. The postcodes
+/// of a month are retrieved as a batch, once each if repeated, within the limit of
+/// requests of the client. The month in progress is averaged up to the forecast
+/// horizon. The first error aborts the report.
+pub async fn scope2_report(
+    consumption: &[MonthlyConsumption],
+    options: &QueryOptions,
+) -> Result<Vec<Scope2Line>> {
+    let vintage = options.clock().now().date();
+    let mut months: Vec<NaiveDate> = consumption.iter().map(|item| item.month).collect();
+    months.sort_unstable();
+    months.dedup();
+
+    let mut lines = Vec::with_capacity(consumption.len());
+    for month in months {
+        let end = month + Months::new(1);
+        let range = DateRange::within_horizon(month, Some(end.into()), options.clock())?;
+        let items: Vec<(usize, &MonthlyConsumption)> = consumption
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.month == month)
+            .collect();
+        let targets: Vec<Target> = items.iter().map(|(_, item)| target(item)).collect();
+        let mut outcomes = CarbonIntensityClient::shared()
+            .get_intensities_batch_in_range(&targets, &range, options)
+            .await;
+
+        for ((index, item), target) in items.into_iter().zip(targets) {
+            let mean = match outcomes.get(&target) {
+                Some(Ok(outcome)) => mean_before(outcome, end),
+                _ => match outcomes.remove(&target) {
+                    Some(Err(error)) => return Err(error),
+                    _ => None,
+                },
+            };
+            let mean = mean.ok_or_else(|| {
+                ApiError::Error(format!(
+                    "Incomplete data for {} in {}",
+                    item.site,
+                    item.month.format("%Y-%m")
+                ))
+            })?;
+            lines.push((index, Scope2Line::new(item.clone(), mean, vintage)));
+        }
+    }
+
+    // in the order of the consumption
+    lines.sort_by_key(|(index, _)| *index);
+    Ok(lines.into_iter().map(|(_, line)| line).collect())
+}
+
+/// Target of a postcode, in upper case so that its repetitions are retrieved once
+fn target(item: &MonthlyConsumption) -> Target {
+    Target::Postcode(item.postcode.to_uppercase())
+}
+
+/// Mean intensity before the end of the month, `None` if some is missing
+fn mean_before(outcome: &QueryOutcome, end: NaiveDate) -> Option<f64> {
+    // the range includes the half-hour starting at the end
+    let values: Vec<i32> = outcome
+        .intensities
+        .iter()
+        .filter(|(time, _)| time.date() < end)
+        .map(|(_, value)| *value)
+        .collect();
+    if values.is_empty() || outcome.is_partial() {
+        return None;
+    }
+    let total: i64 = values.iter().copied().map(i64::from).sum();
+    Some(total as f64 / values.len() as f64)
+}
+
+/// Writes the Scope 2 report as CSV, the names of the sites are quoted if needed
+pub fn write_scope2_csv<W: Write>(writer: &mut W, lines: &[Scope2Line]) -> io::Result<()> {
+    writeln!(
+        writer,
+        "site,postcode,month,kwh,mean_intensity,emissions_kg,source,vintage"
+    )?;
+    for line in lines {
+        writeln!(
+            writer,
+            "{},{},{},{},{:.1},{:.1},{},{}",
+            csv_field(&line.consumption.site),
+            csv_field(&line.consumption.postcode),
+            line.consumption.month.format("%Y-%m"),
+            line.consumption.kwh,
+            line.mean_intensity,
+            line.emissions_kg,
+            line.source,
+            line.vintage
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_consumption() {
+        let csv = "site,postcode,month,kwh\nHead office, BS7 ,2024-01,10400\n\nWarehouse,RG10,2024-02,980.5\n";
+        let consumption = parse_monthly_consumption(csv).unwrap();
+        assert_eq!(
+            consumption,
+            vec![
+                MonthlyConsumption {
+                    site: "Head office".to_string(),
+                    postcode: "BS7".to_string(),
+                    month: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                    kwh: 10400.0,
+                },
+                MonthlyConsumption {
+                    site: "Warehouse".to_string(),
+                    postcode: "RG10".to_string(),
+                    month: NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+                    kwh: 980.5,
+                },
+            ]
+        );
+
+        assert!(parse_monthly_consumption("header\nHead office,BS7,2024-01").is_err());
+        assert!(parse_monthly_consumption("header\nHead office,BS7,2024-13,10").is_err());
+        assert!(parse_monthly_consumption("header\nHead office,BS7,2024-01,lots").is_err());
+    }
+
+    #[test]
+    fn write_report() {
+        let consumption = MonthlyConsumption {
+            site: "Head office".to_string(),
+            postcode: "BS7".to_string(),
+            month: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            kwh: 2000.0,
+        };
+        let vintage = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        let line = Scope2Line::new(consumption, 150.25, vintage);
+        assert_eq!(line.emissions_kg, 300.5);
+        let mut quoted = line.clone();
+        quoted.consumption.site = "Office, \"North\"".to_string();
+
+        let mut output = Vec::new();
+        write_scope2_csv(&mut output, &[line, quoted]).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "site,postcode,month,kwh,mean_intensity,emissions_kg,source,vintage\n\
+             Head office,BS7,2024-01,2000,150.2,300.5,Carbon Intensity API regional forecast,2024-03-05\n\
+             \"Office, \"\"North\"\"\",BS7,2024-01,2000,150.2,300.5,Carbon Intensity API regional forecast,2024-03-05\n"
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use std::f64::consts::PI;
+
+use chrono::{Datelike, Duration, NaiveDateTime, Timelike};
+
+use crate::IntensityForDate;
+
+/// Generator of synthetic half-hourly intensities
+///
+/// The curves combine a daily cycle peaking in the early evening, a seasonal
+/// cycle peaking in winter and some noise. Values only depend on the seed and
+/// the half-hour, so that overlapping ranges get the same values. Useful for
+/// testing or demoing without network access.
+///
+/// ```
+/// # use chrono::NaiveDate;
+/// # use carbonintensity::SyntheticProvider;
+/// let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+/// let end = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(0, 0, 0).unwrap();
+/// let provider = SyntheticProvider::new(42);
+/// let intensities = provider.intensities(start, end);
+/// assert_eq!(intensities.len(), 48);
+/// assert_eq!(intensities, SyntheticProvider::new(42).intensities(start, end));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntheticProvider {
+    seed: u64,
+    mean: f64,
+    daily_amplitude: f64,
+    seasonal_amplitude: f64,
+    noise: f64,
+}
+
+impl SyntheticProvider {
+    /// Generator with values typical of the GB grid, around 150 gCO2/kWh
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            mean: 150.0,
+            daily_amplitude: 40.0,
+            seasonal_amplitude: 50.0,
+            noise: 15.0,
+        }
+    }
+
+    /// Average intensity in gCO2/kWh
+    pub fn with_mean(mut self, mean: f64) -> Self {
+        self.mean = mean;
+        self
+    }
+
+    /// Amplitudes in gCO2/kWh of the daily and seasonal cycles
+    pub fn with_amplitudes(mut self, daily: f64, seasonal: f64) -> Self {
+        self.daily_amplitude = daily;
+        self.seasonal_amplitude = seasonal;
+        self
+    }
+
+    /// Maximum deviation in gCO2/kWh added at random to each value
+    pub fn with_noise(mut self, noise: f64) -> Self {
+        self.noise = noise;
+        self
+    }
+
+    /// Intensity of the half-hour starting at `time`
+    pub fn intensity_at(&self, time: NaiveDateTime) -> i32 {
+        let hour = f64::from(time.hour()) + f64::from(time.minute()) / 60.0;
+        // peaks at 18:00
+        let daily = (2.0 * PI * (hour - 18.0) / 24.0).cos();
+        // peaks mid-January
+        let seasonal = (2.0 * PI * (f64::from(time.ordinal0()) - 14.0) / 365.0).cos();
+
+        let half_hour = time.and_utc().timestamp().div_euclid(30 * 60) as u64;
+        let noise = unit_noise(self.seed, half_hour);
+
+        let value = self.mean
+            + self.daily_amplitude * daily
+            + self.seasonal_amplitude * seasonal
+            + self.noise * noise;
+        value.round().max(0.0) as i32
+    }
+
+    /// Intensities of the half-hours starting between `start` (inclusive)
+    /// and `end` (exclusive)
+    ///
+    /// `start` is rounded down to the half-hour.
+    pub fn intensities(&self, start: NaiveDateTime, end: NaiveDateTime) -> Vec<IntensityForDate> {
+        let minutes = i64::from(start.minute() % 30);
+        let mut time = start.with_second(0).unwrap_or(start) - Duration::minutes(minutes);
+
+        let mut intensities = Vec::new();
+        while time < end {
+            intensities.push((time, self.intensity_at(time)));
+            time += Duration::minutes(30);
+        }
+        intensities
+    }
+}
+
+/// Pseudo random value between -1 and 1 derived from the seed and
+/// the half-hour (SplitMix64)
+fn unit_noise(seed: u64, half_hour: u64) -> f64 {
+    let mut z = seed ^ half_hour.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    // 53 bits of precision
+    (z >> 11) as f64 / (1_u64 << 53) as f64 * 2.0 - 1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn time(month: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, month, 15)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn cycles() {
+        let provider = SyntheticProvider::new(1).with_noise(0.0);
+        // evening peak, night trough
+        assert!(provider.intensity_at(time(1, 18)) > provider.intensity_at(time(1, 6)));
+        // winter above summer
+        assert!(provider.intensity_at(time(1, 12)) > provider.intensity_at(time(7, 12)));
+        assert_eq!(provider.intensity_at(time(1, 18)), 240);
+
+        // never negative
+        let provider = SyntheticProvider::new(1).with_mean(10.0);
+        assert!(provider.intensity_at(time(7, 6)) >= 0);
+    }
+
+    #[test]
+    fn seeded() {
+        let (start, end) = (time(3, 0), time(3, 12));
+        let a = SyntheticProvider::new(1).intensities(start, end);
+        let b = SyntheticProvider::new(2).intensities(start, end);
+        assert_eq!(a.len(), 24);
+        assert_ne!(a, b);
+
+        // overlapping ranges agree, start rounded down to the half-hour
+        let c = SyntheticProvider::new(1).intensities(start + Duration::minutes(70), end);
+        assert_eq!(c[0].0, start + Duration::hours(1));
+        assert_eq!(c[..], a[2..]);
+    }
+
+    #[test]
+    fn noise_range() {
+        for half_hour in 0..10_000 {
+            let noise = unit_noise(7, half_hour);
+            assert!((-1.0..1.0).contains(&noise));
+        }
+    }
+}
@@ -0,0 +1,600 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Stdout, Write},
+    path::Path,
+};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    NaiveDateTime,
+};
+use serde::Serialize;
+
+use crate::{Intensity, IntensityForDate, IntensityIndex, RegionalIntensity, Target};
+
+/// Format of the data written by a `WriterSink`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum OutputFormat {
+    /// Human readable text
+    #[default]
+    Text,
+    /// JSON for a [shields.io endpoint badge](https://shields.io/badges/endpoint-badge),
+    /// only applies to the current intensity
+    Badge,
+    /// JSON array of objects with a `datetime` and an `intensity`
+    Json,
+    /// Newline delimited JSON, one object per line with a `datetime` and an `intensity`
+    Ndjson,
+}
+
+/// Representation of the dates written by a `WriterSink`
+///
+/// Parsed from `unix`, `unix-ms` or a strftime pattern.
+///
+/// ```
+/// # use chrono::NaiveDate;
+/// # use carbonintensity::DateFormat;
+/// let time = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 30, 0).unwrap();
+/// let format: DateFormat = "unix".parse().unwrap();
+/// assert_eq!(format.format(time), "1704069000");
+/// let format: DateFormat = "%d/%m/%Y %H:%M".parse().unwrap();
+/// assert_eq!(format.format(time), "01/01/2024 00:30");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum DateFormat {
+    /// Seconds since the Unix epoch
+    Unix,
+    /// Milliseconds since the Unix epoch
+    UnixMillis,
+    /// strftime pattern e.g. `%Y-%m-%dT%H:%M:%SZ`
+    Pattern(String),
+}
+
+impl Default for DateFormat {
+    fn default() -> Self {
+        Self::Pattern("%Y-%m-%d %H:%M:%S".to_string())
+    }
+}
+
+impl DateFormat {
+    /// Formats a date, interpreted as UTC
+    pub fn format(&self, time: NaiveDateTime) -> String {
+        match self {
+            DateFormat::Unix => time.and_utc().timestamp().to_string(),
+            DateFormat::UnixMillis => time.and_utc().timestamp_millis().to_string(),
+            // with the offset of UTC, for the patterns with %z or %Z
+            DateFormat::Pattern(pattern) => time.and_utc().format(pattern).to_string(),
+        }
+    }
+
+    /// Formats a date as a JSON value, the Unix timestamps are numbers
+    fn to_json(&self, time: NaiveDateTime) -> serde_json::Value {
+        match self {
+            DateFormat::Unix => time.and_utc().timestamp().into(),
+            DateFormat::UnixMillis => time.and_utc().timestamp_millis().into(),
+            DateFormat::Pattern(_) => self.format(time).into(),
+        }
+    }
+}
+
+impl std::str::FromStr for DateFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unix" => Ok(Self::Unix),
+            "unix-ms" => Ok(Self::UnixMillis),
+            pattern => {
+                if pattern.is_empty() || StrftimeItems::new(pattern).any(|i| i == Item::Error) {
+                    return Err(format!("Invalid date format: {pattern}"));
+                }
+                Ok(Self::Pattern(pattern.to_string()))
+            }
+        }
+    }
+}
+
+/// Destination for the results of a query
+///
+/// The CLI routes all its output through a sink so that embedders can
+/// send results elsewhere (HTTP endpoint, message queue, database...)
+/// by implementing this trait.
+pub trait OutputSink {
+    /// Writes the current intensity for a target
+    fn write_intensity(&mut self, target: &Target, intensity: &Intensity) -> io::Result<()>;
+
+    /// Writes the intensities for a target over a range of dates
+    fn write_intensities(
+        &mut self,
+        target: &Target,
+        intensities: &[IntensityForDate],
+    ) -> io::Result<()>;
+
+    /// Writes the region a target (e.g. a postcode) resolved to
+    ///
+    /// Ignored by default.
+    fn write_region(&mut self, _target: &Target, _region: &RegionalIntensity) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Flushes any buffered output
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Sink writing to any `Write` implementation
+///
+/// ```
+/// # use chrono::NaiveDate;
+/// # use carbonintensity::{OutputSink, Target, WriterSink};
+/// let time = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+/// let mut sink = WriterSink::new(Vec::new());
+/// sink.write_intensities(&Target::National, &[(time, 123)]).unwrap();
+/// assert_eq!(sink.into_inner(), b"2024-01-01 00:00:00, 123\n");
+/// ```
+#[derive(Debug)]
+pub struct WriterSink<W: Write> {
+    writer: W,
+    format: OutputFormat,
+    date_format: DateFormat,
+}
+
+impl<W: Write> WriterSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            format: OutputFormat::default(),
+            date_format: DateFormat::default(),
+        }
+    }
+
+    pub fn with_format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Representation of the dates of the intensities
+    pub fn with_date_format(mut self, date_format: DateFormat) -> Self {
+        self.date_format = date_format;
+        self
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl WriterSink<Stdout> {
+    /// Sink writing to the standard output
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl WriterSink<BufWriter<File>> {
+    /// Sink writing to a file, which is created or truncated
+    pub fn create_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::create(path)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> OutputSink for WriterSink<W> {
+    fn write_intensity(&mut self, target: &Target, intensity: &Intensity) -> io::Result<()> {
+        let Some(value) = intensity.value() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("No intensity sent for {target}"),
+            ));
+        };
+        match self.format {
+            OutputFormat::Text => writeln!(self.writer, "Carbon intensity for {target}: {value}"),
+            OutputFormat::Badge => {
+                let badge = Badge::new(value, intensity.index());
+                serde_json::to_writer(&mut self.writer, &badge)?;
+                writeln!(self.writer)
+            }
+            OutputFormat::Json | OutputFormat::Ndjson => {
+                serde_json::to_writer(&mut self.writer, intensity)?;
+                writeln!(self.writer)
+            }
+        }
+    }
+
+    fn write_region(&mut self, target: &Target, region: &RegionalIntensity) -> io::Result<()> {
+        match self.format {
+            OutputFormat::Text => writeln!(
+                self.writer,
+                "Region for {target}: {} ({})",
+                region.shortname, region.region as u8
+            ),
+            OutputFormat::Badge | OutputFormat::Json | OutputFormat::Ndjson => Ok(()),
+        }
+    }
+
+    fn write_intensities(
+        &mut self,
+        _target: &Target,
+        intensities: &[IntensityForDate],
+    ) -> io::Result<()> {
+        let json = |(time, value): &IntensityForDate| {
+            serde_json::json!({
+                "datetime": self.date_format.to_json(*time),
+                "intensity": value,
+            })
+        };
+        match self.format {
+            OutputFormat::Text => {
+                for (time, value) in intensities {
+                    writeln!(self.writer, "{}, {value}", self.date_format.format(*time))?;
+                }
+            }
+            OutputFormat::Badge => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Badges can only be generated for the current intensity",
+                ));
+            }
+            OutputFormat::Json => {
+                let values: Vec<_> = intensities.iter().map(json).collect();
+                serde_json::to_writer(&mut self.writer, &values)?;
+                writeln!(self.writer)?;
+            }
+            OutputFormat::Ndjson => {
+                for intensity in intensities {
+                    serde_json::to_writer(&mut self.writer, &json(intensity))?;
+                    writeln!(self.writer)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Header of the files written by a `CsvAppendSink`
+const CSV_HEADER: &str = "target,datetime,intensity";
+
+/// Format of the dates in the files written by a `CsvAppendSink`
+const CSV_DATE_FORMAT: &str = "%Y-%m-%dT%H:%MZ";
+
+/// Sink appending intensities to a CSV file
+///
+/// Each line holds the target, the start of the half-hour and the intensity.
+/// The target is written as a value which can be parsed back into a `Target`
+/// e.g. `national`, `13` for a region or `BS7` for a postcode.
+/// A file can contain data for multiple targets; combined with
+/// `CsvAppendSink::latest()` this allows to collect data incrementally.
+#[derive(Debug)]
+pub struct CsvAppendSink {
+    writer: BufWriter<File>,
+}
+
+impl CsvAppendSink {
+    /// Opens a file for appending, creating it with a header if needed
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let is_empty = file.metadata()?.len() == 0;
+        let mut writer = BufWriter::new(file);
+        if is_empty {
+            writeln!(writer, "{CSV_HEADER}")?;
+        }
+        Ok(Self { writer })
+    }
+
+    /// Latest half-hour recorded for a target in a file
+    ///
+    /// Returns `None` if the file doesn't exist or has no data for the target.
+    pub fn latest<P: AsRef<Path>>(path: P, target: &Target) -> io::Result<Option<NaiveDateTime>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error),
+        };
+
+        let key = csv_key(target);
+        let mut latest = None;
+        for line in BufReader::new(file).lines().skip(1) {
+            let line = line?;
+            let mut columns = line.split(',');
+            if columns.next() != Some(key.as_str()) {
+                continue;
+            }
+            let date = columns
+                .next()
+                .and_then(|date| NaiveDateTime::parse_from_str(date, CSV_DATE_FORMAT).ok())
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid line: {line}"))
+                })?;
+            latest = latest.max(Some(date));
+        }
+        Ok(latest)
+    }
+}
+
+impl OutputSink for CsvAppendSink {
+    fn write_intensity(&mut self, _target: &Target, _intensity: &Intensity) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Only ranges of intensities can be appended",
+        ))
+    }
+
+    fn write_intensities(
+        &mut self,
+        target: &Target,
+        intensities: &[IntensityForDate],
+    ) -> io::Result<()> {
+        let key = csv_key(target);
+        for (time, value) in intensities {
+            writeln!(
+                self.writer,
+                "{key},{},{value}",
+                time.format(CSV_DATE_FORMAT)
+            )?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Representation of a target in a CSV file
+pub(crate) fn csv_key(target: &Target) -> String {
+    match target {
+        Target::National => "national".to_string(),
+        Target::Postcode(postcode) => postcode.to_uppercase(),
+        Target::Region(region) => (*region as u8).to_string(),
+    }
+}
+
+/// Content of a shields.io endpoint badge
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Badge<'a> {
+    schema_version: u8,
+    label: &'a str,
+    message: String,
+    color: &'a str,
+}
+
+impl<'a> Badge<'a> {
+    fn new(value: i32, index: IntensityIndex) -> Self {
+        let color = match index {
+            IntensityIndex::VeryLow => "brightgreen",
+            IntensityIndex::Low => "green",
+            IntensityIndex::Moderate => "yellow",
+            IntensityIndex::High => "orange",
+            IntensityIndex::VeryHigh => "red",
+            IntensityIndex::Unknown => "lightgrey",
+        };
+        Self {
+            schema_version: 1,
+            label: "grid carbon",
+            message: format!("{value} gCO2/kWh ({index})"),
+            color,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::Region;
+
+    fn test_intensity() -> Intensity {
+        Intensity {
+            forecast: Some(250),
+            index: IntensityIndex::High,
+            actual: None,
+        }
+    }
+
+    #[test]
+    fn writer_sink_intensity() {
+        let mut sink = WriterSink::new(Vec::new());
+        sink.write_intensity(&Target::Region(Region::London), &test_intensity())
+            .unwrap();
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(output, "Carbon intensity for London: 250\n");
+    }
+
+    #[test]
+    fn writer_sink_region() {
+        let target = Target::Postcode("BS7".to_string());
+        let region = RegionalIntensity {
+            region: Region::SouthWestEngland,
+            shortname: "South West England".to_string(),
+            dno_region: None,
+            intensity: test_intensity(),
+        };
+        let mut sink = WriterSink::new(Vec::new());
+        sink.write_region(&target, &region).unwrap();
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(output, "Region for postcode BS7: South West England (11)\n");
+
+        let mut sink = WriterSink::new(Vec::new()).with_format(OutputFormat::Badge);
+        sink.write_region(&target, &region).unwrap();
+        assert!(sink.into_inner().is_empty());
+    }
+
+    #[test]
+    fn writer_sink_badge() {
+        let mut sink = WriterSink::new(Vec::new()).with_format(OutputFormat::Badge);
+        sink.write_intensity(&Target::Region(Region::London), &test_intensity())
+            .unwrap();
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(
+            output,
+            r#"{"schemaVersion":1,"label":"grid carbon","message":"250 gCO2/kWh (high)","color":"orange"}"#
+                .to_string()
+                + "\n"
+        );
+
+        // not available for ranges
+        let mut sink = WriterSink::new(Vec::new()).with_format(OutputFormat::Badge);
+        let result = sink.write_intensities(&Target::National, &[]);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn csv_append_sink() {
+        let path = std::env::temp_dir().join(format!("csv_append_sink_{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let bs7 = Target::Postcode("bs7".to_string());
+        let london = Target::Region(Region::London);
+
+        assert_eq!(CsvAppendSink::latest(&path, &bs7).unwrap(), None);
+
+        let mut sink = CsvAppendSink::open(&path).unwrap();
+        sink.write_intensities(
+            &bs7,
+            &[
+                (date.and_hms_opt(0, 0, 0).unwrap(), 120),
+                (date.and_hms_opt(0, 30, 0).unwrap(), 110),
+            ],
+        )
+        .unwrap();
+        sink.flush().unwrap();
+        drop(sink);
+
+        // appending to an existing file
+        let mut sink = CsvAppendSink::open(&path).unwrap();
+        sink.write_intensities(&london, &[(date.and_hms_opt(1, 0, 0).unwrap(), 90)])
+            .unwrap();
+        sink.flush().unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            content,
+            "target,datetime,intensity\n\
+             BS7,2024-01-01T00:00Z,120\n\
+             BS7,2024-01-01T00:30Z,110\n\
+             13,2024-01-01T01:00Z,90\n"
+        );
+
+        assert_eq!(
+            CsvAppendSink::latest(&path, &bs7).unwrap(),
+            Some(date.and_hms_opt(0, 30, 0).unwrap())
+        );
+        assert_eq!(
+            CsvAppendSink::latest(&path, &london).unwrap(),
+            Some(date.and_hms_opt(1, 0, 0).unwrap())
+        );
+        assert_eq!(
+            CsvAppendSink::latest(&path, &Target::National).unwrap(),
+            None
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn writer_sink_intensities() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let intensities = vec![
+            (date.and_hms_opt(0, 0, 0).unwrap(), 120),
+            (date.and_hms_opt(0, 30, 0).unwrap(), 110),
+        ];
+
+        let mut sink = WriterSink::new(Vec::new());
+        sink.write_intensities(&Target::National, &intensities)
+            .unwrap();
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(
+            output,
+            "2024-01-01 00:00:00, 120\n2024-01-01 00:30:00, 110\n"
+        );
+
+        let mut sink = WriterSink::new(Vec::new()).with_date_format(DateFormat::UnixMillis);
+        sink.write_intensities(&Target::National, &intensities)
+            .unwrap();
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(output, "1704067200000, 120\n1704069000000, 110\n");
+    }
+
+    #[test]
+    fn writer_sink_json() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let intensities = vec![
+            (date.and_hms_opt(0, 0, 0).unwrap(), 120),
+            (date.and_hms_opt(0, 30, 0).unwrap(), 110),
+        ];
+
+        let mut sink = WriterSink::new(Vec::new())
+            .with_format(OutputFormat::Json)
+            .with_date_format(DateFormat::Unix);
+        sink.write_intensities(&Target::National, &intensities)
+            .unwrap();
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(
+            output,
+            r#"[{"datetime":1704067200,"intensity":120},{"datetime":1704069000,"intensity":110}]"#
+                .to_string()
+                + "\n"
+        );
+
+        let mut sink = WriterSink::new(Vec::new())
+            .with_format(OutputFormat::Ndjson)
+            .with_date_format(DateFormat::UnixMillis);
+        sink.write_intensities(&Target::National, &intensities)
+            .unwrap();
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(
+            output,
+            "{\"datetime\":1704067200000,\"intensity\":120}\n\
+             {\"datetime\":1704069000000,\"intensity\":110}\n"
+        );
+
+        // patterns give strings
+        let mut sink = WriterSink::new(Vec::new()).with_format(OutputFormat::Ndjson);
+        sink.write_intensities(&Target::National, &intensities[..1])
+            .unwrap();
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(
+            output,
+            "{\"datetime\":\"2024-01-01 00:00:00\",\"intensity\":120}\n"
+        );
+
+        // current intensity
+        let mut sink = WriterSink::new(Vec::new()).with_format(OutputFormat::Json);
+        sink.write_intensity(&Target::National, &test_intensity())
+            .unwrap();
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(
+            output,
+            "{\"forecast\":250,\"index\":\"high\",\"actual\":null}\n"
+        );
+    }
+
+    #[test]
+    fn parse_date_format() {
+        assert_eq!("unix".parse(), Ok(DateFormat::Unix));
+        assert_eq!("unix-ms".parse(), Ok(DateFormat::UnixMillis));
+        assert_eq!(
+            "%Y%m%d".parse(),
+            Ok(DateFormat::Pattern("%Y%m%d".to_string()))
+        );
+        assert!("%Y-%Q".parse::<DateFormat>().is_err());
+        assert!("".parse::<DateFormat>().is_err());
+
+        // offsets, of UTC
+        let time = NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 30, 0)
+            .unwrap();
+        let format: DateFormat = "%H:%M%:z %z %Z".parse().unwrap();
+        assert_eq!(format.format(time), "00:30+00:00 +0000 UTC");
+    }
+}
@@ -0,0 +1,187 @@
+use chrono::{Duration, NaiveDateTime};
+use serde::Deserialize;
+
+use crate::{
+    parse_date, ApiError, CarbonIntensityClient, Clock, DateBound, DateRange, IntensityIndex,
+    Result, SystemClock,
+};
+
+/// Longest range accepted by the statistics endpoint
+const MAX_STATS_RANGE: Duration = Duration::days(30);
+
+/// Statistics of the intensity over a block of time, computed by the API
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntensityStats {
+    /// Start of the block
+    pub from: NaiveDateTime,
+    /// End of the block
+    pub to: NaiveDateTime,
+    /// Highest intensity of the block, in gCO2/kWh
+    pub max: i32,
+    pub average: i32,
+    pub min: i32,
+    /// Index of the average intensity
+    pub index: IntensityIndex,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatsValues {
+    max: i32,
+    average: i32,
+    min: i32,
+    index: IntensityIndex,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatsPeriod {
+    from: String,
+    to: String,
+    intensity: StatsValues,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatsData {
+    data: Vec<StatsPeriod>,
+}
+
+impl TryFrom<StatsPeriod> for IntensityStats {
+    type Error = ApiError;
+
+    fn try_from(period: StatsPeriod) -> Result<Self> {
+        Ok(Self {
+            from: parse_date(&period.from)?,
+            to: parse_date(&period.to)?,
+            max: period.intensity.max,
+            average: period.intensity.average,
+            min: period.intensity.min,
+            index: period.intensity.index,
+        })
+    }
+}
+
+/// Path of the statistics for a range, validating the range and the blocks
+fn stats_path(start: &str, end: &str, block_hours: Option<u8>) -> Result<String> {
+    let now = SystemClock.now();
+    let start = DateBound::parse_relative(start, now)?.datetime();
+    let end = DateBound::parse_relative(end, now)?.datetime();
+    range_stats_path(start, end, block_hours)
+}
+
+/// Same as `stats_path` with the dates parsed
+fn range_stats_path(
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    block_hours: Option<u8>,
+) -> Result<String> {
+    let format = |date: NaiveDateTime| date.format("%Y-%m-%dT%H:%MZ").to_string();
+    if end <= start || end - start > MAX_STATS_RANGE {
+        return Err(ApiError::Error(format!(
+            "Invalid range for statistics: {start} to {end}, expected at most {} days",
+            MAX_STATS_RANGE.num_days()
+        )));
+    }
+
+    let path = format!("intensity/stats/{}/{}", format(start), format(end));
+    match block_hours {
+        None => Ok(path),
+        Some(hours @ 1..=24) => Ok(format!("{path}/{hours}")),
+        Some(hours) => Err(ApiError::Error(format!(
+            "Invalid block of {hours} hours, expected between 1 and 24"
+        ))),
+    }
+}
+
+/// Maximum, average and minimum national intensity over a range, computed by the API
+///
+/// The range is at most 30 days. Without `block_hours`, a single set of statistics
+/// covers the whole range, otherwise there is one per block of that many hours (1 to 24).
+///
+/// Uses <https://api.carbonintensity.org.uk/intensity/stats/2024-01-01T00:00Z/2024-01-08T00:00Z/24>
+pub async fn get_intensity_stats(
+    start: &str,
+    end: &str,
+    block_hours: Option<u8>,
+) -> Result<Vec<IntensityStats>> {
+    CarbonIntensityClient::shared()
+        .get_intensity_stats(start, end, block_hours)
+        .await
+}
+
+/// Same as `get_intensity_stats` for a range validated beforehand
+pub async fn get_intensity_stats_in_range(
+    range: &DateRange,
+    block_hours: Option<u8>,
+) -> Result<Vec<IntensityStats>> {
+    CarbonIntensityClient::shared()
+        .get_intensity_stats_in_range(range, block_hours)
+        .await
+}
+
+impl CarbonIntensityClient {
+    /// Statistics of the national intensity over a range, see `get_intensity_stats()`
+    pub async fn get_intensity_stats(
+        &self,
+        start: &str,
+        end: &str,
+        block_hours: Option<u8>,
+    ) -> Result<Vec<IntensityStats>> {
+        let url = format!(
+            "{}/{}",
+            self.base_url(),
+            stats_path(start, end, block_hours)?
+        );
+        self.get_stats(&url).await
+    }
+
+    /// Statistics of the national intensity over a range validated beforehand,
+    /// see `get_intensity_stats_in_range()`
+    pub async fn get_intensity_stats_in_range(
+        &self,
+        range: &DateRange,
+        block_hours: Option<u8>,
+    ) -> Result<Vec<IntensityStats>> {
+        let path = range_stats_path(range.start(), range.end(), block_hours)?;
+        self.get_stats(&format!("{}/{path}", self.base_url())).await
+    }
+
+    async fn get_stats(&self, url: &str) -> Result<Vec<IntensityStats>> {
+        self.get_response::<StatsData>(url)
+            .await?
+            .data
+            .into_iter()
+            .map(IntensityStats::try_from)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path() {
+        assert_eq!(
+            stats_path("2024-01-01", "2024-01-08", None).unwrap(),
+            "intensity/stats/2024-01-01T00:00Z/2024-01-08T00:00Z"
+        );
+        assert_eq!(
+            stats_path("2024-01-01", "2024-01-08T12:00Z", Some(24)).unwrap(),
+            "intensity/stats/2024-01-01T00:00Z/2024-01-08T12:00Z/24"
+        );
+        assert!(stats_path("2024-01-01", "2024-01-08", Some(0)).is_err());
+        assert!(stats_path("2024-01-01", "2024-01-08", Some(25)).is_err());
+        assert!(stats_path("2024-01-01", "2024-03-01", None).is_err());
+        assert!(stats_path("2024-01-08", "2024-01-01", None).is_err());
+    }
+
+    #[test]
+    fn parse() {
+        let json_str = r#"{"data":[{"from":"2024-01-01T00:00Z","to":"2024-01-02T00:00Z",
+            "intensity":{"max":220,"average":151,"min":98,"index":"moderate"}}]}"#;
+        let StatsData { data } = serde_json::from_str(json_str).unwrap();
+        let stats = IntensityStats::try_from(data.into_iter().next().unwrap()).unwrap();
+        assert_eq!(stats.to, parse_date("2024-01-02").unwrap());
+        assert_eq!((stats.max, stats.average, stats.min), (220, 151, 98));
+        assert_eq!(stats.index, IntensityIndex::Moderate);
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+
+use crate::{
+    greenest::contiguous_windows, Decision, HistoricalSlot, IntensityForDate, Job, Strategy,
+    TimeWindow,
+};
+
+/// Starts every job as soon as it is released, the naive baseline
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunImmediately;
+
+impl Strategy for RunImmediately {
+    fn name(&self) -> String {
+        "run immediately".to_string()
+    }
+
+    fn decide(&mut self, _: NaiveDateTime, _: &Job, _: &[IntensityForDate]) -> Decision {
+        Decision::Start
+    }
+}
+
+/// Starts jobs within a daily window, e.g. overnight
+///
+/// Jobs are started at their latest start if the window doesn't come in time.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedWindow {
+    window: TimeWindow,
+}
+
+impl FixedWindow {
+    pub fn new(window: TimeWindow) -> Self {
+        Self { window }
+    }
+}
+
+impl Strategy for FixedWindow {
+    fn name(&self) -> String {
+        "fixed window".to_string()
+    }
+
+    fn decide(&mut self, now: NaiveDateTime, job: &Job, _: &[IntensityForDate]) -> Decision {
+        if self.window.contains(now.time()) || now >= job.latest_start() {
+            Decision::Start
+        } else {
+            Decision::Wait
+        }
+    }
+}
+
+/// Starts jobs once the forecast intensity is at or below a threshold
+///
+/// Jobs are started at their latest start if the intensity doesn't drop in time.
+#[derive(Debug, Clone, Copy)]
+pub struct Threshold {
+    max_intensity: i32,
+}
+
+impl Threshold {
+    /// Threshold in gCO2/kWh
+    pub fn new(max_intensity: i32) -> Self {
+        Self { max_intensity }
+    }
+}
+
+impl Strategy for Threshold {
+    fn name(&self) -> String {
+        format!("threshold {}", self.max_intensity)
+    }
+
+    fn decide(&mut self, now: NaiveDateTime, job: &Job, forecast: &[IntensityForDate]) -> Decision {
+        let below = forecast
+            .first()
+            .is_some_and(|(_, intensity)| *intensity <= self.max_intensity);
+        if below || now >= job.latest_start() {
+            Decision::Start
+        } else {
+            Decision::Wait
+        }
+    }
+}
+
+/// Starts jobs at the best time given the actual intensities
+///
+/// Knows the future so can't be used for real, but gives the best
+/// any strategy could achieve.
+#[derive(Debug, Clone)]
+pub struct PerfectForesight {
+    actuals: HashMap<NaiveDateTime, i32>,
+}
+
+impl PerfectForesight {
+    /// Strategy cheating with the history replayed by the backtest
+    pub fn new(history: &[HistoricalSlot]) -> Self {
+        let actuals = history
+            .iter()
+            .map(|slot| (slot.start, slot.actual))
+            .collect();
+        Self { actuals }
+    }
+
+    /// Sum of the actual intensities of a job running in the half-hours
+    fn cost(&self, window: &[IntensityForDate]) -> i64 {
+        window
+            .iter()
+            .map(|(time, _)| i64::from(self.actuals.get(time).copied().unwrap_or(i32::MAX)))
+            .sum()
+    }
+}
+
+impl Strategy for PerfectForesight {
+    fn name(&self) -> String {
+        "perfect foresight".to_string()
+    }
+
+    fn decide(&mut self, _: NaiveDateTime, job: &Job, forecast: &[IntensityForDate]) -> Decision {
+        // no other choice left to complete by the deadline
+        if forecast.len() <= job.slots {
+            return Decision::Start;
+        }
+        let best = contiguous_windows(forecast, job.slots, |(time, _)| *time)
+            .min_by_key(|(_, window)| self.cost(window))
+            .map_or(0, |(start, _)| start);
+        if best == 0 {
+            Decision::Start
+        } else {
+            Decision::Wait
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate, NaiveTime};
+
+    use super::*;
+    use crate::backtest;
+
+    fn half_hour(n: i64) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            + Duration::minutes(30 * n)
+    }
+
+    /// Forecasts are wrong: the lowest one is at the start, actually the highest
+    fn history() -> Vec<HistoricalSlot> {
+        let values = [(100, 300), (200, 200), (250, 100), (260, 120), (250, 250)];
+        values
+            .iter()
+            .enumerate()
+            .map(|(n, (forecast, actual))| HistoricalSlot {
+                start: half_hour(n as i64),
+                forecast: *forecast,
+                actual: *actual,
+            })
+            .collect()
+    }
+
+    fn job() -> Job {
+        Job {
+            release: half_hour(0),
+            deadline: half_hour(5),
+            slots: 2,
+            kwh: 2.0,
+        }
+    }
+
+    fn start_of(strategy: &mut dyn Strategy) -> NaiveDateTime {
+        backtest(strategy, &history(), &[job()]).unwrap().outcomes[0].start
+    }
+
+    #[test]
+    fn run_immediately() {
+        assert_eq!(start_of(&mut RunImmediately), half_hour(0));
+    }
+
+    #[test]
+    fn fixed_window() {
+        let window = TimeWindow::new(
+            NaiveTime::from_hms_opt(1, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(2, 0, 0).unwrap(),
+        );
+        assert_eq!(start_of(&mut FixedWindow::new(window)), half_hour(2));
+
+        // window too late, started at the latest start
+        let window = TimeWindow::new(
+            NaiveTime::from_hms_opt(20, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+        );
+        assert_eq!(start_of(&mut FixedWindow::new(window)), half_hour(3));
+    }
+
+    #[test]
+    fn threshold() {
+        // the first forecast is below
+        assert_eq!(start_of(&mut Threshold::new(150)), half_hour(0));
+        assert_eq!(start_of(&mut Threshold::new(50)), half_hour(3));
+        assert_eq!(Threshold::new(50).name(), "threshold 50");
+    }
+
+    #[test]
+    fn perfect_foresight() {
+        let mut strategy = PerfectForesight::new(&history());
+        assert_eq!(start_of(&mut strategy), half_hour(2));
+
+        // beats the naive baseline
+        let best = backtest(&mut strategy, &history(), &[job()]).unwrap();
+        let naive = backtest(&mut RunImmediately, &history(), &[job()]).unwrap();
+        assert!(best.total_emissions_kg() < naive.total_emissions_kg());
+        assert_eq!(best.missed_deadlines(), 0);
+    }
+}
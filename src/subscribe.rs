@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use futures::{stream, Stream};
+
+use crate::{
+    check_forecast, runtime, ApiError, CarbonIntensityClient, IntensityIndex, IntensityRecord,
+    Result, Target,
+};
+
+/// Stream of the current intensity of a target, emitting a record whenever the
+/// API publishes a new half-hour or the index of the current one changes
+///
+/// The current half-hour is emitted straight away, then the API is polled every
+/// `interval`. Transient errors (see `ApiError::is_transient()`) are ignored until
+/// the next poll, other errors are emitted without ending the stream. A zero
+/// interval is an error, the stream then ending.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use carbonintensity::{subscribe, Target};
+/// use futures::StreamExt;
+///
+/// # async fn run() {
+/// let mut updates = std::pin::pin!(subscribe(&Target::National, Duration::from_secs(300)));
+/// while let Some(Ok(record)) = updates.next().await {
+///     println!("{}: {} ({:?})", record.from, record.intensity(), record.index);
+/// }
+/// # }
+/// ```
+pub fn subscribe(
+    target: &Target,
+    interval: Duration,
+) -> impl Stream<Item = Result<IntensityRecord>> {
+    CarbonIntensityClient::shared().subscribe(target, interval)
+}
+
+impl CarbonIntensityClient {
+    /// Stream of the changes of the current intensity, see `subscribe()`
+    pub fn subscribe(
+        &self,
+        target: &Target,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<IntensityRecord>> {
+        let subscription = Subscription {
+            client: self.clone(),
+            target: target.clone(),
+            interval,
+            last: None,
+            polled: false,
+            ended: false,
+        };
+        stream::unfold(subscription, |mut subscription| async move {
+            if subscription.ended {
+                return None;
+            }
+            let change = subscription.next_change().await;
+            Some((change, subscription))
+        })
+    }
+}
+
+struct Subscription {
+    client: CarbonIntensityClient,
+    target: Target,
+    interval: Duration,
+    /// Half-hour and index last emitted
+    last: Option<(NaiveDateTime, IntensityIndex)>,
+    polled: bool,
+    ended: bool,
+}
+
+impl Subscription {
+    /// Polls until the current half-hour changes or an error which isn't transient
+    async fn next_change(&mut self) -> Result<IntensityRecord> {
+        if self.interval.is_zero() {
+            self.ended = true;
+            return Err(ApiError::Error(
+                "The interval must be longer than 0".to_string(),
+            ));
+        }
+        loop {
+            if self.polled {
+                runtime::sleep(self.interval).await;
+            }
+            self.polled = true;
+
+            let record = self
+                .client
+                .get_current_data(&self.target)
+                .await
+                .and_then(|data| {
+                    let record = IntensityRecord::try_from(&data)?;
+                    check_forecast(data.intensity).map(|_| record)
+                });
+            match record {
+                Ok(record) => {
+                    let current = Some((record.from, record.index));
+                    if current != self.last {
+                        self.last = current;
+                        return Ok(record);
+                    }
+                }
+                Err(error) if error.is_transient() => {}
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
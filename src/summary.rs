@@ -0,0 +1,146 @@
+use std::fmt::Display;
+
+use crate::IntensityForDate;
+
+/// Percentiles computed by `summarise`
+pub const DEFAULT_PERCENTILES: [f64; 4] = [5.0, 25.0, 75.0, 95.0];
+
+/// Distribution of the intensities over a range, in gCO2/kWh
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntensitySummary {
+    /// Number of half-hours
+    pub count: usize,
+    /// Lowest intensity, with the first half-hour it occurred
+    pub min: IntensityForDate,
+    /// Highest intensity, with the first half-hour it occurred
+    pub max: IntensityForDate,
+    pub mean: f64,
+    /// Mean of the two middle values for an even count
+    pub median: f64,
+    /// Each percentile requested with its value, by nearest rank
+    pub percentiles: Vec<(f64, i32)>,
+}
+
+impl IntensitySummary {
+    /// Value of a percentile, if it was requested
+    pub fn percentile(&self, percentile: f64) -> Option<i32> {
+        self.percentiles
+            .iter()
+            .find(|(p, _)| *p == percentile)
+            .map(|(_, value)| *value)
+    }
+}
+
+/// Minimum, maximum, mean, median and `DEFAULT_PERCENTILES` of intensities,
+/// e.g. as returned by `get_intensities`
+///
+/// Returns `None` if there are none.
+///
+/// ```
+/// # use chrono::NaiveDate;
+/// # use carbonintensity::summarise;
+/// let time = |m| NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, m, 0).unwrap();
+/// let summary = summarise(&[(time(0), 200), (time(30), 100)]).unwrap();
+/// assert_eq!(summary.min, (time(30), 100));
+/// assert_eq!(summary.median, 150.0);
+/// assert_eq!(summary.percentile(95.0), Some(200));
+/// ```
+pub fn summarise(intensities: &[IntensityForDate]) -> Option<IntensitySummary> {
+    summarise_with_percentiles(intensities, &DEFAULT_PERCENTILES)
+}
+
+/// Same as `summarise` with other percentiles, between 0 and 100
+pub fn summarise_with_percentiles(
+    intensities: &[IntensityForDate],
+    percentiles: &[f64],
+) -> Option<IntensitySummary> {
+    // the first half-hour wins ties, max_by_key returning the last one
+    let min = *intensities.iter().min_by_key(|(_, intensity)| *intensity)?;
+    let max = *intensities
+        .iter()
+        .rev()
+        .max_by_key(|(_, intensity)| *intensity)?;
+
+    let count = intensities.len();
+    let mut values: Vec<i32> = intensities.iter().map(|(_, i)| *i).collect();
+    values.sort_unstable();
+    let mean = values.iter().map(|v| f64::from(*v)).sum::<f64>() / count as f64;
+    let median = if count % 2 == 0 {
+        f64::from(values[count / 2 - 1] + values[count / 2]) / 2.0
+    } else {
+        f64::from(values[count / 2])
+    };
+
+    let percentiles = percentiles
+        .iter()
+        .map(|percentile| {
+            // nearest rank
+            let rank = (count as f64 * percentile / 100.0).ceil() as usize;
+            (*percentile, values[rank.clamp(1, count) - 1])
+        })
+        .collect();
+
+    Some(IntensitySummary {
+        count,
+        min,
+        max,
+        mean,
+        median,
+        percentiles,
+    })
+}
+
+impl Display for IntensitySummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "half-hours: {}", self.count)?;
+        writeln!(f, "min: {} at {}", self.min.1, self.min.0)?;
+        writeln!(f, "max: {} at {}", self.max.1, self.max.0)?;
+        writeln!(f, "mean: {:.1}", self.mean)?;
+        writeln!(f, "median: {:.1}", self.median)?;
+        for (percentile, value) in &self.percentiles {
+            writeln!(f, "p{percentile}: {value}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    use super::*;
+
+    fn time(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn summary() {
+        assert_eq!(summarise(&[]), None);
+
+        let intensities: Vec<IntensityForDate> = [120, 80, 200, 80, 150]
+            .into_iter()
+            .enumerate()
+            .map(|(i, intensity)| (time(i as u32, 0), intensity))
+            .collect();
+        let summary = summarise_with_percentiles(&intensities, &[0.0, 50.0, 90.0, 100.0]).unwrap();
+        assert_eq!(summary.count, 5);
+        assert_eq!(summary.min, (time(1, 0), 80));
+        assert_eq!(summary.max, (time(2, 0), 200));
+        assert_eq!(summary.mean, 126.0);
+        assert_eq!(summary.median, 120.0);
+        assert_eq!(
+            summary.percentiles,
+            vec![(0.0, 80), (50.0, 120), (90.0, 200), (100.0, 200)]
+        );
+        assert_eq!(summary.percentile(50.0), Some(120));
+        assert_eq!(summary.percentile(75.0), None);
+
+        let text = summary.to_string();
+        assert!(text.contains("median: 120.0\n"));
+        assert!(text.contains("p90: 200\n"));
+    }
+}
@@ -1,7 +1,7 @@
 use crate::Region;
 
 /// Carbon intensity target, e.g. a postcode or a region
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Target {
     National,
     Postcode(String),
@@ -11,7 +11,8 @@ pub enum Target {
 /// Creates a `Target` from a `String`
 ///
 /// If the string is equal to 'national' or is empty returns `Target::National.
-/// If it contains a valid `Region` id this returns a `Target::Region`,
+/// If it contains a valid `Region` id or the name of the network operator
+/// of a region (see `Region::from_dno`) this returns a `Target::Region`,
 /// otherwise it returns a `Target::Postcode`.
 ///
 /// Note how this is infallible because it balls back to `Target::Postcode`.
@@ -21,6 +22,9 @@ pub enum Target {
 /// let target = Target::from("13".to_string());
 /// assert_eq!(target, Target::Region(Region::London));
 ///
+/// let target = Target::from("UKPN London".to_string());
+/// assert_eq!(target, Target::Region(Region::London));
+///
 /// let target = Target::from("BS7".to_string());
 /// let bs7 = Target::Postcode("BS7".to_string());
 /// assert_eq!(target, bs7);
@@ -35,6 +39,9 @@ impl From<String> for Target {
         if let Ok(region) = s.parse::<Region>() {
             return Self::Region(region);
         }
+        if let Ok(region) = Region::from_dno(&s) {
+            return Self::Region(region);
+        }
 
         // Assumes the string was a postcode
         Self::Postcode(s)
@@ -0,0 +1,201 @@
+use std::str::FromStr;
+
+use chrono::{NaiveTime, TimeZone};
+
+use crate::{ApiError, IntensityForDate};
+
+/// Daily time window of a tariff, e.g. 00:30-07:30
+///
+/// The window can span midnight (e.g. 22:00-08:00). The start
+/// is inclusive and the end exclusive. The times are local, see `summarise_tariff()`.
+///
+/// ```
+/// # use chrono::NaiveTime;
+/// # use carbonintensity::TimeWindow;
+/// let window: TimeWindow = "22:00-08:00".parse().unwrap();
+/// assert!(window.contains(NaiveTime::from_hms_opt(23, 30, 0).unwrap()));
+/// assert!(!window.contains(NaiveTime::from_hms_opt(8, 0, 0).unwrap()));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl TimeWindow {
+    pub fn new(start: NaiveTime, end: NaiveTime) -> Self {
+        Self { start, end }
+    }
+
+    /// Typical Economy 7 off-peak window, 00:30-07:30
+    pub fn economy_7() -> Self {
+        Self {
+            start: NaiveTime::from_hms_opt(0, 30, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(7, 30, 0).unwrap(),
+        }
+    }
+
+    /// Returns `true` if the time falls within the window
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            // spans midnight
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Parses windows formatted as `HH:MM-HH:MM`
+impl FromStr for TimeWindow {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| ApiError::Error(format!("Invalid time window: {s}")))?;
+        let start = NaiveTime::parse_from_str(start.trim(), "%H:%M")?;
+        let end = NaiveTime::parse_from_str(end.trim(), "%H:%M")?;
+        Ok(Self::new(start, end))
+    }
+}
+
+/// Average intensities during the peak and off-peak hours of a tariff
+#[derive(Debug, Clone, PartialEq)]
+pub struct TariffSummary {
+    /// Mean intensity during the off-peak windows, `None` if there was no value
+    pub off_peak_mean: Option<f64>,
+    /// Number of values within the off-peak windows
+    pub off_peak_count: usize,
+    /// Mean intensity outside of the off-peak windows, `None` if there was no value
+    pub peak_mean: Option<f64>,
+    /// Number of values outside of the off-peak windows
+    pub peak_count: usize,
+}
+
+impl TariffSummary {
+    /// Returns `true` if the off-peak hours had a lower intensity on average
+    ///
+    /// `None` if either the peak or off-peak hours had no value.
+    pub fn off_peak_is_greener(&self) -> Option<bool> {
+        Some(self.off_peak_mean? < self.peak_mean?)
+    }
+}
+
+/// Splits the intensities between off-peak and peak hours and averages them
+///
+/// A half-hour is off-peak if its start falls in any of the `off_peak` windows,
+/// every other half-hour is considered peak. The dates of the intensities are in UTC
+/// and the windows in the local time of `timezone`, e.g. `chrono_tz::Europe::London`
+/// or `chrono::Local` for the tariffs in the UK.
+pub fn summarise_tariff<Tz: TimeZone>(
+    intensities: &[IntensityForDate],
+    off_peak: &[TimeWindow],
+    timezone: &Tz,
+) -> TariffSummary {
+    let mut off_peak_total = 0_i64;
+    let mut off_peak_count = 0;
+    let mut peak_total = 0_i64;
+    let mut peak_count = 0;
+
+    for (time, value) in intensities {
+        let local = timezone.from_utc_datetime(time).time();
+        if off_peak.iter().any(|window| window.contains(local)) {
+            off_peak_total += i64::from(*value);
+            off_peak_count += 1;
+        } else {
+            peak_total += i64::from(*value);
+            peak_count += 1;
+        }
+    }
+
+    let mean = |total: i64, count: usize| (count > 0).then(|| total as f64 / count as f64);
+
+    TariffSummary {
+        off_peak_mean: mean(off_peak_total, off_peak_count),
+        off_peak_count,
+        peak_mean: mean(peak_total, peak_count),
+        peak_count,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, NaiveDate, Utc};
+
+    use super::*;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn window_contains() {
+        let economy_7 = TimeWindow::economy_7();
+        assert!(economy_7.contains(time(0, 30)));
+        assert!(economy_7.contains(time(7, 0)));
+        assert!(!economy_7.contains(time(7, 30)));
+        assert!(!economy_7.contains(time(0, 0)));
+
+        let overnight = TimeWindow::new(time(22, 0), time(8, 0));
+        assert!(overnight.contains(time(22, 0)));
+        assert!(overnight.contains(time(0, 0)));
+        assert!(!overnight.contains(time(12, 0)));
+    }
+
+    #[test]
+    fn window_from_str() {
+        assert_eq!(
+            "00:30-07:30".parse::<TimeWindow>().unwrap(),
+            TimeWindow::economy_7()
+        );
+        assert_eq!(
+            " 22:00 - 08:00 ".parse::<TimeWindow>().unwrap(),
+            TimeWindow::new(time(22, 0), time(8, 0))
+        );
+        assert!("22:00".parse::<TimeWindow>().is_err());
+        assert!(matches!(
+            "22:00-25:00".parse::<TimeWindow>(),
+            Err(ApiError::DateParseError(_))
+        ));
+    }
+
+    #[test]
+    fn summary() {
+        let day = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let intensities = vec![
+            (day.and_time(time(0, 30)), 100),
+            (day.and_time(time(1, 0)), 120),
+            (day.and_time(time(12, 0)), 200),
+            (day.and_time(time(18, 0)), 250),
+            (day.and_time(time(18, 30)), 270),
+        ];
+
+        let summary = summarise_tariff(&intensities, &[TimeWindow::economy_7()], &Utc);
+        assert_eq!(
+            summary,
+            TariffSummary {
+                off_peak_mean: Some(110.0),
+                off_peak_count: 2,
+                peak_mean: Some(240.0),
+                peak_count: 3,
+            }
+        );
+        assert_eq!(summary.off_peak_is_greener(), Some(true));
+
+        let summary = summarise_tariff(&[], &[TimeWindow::economy_7()], &Utc);
+        assert_eq!(summary.off_peak_mean, None);
+        assert_eq!(summary.off_peak_is_greener(), None);
+
+        // 23:30 UTC is 00:30 in BST, off-peak, and 06:30 UTC is 07:30, peak
+        let bst = FixedOffset::east_opt(3600).unwrap();
+        let intensities = vec![
+            (day.and_time(time(0, 0)), 100),
+            (day.and_time(time(6, 30)), 200),
+            (day.and_time(time(23, 30)), 120),
+        ];
+        let summary = summarise_tariff(&intensities, &[TimeWindow::economy_7()], &bst);
+        assert_eq!((summary.off_peak_count, summary.peak_count), (2, 1));
+        assert_eq!(summary.peak_mean, Some(200.0));
+    }
+}
@@ -0,0 +1,291 @@
+//! Fixtures and canned responses, for testing code using the crate without
+//! sending requests to the API
+//!
+//! `MockApi` answers the requests of a `CarbonIntensityClient` for every endpoint
+//! with the sample payloads of this module, or with the responses registered for
+//! a path. The fixtures build the types of the crate whose fields are private.
+//!
+//! ```
+//! use carbonintensity::{test_util::MockApi, Target, TransportResponse};
+//!
+//! let error = r#"{"error":{"code":"400 Bad Request","message":"Please enter a valid postcode"}}"#;
+//! let api = MockApi::new().with_response(
+//!     "/regional/postcode/ZE9",
+//!     TransportResponse::new(reqwest::StatusCode::BAD_REQUEST, error),
+//! );
+//! let client = api.client();
+//! let runtime = tokio::runtime::Builder::new_current_thread()
+//!     .enable_all()
+//!     .build()
+//!     .unwrap();
+//!
+//! assert_eq!(runtime.block_on(client.get_intensity(&Target::National)).unwrap(), 116);
+//! let postcode = Target::Postcode("ZE9".to_string());
+//! assert!(runtime.block_on(client.get_intensity(&postcode)).is_err());
+//! assert_eq!(api.requests(), vec!["/intensity", "/regional/postcode/ZE9"]);
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use chrono::Datelike;
+use reqwest::StatusCode;
+
+use crate::{
+    parse_date, thresholds_for_year, CarbonIntensityClient, Data, Fuel, GenerationMix, Intensity,
+    Region, RegionData, Transport, TransportFuture, TransportResponse,
+};
+
+/// Current national intensity, also served for the national ranges
+pub const NATIONAL_INTENSITY: &str = r#"{"data":[
+{"from":"2024-01-01T00:00Z","to":"2024-01-01T00:30Z","intensity":{"forecast":120,"actual":116,"index":"moderate"}},
+{"from":"2024-01-01T00:30Z","to":"2024-01-01T01:00Z","intensity":{"forecast":115,"actual":null,"index":"low"}}
+]}"#;
+
+/// Current intensity of London, as sent for a region or a postcode
+pub const REGIONAL_INTENSITY: &str = r#"{"data":[{"regionid":13,"dnoregion":"UKPN London","shortname":"London","data":[
+{"from":"2024-01-01T00:00Z","to":"2024-01-01T00:30Z","intensity":{"forecast":150,"index":"moderate"},"generationmix":[{"fuel":"biomass","perc":5.0},{"fuel":"coal","perc":0.0},{"fuel":"imports","perc":20.0},{"fuel":"gas","perc":35.0},{"fuel":"nuclear","perc":15.0},{"fuel":"other","perc":0.0},{"fuel":"hydro","perc":0.0},{"fuel":"solar","perc":0.0},{"fuel":"wind","perc":25.0}]}
+]}]}"#;
+
+/// Intensities of London over a range
+pub const REGIONAL_RANGE: &str = r#"{"data":{"regionid":13,"dnoregion":"UKPN London","shortname":"London","data":[
+{"from":"2024-01-01T00:00Z","to":"2024-01-01T00:30Z","intensity":{"forecast":150,"index":"moderate"},"generationmix":[{"fuel":"biomass","perc":5.0},{"fuel":"coal","perc":0.0},{"fuel":"imports","perc":20.0},{"fuel":"gas","perc":35.0},{"fuel":"nuclear","perc":15.0},{"fuel":"other","perc":0.0},{"fuel":"hydro","perc":0.0},{"fuel":"solar","perc":0.0},{"fuel":"wind","perc":25.0}]},
+{"from":"2024-01-01T00:30Z","to":"2024-01-01T01:00Z","intensity":{"forecast":140,"index":"moderate"},"generationmix":[{"fuel":"biomass","perc":5.0},{"fuel":"coal","perc":0.0},{"fuel":"imports","perc":20.0},{"fuel":"gas","perc":30.0},{"fuel":"nuclear","perc":15.0},{"fuel":"other","perc":0.0},{"fuel":"hydro","perc":0.0},{"fuel":"solar","perc":0.0},{"fuel":"wind","perc":30.0}]}
+]}}"#;
+
+/// Intensities of North Scotland and London, current or over a range
+pub const ALL_REGIONS: &str = r#"{"data":[{"from":"2024-01-01T00:00Z","to":"2024-01-01T00:30Z","regions":[
+{"regionid":1,"dnoregion":"Scottish Hydro Electric Power Distribution","shortname":"North Scotland","intensity":{"forecast":20,"index":"very low"},"generationmix":[{"fuel":"biomass","perc":0.0},{"fuel":"coal","perc":0.0},{"fuel":"imports","perc":0.0},{"fuel":"gas","perc":5.0},{"fuel":"nuclear","perc":0.0},{"fuel":"other","perc":0.0},{"fuel":"hydro","perc":10.0},{"fuel":"solar","perc":0.0},{"fuel":"wind","perc":85.0}]},
+{"regionid":13,"dnoregion":"UKPN London","shortname":"London","intensity":{"forecast":150,"index":"moderate"},"generationmix":[{"fuel":"biomass","perc":5.0},{"fuel":"coal","perc":0.0},{"fuel":"imports","perc":20.0},{"fuel":"gas","perc":35.0},{"fuel":"nuclear","perc":15.0},{"fuel":"other","perc":0.0},{"fuel":"hydro","perc":0.0},{"fuel":"solar","perc":0.0},{"fuel":"wind","perc":25.0}]}
+]}]}"#;
+
+/// National generation mix, current or over a range
+pub const GENERATION: &str = r#"{"data":[
+{"from":"2024-01-01T00:00Z","to":"2024-01-01T00:30Z","generationmix":[{"fuel":"biomass","perc":4.1},{"fuel":"coal","perc":0.8},{"fuel":"imports","perc":12.0},{"fuel":"gas","perc":21.3},{"fuel":"nuclear","perc":14.2},{"fuel":"other","perc":0.1},{"fuel":"hydro","perc":2.5},{"fuel":"solar","perc":0.0},{"fuel":"wind","perc":45.0}]}
+]}"#;
+
+/// Carbon intensity of each type of generation
+pub const FACTORS: &str = r#"{"data":[{"Biomass":120,"Coal":937,"Dutch Imports":474,"French Imports":53,"Gas (Combined Cycle)":394,"Gas (Open Cycle)":651,"Hydro":0,"Irish Imports":458,"Nuclear":0,"Oil":935,"Other":300,"Pumped Storage":0,"Solar":0,"Wind":0}]}"#;
+
+/// Statistics of the national intensity over a range
+pub const STATS: &str = r#"{"data":[{"from":"2024-01-01T00:00Z","to":"2024-01-02T00:00Z","intensity":{"max":180,"average":135,"min":98,"index":"moderate"}}]}"#;
+
+/// Error sent by the API for a path it doesn't know
+const NOT_FOUND: &str = r#"{"error":{"code":"404 Not Found","message":"Resource not found"}}"#;
+
+/// Sample payload for the path of a request, see `MockApi`
+fn payload(path: &str) -> Option<&'static str> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["intensity", "factors"] => Some(FACTORS),
+        ["intensity", "stats", ..] => Some(STATS),
+        ["intensity"] | ["intensity", _, _] => Some(NATIONAL_INTENSITY),
+        ["regional"] | ["regional", "intensity", _, _] => Some(ALL_REGIONS),
+        ["regional", "intensity", _, _, "regionid" | "postcode", _] => Some(REGIONAL_RANGE),
+        ["regional", "regionid" | "postcode", _] => Some(REGIONAL_INTENSITY),
+        ["generation"] | ["generation", _, _] => Some(GENERATION),
+        _ => None,
+    }
+}
+
+/// Transport answering the requests of a client without network access
+///
+/// Every endpoint used by the crate is served with the sample payloads of this
+/// module, whatever the dates, region or postcode requested. Unknown paths get a
+/// 404. The clones of a `MockApi` share their responses and requests.
+#[derive(Debug, Clone, Default)]
+pub struct MockApi {
+    responses: Arc<Mutex<Vec<(String, TransportResponse)>>>,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockApi {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers the requests whose path starts with `path` with the response
+    ///
+    /// The response registered last is used when several paths match.
+    pub fn with_response(self, path: &str, response: TransportResponse) -> Self {
+        if let Ok(mut responses) = self.responses.lock() {
+            responses.push((path.to_string(), response));
+        }
+        self
+    }
+
+    /// Client sending its requests to this API
+    pub fn client(&self) -> CarbonIntensityClient {
+        CarbonIntensityClient::builder()
+            .with_transport(self.clone())
+            .build()
+            .expect("Error initialising the client")
+    }
+
+    /// Paths requested so far, with their query if any, in the order received
+    pub fn requests(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .map(|requests| requests.clone())
+            .unwrap_or_default()
+    }
+
+    fn respond(&self, path: &str) -> TransportResponse {
+        let registered = self.responses.lock().ok().and_then(|responses| {
+            responses
+                .iter()
+                .rev()
+                .find(|(prefix, _)| path.starts_with(prefix.as_str()))
+                .map(|(_, response)| response.clone())
+        });
+        registered.unwrap_or_else(|| match payload(path) {
+            Some(body) => TransportResponse::ok(body),
+            None => TransportResponse::new(StatusCode::NOT_FOUND, NOT_FOUND),
+        })
+    }
+}
+
+impl Transport for MockApi {
+    fn get<'a>(&'a self, url: &'a str) -> TransportFuture<'a> {
+        Box::pin(async move {
+            let url = url::Url::parse(url)?;
+            let path = match url.query() {
+                Some(query) => format!("{}?{query}", url.path()),
+                None => url.path().to_string(),
+            };
+            if let Ok(mut requests) = self.requests.lock() {
+                requests.push(path.clone());
+            }
+            Ok(self.respond(&path))
+        })
+    }
+}
+
+impl Intensity {
+    /// Intensity with the index the API would give it in 2024
+    pub fn fixture(forecast: i32, actual: Option<i32>) -> Self {
+        Self {
+            forecast: Some(forecast),
+            index: thresholds_for_year(2024).classify(actual.unwrap_or(forecast)),
+            actual,
+        }
+    }
+}
+
+impl Data {
+    /// Forecast for the half-hour starting at `from` e.g. "2024-01-01T00:30Z",
+    /// indexed with the thresholds of its year
+    ///
+    /// # Panics
+    ///
+    /// If `from` is not a valid date.
+    pub fn fixture(from: &str, forecast: i32) -> Self {
+        let start = parse_date(from).expect("Invalid date for a fixture");
+        let end = start + chrono::Duration::minutes(30);
+        Self {
+            from: start.format("%Y-%m-%dT%H:%MZ").to_string(),
+            to: end.format("%Y-%m-%dT%H:%MZ").to_string(),
+            intensity: Intensity {
+                forecast: Some(forecast),
+                index: thresholds_for_year(start.year()).classify(forecast),
+                actual: None,
+            },
+            generationmix: None,
+            provenance: None,
+        }
+    }
+
+    /// Sets the actual intensity, as sent at the national level
+    pub fn with_actual(mut self, actual: i32) -> Self {
+        self.intensity.actual = Some(actual);
+        self
+    }
+
+    /// Sets the generation mix, in percent for each fuel
+    pub fn with_generation_mix(mut self, mix: &[(Fuel, f64)]) -> Self {
+        self.generationmix = Some(
+            mix.iter()
+                .map(|(fuel, perc)| GenerationMix {
+                    fuel: fuel.to_string().into(),
+                    perc: *perc,
+                })
+                .collect(),
+        );
+        self
+    }
+}
+
+impl RegionData {
+    /// Data of a region, named as by the API
+    pub fn fixture(region: Region, data: Vec<Data>) -> Self {
+        Self {
+            regionid: region as i32,
+            dnoregion: region.dno_region().map(str::to_string),
+            shortname: region.short_name().to_string(),
+            postcode: None,
+            data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{IntensityIndex, RegionalIntensity};
+
+    use super::*;
+
+    #[test]
+    fn routes() {
+        for path in [
+            "/intensity",
+            "/intensity/2024-01-01T00:01Z/2024-01-02T00:01Z/",
+            "/intensity/factors",
+            "/intensity/stats/2024-01-01T00:00Z/2024-01-02T00:00Z/24",
+            "/regional",
+            "/regional/intensity/2024-01-01T00:01Z/2024-01-02T00:01Z",
+            "/regional/intensity/2024-01-01T00:01Z/2024-01-02T00:01Z/regionid/13",
+            "/regional/intensity/2024-01-01T00:01Z/2024-01-02T00:01Z/postcode/BS7",
+            "/regional/regionid/13",
+            "/regional/postcode/BS7",
+            "/generation",
+            "/generation/2024-01-01T00:01Z/2024-01-02T00:01Z",
+        ] {
+            let body = payload(path).unwrap();
+            assert!(
+                serde_json::from_str::<serde_json::Value>(body).is_ok(),
+                "{path}"
+            );
+        }
+        assert_eq!(payload("/unknown"), None);
+
+        let api = MockApi::new()
+            .with_response("/regional", TransportResponse::ok("[]"))
+            .with_response("/regional/postcode", TransportResponse::ok("{}"));
+        assert_eq!(api.respond("/regional/postcode/BS7").body, &b"{}"[..]);
+        assert_eq!(api.respond("/regional/regionid/13").body, &b"[]"[..]);
+        assert_eq!(api.respond("/unknown").status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn fixtures() {
+        let data = Data::fixture("2024-01-01T00:30Z", 150)
+            .with_actual(140)
+            .with_generation_mix(&[(Fuel::Wind, 60.0), (Fuel::Gas, 40.0)]);
+        assert_eq!(data.to(), "2024-01-01T01:00Z");
+        assert_eq!(data.intensity().index(), IntensityIndex::Moderate);
+        assert_eq!(data.intensity_for_date().unwrap().1, 140);
+        assert_eq!(data.mix_total(), Some(100.0));
+        assert_eq!(data.renewable_share(), Some(60.0));
+
+        let regional = RegionalIntensity::try_from(RegionData::fixture(
+            Region::London,
+            vec![Data::fixture("2024-01-01T00:00Z", 90)],
+        ))
+        .unwrap();
+        assert_eq!(regional.region, Region::London);
+        assert_eq!(regional.intensity.forecast(), Some(90));
+        assert_eq!(
+            Intensity::fixture(350, None).index(),
+            IntensityIndex::VeryHigh
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    hash::{BuildHasher, RandomState},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::NaiveDateTime;
+
+use crate::{planner::start_of_half_hour, Clock, SystemClock};
+
+/// Ticks at the half-hour boundaries, when the settlement periods of the grid start
+///
+/// Consumers polling the API can refresh right after new data is published
+/// instead of on arbitrary timers. An optional jitter delays each tick by a
+/// random duration, so that many consumers don't all hit the API at once.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use carbonintensity::HalfHourTicker;
+/// # async fn run() {
+/// let mut ticker = HalfHourTicker::new().with_jitter(Duration::from_secs(60));
+/// loop {
+///     let period = ticker.tick().await;
+///     println!("settlement period starting at {period}");
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct HalfHourTicker {
+    clock: Arc<dyn Clock>,
+    jitter: Duration,
+    last: Option<NaiveDateTime>,
+    random: RandomState,
+}
+
+impl Default for HalfHourTicker {
+    fn default() -> Self {
+        Self {
+            clock: Arc::new(SystemClock),
+            jitter: Duration::ZERO,
+            last: None,
+            random: RandomState::new(),
+        }
+    }
+}
+
+impl HalfHourTicker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maximum random delay added after each boundary
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Clock used to determine how long to wait for the next boundary
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Waits for the next half-hour boundary, plus the jitter, and returns it
+    ///
+    /// Every boundary is returned at most once, even if the previous tick was
+    /// awaited after the next boundary had passed.
+    pub async fn tick(&mut self) -> NaiveDateTime {
+        let now = self.clock.now();
+        let boundary = next_boundary(now, self.last);
+        let delay = (boundary - now).to_std().unwrap_or_default() + self.jitter_for(boundary);
+        crate::runtime::sleep(delay).await;
+        self.last = Some(boundary);
+        boundary
+    }
+
+    /// Stream of the half-hour boundaries, see `tick`
+    #[cfg(feature = "analysis")]
+    pub fn into_stream(self) -> impl futures::Stream<Item = NaiveDateTime> {
+        futures::stream::unfold(self, |mut ticker| async move {
+            let boundary = ticker.tick().await;
+            Some((boundary, ticker))
+        })
+    }
+
+    /// Random delay up to the jitter, different for each boundary
+    fn jitter_for(&self, boundary: NaiveDateTime) -> Duration {
+        if self.jitter.is_zero() {
+            return Duration::ZERO;
+        }
+        let hash = self.random.hash_one(boundary);
+        // 53 bits of precision
+        let fraction = (hash >> 11) as f64 / (1_u64 << 53) as f64;
+        self.jitter.mul_f64(fraction)
+    }
+}
+
+/// First half-hour boundary after `now`, or at `now` if it is on a boundary,
+/// which is later than the `last` one returned
+fn next_boundary(now: NaiveDateTime, last: Option<NaiveDateTime>) -> NaiveDateTime {
+    let half_hour = chrono::Duration::minutes(30);
+    let start = start_of_half_hour(now);
+    let mut boundary = if start == now { now } else { start + half_hour };
+    if let Some(last) = last {
+        while boundary <= last {
+            boundary += half_hour;
+        }
+    }
+    boundary
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn time(hour: u32, minute: u32, second: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(hour, minute, second)
+            .unwrap()
+    }
+
+    #[test]
+    fn boundaries() {
+        assert_eq!(next_boundary(time(12, 10, 0), None), time(12, 30, 0));
+        assert_eq!(next_boundary(time(12, 45, 1), None), time(13, 0, 0));
+        assert_eq!(
+            next_boundary(time(23, 59, 59), None),
+            time(0, 0, 0) + chrono::Duration::days(1)
+        );
+        // on a boundary
+        assert_eq!(next_boundary(time(12, 30, 0), None), time(12, 30, 0));
+        // already returned
+        assert_eq!(
+            next_boundary(time(12, 30, 0), Some(time(12, 30, 0))),
+            time(13, 0, 0)
+        );
+        // woken up early
+        assert_eq!(
+            next_boundary(time(12, 29, 59), Some(time(12, 30, 0))),
+            time(13, 0, 0)
+        );
+    }
+
+    #[test]
+    fn jitter() {
+        let ticker = HalfHourTicker::new();
+        assert_eq!(ticker.jitter_for(time(12, 0, 0)), Duration::ZERO);
+
+        let jitter = Duration::from_secs(60);
+        let ticker = HalfHourTicker::new().with_jitter(jitter);
+        for hour in 0..24 {
+            assert!(ticker.jitter_for(time(hour, 0, 0)) < jitter);
+        }
+    }
+}
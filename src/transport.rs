@@ -0,0 +1,89 @@
+use std::{fmt::Debug, future::Future, pin::Pin};
+
+use bytes::Bytes;
+use reqwest::StatusCode;
+
+use crate::Result;
+
+/// Response to a request sent by a `Transport`
+///
+/// The body is passed on as received, without being copied, to the parsing
+/// and the caches of the client.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransportResponse {
+    pub status: StatusCode,
+    pub body: Bytes,
+}
+
+impl TransportResponse {
+    pub fn new(status: StatusCode, body: impl Into<Bytes>) -> Self {
+        Self {
+            status,
+            body: body.into(),
+        }
+    }
+
+    /// Successful response with the given body, e.g. a JSON document recorded from the API
+    pub fn ok(body: impl Into<Bytes>) -> Self {
+        Self::new(StatusCode::OK, body)
+    }
+}
+
+/// Future returned by a `Transport`
+#[cfg(not(target_arch = "wasm32"))]
+pub type TransportFuture<'a> = Pin<Box<dyn Future<Output = Result<TransportResponse>> + Send + 'a>>;
+
+/// Future returned by a `Transport`, not sent between threads in the browser
+#[cfg(target_arch = "wasm32")]
+pub type TransportFuture<'a> = Pin<Box<dyn Future<Output = Result<TransportResponse>> + 'a>>;
+
+/// Sends the requests of a `CarbonIntensityClient`
+///
+/// Implemented by `reqwest::Client`, the default. Another implementation can be
+/// set with `ClientBuilder::with_transport()`, e.g. to test code using the crate
+/// without sending requests to the API. The retries, caches and parsing of the
+/// client apply to the responses of any transport.
+///
+/// ```
+/// # use carbonintensity::{
+/// #     CarbonIntensityClient, Target, Transport, TransportFuture, TransportResponse,
+/// # };
+/// #[derive(Debug)]
+/// struct Canned;
+///
+/// impl Transport for Canned {
+///     fn get<'a>(&'a self, _url: &'a str) -> TransportFuture<'a> {
+///         let body = r#"{"data":[{"from":"2024-01-01T00:00Z","to":"2024-01-01T00:30Z",
+///             "intensity":{"forecast":120,"actual":null,"index":"moderate"}}]}"#;
+///         Box::pin(async move { Ok(TransportResponse::ok(body)) })
+///     }
+/// }
+///
+/// let client = CarbonIntensityClient::builder()
+///     .with_transport(Canned)
+///     .build()
+///     .unwrap();
+/// let runtime = tokio::runtime::Builder::new_current_thread()
+///     .enable_all()
+///     .build()
+///     .unwrap();
+/// let intensity = runtime.block_on(client.get_intensity(&Target::National));
+/// assert_eq!(intensity.unwrap(), 120);
+/// ```
+pub trait Transport: Debug + Send + Sync {
+    /// Sends a GET request to the URL, returning the response whatever its status
+    ///
+    /// Returns an `ApiError` if no response was received.
+    fn get<'a>(&'a self, url: &'a str) -> TransportFuture<'a>;
+}
+
+impl Transport for reqwest::Client {
+    fn get<'a>(&'a self, url: &'a str) -> TransportFuture<'a> {
+        Box::pin(async move {
+            let response = reqwest::Client::get(self, url).send().await?;
+            let status = response.status();
+            let body = response.bytes().await?;
+            Ok(TransportResponse::new(status, body))
+        })
+    }
+}
@@ -0,0 +1,363 @@
+//! New version notice and self-update of the binary, based on the GitHub releases
+
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(feature = "self-update")]
+use carbonintensity::ApiError;
+use carbonintensity::Result;
+use reqwest::Client;
+use serde::Deserialize;
+
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/jnioche/carbonintensity-api/releases/latest";
+
+/// Version of the running binary
+const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Disables the new version notice when set
+pub const NO_UPDATE_CHECK_VAR: &str = "CARBONINTENSITY_NO_UPDATE_CHECK";
+
+/// Minimum time between two requests to GitHub for the notice
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The assets are only needed to update the binary
+#[derive(Debug, Deserialize)]
+#[cfg_attr(not(feature = "self-update"), allow(dead_code))]
+struct Release {
+    tag_name: String,
+    html_url: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+#[cfg_attr(not(feature = "self-update"), allow(dead_code))]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl Release {
+    fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+}
+
+fn client() -> reqwest::Result<Client> {
+    Client::builder()
+        // required by the GitHub API
+        .user_agent(concat!(
+            env!("CARGO_PKG_NAME"),
+            "/",
+            env!("CARGO_PKG_VERSION")
+        ))
+        .timeout(Duration::from_secs(10))
+        .build()
+}
+
+async fn latest_release() -> Result<Release> {
+    let response = client()?.get(LATEST_RELEASE_URL).send().await?;
+    Ok(response.error_for_status()?.json().await?)
+}
+
+/// Whether `candidate` is a later version than `current`
+///
+/// The versions are compared numerically, component by component; anything
+/// after the numbers (e.g. "-rc1") is ignored.
+fn is_newer(candidate: &str, current: &str) -> bool {
+    let numbers = |version: &str| -> Vec<u64> {
+        version
+            .split('.')
+            .map_while(|part| {
+                let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+                digits.parse().ok()
+            })
+            .collect()
+    };
+    numbers(candidate) > numbers(current)
+}
+
+/// Message about a newer release, if there is one
+///
+/// GitHub is queried at most once a day, the time of the last check and the
+/// latest version being remembered in the cache directory of the user in between,
+/// even if the check failed. Returns `None` if the check is disabled with
+/// `NO_UPDATE_CHECK_VAR`, if there is nowhere to remember it or if it fails.
+pub async fn new_version_notice() -> Option<String> {
+    if env::var_os(NO_UPDATE_CHECK_VAR).is_some() {
+        return None;
+    }
+
+    let state = state_path()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let remembered = fs::read_to_string(&state)
+        .ok()
+        .and_then(|content| parse_state(&content, now));
+    let latest = match remembered {
+        Some(latest) => latest,
+        None => {
+            let latest = latest_release()
+                .await
+                .map(|release| release.version().to_string())
+                .unwrap_or_default();
+            // failing to remember it only means checking again next time
+            let _ = write_state(&state, &format!("{now} {latest}"));
+            latest
+        }
+    };
+
+    is_newer(&latest, CURRENT_VERSION).then(|| {
+        format!(
+            "Version {latest} is available (current {CURRENT_VERSION}), see {}/releases; \
+             set {NO_UPDATE_CHECK_VAR} to disable this notice",
+            env!("CARGO_PKG_REPOSITORY")
+        )
+    })
+}
+
+/// File remembering the last check, in the cache directory of the user
+fn state_path() -> Option<PathBuf> {
+    let absolute = |var: &str| {
+        env::var_os(var)
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+    };
+    let cache = if cfg!(windows) {
+        absolute("LOCALAPPDATA")?
+    } else if cfg!(target_os = "macos") {
+        absolute("HOME")?.join("Library").join("Caches")
+    } else {
+        absolute("XDG_CACHE_HOME").or_else(|| Some(absolute("HOME")?.join(".cache")))?
+    };
+    Some(cache.join(env!("CARGO_PKG_NAME")).join("version-check"))
+}
+
+/// Writes the state to a new file then renames it, so that a file or link
+/// planted at `path` is replaced rather than written through
+fn write_state(path: &Path, content: &str) -> std::io::Result<()> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| std::io::Error::other("No directory for the state"))?;
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(dir)?;
+
+    let temporary = path.with_extension(format!("{}.tmp", process::id()));
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temporary)?;
+    let written = file
+        .write_all(content.as_bytes())
+        .and_then(|_| fs::rename(&temporary, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    written
+}
+
+/// Latest version from the content of the state file, if checked recently,
+/// empty if the check failed
+fn parse_state(content: &str, now: u64) -> Option<String> {
+    let content = content.trim_end_matches('\n');
+    let (checked, latest) = content.split_once(' ')?;
+    let checked: u64 = checked.parse().ok()?;
+    (checked <= now && now - checked < CHECK_INTERVAL.as_secs()).then(|| latest.to_string())
+}
+
+/// Name of the release asset holding the binary for this platform
+#[cfg(feature = "self-update")]
+fn asset_name() -> String {
+    format!(
+        "{}-{}-{}{}",
+        env!("CARGO_PKG_NAME"),
+        env::consts::ARCH,
+        env::consts::OS,
+        env::consts::EXE_SUFFIX
+    )
+}
+
+/// Replaces the running binary with the one from the latest release
+///
+/// Returns the version installed, or `None` if already up to date.
+#[cfg(feature = "self-update")]
+pub async fn self_update() -> Result<Option<String>> {
+    let release = latest_release().await?;
+    if !is_newer(release.version(), CURRENT_VERSION) {
+        return Ok(None);
+    }
+
+    let name = asset_name();
+    let asset = release
+        .assets
+        .iter()
+        .find(|asset| asset.name == name)
+        .ok_or_else(|| {
+            ApiError::Error(format!(
+                "No binary for this platform ({name}) in release {}, see {}",
+                release.tag_name, release.html_url
+            ))
+        })?;
+    let checksum_name = format!("{name}.sha256");
+    let checksum = release
+        .assets
+        .iter()
+        .find(|asset| asset.name == checksum_name)
+        .ok_or_else(|| {
+            ApiError::Error(format!(
+                "No checksum ({checksum_name}) in release {}, not updating",
+                release.tag_name
+            ))
+        })?;
+    let checksum = download(&checksum.browser_download_url).await?;
+    let binary = download(&asset.browser_download_url).await?;
+    let expected = std::str::from_utf8(&checksum)
+        .ok()
+        .and_then(parse_checksum)
+        .ok_or_else(|| ApiError::Error(format!("Invalid checksum in {checksum_name}")))?;
+    if sha256(&binary) != expected {
+        return Err(ApiError::Error(format!(
+            "The checksum of {name} doesn't match {checksum_name}, not updating"
+        )));
+    }
+
+    replace_executable(&binary)
+        .map_err(|error| ApiError::Error(format!("Could not replace the binary: {error}")))?;
+    Ok(Some(release.version().to_string()))
+}
+
+#[cfg(feature = "self-update")]
+async fn download(url: &str) -> Result<Vec<u8>> {
+    let response = client()?.get(url).send().await?.error_for_status()?;
+    Ok(response.bytes().await?.into())
+}
+
+/// SHA-256 from the content of a checksum file, as written by `sha256sum`
+/// i.e. the hexadecimal digest optionally followed by the name of the file
+#[cfg(feature = "self-update")]
+fn parse_checksum(content: &str) -> Option<String> {
+    let digest = content.split_whitespace().next()?.to_ascii_lowercase();
+    (digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit())).then_some(digest)
+}
+
+/// SHA-256 of the content, in lowercase hexadecimal
+#[cfg(feature = "self-update")]
+fn sha256(content: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, content)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Writes the new binary next to the current one and swaps them
+///
+/// The running binary is renamed first, which is allowed on Windows too.
+#[cfg(feature = "self-update")]
+fn replace_executable(binary: &[u8]) -> std::io::Result<()> {
+    let current = env::current_exe()?;
+    let new = current.with_extension("new");
+    let old = current.with_extension("old");
+
+    fs::write(&new, binary)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&new, fs::Permissions::from_mode(0o755))?;
+    }
+    fs::rename(&current, &old)?;
+    if let Err(error) = fs::rename(&new, &current) {
+        // puts the previous binary back
+        fs::rename(&old, &current)?;
+        return Err(error);
+    }
+    // can't be removed while running on Windows, it is overwritten next time
+    let _ = fs::remove_file(&old);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newer_versions() {
+        assert!(is_newer("0.5.0", "0.4.0"));
+        assert!(is_newer("0.4.10", "0.4.9"));
+        assert!(is_newer("1.0", "0.4.0"));
+        assert!(is_newer("0.4.1", "0.4"));
+        assert!(!is_newer("0.4.0", "0.4.0"));
+        assert!(!is_newer("0.3.9", "0.4.0"));
+        assert!(!is_newer("0.4.0-rc1", "0.4.0"));
+        assert!(!is_newer("latest", "0.4.0"));
+    }
+
+    #[test]
+    fn state() {
+        assert_eq!(parse_state("1000 0.5.0\n", 1000), Some("0.5.0".to_string()));
+        assert_eq!(
+            parse_state("1000 0.5.0", 1000 + CHECK_INTERVAL.as_secs() - 1),
+            Some("0.5.0".to_string())
+        );
+        // too old
+        assert_eq!(
+            parse_state("1000 0.5.0", 1000 + CHECK_INTERVAL.as_secs()),
+            None
+        );
+        // clock went backwards
+        assert_eq!(parse_state("1000 0.5.0", 999), None);
+        assert_eq!(parse_state("", 1000), None);
+        assert_eq!(parse_state("yesterday 0.5.0", 1000), None);
+        // the last check failed
+        assert_eq!(parse_state("1000 ", 1000), Some(String::new()));
+    }
+
+    #[test]
+    fn write_state_file() {
+        let dir = env::temp_dir().join(format!("carbonintensity-update-{}", process::id()));
+        let path = dir.join("nested").join("version-check");
+        write_state(&path, "1000 0.5.0").unwrap();
+        write_state(&path, "2000 0.6.0").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "2000 0.6.0");
+        // no temporary file left behind
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+
+        // a link planted in place of the state is replaced, not followed
+        #[cfg(unix)]
+        {
+            let target = dir.join("target");
+            fs::write(&target, "unchanged").unwrap();
+            fs::remove_file(&path).unwrap();
+            std::os::unix::fs::symlink(&target, &path).unwrap();
+            write_state(&path, "3000 0.7.0").unwrap();
+            assert_eq!(fs::read_to_string(&target).unwrap(), "unchanged");
+            assert!(!fs::symlink_metadata(&path).unwrap().is_symlink());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "self-update")]
+    #[test]
+    fn checksums() {
+        let digest = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert_eq!(sha256(b"abc"), digest);
+        assert_eq!(
+            parse_checksum(&format!("{}  carbonintensity-api\n", digest.to_uppercase())),
+            Some(digest.to_string())
+        );
+        assert_eq!(parse_checksum(digest), Some(digest.to_string()));
+        assert_eq!(parse_checksum("abc123  carbonintensity-api"), None);
+        assert_eq!(parse_checksum(""), None);
+    }
+}
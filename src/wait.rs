@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use crate::{
+    runtime::{self, Stopwatch},
+    ApiError, CarbonIntensityClient, Result, Target,
+};
+
+/// Waits until the current intensity of a target is below `threshold` in gCO2/kWh,
+/// e.g. to defer a batch job until the grid is green
+///
+/// The intensity is checked straight away then every `poll_interval`. The API
+/// publishes new values every half-hour, a client built with
+/// `ClientBuilder::with_cache()` only sending requests when they change.
+/// Transient errors (see `ApiError::is_transient()`) are ignored until the next
+/// check, other errors are returned.
+///
+/// Returns the intensity below the threshold, or `None` if it wasn't reached
+/// within `max_wait`, if set, including when a request is still in progress.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use carbonintensity::{wait_until_below, Target};
+/// # async fn run() -> carbonintensity::Result<()> {
+/// let hour = Duration::from_secs(3600);
+/// match wait_until_below(&Target::National, 150, hour / 4, Some(hour * 12)).await? {
+///     Some(intensity) => println!("running the job at {intensity} gCO2/kWh"),
+///     None => println!("running the job anyway"),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn wait_until_below(
+    target: &Target,
+    threshold: i32,
+    poll_interval: Duration,
+    max_wait: Option<Duration>,
+) -> Result<Option<i32>> {
+    CarbonIntensityClient::shared()
+        .wait_until_below(target, threshold, poll_interval, max_wait)
+        .await
+}
+
+impl CarbonIntensityClient {
+    /// Waits for a low intensity, see `wait_until_below()`
+    pub async fn wait_until_below(
+        &self,
+        target: &Target,
+        threshold: i32,
+        poll_interval: Duration,
+        max_wait: Option<Duration>,
+    ) -> Result<Option<i32>> {
+        if poll_interval.is_zero() {
+            return Err(ApiError::Error(
+                "The poll interval must be longer than 0".to_string(),
+            ));
+        }
+
+        let started = Stopwatch::start();
+        loop {
+            let poll = self.get_intensity(target);
+            // a request hanging past the end of the wait is abandoned
+            let result = match max_wait {
+                None => poll.await,
+                Some(max_wait) => {
+                    let remaining = max_wait.saturating_sub(started.elapsed());
+                    match runtime::timeout(remaining, poll).await {
+                        Some(result) => result,
+                        None => return Ok(None),
+                    }
+                }
+            };
+            match result {
+                Ok(intensity) if intensity < threshold => return Ok(Some(intensity)),
+                Ok(_) => {}
+                Err(error) if error.is_transient() => {}
+                Err(error) => return Err(error),
+            }
+
+            let delay = match max_wait {
+                None => poll_interval,
+                Some(max_wait) => {
+                    let remaining = max_wait.saturating_sub(started.elapsed());
+                    if remaining.is_zero() {
+                        return Ok(None);
+                    }
+                    poll_interval.min(remaining)
+                }
+            };
+            runtime::sleep(delay).await;
+        }
+    }
+}
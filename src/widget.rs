@@ -0,0 +1,210 @@
+use std::io::{self, Write};
+
+use chrono::{Local, NaiveDateTime, Utc};
+use serde::Serialize;
+
+use crate::{get_forecast, parse_date, ApiError, Data, IntensityIndex, Result, Target};
+
+/// Status bars supported by `StatusWidget::write`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WidgetFormat {
+    /// JSON object for a [waybar](https://github.com/Alexays/Waybar) custom module,
+    /// with the index as class e.g. `very-low`
+    Waybar,
+    /// Full text, short text and colour lines for [i3blocks](https://github.com/vivien/i3blocks)
+    I3blocks,
+    /// Single line coloured with [polybar](https://github.com/polybar/polybar) format tags
+    Polybar,
+}
+
+/// Current intensity of a target, as shown in a desktop status bar
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusWidget {
+    pub target: Target,
+    /// Intensity in gCO2/kWh
+    pub intensity: i32,
+    pub index: IntensityIndex,
+    /// Start of the next half-hour with a low or very low index, in UTC,
+    /// the current one if it is already green
+    pub next_green: Option<NaiveDateTime>,
+}
+
+/// Content of a waybar custom module
+#[derive(Debug, Serialize)]
+struct Waybar {
+    text: String,
+    tooltip: String,
+    class: String,
+}
+
+impl StatusWidget {
+    /// Builds the widget from the intensities of the coming half-hours
+    ///
+    /// The first one not finished at `now` (in UTC) is the current one.
+    /// Returns `None` if there is none.
+    pub fn from_forecasts(target: &Target, data: &[Data], now: NaiveDateTime) -> Option<Self> {
+        let periods: Vec<(NaiveDateTime, &Data, IntensityIndex)> = data
+            .iter()
+            .filter_map(|data| {
+                let from = parse_date(data.from()).ok()?;
+                let to = parse_date(data.to()).ok()?;
+                let index = data.intensity().index();
+                (to > now).then_some((from, data, index))
+            })
+            .collect();
+
+        let (_, current, index) = periods.first()?;
+        let intensity = current.intensity();
+        let next_green = periods
+            .iter()
+            .find(|(_, _, index)| *index <= IntensityIndex::Low)
+            .map(|(from, _, _)| *from);
+        Some(Self {
+            target: target.clone(),
+            intensity: intensity.value()?,
+            index: *index,
+            next_green,
+        })
+    }
+
+    /// Writes the widget in the format expected by a status bar
+    pub fn write<W: Write>(&self, writer: &mut W, format: WidgetFormat) -> io::Result<()> {
+        let text = format!("{} gCO2/kWh", self.intensity);
+        match format {
+            WidgetFormat::Waybar => {
+                let waybar = Waybar {
+                    text,
+                    tooltip: self.tooltip(),
+                    class: self.index.to_string().replace(' ', "-"),
+                };
+                serde_json::to_writer(&mut *writer, &waybar)?;
+                writeln!(writer)
+            }
+            WidgetFormat::I3blocks => {
+                writeln!(writer, "{text}")?;
+                writeln!(writer, "{}", self.intensity)?;
+                writeln!(writer, "{}", self.colour())
+            }
+            WidgetFormat::Polybar => writeln!(writer, "%{{F{}}}{text}%{{F-}}", self.colour()),
+        }
+    }
+
+    /// Description of the intensity and of the next green window
+    fn tooltip(&self) -> String {
+        let green = match self.next_green {
+            Some(start) => {
+                let start = start.and_utc().with_timezone(&Local);
+                if start <= Local::now() {
+                    "Green window now".to_string()
+                } else {
+                    format!("Next green window at {}", start.format("%H:%M"))
+                }
+            }
+            None => "No green window in the next 24 hours".to_string(),
+        };
+        format!(
+            "Carbon intensity for {}: {} gCO2/kWh ({})\n{green}",
+            self.target, self.intensity, self.index
+        )
+    }
+
+    fn colour(&self) -> &'static str {
+        match self.index {
+            IntensityIndex::VeryLow => "#4caf50",
+            IntensityIndex::Low => "#8bc34a",
+            IntensityIndex::Moderate => "#ffc107",
+            IntensityIndex::High => "#ff9800",
+            IntensityIndex::VeryHigh => "#f44336",
+            IntensityIndex::Unknown => "#9e9e9e",
+        }
+    }
+}
+
+/// Retrieves the current intensity of a target and its forecasts for the next 24 hours
+pub async fn get_status_widget(target: &Target) -> Result<StatusWidget> {
+    let forecasts = get_forecast(target, 24).await?;
+    StatusWidget::from_forecasts(target, &forecasts, Utc::now().naive_utc())
+        .ok_or_else(|| ApiError::Error(format!("No intensity available for {target}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::Region;
+
+    fn forecasts() -> Vec<Data> {
+        let json_str = r#"[
+            {"from":"2024-01-01T11:30Z","to":"2024-01-01T12:00Z","intensity":{"forecast":180,"index":"moderate"}},
+            {"from":"2024-01-01T12:00Z","to":"2024-01-01T12:30Z","intensity":{"forecast":170,"actual":172,"index":"moderate"}},
+            {"from":"2024-01-01T12:30Z","to":"2024-01-01T13:00Z","intensity":{"forecast":100,"index":"low"}},
+            {"from":"2024-01-01T13:00Z","to":"2024-01-01T13:30Z","intensity":{"forecast":50,"index":"very low"}}
+        ]"#;
+        serde_json::from_str(json_str).unwrap()
+    }
+
+    fn time(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn from_forecasts() {
+        let target = Target::Region(Region::London);
+        let widget = StatusWidget::from_forecasts(&target, &forecasts(), time(12, 10)).unwrap();
+        assert_eq!(
+            widget,
+            StatusWidget {
+                target: target.clone(),
+                intensity: 172,
+                index: IntensityIndex::Moderate,
+                next_green: Some(time(12, 30)),
+            }
+        );
+
+        // already green
+        let widget = StatusWidget::from_forecasts(&target, &forecasts(), time(12, 45)).unwrap();
+        assert_eq!(widget.next_green, Some(time(12, 30)));
+
+        // nothing green ahead
+        let widget =
+            StatusWidget::from_forecasts(&target, &forecasts()[..2], time(11, 30)).unwrap();
+        assert_eq!(widget.intensity, 180);
+        assert_eq!(widget.next_green, None);
+
+        assert_eq!(
+            StatusWidget::from_forecasts(&target, &forecasts(), time(13, 30)),
+            None
+        );
+    }
+
+    #[test]
+    fn write() {
+        let widget = StatusWidget {
+            target: Target::National,
+            intensity: 42,
+            index: IntensityIndex::VeryLow,
+            next_green: None,
+        };
+        let output = |format| {
+            let mut output = Vec::new();
+            widget.write(&mut output, format).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(
+            output(WidgetFormat::Waybar),
+            r#"{"text":"42 gCO2/kWh","tooltip":"Carbon intensity for National: 42 gCO2/kWh (very low)\nNo green window in the next 24 hours","class":"very-low"}"#
+                .to_string()
+                + "\n"
+        );
+        assert_eq!(output(WidgetFormat::I3blocks), "42 gCO2/kWh\n42\n#4caf50\n");
+        assert_eq!(
+            output(WidgetFormat::Polybar),
+            "%{F#4caf50}42 gCO2/kWh%{F-}\n"
+        );
+    }
+}
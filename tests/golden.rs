@@ -0,0 +1,1005 @@
+//! Replays fixture responses through the whole query pipeline
+//!
+//! Each test serves a file of `tests/responses` from a local server, whatever the
+//! request, and checks what the library makes of it. The files are written by hand
+//! in the format of the API, trimmed to a few records, rather than recorded from it.
+//! Edge cases found in real responses (nulls, unknown fuels, error bodies...) should
+//! be added here.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use carbonintensity::{
+    get_detailed_intensities, get_detailed_intensities_in_range, get_forecast_with_options,
+    get_generation_mixes_with_options, get_intensities_with_options,
+    get_intensity_records_with_options, ApiError, CarbonIntensityClient, Chunking, Data, DateRange,
+    FixedClock, Fuel, IntensityIndex, MixShare, NullPolicy, QueryOptions, QueryOutcome, Region,
+    Result, RetryPolicy, Target, Transport, TransportFuture, TransportResponse, Warning,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+
+/// Paths requested from a `Replay` server
+type Requests = Arc<Mutex<Vec<String>>>;
+
+/// Local server answering every request with the same fixture response
+struct Replay {
+    base_url: String,
+    requests: Requests,
+}
+
+impl Replay {
+    fn new(status: u16, file: &str) -> Self {
+        let body = std::fs::read(Path::new("tests/responses").join(file)).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Requests::default();
+
+        let recorded = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut reader = BufReader::new(&stream);
+                let mut request_line = String::new();
+                let _ = reader.read_line(&mut request_line);
+                // skips the headers
+                let mut line = String::new();
+                while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+                    line.clear();
+                }
+                if let Some(path) = request_line.split_whitespace().nth(1) {
+                    recorded.lock().unwrap().push(path.to_string());
+                }
+                let head = format!(
+                    "HTTP/1.1 {status} Recorded\r\ncontent-type: application/json\r\n\
+                     content-length: {}\r\nconnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(&body);
+            }
+        });
+        Self { base_url, requests }
+    }
+
+    /// Options querying the server, on the 15th of June 2024 so that
+    /// the ranges of the tests are in the past
+    fn options(&self) -> QueryOptions {
+        let now = NaiveDate::from_ymd_opt(2024, 6, 15)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        QueryOptions::new()
+            .with_clock(FixedClock::new(now))
+            .with_base_url(&self.base_url)
+    }
+
+    /// Sorted, as the windows are requested concurrently
+    fn requests(&self) -> Vec<String> {
+        let mut requests = self.requests.lock().unwrap().clone();
+        requests.sort();
+        requests
+    }
+}
+
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+fn time(hour: u32, minute: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2024, 1, 1)
+        .unwrap()
+        .and_hms_opt(hour, minute, 0)
+        .unwrap()
+}
+
+/// Range of an hour, one window
+fn detailed(target: &Target, options: &QueryOptions) -> Result<QueryOutcome<Data>> {
+    block_on(get_detailed_intensities(
+        target,
+        "2024-01-01T00:00Z",
+        &Some("2024-01-01T01:00Z"),
+        options,
+    ))
+}
+
+#[test]
+fn national() {
+    let replay = Replay::new(200, "national.json");
+    let outcome = block_on(get_intensities_with_options(
+        &Target::National,
+        "2024-01-01T00:00Z",
+        &Some("2024-01-01T01:00Z"),
+        &replay.options(),
+    ))
+    .unwrap();
+
+    assert_eq!(
+        replay.requests(),
+        vec!["/intensity/2024-01-01T00:01Z/2024-01-01T01:01Z/"]
+    );
+    // the actual values are preferred, the half-hours after the end discarded
+    assert_eq!(
+        outcome.intensities,
+        vec![(time(0, 0), 116), (time(0, 30), 115), (time(1, 0), 111)]
+    );
+    assert!(outcome.warnings.is_empty());
+}
+
+#[test]
+fn chrono_bounds() {
+    let replay = Replay::new(200, "national.json");
+    let client = CarbonIntensityClient::new();
+    let outcome = block_on(client.get_detailed_intensities_between(
+        &Target::National,
+        time(0, 0),
+        Some(time(1, 0).and_utc().into()),
+        &replay.options(),
+    ))
+    .unwrap();
+
+    // same request as with the dates as strings
+    assert_eq!(
+        replay.requests(),
+        vec!["/intensity/2024-01-01T00:01Z/2024-01-01T01:01Z/"]
+    );
+    assert_eq!(outcome.intensities.len(), 3);
+
+    // or validated once in a range
+    let options = replay.options();
+    let range = DateRange::new(time(0, 0), Some(time(1, 0).into()), options.clock()).unwrap();
+    assert!(!range.is_clamped());
+    let in_range =
+        block_on(client.get_detailed_intensities_in_range(&Target::National, &range, &options))
+            .unwrap();
+    assert_eq!(in_range.intensities.len(), 3);
+    assert_eq!(replay.requests().len(), 2);
+}
+
+#[test]
+fn chunking() {
+    let replay = Replay::new(200, "national.json");
+    let client = CarbonIntensityClient::builder()
+        .with_chunking(Chunking::new().with_window(chrono::Duration::days(7)))
+        .build()
+        .unwrap();
+    block_on(client.get_detailed_intensities(
+        &Target::National,
+        "2024-01-01",
+        &Some("2024-01-20"),
+        &replay.options(),
+    ))
+    .unwrap();
+    assert_eq!(
+        replay.requests(),
+        vec![
+            "/intensity/2024-01-01T00:01Z/2024-01-08T00:01Z/",
+            "/intensity/2024-01-08T00:01Z/2024-01-15T00:01Z/",
+            "/intensity/2024-01-15T00:01Z/2024-01-20T00:01Z/",
+        ]
+    );
+}
+
+#[cfg(feature = "analysis")]
+#[test]
+fn stream() {
+    use futures::{StreamExt, TryStreamExt};
+
+    let replay = Replay::new(200, "national.json");
+    let options = replay.options();
+    let client = CarbonIntensityClient::new();
+    let end = NaiveDate::from_ymd_opt(2024, 1, 30).unwrap();
+    let range = DateRange::new(time(0, 0), Some(end.into()), options.clock()).unwrap();
+    let streamed: Vec<_> = block_on(
+        client
+            .stream_intensities_with_options(&Target::National, &range, &options)
+            .try_collect(),
+    )
+    .unwrap();
+
+    // a request per window of 13 days, the server answering the same to each
+    assert_eq!(replay.requests().len(), 3);
+    assert_eq!(streamed.len(), 12);
+    let collected = block_on(get_intensity_records_with_options(
+        &Target::National,
+        "2024-01-01T00:00Z",
+        &Some("2024-01-30"),
+        &options,
+    ))
+    .unwrap();
+    assert_eq!(streamed, collected.intensities);
+
+    // errors are items of the stream
+    let replay = Replay::new(400, "error_postcode.json");
+    let options = replay.options();
+    let target = Target::Postcode("ZE9".to_string());
+    let mut records =
+        std::pin::pin!(client.stream_intensities_with_options(&target, &range, &options));
+    let first = block_on(records.next()).unwrap();
+    assert!(matches!(first, Err(ApiError::Upstream { .. })));
+}
+
+#[cfg(feature = "analysis")]
+#[test]
+fn compare_regions() {
+    let replay = Replay::new(200, "regional_nulls.json");
+    let client = CarbonIntensityClient::new();
+    let regions = [Region::London, Region::Wales, Region::London];
+    let comparison = block_on(client.compare_regions_with_options(
+        &regions,
+        "2024-01-01T00:00Z",
+        &Some("2024-01-01T01:00Z"),
+        &replay.options(),
+    ))
+    .unwrap();
+
+    // each region once, the server answering the same for both
+    assert_eq!(
+        replay.requests(),
+        vec![
+            "/regional/intensity/2024-01-01T00:01Z/2024-01-01T01:01Z/regionid/13",
+            "/regional/intensity/2024-01-01T00:01Z/2024-01-01T01:01Z/regionid/17",
+        ]
+    );
+    assert_eq!(comparison.times, vec![time(0, 0), time(1, 0)]);
+    let regions: Vec<Region> = comparison.rows.iter().map(|(region, _)| *region).collect();
+    assert_eq!(regions, vec![Region::London, Region::Wales]);
+    assert_eq!(
+        comparison.region(Region::London),
+        comparison.region(Region::Wales)
+    );
+    // the null half-hour of each region
+    let warned: Vec<Region> = comparison.warnings.iter().map(|(r, _)| *r).collect();
+    assert_eq!(warned, vec![Region::London, Region::Wales]);
+    assert!(comparison.errors.is_empty());
+}
+
+#[cfg(feature = "analysis")]
+#[test]
+fn batch() {
+    use carbonintensity::get_intensities_batch_with_options;
+
+    let replay = Replay::new(200, "national.json");
+    let london = Target::Region(Region::London);
+    let invalid = Target::Postcode("INVALID".to_string());
+    let targets = [
+        Target::National,
+        london.clone(),
+        Target::National,
+        invalid.clone(),
+    ];
+    let results = block_on(get_intensities_batch_with_options(
+        &targets,
+        "2024-01-01T00:00Z",
+        &Some("2024-01-01T02:00Z"),
+        &replay.options(),
+    ))
+    .unwrap();
+
+    assert_eq!(results.len(), 3);
+    assert_eq!(
+        results[&Target::National]
+            .as_ref()
+            .unwrap()
+            .intensities
+            .len(),
+        4
+    );
+    // the national response is not what a region expects
+    assert!(results[&london].is_err());
+    assert!(results[&invalid].is_err());
+    assert_eq!(replay.requests().len(), 2);
+
+    let result = block_on(get_intensities_batch_with_options(
+        &targets,
+        "not a date",
+        &None,
+        &replay.options(),
+    ));
+    assert!(result.is_err());
+}
+
+#[test]
+fn nulls() {
+    let replay = Replay::new(200, "regional_nulls.json");
+    let london = Target::Region(Region::London);
+
+    let outcome = detailed(&london, &replay.options()).unwrap();
+    assert_eq!(
+        replay.requests(),
+        vec!["/regional/intensity/2024-01-01T00:01Z/2024-01-01T01:01Z/regionid/13"]
+    );
+    let froms: Vec<&str> = outcome.intensities.iter().map(Data::from).collect();
+    assert_eq!(froms, vec!["2024-01-01T00:00Z", "2024-01-01T01:00Z"]);
+    assert_eq!(
+        outcome.warnings,
+        vec![Warning::NullIntensity {
+            from: "2024-01-01T00:30Z".to_string(),
+            interpolated: false
+        }]
+    );
+
+    let options = replay.options().with_null_policy(NullPolicy::Interpolate);
+    let outcome = detailed(&london, &options).unwrap();
+    let forecasts: Vec<_> = outcome
+        .intensities
+        .iter()
+        .map(|data| data.intensity().forecast())
+        .collect();
+    assert_eq!(forecasts, vec![Some(150), Some(140), Some(130)]);
+
+    let options = replay.options().with_null_policy(NullPolicy::Error);
+    assert!(detailed(&london, &options).is_err());
+}
+
+#[test]
+fn unknown_fuel() {
+    let replay = Replay::new(200, "postcode_unknown_fuel.json");
+    let bs7 = Target::Postcode("BS7".to_string());
+    let outcome = detailed(&bs7, &replay.options()).unwrap();
+
+    assert_eq!(
+        replay.requests(),
+        vec!["/regional/intensity/2024-01-01T00:01Z/2024-01-01T01:01Z/postcode/BS7"]
+    );
+    assert_eq!(outcome.intensities.len(), 2);
+    let mix = outcome.intensities[0].generation_mix().unwrap();
+    assert_eq!(mix.last().unwrap().fuel(), "tidal");
+    assert_eq!(outcome.intensities[0].renewable_share(), Some(60.0));
+    // the second mix adds up to 97%
+    assert_eq!(
+        outcome.warnings,
+        vec![Warning::InconsistentMix {
+            from: "2024-01-01T00:30Z".to_string(),
+            total: 97.0,
+            renormalised: false
+        }]
+    );
+}
+
+#[test]
+fn records() {
+    let replay = Replay::new(200, "postcode_unknown_fuel.json");
+    let outcome = block_on(get_intensity_records_with_options(
+        // full postcodes are queried by their outward code
+        &Target::Postcode("bs7 8ab".to_string()),
+        "2024-01-01T00:00Z",
+        &Some("2024-01-01T01:00Z"),
+        &replay.options(),
+    ))
+    .unwrap();
+    assert!(replay.requests()[0].ends_with("/postcode/BS7"));
+
+    let dates: Vec<_> = outcome.intensities.iter().map(|r| (r.from, r.to)).collect();
+    assert_eq!(
+        dates,
+        vec![(time(0, 0), time(0, 30)), (time(0, 30), time(1, 0))]
+    );
+    let record = &outcome.intensities[0];
+    assert_eq!(
+        (record.forecast, record.index),
+        (Some(80), IntensityIndex::Low)
+    );
+    // the unknown fuel is counted as other
+    let mix = record.generationmix.as_ref().unwrap();
+    assert_eq!(mix.last().unwrap(), &(Fuel::Other, MixShare::new(10.0)));
+    assert_eq!(outcome.warnings.len(), 1);
+}
+
+#[test]
+fn empty() {
+    let replay = Replay::new(200, "regional_empty.json");
+    let outcome = detailed(&Target::Region(Region::London), &replay.options()).unwrap();
+    assert!(outcome.intensities.is_empty());
+    assert!(outcome.warnings.is_empty());
+}
+
+#[test]
+fn forecast() {
+    let replay = Replay::new(200, "regional_empty.json");
+    let now = NaiveDate::from_ymd_opt(2024, 6, 15)
+        .unwrap()
+        .and_hms_opt(12, 10, 0)
+        .unwrap();
+    let options = replay.options().with_clock(FixedClock::new(now));
+    let outcome = block_on(get_forecast_with_options(
+        &Target::Region(Region::London),
+        2,
+        &options,
+    ))
+    .unwrap();
+
+    assert!(outcome.intensities.is_empty());
+    // the current half-hour, then the coming ones as a forward period
+    assert_eq!(
+        replay.requests(),
+        vec![
+            "/regional/intensity/2024-06-15T12:01Z/2024-06-15T12:01Z/regionid/13",
+            "/regional/intensity/2024-06-15T12:31Z/fw24h/regionid/13"
+        ]
+    );
+}
+
+#[cfg(feature = "analysis")]
+#[test]
+fn greenest_window() {
+    use carbonintensity::find_greenest_window_with_options;
+    use chrono::Duration;
+
+    let replay = Replay::new(200, "national.json");
+    let window = block_on(find_greenest_window_with_options(
+        &Target::National,
+        Duration::minutes(45),
+        Duration::hours(2),
+        &replay.options(),
+    ))
+    .unwrap();
+    // rounded up to an hour, the actual intensities being used when known
+    assert_eq!((window.start, window.end), (time(1, 0), time(2, 0)));
+    assert_eq!(window.average, 107.0);
+    assert_eq!(replay.requests().len(), 2);
+
+    let result = block_on(find_greenest_window_with_options(
+        &Target::National,
+        Duration::hours(3),
+        Duration::hours(2),
+        &replay.options(),
+    ));
+    assert!(matches!(result, Err(ApiError::Error(_))));
+
+    // searched up to the end of the forecast
+    let result = block_on(find_greenest_window_with_options(
+        &Target::National,
+        Duration::hours(1),
+        Duration::hours(72),
+        &replay.options(),
+    ));
+    assert!(result.is_ok());
+}
+
+#[test]
+fn generation_mixes() {
+    let mixes = |replay: &Replay, target: &Target| {
+        block_on(get_generation_mixes_with_options(
+            target,
+            "2024-01-01T00:00Z",
+            &Some("2024-01-01T01:00Z"),
+            &replay.options(),
+        ))
+        .unwrap()
+    };
+
+    let replay = Replay::new(200, "national_generation.json");
+    let outcome = mixes(&replay, &Target::National);
+    assert_eq!(
+        replay.requests(),
+        vec!["/generation/2024-01-01T00:01Z/2024-01-01T01:01Z"]
+    );
+    let wind: Vec<f64> = outcome
+        .intensities
+        .iter()
+        .map(|mix| mix.share(Fuel::Wind).percent())
+        .collect();
+    assert_eq!(wind, vec![45.0, 46.0, 46.5]);
+    assert_eq!(outcome.intensities[2].from, time(1, 0));
+
+    // from the intensities, with the same handling of the nulls
+    let replay = Replay::new(200, "regional_nulls.json");
+    let outcome = mixes(&replay, &Target::Region(Region::London));
+    let times: Vec<NaiveDateTime> = outcome.intensities.iter().map(|mix| mix.from).collect();
+    assert_eq!(times, vec![time(0, 0), time(1, 0)]);
+    assert_eq!(outcome.intensities[1].share(Fuel::Gas).percent(), 30.0);
+    assert_eq!(outcome.warnings.len(), 1);
+}
+
+#[test]
+fn implausible() {
+    let replay = Replay::new(200, "national_implausible.json");
+    let outcome = detailed(&Target::National, &replay.options()).unwrap();
+
+    let froms: Vec<&str> = outcome.intensities.iter().map(Data::from).collect();
+    assert_eq!(froms, vec!["2024-01-01T00:00Z"]);
+    assert_eq!(
+        outcome.warnings,
+        vec![
+            Warning::ImplausibleIntensity {
+                from: "2024-01-01T00:30Z".to_string(),
+                value: -5
+            },
+            Warning::ImplausibleIntensity {
+                from: "2024-01-01T01:00Z".to_string(),
+                value: 4999
+            }
+        ]
+    );
+}
+
+#[test]
+fn beyond_horizon() {
+    let replay = Replay::new(200, "national.json");
+    let options = replay.options();
+    let june = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+    let july = NaiveDate::from_ymd_opt(2024, 7, 1).unwrap();
+    assert!(DateRange::new(june, Some(july.into()), options.clock()).is_err());
+
+    let range = DateRange::within_horizon(june, Some(july.into()), options.clock()).unwrap();
+    let outcome = block_on(get_detailed_intensities_in_range(
+        &Target::National,
+        &range,
+        &options,
+    ))
+    .unwrap();
+    let horizon = NaiveDate::from_ymd_opt(2024, 6, 17)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap();
+    assert_eq!(
+        outcome.warnings,
+        vec![Warning::BeyondHorizon {
+            start: horizon,
+            end: july.and_hms_opt(0, 0, 0).unwrap(),
+        }]
+    );
+}
+
+#[test]
+fn errors() {
+    let replay = Replay::new(400, "error_postcode.json");
+    let target = Target::Postcode("ZE9".to_string());
+    let error = detailed(&target, &replay.options()).unwrap_err();
+    assert!(matches!(
+        error,
+        ApiError::Upstream { code, message, .. }
+            if code == "400 Bad Request" && message == "Please enter a valid postcode"
+    ));
+
+    let replay = Replay::new(502, "error_gateway.html");
+    let error = detailed(&Target::National, &replay.options()).unwrap_err();
+    assert!(matches!(
+        error,
+        ApiError::RestError { body, .. } if body.contains("502 Bad Gateway")
+    ));
+
+    // a body which isn't the JSON expected
+    let replay = Replay::new(200, "error_postcode.json");
+    let error = detailed(&Target::National, &replay.options()).unwrap_err();
+    assert!(matches!(error, ApiError::Error(_)));
+}
+
+#[test]
+fn retries() {
+    let replay = Replay::new(502, "error_gateway.html");
+    let client = CarbonIntensityClient::builder()
+        .with_base_url(&replay.base_url)
+        .with_retry_policy(RetryPolicy::new(2, Duration::from_millis(10)))
+        .build()
+        .unwrap();
+    let error = block_on(client.get_current_intensity(&Target::National)).unwrap_err();
+    assert!(matches!(error, ApiError::RestError { .. }));
+    assert_eq!(replay.requests(), vec!["/intensity"; 3]);
+
+    // client errors are not retried
+    let replay = Replay::new(400, "error_postcode.json");
+    let client = CarbonIntensityClient::builder()
+        .with_base_url(&replay.base_url)
+        .with_retry_policy(RetryPolicy::new(2, Duration::from_millis(10)))
+        .build()
+        .unwrap();
+    let target = Target::Postcode("ZE9".to_string());
+    assert!(block_on(client.get_current_intensity(&target)).is_err());
+    assert_eq!(replay.requests(), vec!["/regional/postcode/ZE9"]);
+}
+
+/// Clock whose time is set by the test
+#[cfg(feature = "cache")]
+#[derive(Debug, Clone, Default)]
+struct ManualClock(Arc<Mutex<NaiveDateTime>>);
+
+#[cfg(feature = "cache")]
+impl carbonintensity::Clock for ManualClock {
+    fn now(&self) -> NaiveDateTime {
+        *self.0.lock().unwrap()
+    }
+}
+
+#[cfg(feature = "cache")]
+#[test]
+fn cache() {
+    let replay = Replay::new(200, "national.json");
+    let clock = ManualClock::default();
+    *clock.0.lock().unwrap() = "2024-01-01T12:10:00".parse().unwrap();
+    let client = CarbonIntensityClient::builder()
+        .with_base_url(&replay.base_url)
+        .with_cache(true)
+        .with_clock(clock.clone())
+        .build()
+        .unwrap();
+    let first = block_on(client.get_intensity(&Target::National)).unwrap();
+    *clock.0.lock().unwrap() = "2024-01-01T12:29:59".parse().unwrap();
+    let second = block_on(client.clone().get_intensity(&Target::National)).unwrap();
+    assert_eq!(first, second);
+    assert_eq!(replay.requests(), vec!["/intensity"]);
+
+    // sent again once the half-hour is over
+    *clock.0.lock().unwrap() = "2024-01-01T12:30:00".parse().unwrap();
+    block_on(client.get_intensity(&Target::National)).unwrap();
+    assert_eq!(replay.requests().len(), 2);
+}
+
+#[cfg(feature = "cache")]
+#[test]
+fn disk_cache() {
+    let replay = Replay::new(200, "national.json");
+    let dir = std::env::temp_dir().join(format!("golden_disk_cache_{}", std::process::id()));
+    let query = || {
+        let client = CarbonIntensityClient::builder()
+            .with_disk_cache(&dir)
+            .build()
+            .unwrap();
+        block_on(client.get_intensities_with_options(
+            &Target::National,
+            "2024-01-01T00:00Z",
+            &Some("2024-01-01T01:00Z"),
+            &replay.options(),
+        ))
+        .unwrap()
+    };
+
+    let first = query();
+    assert_eq!(replay.requests().len(), 1);
+    // the window is read from the disk by another client
+    let second = query();
+    assert_eq!(replay.requests().len(), 1);
+    assert_eq!(first.intensities, second.intensities);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Transport answering every request with a fixture response, without a server
+#[derive(Debug, Default)]
+struct Recorded {
+    requests: Requests,
+}
+
+impl Transport for Recorded {
+    fn get<'a>(&'a self, url: &'a str) -> TransportFuture<'a> {
+        self.requests.lock().unwrap().push(url.to_string());
+        Box::pin(async {
+            let body = std::fs::read("tests/responses/national.json").unwrap();
+            Ok(TransportResponse::ok(body))
+        })
+    }
+}
+
+#[test]
+fn transport() {
+    let requests = Requests::default();
+    let client = CarbonIntensityClient::builder()
+        .with_base_url("http://unreachable")
+        .with_transport(Recorded {
+            requests: requests.clone(),
+        })
+        .build()
+        .unwrap();
+    assert_eq!(
+        block_on(client.get_intensity(&Target::National)).unwrap(),
+        116
+    );
+    assert_eq!(
+        *requests.lock().unwrap(),
+        vec!["http://unreachable/intensity"]
+    );
+
+    // error responses go through the same handling as those of the API
+    #[derive(Debug)]
+    struct Unavailable;
+    impl Transport for Unavailable {
+        fn get<'a>(&'a self, _url: &'a str) -> TransportFuture<'a> {
+            Box::pin(async {
+                Ok(TransportResponse::new(
+                    reqwest::StatusCode::SERVICE_UNAVAILABLE,
+                    "busy",
+                ))
+            })
+        }
+    }
+    let client = CarbonIntensityClient::builder()
+        .with_transport(Unavailable)
+        .build()
+        .unwrap();
+    let error = block_on(client.get_intensity(&Target::National)).unwrap_err();
+    assert!(error.is_transient());
+}
+
+#[cfg(feature = "analysis")]
+#[test]
+fn nowcast_transport() {
+    let requests = Requests::default();
+    let client = CarbonIntensityClient::builder()
+        .with_base_url("http://unreachable")
+        .with_transport(Recorded {
+            requests: requests.clone(),
+        })
+        .build()
+        .unwrap();
+    let options = QueryOptions::new().with_clock(FixedClock::new(time(2, 0)));
+    let nowcast = block_on(client.get_nowcast_with_options(&Target::National, &options)).unwrap();
+    assert_eq!(nowcast.intensity, 116);
+    // the current intensity and the recent values go through the transport of the client
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert!(requests
+        .iter()
+        .all(|url| url.starts_with("http://unreachable/")));
+}
+
+#[test]
+fn concurrency() {
+    /// Counts the requests in flight, each taking a few milliseconds
+    #[derive(Debug, Default, Clone)]
+    struct Slow {
+        in_flight: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    impl Transport for Slow {
+        fn get<'a>(&'a self, _url: &'a str) -> TransportFuture<'a> {
+            Box::pin(async {
+                let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                self.peak.fetch_max(in_flight, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                self.in_flight.fetch_sub(1, Ordering::SeqCst);
+                let body = std::fs::read("tests/responses/national.json").unwrap();
+                Ok(TransportResponse::ok(body))
+            })
+        }
+    }
+
+    let slow = Slow::default();
+    let client = CarbonIntensityClient::builder()
+        .with_transport(slow.clone())
+        .with_max_concurrent_requests(2)
+        .build()
+        .unwrap();
+    let outcome = block_on(client.get_detailed_intensities(
+        &Target::National,
+        "2024-01-01",
+        &Some("2024-03-01"),
+        &QueryOptions::new().with_execution_report(true),
+    ))
+    .unwrap();
+    // 5 windows, requested 2 at a time
+    assert_eq!(outcome.intensities.len(), 20);
+    assert_eq!(slow.peak.load(Ordering::SeqCst), 2);
+    // the windows are timed once they may be sent, without the wait for the others
+    let report = outcome.report.unwrap();
+    let slowest = report
+        .windows
+        .iter()
+        .filter_map(|w| w.elapsed)
+        .max()
+        .unwrap();
+    assert!(slowest * 2 < report.wall_time, "{slowest:?} {report:?}");
+}
+
+/// Serves the next response of a sequence at each request, the last one once
+/// the sequence is exhausted, `None` being unavailable
+#[derive(Debug, Clone)]
+struct Sequence {
+    bodies: Vec<Option<String>>,
+    served: Arc<AtomicUsize>,
+}
+
+impl Sequence {
+    /// Current half-hour of the national intensity, starting at `from`
+    fn current(from: &str, forecast: i32, index: &str) -> Option<String> {
+        Some(format!(
+            r#"{{"data":[{{"from":"{from}","to":"{from}",
+                "intensity":{{"forecast":{forecast},"actual":null,"index":"{index}"}}}}]}}"#
+        ))
+    }
+
+    fn new(bodies: Vec<Option<String>>) -> Self {
+        Self {
+            bodies,
+            served: Arc::default(),
+        }
+    }
+
+    fn served(&self) -> usize {
+        self.served.load(Ordering::SeqCst)
+    }
+}
+
+impl Transport for Sequence {
+    fn get<'a>(&'a self, _url: &'a str) -> TransportFuture<'a> {
+        let served = self.served.fetch_add(1, Ordering::SeqCst);
+        let next = self.bodies[served.min(self.bodies.len() - 1)].clone();
+        Box::pin(async move {
+            Ok(match next {
+                Some(body) => TransportResponse::ok(body),
+                None => TransportResponse::new(reqwest::StatusCode::SERVICE_UNAVAILABLE, "busy"),
+            })
+        })
+    }
+}
+
+#[test]
+fn wait_until_below() {
+    let from = "2024-01-01T00:00Z";
+    let sequence = Sequence::new(vec![
+        Sequence::current(from, 250, "high"),
+        None,
+        Sequence::current(from, 200, "moderate"),
+        Sequence::current(from, 150, "low"),
+    ]);
+    let client = CarbonIntensityClient::builder()
+        .with_transport(sequence.clone())
+        .build()
+        .unwrap();
+    let poll = Duration::from_millis(1);
+    let below = block_on(client.wait_until_below(&Target::National, 180, poll, None)).unwrap();
+    assert_eq!(below, Some(150));
+    assert_eq!(sequence.served(), 4);
+
+    // still at 150
+    let below = block_on(client.wait_until_below(
+        &Target::National,
+        100,
+        poll,
+        Some(Duration::from_millis(20)),
+    ))
+    .unwrap();
+    assert_eq!(below, None);
+    assert!(sequence.served() > 5);
+
+    // a request hanging past the end of the wait
+    #[derive(Debug)]
+    struct Hanging;
+    impl Transport for Hanging {
+        fn get<'a>(&'a self, _url: &'a str) -> TransportFuture<'a> {
+            Box::pin(async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(TransportResponse::ok("{}"))
+            })
+        }
+    }
+    let client = CarbonIntensityClient::builder()
+        .with_transport(Hanging)
+        .build()
+        .unwrap();
+    let started = std::time::Instant::now();
+    let max_wait = Some(Duration::from_millis(20));
+    let below = block_on(client.wait_until_below(&Target::National, 100, poll, max_wait));
+    assert_eq!(below.unwrap(), None);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[cfg(feature = "analysis")]
+#[test]
+fn subscribe() {
+    use futures::StreamExt;
+
+    let sequence = Sequence::new(vec![
+        Sequence::current("2024-01-01T00:00Z", 180, "moderate"),
+        Sequence::current("2024-01-01T00:00Z", 180, "moderate"),
+        None,
+        // revised within the half-hour
+        Sequence::current("2024-01-01T00:00Z", 150, "low"),
+        Sequence::current("2024-01-01T00:00Z", 140, "low"),
+        Sequence::current("2024-01-01T00:30Z", 140, "low"),
+    ]);
+    let client = CarbonIntensityClient::builder()
+        .with_transport(sequence.clone())
+        .build()
+        .unwrap();
+    let updates = client.subscribe(&Target::National, Duration::from_millis(1));
+    let records: Vec<_> = block_on(updates.take(3).collect());
+    let changes: Vec<_> = records
+        .into_iter()
+        .map(|record| {
+            let record = record.unwrap();
+            (record.from, record.forecast, record.index)
+        })
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            (time(0, 0), Some(180), IntensityIndex::Moderate),
+            (time(0, 0), Some(150), IntensityIndex::Low),
+            (time(0, 30), Some(140), IntensityIndex::Low),
+        ]
+    );
+    assert_eq!(sequence.served(), 6);
+
+    let updates = client.subscribe(&Target::National, Duration::ZERO);
+    let records: Vec<_> = block_on(updates.collect());
+    assert!(matches!(records[..], [Err(ApiError::Error(_))]));
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn blocking() {
+    let replay = Replay::new(200, "national.json");
+    let builder = CarbonIntensityClient::builder().with_base_url(&replay.base_url);
+    let client = carbonintensity::blocking::CarbonIntensityClient::from_builder(builder).unwrap();
+
+    let outcome = client
+        .get_intensities_with_options(
+            &Target::National,
+            "2024-01-01T00:00Z",
+            &Some("2024-01-01T01:00Z"),
+            &replay.options(),
+        )
+        .unwrap();
+    assert_eq!(outcome.intensities.len(), 3);
+    assert_eq!(client.get_intensity(&Target::National).unwrap(), 116);
+
+    // the windows are requested one after the other
+    let builder = CarbonIntensityClient::builder()
+        .with_base_url(&replay.base_url)
+        .with_chunking(Chunking::new().with_window(chrono::Duration::days(7)));
+    let client = carbonintensity::blocking::CarbonIntensityClient::from_builder(builder).unwrap();
+    let report = client
+        .get_detailed_intensities(
+            &Target::National,
+            "2024-01-01",
+            &Some("2024-01-20"),
+            &replay.options().with_execution_report(true),
+        )
+        .unwrap()
+        .report
+        .unwrap();
+    assert_eq!((report.windows.len(), report.concurrency()), (3, 1));
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn mock_api() {
+    let api = carbonintensity::test_util::MockApi::new();
+    let client = api.client();
+    let london = Target::Region(Region::London);
+    let options = QueryOptions::new().with_clock(FixedClock::new(time(12, 0)));
+    let range = |target| {
+        block_on(client.get_intensities_with_options(
+            target,
+            "2024-01-01T00:00Z",
+            &Some("2024-01-01T01:00Z"),
+            &options,
+        ))
+        .unwrap()
+        .intensities
+    };
+
+    // every endpoint is served with a payload the client can parse
+    assert_eq!(block_on(client.get_intensity(&london)).unwrap(), 150);
+    assert_eq!(
+        block_on(client.get_all_regional_intensities())
+            .unwrap()
+            .len(),
+        2
+    );
+    assert_eq!(range(&london), vec![(time(0, 0), 150), (time(0, 30), 140)]);
+    assert_eq!(
+        range(&Target::National),
+        vec![(time(0, 0), 116), (time(0, 30), 115)]
+    );
+    assert!(block_on(client.get_generation_mix(&Target::National)).is_ok());
+    assert!(block_on(client.get_carbon_factors()).is_ok());
+    assert!(
+        block_on(client.get_intensity_stats("2024-01-01T00:00Z", "2024-01-02T00:00Z", None))
+            .is_ok()
+    );
+    assert_eq!(api.requests().len(), 7);
+}